@@ -2,115 +2,144 @@ use std::env;
 use std::fs;
 use std::path::Path;
 
+use base64::Engine;
+use sti_manager_lib::sti::StiParser;
+
 fn main() {
     let args: Vec<String> = env::args().collect();
-    if args.len() != 2 {
-        eprintln!("Usage: {} <sti_file>", args[0]);
+    let json = args.iter().skip(1).any(|arg| arg == "--json");
+    let files: Vec<&String> = args.iter().skip(1).filter(|arg| *arg != "--json").collect();
+    
+    if files.is_empty() {
+        eprintln!("Usage: {} [--json] <sti_file>...", args[0]);
         std::process::exit(1);
     }
     
-    let file_path = &args[1];
+    let mut failed = false;
+    for file_path in files {
+        let file_data = match read_sti_bytes(file_path) {
+            Ok(data) => data,
+            Err(e) => {
+                eprintln!("{}", e);
+                failed = true;
+                continue;
+            }
+        };
+        
+        if json {
+            match json_report(file_path, &file_data) {
+                Ok(report) => println!("{}", report),
+                Err(e) => {
+                    eprintln!("{}: {}", file_path, e);
+                    failed = true;
+                }
+            }
+        } else if let Err(e) = print_report(file_path, &file_data) {
+            eprintln!("{}: {}", file_path, e);
+            failed = true;
+        }
+    }
+    
+    if failed {
+        std::process::exit(1);
+    }
+}
+
+fn read_sti_bytes(file_path: &str) -> Result<Vec<u8>, String> {
     let path = Path::new(file_path);
     
     if !path.exists() {
-        eprintln!("File does not exist: {}", file_path);
-        std::process::exit(1);
+        return Err(format!("File does not exist: {}", file_path));
     }
     
-    let file_data = match fs::read(path) {
-        Ok(data) => data,
-        Err(e) => {
-            eprintln!("Failed to read file: {}", e);
-            std::process::exit(1);
-        }
-    };
+    let file_data = fs::read(path)
+        .map_err(|e| format!("Failed to read file: {}", e))?;
     
     if file_data.len() < 64 {
-        eprintln!("File too small: {} bytes (need at least 64 for header)", file_data.len());
-        std::process::exit(1);
+        return Err(format!("File too small: {} bytes (need at least 64 for header)", file_data.len()));
     }
     
-    // Read main header (first 64 bytes)
-    let signature = &file_data[0..4];
-    let original_size = u32::from_le_bytes([file_data[4], file_data[5], file_data[6], file_data[7]]);
-    let compressed_size = u32::from_le_bytes([file_data[8], file_data[9], file_data[10], file_data[11]]);
-    let transparent_color = u32::from_le_bytes([file_data[12], file_data[13], file_data[14], file_data[15]]);
+    Ok(file_data)
+}
+
+// One JSON object per file, built from the same parser the app uses
+fn json_report(file_path: &str, file_data: &[u8]) -> Result<serde_json::Value, String> {
+    let sti_file = StiParser::parse_headers_only(file_data)
+        .map_err(|e| format!("Failed to parse STI headers: {}", e))?;
+    let data_start = StiParser::image_data_start(file_data)
+        .map_err(|e| format!("Failed to locate image data: {}", e))?;
     let flags_value = u32::from_le_bytes([file_data[16], file_data[17], file_data[18], file_data[19]]);
-    let height = u16::from_le_bytes([file_data[20], file_data[21]]);
-    let width = u16::from_le_bytes([file_data[22], file_data[23]]);
-    let color_depth = u8::from_le_bytes([file_data[24]]);
-    let num_images = u16::from_le_bytes([file_data[26], file_data[27]]);
-    let palette_colors = u16::from_le_bytes([file_data[30], file_data[31]]);
+    
+    let sub_images: Vec<serde_json::Value> = sti_file.images.iter()
+        .filter_map(|image| image.header.as_ref())
+        .map(|header| serde_json::json!({
+            "data_offset": header.data_offset,
+            "data_size": header.data_size,
+            "offset_x": header.offset_x,
+            "offset_y": header.offset_y,
+            "width": header.width,
+            "height": header.height,
+        }))
+        .collect();
+    
+    let first_bytes = &file_data[..file_data.len().min(128)];
+    
+    Ok(serde_json::json!({
+        "file": file_path,
+        "file_size": file_data.len(),
+        "flags_value": flags_value,
+        "header": sti_file.header,
+        "sub_images": sub_images,
+        "image_data_offset": data_start,
+        "first_bytes_base64": base64::engine::general_purpose::STANDARD.encode(first_bytes),
+    }))
+}
+
+fn print_report(file_path: &str, file_data: &[u8]) -> Result<(), String> {
+    // Header fields come from the app's parser, so this report can't drift from what it reads
+    let sti_file = StiParser::parse_headers_only(file_data)
+        .map_err(|e| format!("Failed to parse STI headers: {}", e))?;
+    let header = &sti_file.header;
+    let signature = &header.signature;
     
     println!("STI File Analysis: {}", file_path);
     println!("=================");
     println!("File size: {} bytes", file_data.len());
     println!("Signature: {:?} ({})", signature, String::from_utf8_lossy(signature));
-    println!("Original size: {}", original_size);
-    println!("Compressed size: {}", compressed_size);
-    println!("Transparent color: {}", transparent_color);
-    println!("Flags: 0x{:08X}", flags_value);
-    println!("Height: {}", height);
-    println!("Width: {}", width);
-    println!("Color depth: {}", color_depth);
-    println!("Num images: {}", num_images);
-    println!("Palette colors: {}", palette_colors);
-    
-    // Analyze flags
-    let transparent = (flags_value & 0x01) != 0;
-    let alpha = (flags_value & 0x02) != 0;
-    let rgb = (flags_value & 0x04) != 0;
-    let indexed = (flags_value & 0x08) != 0;
-    let zlib_compressed = (flags_value & 0x10) != 0;
-    let etrle_compressed = (flags_value & 0x20) != 0;
+    println!("Original size: {}", header.original_size);
+    println!("Compressed size: {}", header.compressed_size);
+    println!("Transparent color: {}", header.transparent_color);
+    println!("Flags: 0x{:08X}", header.flags_value());
+    println!("Height: {}", header.height);
+    println!("Width: {}", header.width);
+    println!("Color depth: {}", header.color_depth);
+    println!("Num images: {}", header.num_images);
+    println!("Palette colors: {}", header.palette_colors);
     
     println!("\nFlags breakdown:");
-    println!("- Transparent: {}", transparent);
-    println!("- Alpha: {}", alpha);
-    println!("- RGB: {}", rgb);
-    println!("- Indexed: {}", indexed);
-    println!("- ZLIB compressed: {}", zlib_compressed);
-    println!("- ETRLE compressed: {}", etrle_compressed);
+    println!("- Transparent: {}", header.flags.transparent);
+    println!("- Alpha: {}", header.flags.alpha);
+    println!("- RGB: {}", header.flags.rgb);
+    println!("- Indexed: {}", header.flags.indexed);
+    println!("- ZLIB compressed: {}", header.flags.zlib_compressed);
+    println!("- ETRLE compressed: {}", header.flags.etrle_compressed);
     
-    // If this is an indexed (8-bit) file, analyze sub-image headers
-    if indexed && num_images > 0 {
+    let sub_headers: Vec<_> = sti_file.images.iter()
+        .filter_map(|image| image.header.as_ref())
+        .collect();
+    if !sub_headers.is_empty() {
         println!("\nSub-image headers:");
-        let mut offset = 64; // Start after main header
-        
-        // Skip palette (always 768 bytes for 8-bit files)
-        offset += 768; // 256 colors * 3 bytes
-        println!("Palette size: 256 colors (768 bytes)");
-        
-        for i in 0..num_images {
-            if offset + 16 <= file_data.len() {
-                let data_offset = u32::from_le_bytes([
-                    file_data[offset],
-                    file_data[offset + 1],
-                    file_data[offset + 2],
-                    file_data[offset + 3]
-                ]);
-                let data_size = u32::from_le_bytes([
-                    file_data[offset + 4],
-                    file_data[offset + 5],
-                    file_data[offset + 6],
-                    file_data[offset + 7]
-                ]);
-                let offset_x = i16::from_le_bytes([file_data[offset + 8], file_data[offset + 9]]);
-                let offset_y = i16::from_le_bytes([file_data[offset + 10], file_data[offset + 11]]);
-                let img_height = u16::from_le_bytes([file_data[offset + 12], file_data[offset + 13]]);
-                let img_width = u16::from_le_bytes([file_data[offset + 14], file_data[offset + 15]]);
-                
-                println!("Image {}: data_offset={}, data_size={}, offset_x={}, offset_y={}, width={}, height={}",
-                    i, data_offset, data_size, offset_x, offset_y, img_width, img_height);
-                
-                offset += 16;
-            } else {
-                println!("Image {}: Header extends beyond file boundary", i);
-                break;
-            }
+        if sti_file.palette.is_some() {
+            println!("Palette size: 256 colors (768 bytes)");
+        }
+        for (i, sub) in sub_headers.iter().enumerate() {
+            println!("Image {}: data_offset={}, data_size={}, offset_x={}, offset_y={}, width={}, height={}",
+                i, sub.data_offset, sub.data_size, sub.offset_x, sub.offset_y, sub.width, sub.height);
         }
         
-        println!("Image data starts at offset: {}", offset);
+        let data_start = StiParser::image_data_start(file_data)
+            .map_err(|e| format!("Failed to locate image data: {}", e))?;
+        println!("Image data starts at offset: {}", data_start);
     }
     
     // Show first 128 bytes as hex for low-level analysis
@@ -128,7 +157,7 @@ fn main() {
         for j in 0..16 {
             if i + j < file_data.len() {
                 let c = file_data[i + j];
-                if c >= 32 && c <= 126 {
+                if (32..=126).contains(&c) {
                     print!("{}", c as char);
                 } else {
                     print!(".");
@@ -137,4 +166,6 @@ fn main() {
         }
         println!();
     }
-}
\ No newline at end of file
+    
+    Ok(())
+}
@@ -0,0 +1,89 @@
+use serde::Serialize;
+
+use crate::sti::StiError;
+
+/// Error returned by the Tauri commands, serialized as `{ code, message }` so the UI can
+/// branch on the kind of failure instead of matching message text
+#[derive(Debug, Clone, PartialEq, Serialize, thiserror::Error)]
+#[serde(tag = "code", content = "message", rename_all = "snake_case")]
+pub enum CommandError {
+    #[error("{0}")]
+    NotFound(String),
+    #[error("{0}")]
+    InvalidFormat(String),
+    #[error("{0}")]
+    OutOfBounds(String),
+    #[error("{0}")]
+    Unsupported(String),
+    #[error("{0}")]
+    AlreadyExists(String),
+    #[error("{0}")]
+    Io(String),
+    #[error("{0}")]
+    Internal(String),
+}
+
+impl From<StiError> for CommandError {
+    fn from(error: StiError) -> Self {
+        let message = error.to_string();
+        match error {
+            StiError::Io(io_error) if io_error.kind() == std::io::ErrorKind::NotFound => Self::NotFound(message),
+            StiError::Io(io_error) if io_error.kind() == std::io::ErrorKind::AlreadyExists => Self::AlreadyExists(message),
+            StiError::Io(_) => Self::Io(message),
+            StiError::InvalidFormat(_) | StiError::Decompression(_) => Self::InvalidFormat(message),
+            StiError::UnsupportedFormat(_) => Self::Unsupported(message),
+            StiError::Encoding(_) => Self::Internal(message),
+        }
+    }
+}
+
+impl From<std::io::Error> for CommandError {
+    fn from(error: std::io::Error) -> Self {
+        match error.kind() {
+            std::io::ErrorKind::NotFound => Self::NotFound(error.to_string()),
+            std::io::ErrorKind::AlreadyExists => Self::AlreadyExists(error.to_string()),
+            _ => Self::Io(error.to_string()),
+        }
+    }
+}
+
+// Internal helpers still report plain strings, those surface as internal errors
+impl From<String> for CommandError {
+    fn from(message: String) -> Self {
+        Self::Internal(message)
+    }
+}
+
+impl From<&str> for CommandError {
+    fn from(message: &str) -> Self {
+        Self::Internal(message.to_string())
+    }
+}
+
+// Lets helpers that still return String errors call code that reports a CommandError
+impl From<CommandError> for String {
+    fn from(error: CommandError) -> Self {
+        error.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serializes_as_code_and_message() {
+        let json = serde_json::to_value(CommandError::OutOfBounds("Image index out of bounds".to_string())).unwrap();
+        assert_eq!(json, serde_json::json!({ "code": "out_of_bounds", "message": "Image index out of bounds" }));
+    }
+
+    #[test]
+    fn test_sti_error_conversion() {
+        let missing = StiError::Io(std::io::Error::new(std::io::ErrorKind::NotFound, "missing"));
+        assert!(matches!(CommandError::from(missing), CommandError::NotFound(_)));
+        let taken = StiError::Io(std::io::Error::new(std::io::ErrorKind::AlreadyExists, "taken"));
+        assert!(matches!(CommandError::from(taken), CommandError::AlreadyExists(_)));
+        assert!(matches!(CommandError::from(StiError::Decompression("bad run".to_string())), CommandError::InvalidFormat(_)));
+        assert!(matches!(CommandError::from(StiError::UnsupportedFormat("24-bit".to_string())), CommandError::Unsupported(_)));
+    }
+}
@@ -1,22 +1,136 @@
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 use serde::{Deserialize, Serialize};
+use tauri::Emitter;
 use tauri_plugin_dialog::DialogExt;
 use image::GenericImageView;
+use rayon::prelude::*;
 
-mod sti;
+pub mod sti;
+mod error;
+mod logging;
 
 use sti::{StiParser, StiFile};
+use sti::ops::{ExportOptions, export_frame, frame_to_rgba, read_png_frame_offsets};
+pub use sti::ops::{EditableImage, EditableStiFile};
+use error::CommandError;
+
+// On-disk identity of a cached file, used to detect external modifications
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct FileStamp {
+    modified: Option<std::time::SystemTime>,
+    len: u64,
+}
+
+impl FileStamp {
+    fn of(path: &Path) -> Option<Self> {
+        let metadata = fs::metadata(path).ok()?;
+        Some(Self {
+            modified: metadata.modified().ok(),
+            len: metadata.len(),
+        })
+    }
+}
+
+struct CachedStiFile {
+    file: Arc<StiFile>,
+    stamp: Option<FileStamp>,
+    dirty: bool, // Holds in-memory edits that haven't been saved yet
+    in_memory: bool, // Opened from bytes under a caller-chosen name, no file on disk backs it
+}
 
 // Global caches for parsed STI files and directory scan results
-type StiCache = Arc<Mutex<HashMap<String, Arc<StiFile>>>>;
-type DirectoryCache = Arc<Mutex<HashMap<String, bool>>>;
+type StiCache = Arc<Mutex<HashMap<String, CachedStiFile>>>;
+// Directory scan results keyed by path, with the depth limit each answer was computed at
+type DirectoryCache = Arc<Mutex<HashMap<String, (bool, usize)>>>;
+// Encoded PNG thumbnails keyed by path and size, with the stamp they were rendered from
+type ThumbnailCache = Arc<Mutex<HashMap<(String, u32), (Option<FileStamp>, Vec<u8>)>>>;
+// Undo/redo snapshots for files changed through the editing commands
+type EditHistoryMap = Arc<Mutex<HashMap<String, EditHistory>>>;
+// Cancellation flags for running batch operations, keyed by operation id
+type BatchOperations = Arc<Mutex<HashMap<String, Arc<AtomicBool>>>>;
+
+// Entry limits for the in-memory caches, adjustable at runtime through set_cache_limits
+#[derive(Debug, Clone, Copy)]
+struct CacheConfig {
+    sti_max: usize,
+    dir_max: usize,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self { sti_max: 50, dir_max: 200 }
+    }
+}
 
 lazy_static::lazy_static! {
     static ref STI_CACHE: StiCache = Arc::new(Mutex::new(HashMap::new()));
     static ref DIRECTORY_CACHE: DirectoryCache = Arc::new(Mutex::new(HashMap::new()));
+    static ref THUMBNAIL_CACHE: ThumbnailCache = Arc::new(Mutex::new(HashMap::new()));
+    static ref EDIT_HISTORY: EditHistoryMap = Arc::new(Mutex::new(HashMap::new()));
+    static ref BATCH_OPERATIONS: BatchOperations = Arc::new(Mutex::new(HashMap::new()));
+    static ref SCAN_OPERATIONS: BatchOperations = Arc::new(Mutex::new(HashMap::new()));
+    static ref CACHE_CONFIG: Arc<Mutex<CacheConfig>> = Arc::new(Mutex::new(CacheConfig::default()));
+    // Canonical directories writes are confined to; empty means anywhere, set via set_allowed_roots
+    static ref ALLOWED_ROOTS: Arc<Mutex<Vec<PathBuf>>> = Arc::new(Mutex::new(Vec::new()));
+}
+
+static NEXT_OPERATION_ID: AtomicU64 = AtomicU64::new(1);
+
+// Maximum number of undo states kept per file
+const MAX_EDIT_HISTORY: usize = 20;
+
+#[derive(Default)]
+struct EditHistory {
+    undo: Vec<Arc<StiFile>>,
+    redo: Vec<Arc<StiFile>>,
+}
+
+// Lock a shared cache without panicking if an earlier command panicked while holding it.
+// A poisoned cache is reset and reported once as an error, later calls see an empty cache.
+fn lock_cache<T: Default>(cache: &Mutex<T>) -> Result<MutexGuard<'_, T>, String> {
+    cache.lock().map_err(|poisoned| {
+        *poisoned.into_inner() = T::default();
+        cache.clear_poison();
+        "Internal cache was reset after an earlier failure, please retry".to_string()
+    })
+}
+
+// Resolve `path` the way the OS will and check it lands under one of `roots`.
+// A file that doesn't exist yet is resolved through its parent directory, so `..`
+// components and symlinks can't be used to step outside.
+fn path_within_roots(path: &Path, roots: &[PathBuf]) -> Result<PathBuf, String> {
+    let resolved = if path.exists() {
+        path.canonicalize().map_err(|e| format!("Cannot resolve '{}': {}", path.display(), e))?
+    } else {
+        let file_name = path.file_name()
+            .ok_or_else(|| format!("'{}' does not name a file", path.display()))?;
+        let parent = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        parent.canonicalize()
+            .map_err(|e| format!("Cannot resolve '{}': {}", parent.display(), e))?
+            .join(file_name)
+    };
+    
+    if roots.is_empty() || roots.iter().any(|root| resolved.starts_with(root)) {
+        Ok(resolved)
+    } else {
+        Err(format!("Refusing to write '{}': it is outside the allowed roots", path.display()))
+    }
+}
+
+// Guard for every command that writes or backs up a file; a no-op until roots are configured
+fn check_write_allowed(file_path: &str) -> Result<(), CommandError> {
+    let roots = lock_cache(&ALLOWED_ROOTS)?;
+    if roots.is_empty() {
+        return Ok(());
+    }
+    path_within_roots(Path::new(file_path), &roots).map(|_| ()).map_err(CommandError::Io)
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -33,17 +147,10 @@ pub struct StiFileInfo {
 
 impl From<&StiFile> for StiFileInfo {
     fn from(sti_file: &StiFile) -> Self {
+        let (width, height) = sti_file.dimensions();
         Self {
-            width: if sti_file.is_16bit() { 
-                sti_file.header.width 
-            } else { 
-                sti_file.images.first().map(|img| img.width).unwrap_or(0)
-            },
-            height: if sti_file.is_16bit() { 
-                sti_file.header.height 
-            } else { 
-                sti_file.images.first().map(|img| img.height).unwrap_or(0)
-            },
+            width,
+            height,
             num_images: sti_file.header.num_images,
             is_16bit: sti_file.is_16bit(),
             is_8bit: sti_file.is_8bit(),
@@ -60,6 +167,10 @@ pub struct StiImageData {
     pub height: u16,
     pub data: Vec<u8>,
     pub palette: Option<Vec<[u8; 3]>>,
+    pub rgb_data: Option<Vec<u8>>, // 16-bit only: pixels decoded to RGB888 via the header masks
+    pub alpha_data: Option<Vec<u8>>, // 16-bit files with an alpha mask only: one alpha byte per pixel
+    pub offset_x: i16,
+    pub offset_y: i16,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -81,51 +192,39 @@ pub struct DirectoryContents {
 
 // Tauri commands
 #[tauri::command]
-async fn open_sti_file(file_path: String) -> Result<StiFileInfo, String> {
+async fn open_sti_file(file_path: String) -> Result<StiFileInfo, CommandError> {
     let path = Path::new(&file_path);
     
     if !path.exists() {
-        return Err("File does not exist".to_string());
+        return Err(CommandError::NotFound("File does not exist".to_string()));
     }
     
     // Try to get from cache first
-    let cached_file = {
-        let cache = STI_CACHE.lock().unwrap();
-        cache.get(&file_path).cloned()
-    };
-    
-    let (sti_file, file_size) = if let Some(cached) = cached_file {
+    let (sti_file, file_size) = if let Some(cached) = cached_sti_file(&file_path)? {
         // Get file size without re-reading the entire file
         let metadata = fs::metadata(path)
-            .map_err(|e| format!("Failed to get file metadata: {}", e))?;
+            .map_err(|e| CommandError::Io(format!("Failed to get file metadata: {}", e)))?;
         (cached, metadata.len())
     } else {
         // Parse and cache the file
+        let stamp = FileStamp::of(path);
         let file_data = fs::read(path)
-            .map_err(|e| format!("Failed to read file: {}", e))?;
+            .map_err(|e| CommandError::Io(format!("Failed to read file: {}", e)))?;
         
         let parsed_file = StiParser::parse(&file_data)
             .map_err(|e| {
                 match e {
-                    sti::types::StiError::InvalidFormat(msg) => format!("Invalid STI format in '{}': {}", file_path, msg),
-                    sti::types::StiError::Io(io_err) => format!("IO error reading '{}': {}", file_path, io_err),
-                    sti::types::StiError::Decompression(decomp_err) => format!("Decompression error in '{}': {}", file_path, decomp_err),
-                    sti::types::StiError::UnsupportedFormat(unsup_err) => format!("Unsupported format in '{}': {}", file_path, unsup_err),
+                    sti::types::StiError::InvalidFormat(msg) => CommandError::InvalidFormat(format!("Invalid STI format in '{}': {}", file_path, msg)),
+                    sti::types::StiError::Io(io_err) => CommandError::Io(format!("IO error reading '{}': {}", file_path, io_err)),
+                    sti::types::StiError::Decompression(decomp_err) => CommandError::InvalidFormat(format!("Decompression error in '{}': {}", file_path, decomp_err)),
+                    sti::types::StiError::UnsupportedFormat(unsup_err) => CommandError::Unsupported(format!("Unsupported format in '{}': {}", file_path, unsup_err)),
+                    sti::types::StiError::Encoding(enc_err) => CommandError::Internal(format!("Encoding error in '{}': {}", file_path, enc_err)),
                 }
             })?;
         
         let file_size = file_data.len() as u64;
         let arc_file = Arc::new(parsed_file);
-        
-        // Cache the parsed file
-        {
-            let mut cache = STI_CACHE.lock().unwrap();
-            // Limit cache size to prevent memory issues
-            if cache.len() > 50 {
-                cache.clear(); // Simple eviction strategy
-            }
-            cache.insert(file_path.clone(), arc_file.clone());
-        }
+        cache_sti_file(&file_path, arc_file.clone(), stamp)?;
         
         (arc_file, file_size)
     };
@@ -136,16 +235,143 @@ async fn open_sti_file(file_path: String) -> Result<StiFileInfo, String> {
     Ok(info)
 }
 
+// Parse a file the caller already holds in memory, e.g. from drag-and-drop or outside the fs scope.
+// It's cached under `name`, which the other commands then accept in place of a path
+#[tauri::command]
+async fn open_sti_bytes(name: String, bytes: Vec<u8>) -> Result<StiFileInfo, CommandError> {
+    let sti_file = StiParser::parse(&bytes)
+        .map_err(|e| CommandError::InvalidFormat(format!("Invalid STI format in '{}': {}", name, e)))?;
+    
+    let arc_file = Arc::new(sti_file);
+    insert_cache_entry(&name, CachedStiFile {
+        file: arc_file.clone(),
+        stamp: None,
+        dirty: false,
+        in_memory: true,
+    })?;
+    
+    let mut info = StiFileInfo::from(arc_file.as_ref());
+    info.file_size = bytes.len() as u64;
+    Ok(info)
+}
+
+// Open an SLF archive and read its file table
+fn open_slf_archive(slf_path: &str) -> Result<(fs::File, sti::slf::SlfArchive), CommandError> {
+    let mut file = fs::File::open(slf_path)
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => CommandError::NotFound(format!("SLF archive '{}' does not exist", slf_path)),
+            _ => CommandError::Io(format!("Failed to open SLF archive '{}': {}", slf_path, e)),
+        })?;
+    let archive = sti::slf::SlfArchive::read(&mut file)?;
+    Ok((file, archive))
+}
+
+// List the STI files stored in a JA2 SLF archive
+#[tauri::command]
+async fn browse_slf(slf_path: String) -> Result<Vec<String>, CommandError> {
+    let (_, archive) = open_slf_archive(&slf_path)?;
+    Ok(archive.sti_entries().map(|entry| entry.name.clone()).collect())
+}
+
+// Describe an STI file stored inside an SLF archive, read-only and uncached
+#[tauri::command]
+async fn open_sti_from_slf(slf_path: String, entry_name: String) -> Result<StiFileInfo, CommandError> {
+    let (mut file, archive) = open_slf_archive(&slf_path)?;
+    let entry = archive.find(&entry_name)
+        .ok_or_else(|| CommandError::NotFound(format!("'{}' is not in {}", entry_name, slf_path)))?;
+    
+    let file_data = sti::slf::SlfArchive::read_entry(&mut file, entry)?;
+    let sti_file = StiParser::parse(&file_data)
+        .map_err(|e| CommandError::InvalidFormat(format!("Failed to parse '{}' in {}: {}", entry_name, slf_path, e)))?;
+    
+    let mut info = StiFileInfo::from(&sti_file);
+    info.file_size = file_data.len() as u64;
+    Ok(info)
+}
+
+#[derive(Debug, Serialize)]
+pub struct SalvagedStiFile {
+    pub info: StiFileInfo,
+    pub warnings: Vec<sti::StiWarning>,
+}
+
+// Open a partly corrupt file for inspection: unreadable frames come back blank, with a
+// warning for each. The salvaged parse replaces any cached one, so exports and saves use it
+#[tauri::command]
+async fn open_sti_file_salvage(file_path: String) -> Result<SalvagedStiFile, CommandError> {
+    let path = Path::new(&file_path);
+    if !path.exists() {
+        return Err(CommandError::NotFound("File does not exist".to_string()));
+    }
+    
+    let stamp = FileStamp::of(path);
+    let file_data = fs::read(path)
+        .map_err(|e| CommandError::Io(format!("Failed to read file: {}", e)))?;
+    let (sti_file, warnings) = StiParser::parse_salvage(&file_data)
+        .map_err(|e| CommandError::InvalidFormat(format!("Could not salvage '{}': {}", file_path, e)))?;
+    for warning in &warnings {
+        log::warn!("Salvaging '{}': {}", file_path, warning.message);
+    }
+    
+    let sti_file = Arc::new(sti_file);
+    cache_sti_file(&file_path, sti_file.clone(), stamp)?;
+    
+    let mut info = StiFileInfo::from(sti_file.as_ref());
+    info.file_size = file_data.len() as u64;
+    
+    Ok(SalvagedStiFile { info, warnings })
+}
+
+// Describe a file from its headers alone, without decompressing any frames
+#[tauri::command]
+async fn open_sti_file_fast(file_path: String) -> Result<StiFileInfo, CommandError> {
+    // A full parse that's already cached is just as cheap
+    if let Some(cached) = cached_sti_file(&file_path)? {
+        let mut info = StiFileInfo::from(cached.as_ref());
+        info.file_size = fs::metadata(&file_path)
+            .map_err(|e| CommandError::Io(format!("Failed to get file metadata: {}", e)))?
+            .len();
+        return Ok(info);
+    }
+    
+    let file_data = fs::read(&file_path)
+        .map_err(|e| CommandError::Io(format!("Failed to read file: {}", e)))?;
+    let sti_file = StiParser::parse_headers_only(&file_data)
+        .map_err(|e| CommandError::InvalidFormat(format!("Failed to parse STI headers in '{}': {}", file_path, e)))?;
+    
+    let mut info = StiFileInfo::from(&sti_file);
+    info.file_size = file_data.len() as u64;
+    
+    Ok(info)
+}
+
+// Quick header-only triage for files that fail to load; reads just the first 64 bytes
+#[tauri::command]
+async fn identify_sti(file_path: String) -> Result<sti::FormatIdentification, CommandError> {
+    use std::io::Read;
+    
+    let file = fs::File::open(&file_path).map_err(|e| match e.kind() {
+        std::io::ErrorKind::NotFound => CommandError::NotFound(format!("File does not exist: {}", file_path)),
+        _ => CommandError::Io(format!("Failed to open file: {}", e)),
+    })?;
+    let file_len = file.metadata()?.len();
+    
+    let mut header = Vec::with_capacity(64);
+    file.take(64).read_to_end(&mut header)?;
+    
+    Ok(StiParser::identify(&header, file_len))
+}
+
 #[tauri::command]
-async fn debug_sti_file(file_path: String) -> Result<String, String> {
+async fn debug_sti_file(file_path: String) -> Result<String, CommandError> {
     let path = Path::new(&file_path);
     
     if !path.exists() {
-        return Err("File does not exist".to_string());
+        return Err(CommandError::NotFound("File does not exist".to_string()));
     }
     
     let file_data = fs::read(path)
-        .map_err(|e| format!("Failed to read file: {}", e))?;
+        .map_err(|e| CommandError::Io(format!("Failed to read file: {}", e)))?;
     
     if file_data.len() < 64 {
         return Ok(format!("File too small: {} bytes (need at least 64 for header)", file_data.len()));
@@ -201,6 +427,39 @@ async fn debug_sti_file(file_path: String) -> Result<String, String> {
     match StiParser::parse(&file_data) {
         Ok(sti_file) => {
             debug_info.push_str(&format!("\nParsing: SUCCESS\nImages loaded: {}\n", sti_file.images.len()));
+            if flags.alpha {
+                let found = sti_file.images.iter().filter(|image| image.alpha.is_some()).count();
+                debug_info.push_str(&if sti_file.is_8bit() && found > 0 {
+                    format!("Alpha table: {} of {} images\n", found, sti_file.images.len())
+                } else if sti_file.is_8bit() {
+                    "Alpha table: flag set but no table follows the image data, alpha ignored\n".to_string()
+                } else {
+                    "Alpha flag set: 16-bit alpha comes from the alpha mask instead\n".to_string()
+                });
+            }
+            
+            // Re-run the decoders to show what each image's stored bytes actually produce
+            debug_info.push_str("\nImage decoding (declared size / consumed / decompressed / expected):\n");
+            for report in StiParser::decode_report(&sti_file) {
+                debug_info.push_str(&format!(
+                    "  Image {}: {} / {} / {} / {}{}\n",
+                    report.index,
+                    report.declared_size,
+                    report.consumed,
+                    report.decompressed_len,
+                    report.expected_len,
+                    if report.has_mismatch() { "  MISMATCH" } else { "" }
+                ));
+                if let Some(etrle) = report.etrle.as_ref().filter(|etrle| !etrle.is_clean()) {
+                    debug_info.push_str(&format!(
+                        "    Salvaged: {} pixels padded, {} truncated, {} rows wrapped, {} trailing bytes\n",
+                        etrle.padded_pixels, etrle.truncated_pixels, etrle.wrapped_rows, etrle.trailing_bytes
+                    ));
+                }
+                if let Some(error) = &report.error {
+                    debug_info.push_str(&format!("    Error: {}\n", error));
+                }
+            }
         }
         Err(e) => {
             debug_info.push_str(&format!("\nParsing: FAILED\nError: {}\n", e));
@@ -210,101 +469,363 @@ async fn debug_sti_file(file_path: String) -> Result<String, String> {
     Ok(debug_info)
 }
 
+// One frame's entry from debug_sti_file's decode section: clean decode or best-effort salvage
 #[tauri::command]
-async fn get_sti_image(file_path: String, image_index: usize) -> Result<StiImageData, String> {
-    // Try to get from cache first
-    let cached_file = {
-        let cache = STI_CACHE.lock().unwrap();
-        cache.get(&file_path).cloned()
-    };
-    
-    let sti_file = if let Some(cached) = cached_file {
-        cached
-    } else {
-        // Parse and cache the file
-        let path = Path::new(&file_path);
-        let file_data = fs::read(path)
-            .map_err(|e| format!("Failed to read file: {}", e))?;
-        
-        let parsed_file = StiParser::parse(&file_data)
-            .map_err(|e| format!("Failed to parse STI file: {}", e))?;
-        
-        let arc_file = Arc::new(parsed_file);
-        
-        // Cache the parsed file
-        {
-            let mut cache = STI_CACHE.lock().unwrap();
-            // Limit cache size to prevent memory issues
-            if cache.len() > 50 {
-                cache.clear(); // Simple eviction strategy
-            }
-            cache.insert(file_path.clone(), arc_file.clone());
-        }
-        
-        arc_file
-    };
+async fn analyze_frame(file_path: String, index: usize) -> Result<sti::ImageDecodeReport, CommandError> {
+    let sti_file = load_sti_file(&file_path)?;
+    StiParser::frame_decode_report(&sti_file, index)
+        .ok_or_else(|| CommandError::OutOfBounds(format!("Image index {} out of bounds", index)))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RawFrameBytes {
+    pub data: Vec<u8>,                     // Stored bytes as read from disk (ETRLE/ZLIB for 8-bit)
+    pub decompressed_len: Option<usize>,   // None if the frame hasn't been decoded
+}
+
+// Per-frame companion to debug_sti_file; edits made since the file was read aren't re-encoded here
+#[tauri::command]
+async fn get_raw_frame_bytes(file_path: String, image_index: usize) -> Result<RawFrameBytes, CommandError> {
+    let sti_file = load_sti_file(&file_path)?;
+    let image = sti_file.images.get(image_index)
+        .ok_or_else(|| CommandError::OutOfBounds("Image index out of bounds".to_string()))?;
+    
+    Ok(RawFrameBytes {
+        data: image.raw_data.clone(),
+        decompressed_len: image.decompressed_data.as_ref().map(|data| data.len()),
+    })
+}
+
+#[tauri::command]
+async fn get_sti_image(file_path: String, image_index: usize) -> Result<StiImageData, CommandError> {
+    let sti_file = load_sti_file(&file_path)?;
+    frame_image_data(&sti_file, image_index)
+}
+
+// Filmstrip read: one cache lookup for many frames. The palette is the same for every
+// frame, so only the first returned frame carries it
+#[tauri::command]
+async fn get_sti_images(file_path: String, indices: Option<Vec<usize>>) -> Result<Vec<StiImageData>, CommandError> {
+    let sti_file = load_sti_file(&file_path)?;
+    let indices = indices.unwrap_or_else(|| (0..sti_file.images.len()).collect());
+    if let Some(&index) = indices.iter().find(|&&index| index >= sti_file.images.len()) {
+        return Err(CommandError::OutOfBounds(format!(
+            "Image index {} out of bounds ({} images)", index, sti_file.images.len()
+        )));
+    }
     
+    let mut frames = indices.par_iter()
+        .map(|&index| frame_image_data(&sti_file, index))
+        .collect::<Result<Vec<_>, _>>()?;
+    for frame in frames.iter_mut().skip(1) {
+        frame.palette = None;
+    }
+    Ok(frames)
+}
+
+fn frame_image_data(sti_file: &StiFile, image_index: usize) -> Result<StiImageData, CommandError> {
     if image_index >= sti_file.images.len() {
-        return Err("Image index out of bounds".to_string());
+        return Err(CommandError::OutOfBounds("Image index out of bounds".to_string()));
     }
     
     let image = &sti_file.images[image_index];
-    let pixel_data = image.decompressed_data.as_ref()
-        .ok_or("Image data not decompressed")?;
+    let decoded;
+    let pixel_data = match &image.decompressed_data {
+        Some(data) => data,
+        None => {
+            // Frames skipped by a ranged parse are decoded on demand
+            decoded = StiParser::decompress_image(sti_file, image_index)
+                .map_err(|e| CommandError::InvalidFormat(format!("Failed to decompress image {}: {}", image_index, e)))?
+                .ok_or("Image data not decompressed")?;
+            &decoded
+        }
+    };
     
     let palette = sti_file.palette.map(|p| p.to_vec());
     
+    let (rgb_data, alpha_data) = match sti_file.format() {
+        sti::StiFormat::Rgb16 => {
+            let has_alpha = sti_file.header.decode_alpha16(0).is_some();
+            let mut rgb = Vec::with_capacity(pixel_data.len() / 2 * 3);
+            let mut alpha = Vec::with_capacity(if has_alpha { pixel_data.len() / 2 } else { 0 });
+            for chunk in pixel_data.chunks_exact(2) {
+                let value = u16::from_le_bytes([chunk[0], chunk[1]]);
+                rgb.extend_from_slice(&sti_file.header.decode_rgb16(value));
+                if let Some(value) = sti_file.header.decode_alpha16(value) {
+                    alpha.push(value);
+                }
+            }
+            (Some(rgb), if has_alpha { Some(alpha) } else { None })
+        }
+        sti::StiFormat::Indexed8 => (None, None),
+        sti::StiFormat::Unknown => return Err(ambiguous_format_error(sti_file)),
+    };
+    
     Ok(StiImageData {
         width: image.width,
         height: image.height,
         data: pixel_data.clone(),
         palette,
+        rgb_data,
+        alpha_data,
+        offset_x: image.header.as_ref().map_or(0, |header| header.offset_x),
+        offset_y: image.header.as_ref().map_or(0, |header| header.offset_y),
+    })
+}
+
+// Rows x..x+width of every line in y..y+height, for a buffer with `channels` bytes per pixel
+fn crop_rows(data: &[u8], frame_width: usize, channels: usize, x: usize, y: usize, width: usize, height: usize) -> Vec<u8> {
+    let row_len = frame_width * channels;
+    (y..y + height)
+        .flat_map(|row| &data[row * row_len + x * channels..row * row_len + (x + width) * channels])
+        .copied()
+        .collect()
+}
+
+// Part of a frame for a zoomed viewport, clipped to the frame. The offsets place the region
+// the way the frame's own offsets place the frame
+#[tauri::command]
+async fn get_region(file_path: String, image_index: usize, x: u16, y: u16, w: u16, h: u16) -> Result<StiImageData, CommandError> {
+    let sti_file = load_sti_file(&file_path)?;
+    let frame = frame_image_data(&sti_file, image_index)?;
+    
+    if x >= frame.width || y >= frame.height || w == 0 || h == 0 {
+        return Err(CommandError::OutOfBounds(format!(
+            "Region {}x{} at ({}, {}) lies outside the {}x{} frame",
+            w, h, x, y, frame.width, frame.height
+        )));
+    }
+    let width = w.min(frame.width - x) as usize;
+    let height = h.min(frame.height - y) as usize;
+    let (offset_x, offset_y) = (shift_offset(frame.offset_x, x)?, shift_offset(frame.offset_y, y)?);
+    let (frame_width, x, y) = (frame.width as usize, x as usize, y as usize);
+    
+    let bytes_per_pixel = if sti_file.is_16bit() { 2 } else { 1 };
+    if frame.data.len() != frame_width * frame.height as usize * bytes_per_pixel {
+        return Err(CommandError::InvalidFormat(format!(
+            "Image {} has {} bytes of pixel data, too few for {}x{}",
+            image_index, frame.data.len(), frame.width, frame.height
+        )));
+    }
+    let crop = |data: &[u8], channels: usize| crop_rows(data, frame_width, channels, x, y, width, height);
+    
+    Ok(StiImageData {
+        width: width as u16,
+        height: height as u16,
+        data: crop(&frame.data, bytes_per_pixel),
+        rgb_data: frame.rgb_data.as_deref().map(|rgb| crop(rgb, 3)),
+        alpha_data: frame.alpha_data.as_deref().map(|alpha| crop(alpha, 1)),
+        palette: frame.palette,
+        offset_x,
+        offset_y,
     })
 }
 
+// Move a frame offset right or down by `delta` pixels, refusing results an i16 can't hold
+fn shift_offset(offset: i16, delta: u16) -> Result<i16, CommandError> {
+    i16::try_from(offset as i32 + delta as i32).map_err(|_| CommandError::OutOfBounds(format!(
+        "Offset {} moved by {} pixels is outside the -32768..32767 offset range", offset, delta
+    )))
+}
+
 #[tauri::command]
-async fn get_sti_metadata(file_path: String) -> Result<serde_json::Value, String> {
-    // Try to get from cache first
-    let cached_file = {
-        let cache = STI_CACHE.lock().unwrap();
-        cache.get(&file_path).cloned()
+async fn get_sti_metadata(file_path: String) -> Result<serde_json::Value, CommandError> {
+    let sti_file = load_sti_file(&file_path)?;
+    
+    let mut metadata = serde_json::to_value(&sti_file.header)
+        .map_err(|e| format!("Failed to serialize metadata: {}", e))?;
+    
+    // Header fields stay at the top level; per-image and palette details are added alongside
+    let images: Vec<serde_json::Value> = sti_file.images.iter()
+        .map(|image| {
+            let (offset_x, offset_y, data_size) = match &image.header {
+                Some(header) => (header.offset_x, header.offset_y, header.data_size),
+                None => (0, 0, image.raw_data.len() as u32),
+            };
+            serde_json::json!({
+                "width": image.width,
+                "height": image.height,
+                "offset_x": offset_x,
+                "offset_y": offset_y,
+                "data_size": data_size,
+            })
+        })
+        .collect();
+    
+    let palette_color_count = if sti_file.palette.is_some() { sti_file.header.palette_colors } else { 0 };
+    let animation_frame_counts: Vec<u8> = sti_file.animation_data.iter()
+        .map(|anim| anim.frame_count)
+        .collect();
+    
+    if let Some(object) = metadata.as_object_mut() {
+        // 8-bit headers store 0 here, report the size the frames actually cover
+        let (width, height) = sti_file.dimensions();
+        object.insert("width".to_string(), serde_json::json!(width));
+        object.insert("height".to_string(), serde_json::json!(height));
+        object.insert("images".to_string(), serde_json::json!(images));
+        object.insert("palette_color_count".to_string(), serde_json::json!(palette_color_count));
+        object.insert("animation_frame_counts".to_string(), serde_json::json!(animation_frame_counts));
+    }
+    
+    Ok(metadata)
+}
+
+// Unsaved edits to a file that another program has since changed on disk; saving them
+// would overwrite that change
+fn has_edit_conflict(entry: &CachedStiFile, current_stamp: &Option<FileStamp>) -> bool {
+    entry.dirty && !entry.in_memory && entry.stamp != *current_stamp
+}
+
+// Return the cached parse of a file if it hasn't changed on disk since it was cached.
+// Unsaved edits are never dropped, a conflicting disk change is only reported
+fn cached_sti_file(file_path: &str) -> Result<Option<Arc<StiFile>>, String> {
+    let current_stamp = FileStamp::of(Path::new(file_path));
+    
+    let mut cache = lock_cache(&STI_CACHE)?;
+    let is_fresh = match cache.get(file_path) {
+        Some(entry) if has_edit_conflict(entry, &current_stamp) => {
+            log::warn!("{} changed on disk while it has unsaved edits, keeping the edits", file_path);
+            true
+        }
+        Some(entry) => entry.in_memory || (entry.stamp.is_some() && entry.stamp == current_stamp),
+        None => return Ok(None),
     };
     
-    let sti_file = if let Some(cached) = cached_file {
-        cached
+    if is_fresh {
+        Ok(cache.get(file_path).map(|entry| entry.file.clone()))
     } else {
-        // Parse and cache the file
-        let path = Path::new(&file_path);
-        let file_data = fs::read(path)
-            .map_err(|e| format!("Failed to read file: {}", e))?;
-        
-        let parsed_file = StiParser::parse(&file_data)
-            .map_err(|e| format!("Failed to parse STI file: {}", e))?;
-        
-        let arc_file = Arc::new(parsed_file);
-        
-        // Cache the parsed file
-        {
-            let mut cache = STI_CACHE.lock().unwrap();
-            // Limit cache size to prevent memory issues
-            if cache.len() > 50 {
-                cache.clear(); // Simple eviction strategy
-            }
-            cache.insert(file_path.clone(), arc_file.clone());
-        }
-        
-        arc_file
+        // Modified or deleted behind our back
+        cache.remove(file_path);
+        Ok(None)
+    }
+}
+
+// Cache a parsed file together with the stamp taken before it was read
+fn cache_sti_file(file_path: &str, sti_file: Arc<StiFile>, stamp: Option<FileStamp>) -> Result<(), String> {
+    insert_cache_entry(file_path, CachedStiFile {
+        file: sti_file,
+        stamp,
+        dirty: false,
+        in_memory: false,
+    })
+}
+
+// Current cache limits, falling back to the defaults if the config lock was poisoned
+fn cache_config() -> CacheConfig {
+    lock_cache(&CACHE_CONFIG).map(|config| *config).unwrap_or_default()
+}
+
+fn insert_cache_entry(file_path: &str, entry: CachedStiFile) -> Result<(), String> {
+    let sti_max = cache_config().sti_max;
+    let mut cache = lock_cache(&STI_CACHE)?;
+    // Limit cache size to prevent memory issues
+    if cache.len() > sti_max {
+        // Simple eviction strategy, but never drop unsaved edits or files that can't be re-read
+        cache.retain(|_, entry| entry.dirty || entry.in_memory);
+    }
+    cache.insert(file_path.to_string(), entry);
+    Ok(())
+}
+
+// Replace the cached parse with an in-memory edit that a later save will persist
+fn store_edited_sti_file(file_path: &str, sti_file: StiFile) -> Result<(), String> {
+    let stamp = FileStamp::of(Path::new(file_path));
+    let in_memory = lock_cache(&STI_CACHE)?.get(file_path).is_some_and(|entry| entry.in_memory);
+    insert_cache_entry(file_path, CachedStiFile {
+        file: Arc::new(sti_file),
+        stamp,
+        dirty: true,
+        in_memory,
+    })
+}
+
+// Move a cached parse to a new key. Clean entries keep the stamp they were read with, so a
+// rename (which keeps size and mtime) stays fresh while a different file at `new_path` is
+// re-parsed; unsaved edits follow the file whatever is on disk there.
+fn rekey_cache_entry(cache: &mut HashMap<String, CachedStiFile>, old_path: &str, new_path: &str) -> bool {
+    let mut entry = match cache.remove(old_path) {
+        Some(entry) => entry,
+        None => return false,
     };
+    if entry.dirty || entry.in_memory {
+        entry.stamp = FileStamp::of(Path::new(new_path));
+    }
+    cache.insert(new_path.to_string(), entry);
+    true
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct CacheInvalidated {
+    file_path: String,
+}
+
+// Tell open views a file's cached state is stale; best-effort, since a missing window must not fail the edit
+fn notify_cache_invalidated(app: &tauri::AppHandle, file_path: &str) {
+    let _ = app.emit("sti-cache-invalidated", CacheInvalidated { file_path: file_path.to_string() });
+}
+
+// Files whose flags set neither or both of rgb/indexed can't be decoded without guessing
+fn ambiguous_format_error(sti_file: &StiFile) -> CommandError {
+    let flags = sti_file.header.flags;
+    CommandError::Unsupported(format!(
+        "Unsupported or ambiguous STI format: indexed={} rgb={}, expected exactly one",
+        flags.indexed, flags.rgb
+    ))
+}
+
+// Load an STI file through the parse cache
+fn load_sti_file(file_path: &str) -> Result<Arc<StiFile>, CommandError> {
+    if let Some(cached) = cached_sti_file(file_path)? {
+        return Ok(cached);
+    }
+    
+    let stamp = FileStamp::of(Path::new(file_path));
+    let file_data = fs::read(Path::new(file_path))
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => CommandError::NotFound(format!("Failed to read file: {}", e)),
+            _ => CommandError::Io(format!("Failed to read file: {}", e)),
+        })?;
+    
+    let parsed_file = StiParser::parse(&file_data)
+        .map_err(|e| {
+            log::warn!("Failed to parse {}: {}", file_path, e);
+            CommandError::InvalidFormat(format!("Failed to parse STI file: {}", e))
+        })?;
+    
+    let arc_file = Arc::new(parsed_file);
+    cache_sti_file(file_path, arc_file.clone(), stamp)?;
     
-    serde_json::to_value(&sti_file.header)
-        .map_err(|e| format!("Failed to serialize metadata: {}", e))
+    Ok(arc_file)
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct EditableImage {
-    pub width: u16,
-    pub height: u16,
-    pub data: Vec<u8>, // Palette indices for 8-bit, RGB565 bytes for 16-bit
+// Decode a frame into per-pixel colors, None marks a transparent pixel
+fn decode_frame_colors(sti_file: &StiFile, image: &sti::StiImage) -> Result<Vec<Option<[u8; 3]>>, String> {
+    let pixel_data = image.decompressed_data.as_ref()
+        .ok_or("Image data not decompressed")?;
+    
+    if sti_file.is_8bit() {
+        let palette = sti_file.palette.as_ref()
+            .ok_or("8-bit image missing palette")?;
+        let transparent_index = sti_file.header.etrle_transparent_index();
+        
+        Ok(pixel_data.iter()
+            .map(|&index| if index == transparent_index { None } else { Some(palette[index as usize]) })
+            .collect())
+    } else {
+        let transparent = sti_file.header.flags.transparent;
+        let transparent_value = sti_file.header.transparent_color;
+        
+        Ok(pixel_data.chunks_exact(2)
+            .map(|chunk| {
+                let value = u16::from_le_bytes([chunk[0], chunk[1]]);
+                if transparent && value as u32 == transparent_value {
+                    None
+                } else {
+                    Some(sti_file.header.decode_rgb16(value))
+                }
+            })
+            .collect())
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -325,80 +846,24 @@ pub struct ImportOptions {
     pub transparent_color: Option<u8>, // Palette index for transparency
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct EditableStiFile {
-    pub file_path: String,
-    pub is_8bit: bool,
-    pub is_16bit: bool,
-    pub palette: Option<Vec<[u8; 3]>>,
-    pub images: Vec<EditableImage>,
-    pub transparent_color: u32,
-    pub flags: u32,
-}
-
 #[tauri::command]
-async fn enter_edit_mode(file_path: String) -> Result<EditableStiFile, String> {
-    // Try to get from cache first
-    let cached_file = {
-        let cache = STI_CACHE.lock().unwrap();
-        cache.get(&file_path).cloned()
-    };
-    
-    let sti_file = if let Some(cached) = cached_file {
-        cached
-    } else {
-        // Parse and cache the file
-        let path = Path::new(&file_path);
-        let file_data = fs::read(path)
-            .map_err(|e| format!("Failed to read file: {}", e))?;
-        
-        let parsed_file = StiParser::parse(&file_data)
-            .map_err(|e| format!("Failed to parse STI file: {}", e))?;
-        
-        let arc_file = Arc::new(parsed_file);
-        
-        // Cache the parsed file
-        {
-            let mut cache = STI_CACHE.lock().unwrap();
-            if cache.len() > 50 {
-                cache.clear();
-            }
-            cache.insert(file_path.clone(), arc_file.clone());
-        }
-        
-        arc_file
-    };
-    
-    // Convert to editable format
-    let mut editable_images = Vec::new();
-    for image in &sti_file.images {
-        let pixel_data = image.decompressed_data.as_ref()
-            .ok_or("Image data not decompressed")?;
-        
-        editable_images.push(EditableImage {
-            width: image.width,
-            height: image.height,
-            data: pixel_data.clone(),
-        });
+async fn enter_edit_mode(file_path: String) -> Result<EditableStiFile, CommandError> {
+    let sti_file = load_sti_file(&file_path)?;
+    if sti_file.format() == sti::StiFormat::Unknown {
+        return Err(ambiguous_format_error(&sti_file));
     }
     
-    Ok(EditableStiFile {
-        file_path: file_path.clone(),
-        is_8bit: sti_file.is_8bit(),
-        is_16bit: sti_file.is_16bit(),
-        palette: sti_file.palette.map(|p| p.to_vec()),
-        images: editable_images,
-        transparent_color: sti_file.header.transparent_color,
-        flags: sti_file.header.flags.into(),
-    })
+    let mut editable = sti::ops::editable_from_sti(&sti_file)?;
+    editable.file_path = file_path.clone();
+    Ok(editable)
 }
 
 #[tauri::command]
-async fn update_image_data(_file_path: String, _image_index: usize, image_data: EditableImage) -> Result<(), String> {
+async fn update_image_data(_file_path: String, _image_index: usize, image_data: EditableImage) -> Result<(), CommandError> {
     // For now, just validate the operation - actual implementation would update cached data
     if image_data.data.len() != (image_data.width as usize * image_data.height as usize) &&
        image_data.data.len() != (image_data.width as usize * image_data.height as usize * 2) {
-        return Err("Invalid image data size".to_string());
+        return Err(CommandError::InvalidFormat("Invalid image data size".to_string()));
     }
     
     // TODO: Update the cached STI file with new image data
@@ -406,10 +871,10 @@ async fn update_image_data(_file_path: String, _image_index: usize, image_data:
 }
 
 #[tauri::command]
-async fn create_sti_backup(file_path: String) -> Result<String, String> {
+async fn create_sti_backup(file_path: String) -> Result<String, CommandError> {
     let path = Path::new(&file_path);
     if !path.exists() {
-        return Err("STI file does not exist".to_string());
+        return Err(CommandError::NotFound("STI file does not exist".to_string()));
     }
     
     // Create backup path with timestamp
@@ -418,84 +883,288 @@ async fn create_sti_backup(file_path: String) -> Result<String, String> {
         .unwrap()
         .as_secs();
     let backup_path = format!("{}.backup.{}", file_path, timestamp);
+    check_write_allowed(&backup_path)?;
     
     // Copy the file to backup location
     fs::copy(&file_path, &backup_path)
-        .map_err(|e| format!("Failed to create backup: {}", e))?;
+        .map_err(|e| CommandError::Io(format!("Failed to create backup: {}", e)))?;
     
     Ok(backup_path)
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IntegrityReport {
+    pub parses: bool,
+    pub header_sizes_consistent: bool,
+    pub all_frames_decompressed: bool, // Every frame decoded to exactly width * height pixels
+    pub content_hash: Option<String>,  // blake3 of the concatenated decompressed frames
+    pub error: Option<String>,
+}
+
 #[tauri::command]
-async fn validate_sti_integrity(file_path: String) -> Result<bool, String> {
-    let path = Path::new(&file_path);
+async fn validate_sti_integrity(file_path: String) -> Result<bool, CommandError> {
+    let report = check_sti_integrity(file_path).await?;
+    Ok(report.parses)
+}
+
+#[tauri::command]
+async fn check_sti_integrity(file_path: String) -> Result<IntegrityReport, CommandError> {
+    Ok(integrity_report(Path::new(&file_path)))
+}
+
+fn integrity_report(path: &Path) -> IntegrityReport {
+    let mut report = IntegrityReport {
+        parses: false,
+        header_sizes_consistent: false,
+        all_frames_decompressed: false,
+        content_hash: None,
+        error: None,
+    };
+    
     if !path.exists() {
-        return Ok(false);
+        report.error = Some("File does not exist".to_string());
+        return report;
     }
     
-    // Try to parse the STI file to validate integrity
-    match fs::read(path) {
-        Ok(file_data) => {
-            match StiParser::parse(&file_data) {
-                Ok(_) => Ok(true),
-                Err(_) => Ok(false),
-            }
-        },
-        Err(_) => Ok(false),
+    // Always re-read from disk, the cache may hold unsaved edits
+    let file_data = match fs::read(path) {
+        Ok(data) => data,
+        Err(e) => {
+            report.error = Some(format!("Failed to read file: {}", e));
+            return report;
+        }
+    };
+    
+    let sti_file = match StiParser::parse(&file_data) {
+        Ok(sti_file) => sti_file,
+        Err(e) => {
+            report.error = Some(e.to_string());
+            return report;
+        }
+    };
+    report.parses = true;
+    report.header_sizes_consistent = StiParser::validate_sizes(&sti_file).is_ok();
+    
+    let bytes_per_pixel = if sti_file.is_16bit() { 2 } else { 1 };
+    report.all_frames_decompressed = sti_file.images.iter().all(|image| {
+        image.decompressed_data.as_ref()
+            .is_some_and(|data| data.len() == image.width as usize * image.height as usize * bytes_per_pixel)
+    });
+    
+    let mut hasher = blake3::Hasher::new();
+    for data in sti_file.images.iter().filter_map(|image| image.decompressed_data.as_ref()) {
+        hasher.update(data);
     }
+    report.content_hash = Some(hasher.finalize().to_hex().to_string());
+    
+    report
 }
 
-#[tauri::command]
-async fn restore_sti_from_backup(file_path: String, backup_path: String) -> Result<(), String> {
-    let backup = Path::new(&backup_path);
-    if !backup.exists() {
-        return Err("Backup file does not exist".to_string());
+// Integrity problems of one file as short messages, empty when the file is sound
+fn integrity_issues(report: &IntegrityReport) -> Vec<String> {
+    let mut issues = Vec::new();
+    if let Some(error) = &report.error {
+        issues.push(error.clone());
+    }
+    if report.parses && !report.header_sizes_consistent {
+        issues.push("Header sizes don't match the image data".to_string());
+    }
+    if report.parses && !report.all_frames_decompressed {
+        issues.push("Not every frame decodes to width * height pixels".to_string());
+    }
+    issues
+}
+
+// Integrity check of every STI file under a directory; a broken file is reported, never fatal
+#[tauri::command]
+async fn validate_directory(directory_path: String, recursive: bool) -> Result<Vec<serde_json::Value>, CommandError> {
+    let mut sti_paths = Vec::new();
+    scan_directory_for_sti(Path::new(&directory_path), &mut sti_paths, recursive)?;
+    
+    Ok(sti_paths.par_iter()
+        .map(|path| {
+            let report = integrity_report(Path::new(path));
+            serde_json::json!({
+                "path": path,
+                "parses": report.parses,
+                "issues": integrity_issues(&report),
+            })
+        })
+        .collect())
+}
+
+// Re-encode every frame with the minimal ETRLE encoder, writing (with a backup) only if the file shrinks.
+// Files saved by tools that split runs early or pad frames get smaller; ours are already minimal
+#[tauri::command]
+async fn optimize_compression(app: tauri::AppHandle, file_path: String) -> Result<serde_json::Value, CommandError> {
+    let sti_file = load_sti_file(&file_path)?;
+    if !sti_file.is_8bit() || !sti_file.header.flags.etrle_compressed {
+        return Err(CommandError::Unsupported("Only ETRLE-compressed 8-bit files can be recompressed".to_string()));
+    }
+    if let Some(index) = sti_file.images.iter().position(|image| image.decompressed_data.is_none()) {
+        return Err(CommandError::InvalidFormat(format!("Image {} failed to decompress", index)));
     }
     
-    // Copy backup back to original location
-    fs::copy(&backup_path, &file_path)
-        .map_err(|e| format!("Failed to restore from backup: {}", e))?;
-    
-    // Clear cache to force reload
-    {
-        let mut cache = STI_CACHE.lock().unwrap();
-        cache.remove(&file_path);
+    let mut optimized = (*sti_file).clone();
+    sti::builder::encode_frames(&mut optimized, &[])?;
+    
+    let stored_size = |sti_file: &StiFile| sti_file.images.iter().map(|image| image.raw_data.len()).sum::<usize>();
+    let before = stored_size(&sti_file);
+    let after = stored_size(&optimized);
+    let frames_shrunk = sti_file.images.iter().zip(&optimized.images)
+        .filter(|(old, new)| new.raw_data.len() < old.raw_data.len())
+        .count();
+    
+    let written = after < before;
+    if written {
+        create_sti_backup(file_path.clone()).await?;
+        write_sti_file(&file_path, &mut optimized)?;
+        notify_cache_invalidated(&app, &file_path);
     }
     
+    Ok(serde_json::json!({
+        "before": before,
+        "after": after,
+        "frames_shrunk": frames_shrunk,
+        "written": written,
+    }))
+}
+
+// Re-read frames back to back, ignoring stored data offsets, and rewrite correct ones
+#[tauri::command]
+async fn repair_offsets(app: tauri::AppHandle, file_path: String) -> Result<(), CommandError> {
+    let file_data = fs::read(&file_path)
+        .map_err(|e| CommandError::Io(format!("Failed to read file: {}", e)))?;
+    
+    let options = sti::ParseOptions { sequential_offsets: true, ..sti::ParseOptions::default() };
+    let mut sti_file = StiParser::parse_with_options(&file_data, &options)
+        .map_err(|e| CommandError::InvalidFormat(format!("Failed to parse STI file: {}", e)))?;
+    
+    create_sti_backup(file_path.clone()).await?;
+    write_sti_file(&file_path, &mut sti_file)?;
+    notify_cache_invalidated(&app, &file_path);
+    
     Ok(())
 }
 
 #[tauri::command]
-async fn add_new_image(file_path: String, image_data: EditableImage, position: Option<usize>) -> Result<usize, String> {
-    // Validate image data
-    if image_data.data.len() != (image_data.width as usize * image_data.height as usize) &&
-       image_data.data.len() != (image_data.width as usize * image_data.height as usize * 2) {
-        return Err("Invalid image data size".to_string());
+async fn restore_sti_from_backup(app: tauri::AppHandle, file_path: String, backup_path: String, force: Option<bool>) -> Result<Option<StiFileInfo>, CommandError> {
+    check_write_allowed(&file_path)?;
+    let backup = Path::new(&backup_path);
+    if !backup.exists() {
+        return Err(CommandError::NotFound("Backup file does not exist".to_string()));
     }
     
-    // Try to get cached STI file or parse it
-    let cached_file = {
-        let cache = STI_CACHE.lock().unwrap();
-        cache.get(&file_path).cloned()
+    // A corrupt backup would silently replace a good file, so only force skips the check
+    let backup_data = fs::read(backup)
+        .map_err(|e| CommandError::Io(format!("Failed to read backup: {}", e)))?;
+    let parsed = match StiParser::parse(&backup_data) {
+        Ok(sti_file) => Some(sti_file),
+        Err(e) if force.unwrap_or(false) => {
+            log::warn!("Restoring {} from backup {} that doesn't parse: {}", file_path, backup_path, e);
+            None
+        }
+        Err(e) => return Err(CommandError::InvalidFormat(format!(
+            "Backup '{}' is not a valid STI file, refusing to restore it: {}", backup_path, e
+        ))),
     };
     
-    let mut sti_file = if let Some(cached) = cached_file {
-        (*cached).clone()
-    } else {
-        let path = Path::new(&file_path);
-        let file_data = fs::read(path)
-            .map_err(|e| format!("Failed to read file: {}", e))?;
-        StiParser::parse(&file_data)
-            .map_err(|e| format!("Failed to parse STI file: {}", e))?
+    // Copy backup back to original location
+    fs::copy(&backup_path, &file_path)
+        .map_err(|e| CommandError::Io(format!("Failed to restore from backup: {}", e)))?;
+    
+    // Clear cache to force reload
+    {
+        let mut cache = lock_cache(&STI_CACHE)?;
+        cache.remove(&file_path);
+    }
+    notify_cache_invalidated(&app, &file_path);
+    
+    Ok(parsed.map(|sti_file| {
+        let mut info = StiFileInfo::from(&sti_file);
+        info.file_size = backup_data.len() as u64;
+        info
+    }))
+}
+
+// Find `<file>.backup.<unix_ts>` siblings of a file, newest first
+fn find_backups(file_path: &str) -> Result<Vec<(std::path::PathBuf, u64)>, String> {
+    let path = Path::new(file_path);
+    let file_name = path.file_name()
+        .and_then(|name| name.to_str())
+        .ok_or("Invalid file path")?;
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
     };
+    let prefix = format!("{}.backup.", file_name);
+    
+    let entries = fs::read_dir(parent)
+        .map_err(|e| format!("Failed to read directory {}: {}", parent.display(), e))?;
+    
+    let mut backups = Vec::new();
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        let timestamp = name.to_str()
+            .and_then(|name| name.strip_prefix(&prefix))
+            .and_then(|suffix| suffix.parse::<u64>().ok());
+        if let Some(timestamp) = timestamp {
+            backups.push((entry.path(), timestamp));
+        }
+    }
+    
+    backups.sort_by_key(|&(_, timestamp)| std::cmp::Reverse(timestamp));
+    Ok(backups)
+}
+
+#[tauri::command]
+async fn list_backups(file_path: String) -> Result<Vec<serde_json::Value>, CommandError> {
+    let backups = find_backups(&file_path)?;
+    
+    Ok(backups.into_iter()
+        .map(|(path, timestamp)| {
+            let size = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+            serde_json::json!({
+                "path": path.to_string_lossy(),
+                "timestamp": timestamp,
+                "size": size,
+            })
+        })
+        .collect())
+}
+
+#[tauri::command]
+async fn prune_backups(file_path: String, keep: usize) -> Result<usize, CommandError> {
+    let backups = find_backups(&file_path)?;
+    
+    let mut removed = 0;
+    for (path, _) in backups.iter().skip(keep) {
+        check_write_allowed(&path.to_string_lossy())?;
+        fs::remove_file(path)
+            .map_err(|e| CommandError::Io(format!("Failed to remove backup {}: {}", path.display(), e)))?;
+        removed += 1;
+    }
+    
+    Ok(removed)
+}
+
+// Insert a frame into an in-memory file, returning its position
+fn insert_new_image(sti_file: &mut StiFile, image_data: EditableImage, position: Option<usize>) -> Result<usize, CommandError> {
+    // Validate image data (indices, RGB565 bytes, or RGB888 for 16-bit files)
+    let pixel_count = image_data.width as usize * image_data.height as usize;
+    if image_data.data.len() != pixel_count &&
+       image_data.data.len() != pixel_count * 2 &&
+       image_data.data.len() != pixel_count * 3 {
+        return Err(CommandError::InvalidFormat("Invalid image data size".to_string()));
+    }
     
     // Create new STI image
     let mut new_image = if sti_file.is_8bit() {
         let sub_header = sti::StiSubImageHeader {
             data_offset: 0,
             data_size: 0,
-            offset_x: 0,
-            offset_y: 0,
+            offset_x: image_data.offset_x,
+            offset_y: image_data.offset_y,
             height: image_data.height,
             width: image_data.width,
         };
@@ -515,43 +1184,23 @@ async fn add_new_image(file_path: String, image_data: EditableImage, position: O
     sti_file.images.insert(insert_pos, new_image);
     sti_file.header.num_images = sti_file.images.len() as u16;
     
-    // Save the modified STI file
-    save_modified_sti_file(&file_path, &sti_file).await?;
-    
     Ok(insert_pos)
 }
 
-#[tauri::command]
-async fn reorder_images(file_path: String, new_order: Vec<usize>) -> Result<(), String> {
-    
-    // Get cached STI file or parse it
-    let cached_file = {
-        let cache = STI_CACHE.lock().unwrap();
-        cache.get(&file_path).cloned()
-    };
-    
-    let mut sti_file = if let Some(cached) = cached_file {
-        (*cached).clone()
-    } else {
-        let path = Path::new(&file_path);
-        let file_data = fs::read(path)
-            .map_err(|e| format!("Failed to read file: {}", e))?;
-        StiParser::parse(&file_data)
-            .map_err(|e| format!("Failed to parse STI file: {}", e))?
-    };
-    
+// Put an in-memory file's frames in the given order
+fn apply_image_order(sti_file: &mut StiFile, new_order: &[usize]) -> Result<(), CommandError> {
     // Validate new_order
     if new_order.len() != sti_file.images.len() {
-        return Err("New order length doesn't match image count".to_string());
+        return Err(CommandError::InvalidFormat("New order length doesn't match image count".to_string()));
     }
     
     let mut used_indices = vec![false; sti_file.images.len()];
-    for &index in &new_order {
+    for &index in new_order {
         if index >= sti_file.images.len() {
-            return Err(format!("Invalid index {} in new order", index));
+            return Err(CommandError::InvalidFormat(format!("Invalid index {} in new order", index)));
         }
         if used_indices[index] {
-            return Err(format!("Duplicate index {} in new order", index));
+            return Err(CommandError::InvalidFormat(format!("Duplicate index {} in new order", index)));
         }
         used_indices[index] = true;
     }
@@ -560,53 +1209,90 @@ async fn reorder_images(file_path: String, new_order: Vec<usize>) -> Result<(),
     let original_images = sti_file.images.clone();
     sti_file.images.clear();
     
-    for &index in &new_order {
+    for &index in new_order {
         sti_file.images.push(original_images[index].clone());
     }
     
-    // Save the modified STI file
-    save_modified_sti_file(&file_path, &sti_file).await?;
-    
     Ok(())
 }
 
-#[tauri::command]
-async fn remove_images_from_sti(file_path: String, indices: Vec<usize>) -> Result<(), String> {
-    if indices.is_empty() {
+// Keep the animation records in step with a frame removal. A sequence that loses frames is
+// rejected unless fix_animation is set, in which case its frame count shrinks and moves to the
+// first surviving frame of the sequence
+fn remove_animation_frames(sti_file: &mut StiFile, indices: &[usize], fix_animation: bool) -> Result<(), CommandError> {
+    if sti_file.animation_data.is_empty() {
         return Ok(());
     }
     
+    let image_count = sti_file.images.len();
+    // Work out every change against the original records before touching any of them
+    let mut updates = Vec::new();
+    for start in 0..sti_file.animation_data.len().min(image_count) {
+        let frame_count = sti_file.animation_data[start].frame_count as usize;
+        if frame_count == 0 {
+            continue;
+        }
+        let span = start..(start + frame_count).min(image_count);
+        let removed = indices.iter().filter(|&&index| span.contains(&index)).count();
+        if removed == 0 {
+            continue;
+        }
+        
+        if !fix_animation {
+            let index = indices.iter().find(|&&index| span.contains(&index)).copied().unwrap_or(start);
+            return Err(CommandError::Unsupported(format!(
+                "Image {} belongs to the {}-frame animation starting at image {}, removing it would break the animation",
+                index, frame_count, start
+            )));
+        }
+        
+        let remaining = (frame_count - removed) as u8;
+        let flags = sti_file.animation_data[start].unknown2;
+        if let Some(target) = span.clone().find(|index| !indices.contains(index)) {
+            updates.push((target, remaining, flags));
+        }
+    }
+    for (target, frame_count, flags) in updates {
+        sti_file.animation_data[target].frame_count = frame_count;
+        sti_file.animation_data[target].unknown2 = flags;
+    }
     
-    // Get cached STI file or parse it
-    let cached_file = {
-        let cache = STI_CACHE.lock().unwrap();
-        cache.get(&file_path).cloned()
-    };
+    let mut sorted_indices = indices.to_vec();
+    sorted_indices.sort_by(|a, b| b.cmp(a));
+    for &index in &sorted_indices {
+        if index < sti_file.animation_data.len() {
+            sti_file.animation_data.remove(index);
+        }
+    }
+    sti_file.header.app_data_size = sti_file.app_data_bytes().len() as u32;
     
-    let mut sti_file = if let Some(cached) = cached_file {
-        (*cached).clone()
-    } else {
-        let path = Path::new(&file_path);
-        let file_data = fs::read(path)
-            .map_err(|e| format!("Failed to read file: {}", e))?;
-        StiParser::parse(&file_data)
-            .map_err(|e| format!("Failed to parse STI file: {}", e))?
-    };
+    Ok(())
+}
+
+// Drop frames from an in-memory file
+fn remove_images(sti_file: &mut StiFile, indices: &[usize], fix_animation: bool) -> Result<(), CommandError> {
+    // A frame listed twice is still one frame
+    let mut unique_indices = indices.to_vec();
+    unique_indices.sort_unstable();
+    unique_indices.dedup();
+    let indices = unique_indices.as_slice();
     
     // Validate indices
-    for &index in &indices {
+    for &index in indices {
         if index >= sti_file.images.len() {
-            return Err(format!("Invalid index {} for removal", index));
+            return Err(CommandError::InvalidFormat(format!("Invalid index {} for removal", index)));
         }
     }
     
     // Check if we're not removing all images
     if indices.len() >= sti_file.images.len() {
-        return Err("Cannot remove all images from STI file".to_string());
+        return Err(CommandError::Unsupported("Cannot remove all images from STI file".to_string()));
     }
     
+    remove_animation_frames(sti_file, indices, fix_animation)?;
+    
     // Sort indices in descending order to remove from the end first
-    let mut sorted_indices = indices.clone();
+    let mut sorted_indices = indices.to_vec();
     sorted_indices.sort_by(|a, b| b.cmp(a));
     
     // Remove images
@@ -617,51 +1303,281 @@ async fn remove_images_from_sti(file_path: String, indices: Vec<usize>) -> Resul
     // Update header
     sti_file.header.num_images = sti_file.images.len() as u16;
     
+    Ok(())
+}
+
+// Structure of a file after a previewed edit, for the UI to confirm before committing
+fn structure_preview(sti_file: &StiFile) -> serde_json::Value {
+    let frames: Vec<serde_json::Value> = sti_file.images.iter()
+        .map(|image| serde_json::json!({ "width": image.width, "height": image.height }))
+        .collect();
+    serde_json::json!({
+        "image_count": sti_file.images.len(),
+        "frames": frames,
+    })
+}
+
+#[tauri::command]
+async fn add_new_image(app: tauri::AppHandle, file_path: String, image_data: EditableImage, position: Option<usize>) -> Result<usize, CommandError> {
+    // Try to get cached STI file or parse it
+    let mut sti_file = (*load_sti_file(&file_path)?).clone();
+    let insert_pos = insert_new_image(&mut sti_file, image_data, position)?;
+    
+    // Save the modified STI file
+    save_modified_sti_file(&file_path, &sti_file).await?;
+    notify_cache_invalidated(&app, &file_path);
+    
+    Ok(insert_pos)
+}
+
+// Insert a frame filled with the transparent color, as an empty canvas to paint on
+#[tauri::command]
+async fn add_blank_frame(app: tauri::AppHandle, file_path: String, width: u16, height: u16, position: Option<usize>) -> Result<usize, CommandError> {
+    if width == 0 || height == 0 {
+        return Err(CommandError::OutOfBounds(format!("Blank frame size {}x{} must be at least 1x1", width, height)));
+    }
+    
+    let sti_file = load_sti_file(&file_path)?;
+    let pixel_count = width as usize * height as usize;
+    let transparent_color = sti_file.header.transparent_color;
+    let data = match sti_file.format() {
+        sti::StiFormat::Indexed8 => vec![transparent_color as u8; pixel_count],
+        sti::StiFormat::Rgb16 => (transparent_color as u16).to_le_bytes().repeat(pixel_count),
+        sti::StiFormat::Unknown => return Err(ambiguous_format_error(&sti_file)),
+    };
+    
+    let image_data = EditableImage { width, height, data, offset_x: 0, offset_y: 0, alpha: None };
+    add_new_image(app, file_path, image_data, position).await
+}
+
+// Dry run of add_new_image: validates and applies the insert in memory without backing up or writing
+#[tauri::command]
+async fn add_new_image_preview(file_path: String, image_data: EditableImage, position: Option<usize>) -> Result<serde_json::Value, CommandError> {
+    let mut sti_file = (*load_sti_file(&file_path)?).clone();
+    insert_new_image(&mut sti_file, image_data, position)?;
+    Ok(structure_preview(&sti_file))
+}
+
+#[tauri::command]
+async fn reorder_images(app: tauri::AppHandle, file_path: String, new_order: Vec<usize>) -> Result<(), CommandError> {
+    
+    // Get cached STI file or parse it
+    let mut sti_file = (*load_sti_file(&file_path)?).clone();
+    apply_image_order(&mut sti_file, &new_order)?;
+    
+    // Save the modified STI file
+    save_modified_sti_file(&file_path, &sti_file).await?;
+    notify_cache_invalidated(&app, &file_path);
+    
+    Ok(())
+}
+
+// Exchange two frames, sub-headers included, for drag-to-swap in the frame list
+#[tauri::command]
+async fn swap_frames(app: tauri::AppHandle, file_path: String, a: usize, b: usize) -> Result<(), CommandError> {
+    let image_count = load_sti_file(&file_path)?.images.len();
+    for index in [a, b] {
+        if index >= image_count {
+            return Err(CommandError::OutOfBounds(format!("Frame index {} out of bounds ({} frames)", index, image_count)));
+        }
+    }
+    if a == b {
+        return Ok(());
+    }
+    
+    let mut new_order: Vec<usize> = (0..image_count).collect();
+    new_order.swap(a, b);
+    reorder_images(app, file_path, new_order).await
+}
+
+#[tauri::command]
+async fn reorder_images_preview(file_path: String, new_order: Vec<usize>) -> Result<serde_json::Value, CommandError> {
+    let mut sti_file = (*load_sti_file(&file_path)?).clone();
+    apply_image_order(&mut sti_file, &new_order)?;
+    Ok(structure_preview(&sti_file))
+}
+
+#[tauri::command]
+async fn remove_images_from_sti(app: tauri::AppHandle, file_path: String, indices: Vec<usize>, fix_animation: bool) -> Result<(), CommandError> {
+    if indices.is_empty() {
+        return Ok(());
+    }
+    
+    
+    // Get cached STI file or parse it
+    let mut sti_file = (*load_sti_file(&file_path)?).clone();
+    remove_images(&mut sti_file, &indices, fix_animation)?;
+    
     // Save the modified STI file
     save_modified_sti_file(&file_path, &sti_file).await?;
+    notify_cache_invalidated(&app, &file_path);
     
     Ok(())
 }
 
 #[tauri::command]
-async fn delete_image(file_path: String, image_index: usize) -> Result<(), String> {
+async fn remove_images_from_sti_preview(file_path: String, indices: Vec<usize>, fix_animation: bool) -> Result<serde_json::Value, CommandError> {
+    let mut sti_file = (*load_sti_file(&file_path)?).clone();
+    if !indices.is_empty() {
+        remove_images(&mut sti_file, &indices, fix_animation)?;
+    }
+    Ok(structure_preview(&sti_file))
+}
+
+// Indices of the fully transparent frames at the start and/or end of a file, in order
+fn blank_edge_frames(sti_file: &StiFile, leading: bool, trailing: bool) -> Result<Vec<usize>, String> {
+    let blank = sti_file.images.iter().enumerate()
+        .map(|(index, image)| {
+            opaque_bounds(sti_file, image)
+                .map(|bounds| bounds.is_none())
+                .map_err(|e| format!("Image {}: {}", index, e))
+        })
+        .collect::<Result<Vec<bool>, String>>()?;
+    
+    let leading_count = if leading { blank.iter().take_while(|&&is_blank| is_blank).count() } else { 0 };
+    let trailing_count = if trailing { blank.iter().rev().take_while(|&&is_blank| is_blank).count() } else { 0 };
+    // A file of nothing but blank frames counts each frame once
+    let trailing_start = (blank.len() - trailing_count).max(leading_count);
+    
+    Ok((0..leading_count).chain(trailing_start..blank.len()).collect())
+}
+
+// Drop blank frames some tools pad files with; an all-blank file is refused rather than emptied
+#[tauri::command]
+async fn trim_blank_frames(app: tauri::AppHandle, file_path: String, leading: bool, trailing: bool) -> Result<usize, CommandError> {
+    let mut sti_file = (*load_sti_file(&file_path)?).clone();
+    let indices = blank_edge_frames(&sti_file, leading, trailing)?;
+    if indices.is_empty() {
+        return Ok(0);
+    }
+    
+    remove_images(&mut sti_file, &indices, true)?;
+    create_sti_backup(file_path.clone()).await?;
+    save_modified_sti_file(&file_path, &sti_file).await?;
+    notify_cache_invalidated(&app, &file_path);
+    
+    Ok(indices.len())
+}
+
+#[tauri::command]
+async fn delete_image(app: tauri::AppHandle, file_path: String, image_index: usize) -> Result<(), CommandError> {
     // Use the new remove_images_from_sti function for single image removal
-    remove_images_from_sti(file_path, vec![image_index]).await
+    remove_images_from_sti(app, file_path, vec![image_index], false).await
+}
+
+#[tauri::command]
+async fn save_sti_file(app: tauri::AppHandle, file_path: String, editable_sti: EditableStiFile) -> Result<(), CommandError> {
+    editable_sti.validate()?;
+    check_write_allowed(&file_path)?;
+    write_editable_sti_file(&file_path, &editable_sti)?;
+    notify_cache_invalidated(&app, &file_path);
+    
+    Ok(())
 }
 
+// Write an edited copy to a new path, leaving the source file and its cache entry untouched
 #[tauri::command]
-async fn save_sti_file(file_path: String, editable_sti: EditableStiFile) -> Result<(), String> {
-    // Convert EditableStiFile back to StiFile format
-    let mut sti_file = convert_editable_to_sti_file(&editable_sti)
-        .map_err(|e| format!("Error converting editable STI: {}", e))?;
+async fn save_sti_file_as(editable_sti: EditableStiFile, output_path: String, overwrite: bool) -> Result<(), CommandError> {
+    editable_sti.validate()?;
+    check_write_allowed(&output_path)?;
+    if !overwrite && Path::new(&output_path).exists() {
+        return Err(CommandError::AlreadyExists(format!("'{}' already exists", output_path)));
+    }
+    
+    let file_bytes = sti::ops::encode_editable(&editable_sti, None)?;
+    fs::write(&output_path, &file_bytes)
+        .map_err(|e| CommandError::Io(format!("Error writing to disk '{}': {}", output_path, e)))?;
+    
+    // Only a previous parse of the output itself is stale now
+    lock_cache(&STI_CACHE)?.remove(&output_path);
+    
+    Ok(())
+}
+
+// Single fully transparent frame with the flags and palette a new sprite needs
+fn new_blank_sti(is_8bit: bool, width: u16, height: u16, palette: Option<Vec<[u8; 3]>>, transparent_color: u32) -> Result<StiFile, CommandError> {
+    if width == 0 || height == 0 {
+        return Err(CommandError::OutOfBounds(format!("Frame size {}x{} is empty", width, height)));
+    }
+    let pixel_count = width as usize * height as usize;
+    
+    let builder = if is_8bit {
+        let transparent_index = u8::try_from(transparent_color)
+            .map_err(|_| CommandError::OutOfBounds(format!("Transparent index {} is not a palette index", transparent_color)))?;
+        let palette = match palette {
+            Some(colors) if colors.is_empty() || colors.len() > 256 => {
+                return Err(CommandError::InvalidFormat(format!("Palette has {} colors, expected 1 to 256", colors.len())));
+            }
+            Some(colors) => {
+                let mut full = [[0u8; 3]; 256];
+                full[..colors.len()].copy_from_slice(&colors);
+                full
+            }
+            None => sti::palette::grayscale_palette(),
+        };
+        sti::builder::StiBuilder::new_indexed(palette)
+            .transparent_color(transparent_color)
+            .add_frame(width, height, vec![transparent_index; pixel_count], 0, 0)
+    } else {
+        let transparent_pixel = u16::try_from(transparent_color)
+            .map_err(|_| CommandError::OutOfBounds(format!("Transparent color {} is not a 16-bit pixel", transparent_color)))?;
+        let pixels = transparent_pixel.to_le_bytes().repeat(pixel_count);
+        sti::builder::StiBuilder::new_rgb16((0xF800, 0x07E0, 0x001F))
+            .transparent_color(transparent_color)
+            .add_frame(width, height, pixels, 0, 0)
+    };
     
-    // Compress image data using ETRLE if needed
-    compress_sti_images(&mut sti_file)
-        .map_err(|e| format!("Error compressing images: {}", e))?;
+    Ok(builder.build()?)
+}
+
+#[tauri::command]
+async fn create_new_sti(output_path: String, is_8bit: bool, width: u16, height: u16, palette: Option<Vec<[u8; 3]>>, transparent_color: u32) -> Result<(), CommandError> {
+    check_write_allowed(&output_path)?;
+    if Path::new(&output_path).exists() {
+        return Err(CommandError::AlreadyExists(format!("'{}' already exists", output_path)));
+    }
     
-    // Calculate and update header sizes
-    update_sti_header_sizes(&mut sti_file)
-        .map_err(|e| format!("Error updating header sizes: {}", e))?;
+    let mut sti_file = new_blank_sti(is_8bit, width, height, palette, transparent_color)?;
+    sti::ops::write_file(Path::new(&output_path), &mut sti_file)?;
+    lock_cache(&STI_CACHE)?.remove(&output_path);
     
-    // Write the STI file to bytes
-    let file_bytes = sti::StiParser::write(&sti_file)
-        .map_err(|e| format!("Error writing STI file structure: {}", e))?;
+    Ok(())
+}
+
+// Files opened with open_sti_bytes are keyed by a caller-chosen name, which must never be
+// taken as a path to overwrite; they reach disk only through save_sti_file_as
+fn check_backed_by_disk(file_path: &str) -> Result<(), String> {
+    if lock_cache(&STI_CACHE)?.get(file_path).is_some_and(|entry| entry.in_memory) {
+        return Err(format!("'{}' was opened from memory, save it to a new path instead", file_path));
+    }
+    Ok(())
+}
+
+fn has_unsaved_edits(file_path: &str) -> Result<bool, String> {
+    Ok(lock_cache(&STI_CACHE)?.get(file_path).is_some_and(|entry| entry.dirty))
+}
+
+// Convert, compress and write an editable file, then drop its stale cache entry
+fn write_editable_sti_file(file_path: &str, editable_sti: &EditableStiFile) -> Result<(), String> {
+    check_write_allowed(file_path)?;
+    check_backed_by_disk(file_path)?;
     
-    // Write to disk
-    fs::write(&file_path, &file_bytes)
-        .map_err(|e| format!("Error writing to disk '{}': {}", file_path, e))?;
+    // Frames and header fields the edit left alone are written back from the file on disk
+    let written = sti::ops::write_editable(Path::new(file_path), editable_sti)
+        .map_err(|e| format!("Error writing '{}': {}", file_path, e))?;
+    log::debug!("Saved {} bytes to {}", written, file_path);
     
     // Clear the cache to force reload from disk
     {
-        let mut cache = STI_CACHE.lock().unwrap();
-        cache.remove(&file_path);
+        let mut cache = lock_cache(&STI_CACHE)?;
+        cache.remove(file_path);
     }
     
     Ok(())
 }
 
 #[tauri::command]
-async fn select_directory(app: tauri::AppHandle) -> Result<Option<String>, String> {
+async fn select_directory(app: tauri::AppHandle) -> Result<Option<String>, CommandError> {
     use std::sync::mpsc;
     
     let (tx, rx) = mpsc::channel();
@@ -680,20 +1596,22 @@ async fn select_directory(app: tauri::AppHandle) -> Result<Option<String>, Strin
             Ok(Some(path_buf.to_string_lossy().to_string()))
         },
         Ok(None) => Ok(None),
-        Err(_) => Err("Failed to receive dialog result".to_string()),
+        Err(_) => Err(CommandError::Internal("Failed to receive dialog result".to_string())),
     }
 }
 
 #[tauri::command]
-async fn browse_directory(directory_path: String) -> Result<DirectoryContents, String> {
+async fn browse_directory(directory_path: String, max_depth: Option<usize>, include_hidden: Option<bool>) -> Result<DirectoryContents, CommandError> {
     let path = Path::new(&directory_path);
+    let max_depth = max_depth.unwrap_or(DEFAULT_DIRECTORY_SCAN_DEPTH);
+    let include_hidden = include_hidden.unwrap_or(false);
     
     if !path.exists() {
-        return Err("Directory does not exist".to_string());
+        return Err(CommandError::NotFound("Directory does not exist".to_string()));
     }
     
     if !path.is_dir() {
-        return Err("Path is not a directory".to_string());
+        return Err(CommandError::InvalidFormat("Path is not a directory".to_string()));
     }
     
     let mut directories = Vec::new();
@@ -702,7 +1620,7 @@ async fn browse_directory(directory_path: String) -> Result<DirectoryContents, S
     
     // Read directory entries - optimized single pass
     let entries = fs::read_dir(path)
-        .map_err(|e| format!("Failed to read directory: {}", e))?;
+        .map_err(|e| CommandError::Io(format!("Failed to read directory: {}", e)))?;
     
     for entry in entries {
         let entry = entry.map_err(|e| format!("Failed to read directory entry: {}", e))?;
@@ -714,8 +1632,7 @@ async fn browse_directory(directory_path: String) -> Result<DirectoryContents, S
             None => continue,
         };
         
-        // Skip hidden files/directories
-        if file_name.starts_with('.') {
+        if !include_hidden && is_hidden(&entry_path) {
             continue;
         }
         
@@ -723,7 +1640,7 @@ async fn browse_directory(directory_path: String) -> Result<DirectoryContents, S
         
         if is_directory {
             // Only check if directory contains STI files when needed, not recursively
-            let contains_sti_files = directory_contains_sti_files(&entry_path);
+            let contains_sti_files = directory_contains_sti_files(&entry_path, max_depth, include_hidden);
             if contains_sti_files {
                 directories.push(DirectoryItem {
                     name: file_name.to_string(),
@@ -746,8 +1663,8 @@ async fn browse_directory(directory_path: String) -> Result<DirectoryContents, S
     }
     
     // Simple sorting: directories first, then STI files, both by name
-    directories.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
-    sti_files.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
+    directories.sort_by_key(|item| item.name.to_lowercase());
+    sti_files.sort_by_key(|item| item.name.to_lowercase());
     
     // Combine directories and files
     let mut items = directories;
@@ -764,48 +1681,137 @@ async fn browse_directory(directory_path: String) -> Result<DirectoryContents, S
     })
 }
 
+// Default number of levels below a directory searched for STI files
+const DEFAULT_DIRECTORY_SCAN_DEPTH: usize = 3;
+
+// Dot-prefixed files and directories are hidden. Browsing, scanning and the contains-STI
+// checks all skip them unless the caller opts in, and a skipped directory's contents are
+// skipped with it
+fn is_hidden(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.starts_with('.'))
+}
+
 // Cached recursive check if a directory contains STI files (with depth limit for performance)
-fn directory_contains_sti_files(dir_path: &Path) -> bool {
+fn directory_contains_sti_files(dir_path: &Path, max_depth: usize, include_hidden: bool) -> bool {
+    // The cache only records the default view, which leaves hidden entries out
+    if include_hidden {
+        return directory_contains_sti_files_with_depth(dir_path, 0, max_depth, true);
+    }
+    
     let path_str = dir_path.to_string_lossy().to_string();
     
-    // Check cache first
-    {
-        let cache = DIRECTORY_CACHE.lock().unwrap();
-        if let Some(&cached_result) = cache.get(&path_str) {
-            return cached_result;
+    // A hit found within a shallower limit holds for deeper ones, and a miss
+    // at a deeper limit holds for shallower ones; anything else is rescanned
+    if let Ok(cache) = lock_cache(&DIRECTORY_CACHE) {
+        if let Some(&(cached_result, cached_depth)) = cache.get(&path_str) {
+            if (cached_result && cached_depth <= max_depth) || (!cached_result && cached_depth >= max_depth) {
+                return cached_result;
+            }
         }
     }
     
     // Perform the check with depth limit
-    let result = directory_contains_sti_files_with_depth(dir_path, 0, 3); // Limit to 3 levels deep
+    let result = directory_contains_sti_files_with_depth(dir_path, 0, max_depth, false);
+    remember_directory_scan(path_str, result, max_depth);
     
-    // Cache the result
-    {
-        let mut cache = DIRECTORY_CACHE.lock().unwrap();
+    result
+}
+
+// Cache whether a directory holds STI files within `max_depth` levels below it
+fn remember_directory_scan(path_str: String, contains_sti_files: bool, max_depth: usize) {
+    let dir_max = cache_config().dir_max;
+    if let Ok(mut cache) = lock_cache(&DIRECTORY_CACHE) {
         // Limit cache size to prevent memory issues
-        if cache.len() > 200 {
+        if cache.len() > dir_max {
             cache.clear(); // Simple eviction strategy
         }
-        cache.insert(path_str, result);
+        cache.insert(path_str, (contains_sti_files, max_depth));
     }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DirectoryTreeNode {
+    pub name: String,
+    pub path: String,
+    pub sti_count: usize,       // STI files directly inside this directory
+    pub total_sti_count: usize, // Including every listed subdirectory
+    pub children: Vec<DirectoryTreeNode>,
+}
+
+// Scan a directory and its subdirectories up to max_depth, keeping only branches that hold STI files
+fn build_directory_node(dir_path: &Path, depth: usize, max_depth: usize) -> DirectoryTreeNode {
+    let mut sti_count = 0;
+    let mut subdirectories = Vec::new();
     
-    result
+    if let Ok(entries) = fs::read_dir(dir_path) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            
+            if is_hidden(&path) {
+                continue;
+            }
+            
+            if path.is_file() {
+                if path.extension().and_then(|ext| ext.to_str()).is_some_and(|ext| ext.eq_ignore_ascii_case("sti")) {
+                    sti_count += 1;
+                }
+            } else if path.is_dir() && depth < max_depth {
+                subdirectories.push(path);
+            }
+        }
+    }
+    
+    let mut children: Vec<DirectoryTreeNode> = subdirectories.par_iter()
+        .map(|path| build_directory_node(path, depth + 1, max_depth))
+        .filter(|child| child.total_sti_count > 0)
+        .collect();
+    children.sort_by_key(|child| child.name.to_lowercase());
+    
+    let total_sti_count = sti_count + children.iter().map(|child| child.total_sti_count).sum::<usize>();
+    let path = dir_path.to_string_lossy().to_string();
+    // The walk is exactly the check browse_directory does, so later listings can reuse it
+    remember_directory_scan(path.clone(), total_sti_count > 0, max_depth - depth);
+    
+    DirectoryTreeNode {
+        name: dir_path.file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| path.clone()),
+        path,
+        sti_count,
+        total_sti_count,
+        children,
+    }
+}
+
+// Whole subtree of directories containing STI files, for tree views that would otherwise browse level by level
+#[tauri::command]
+async fn build_directory_tree(root: String, max_depth: usize) -> Result<DirectoryTreeNode, CommandError> {
+    let path = Path::new(&root);
+    if !path.exists() {
+        return Err(CommandError::NotFound("Directory does not exist".to_string()));
+    }
+    if !path.is_dir() {
+        return Err(CommandError::InvalidFormat("Path is not a directory".to_string()));
+    }
+    
+    Ok(build_directory_node(path, 0, max_depth))
 }
 
-fn directory_contains_sti_files_with_depth(dir_path: &Path, current_depth: usize, max_depth: usize) -> bool {
+fn directory_contains_sti_files_with_depth(dir_path: &Path, current_depth: usize, max_depth: usize, include_hidden: bool) -> bool {
     if current_depth > max_depth {
         return false;
     }
     
+    let mut subdirectories = Vec::new();
+    
     if let Ok(entries) = fs::read_dir(dir_path) {
         for entry in entries.flatten() {
             let path = entry.path();
             
-            // Skip hidden files and directories
-            if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
-                if name.starts_with('.') {
-                    continue;
-                }
+            if !include_hidden && is_hidden(&path) {
+                continue;
             }
             
             if path.is_file() {
@@ -815,42 +1821,145 @@ fn directory_contains_sti_files_with_depth(dir_path: &Path, current_depth: usize
                     }
                 }
             } else if path.is_dir() && current_depth < max_depth {
-                // Recursively check subdirectories with depth limit
-                if directory_contains_sti_files_with_depth(&path, current_depth + 1, max_depth) {
-                    return true;
-                }
+                subdirectories.push(path);
             }
         }
     }
-    false
+    
+    // Recursively check subdirectories concurrently with depth limit
+    subdirectories.par_iter()
+        .any(|path| directory_contains_sti_files_with_depth(path, current_depth + 1, max_depth, include_hidden))
 }
 
-#[tauri::command]
-async fn scan_for_sti_files(directory_path: String, recursive: bool) -> Result<Vec<String>, String> {
-    let mut sti_files = Vec::new();
-    scan_directory_for_sti(&Path::new(&directory_path), &mut sti_files, recursive)?;
-    Ok(sti_files)
+#[derive(Debug, Clone, Serialize)]
+struct ScanProgress {
+    scan_id: String,
+    scanned: usize,
+    found: usize,
+    current_path: String,
 }
 
-fn scan_directory_for_sti(dir: &Path, sti_files: &mut Vec<String>, recursive: bool) -> Result<(), String> {
-    if !dir.is_dir() {
-        return Ok(());
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ScanResult {
+    pub scan_id: String,
+    pub files: Vec<String>, // Everything found before the scan finished or was cancelled
+    pub aborted: bool,
+}
+
+// Shared counters for a scan running across rayon threads
+struct ScanReporter {
+    app: tauri::AppHandle,
+    scan_id: String,
+    cancelled: Arc<AtomicBool>,
+    scanned: AtomicUsize,
+    found: AtomicUsize,
+}
+
+impl ScanReporter {
+    fn new(app: tauri::AppHandle, scan_id: String, cancelled: Arc<AtomicBool>) -> Self {
+        ScanReporter {
+            app,
+            scan_id,
+            cancelled,
+            scanned: AtomicUsize::new(0),
+            found: AtomicUsize::new(0),
+        }
     }
-    
-    let entries = fs::read_dir(dir)
-        .map_err(|e| format!("Failed to read directory {}: {}", dir.display(), e))?;
-    
+
+    fn progress(&self, current_path: String) -> ScanProgress {
+        ScanProgress {
+            scan_id: self.scan_id.clone(),
+            scanned: self.scanned.load(Ordering::Relaxed),
+            found: self.found.load(Ordering::Relaxed),
+            current_path,
+        }
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    fn directory_scanned(&self, dir: &Path, entries: usize, found: usize) {
+        self.scanned.fetch_add(entries, Ordering::Relaxed);
+        self.found.fetch_add(found, Ordering::Relaxed);
+        let _ = self.app.emit("sti-scan-progress", self.progress(dir.to_string_lossy().to_string()));
+    }
+}
+
+// The scan id comes from the caller or is generated; either way every progress event carries
+// it, so cancel_scan can stop the scan before this command returns
+#[tauri::command]
+async fn scan_for_sti_files(
+    app: tauri::AppHandle,
+    directory_path: String,
+    recursive: bool,
+    scan_id: Option<String>,
+    include_hidden: Option<bool>,
+) -> Result<ScanResult, CommandError> {
+    let scan_id = scan_id.unwrap_or_else(|| format!("scan-{}", NEXT_OPERATION_ID.fetch_add(1, Ordering::SeqCst)));
+    let cancelled = Arc::new(AtomicBool::new(false));
+    lock_cache(&SCAN_OPERATIONS)?.insert(scan_id.clone(), cancelled.clone());
+    
+    let reporter = ScanReporter::new(app, scan_id.clone(), cancelled);
+    let result = collect_sti_files(Path::new(&directory_path), recursive, include_hidden.unwrap_or(false), Some(&reporter));
+    if let Ok(mut scans) = lock_cache(&SCAN_OPERATIONS) {
+        scans.remove(&scan_id);
+    }
+    let mut sti_files = result?;
+    sti_files.sort();
+    
+    let _ = reporter.app.emit("sti-scan-complete", reporter.progress(directory_path));
+    Ok(ScanResult {
+        scan_id,
+        files: sti_files,
+        aborted: reporter.is_cancelled(),
+    })
+}
+
+#[tauri::command]
+async fn cancel_scan(scan_id: String) -> Result<bool, CommandError> {
+    let scans = lock_cache(&SCAN_OPERATIONS)?;
+    match scans.get(&scan_id) {
+        Some(flag) => {
+            flag.store(true, Ordering::SeqCst);
+            Ok(true)
+        }
+        None => Ok(false), // Already finished or never started
+    }
+}
+
+// Collect STI files under a directory, sorted so results don't depend on thread timing
+fn scan_directory_for_sti(dir: &Path, sti_files: &mut Vec<String>, recursive: bool) -> Result<(), String> {
+    let mut found = collect_sti_files(dir, recursive, false, None)?;
+    found.sort();
+    sti_files.extend(found);
+    Ok(())
+}
+
+fn collect_sti_files(dir: &Path, recursive: bool, include_hidden: bool, reporter: Option<&ScanReporter>) -> Result<Vec<String>, String> {
+    // A cancelled scan stops descending, directories already read keep their results
+    if !dir.is_dir() || reporter.map(|r| r.is_cancelled()).unwrap_or(false) {
+        return Ok(Vec::new());
+    }
+    
+    let entries = fs::read_dir(dir)
+        .map_err(|e| format!("Failed to read directory {}: {}", dir.display(), e))?;
+    
+    let mut sti_files = Vec::new();
+    let mut subdirectories = Vec::new();
+    let mut scanned = 0;
+    
     for entry in entries {
         let entry = entry.map_err(|e| format!("Failed to read directory entry: {}", e))?;
         let path = entry.path();
+        scanned += 1;
+        
+        if !include_hidden && is_hidden(&path) {
+            continue;
+        }
         
         if path.is_dir() && recursive {
-            // Skip hidden directories
-            if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
-                if !name.starts_with('.') {
-                    scan_directory_for_sti(&path, sti_files, recursive)?;
-                }
-            }
+            subdirectories.push(path);
         } else if path.is_file() {
             if let Some(extension) = path.extension().and_then(|ext| ext.to_str()) {
                 if extension.to_lowercase() == "sti" {
@@ -860,338 +1969,409 @@ fn scan_directory_for_sti(dir: &Path, sti_files: &mut Vec<String>, recursive: bo
         }
     }
     
-    Ok(())
+    if let Some(reporter) = reporter {
+        reporter.directory_scanned(dir, scanned, sti_files.len());
+    }
+    
+    // Walk subdirectories on the rayon pool
+    let nested = subdirectories.par_iter()
+        .map(|path| collect_sti_files(path, recursive, include_hidden, reporter))
+        .collect::<Result<Vec<_>, String>>()?;
+    
+    for files in nested {
+        sti_files.extend(files);
+    }
+    
+    Ok(sti_files)
 }
 
+#[tauri::command]
+async fn export_image(
+    file_path: String,
+    image_index: usize,
+    output_path: String,
+    format: String,
+    transparent: Option<bool>,
+    options: Option<ExportOptions>
+) -> Result<(), CommandError> {
+    check_write_allowed(&output_path)?;
+    let sti_file = load_sti_file(&file_path)?;
+    if sti_file.format() == sti::StiFormat::Unknown {
+        return Err(ambiguous_format_error(&sti_file));
+    }
+    if image_index >= sti_file.images.len() {
+        return Err(CommandError::OutOfBounds("Image index out of bounds".to_string()));
+    }
+    let options = options.unwrap_or_default();
+    Ok(export_frame(&sti_file, image_index, &output_path, &format, transparent.unwrap_or(false), &options)?)
+}
 
 #[tauri::command]
-async fn export_image(file_path: String, image_index: usize, output_path: String, format: String) -> Result<(), String> {
-    // Try to get from cache first
-    let cached_file = {
-        let cache = STI_CACHE.lock().unwrap();
-        cache.get(&file_path).cloned()
-    };
+async fn generate_thumbnail(file_path: String, max_size: u32) -> Result<Vec<u8>, CommandError> {
+    if max_size == 0 {
+        return Err(CommandError::OutOfBounds("Thumbnail size must be at least 1".to_string()));
+    }
     
-    let sti_file = if let Some(cached) = cached_file {
-        cached
-    } else {
-        // Parse and cache the file
-        let path = Path::new(&file_path);
-        let file_data = fs::read(path)
-            .map_err(|e| format!("Failed to read file: {}", e))?;
-        
-        let parsed_file = StiParser::parse(&file_data)
-            .map_err(|e| format!("Failed to parse STI file: {}", e))?;
-        
-        let arc_file = Arc::new(parsed_file);
-        
-        // Cache the parsed file
-        {
-            let mut cache = STI_CACHE.lock().unwrap();
-            // Limit cache size to prevent memory issues
-            if cache.len() > 50 {
-                cache.clear(); // Simple eviction strategy
-            }
-            cache.insert(file_path.clone(), arc_file.clone());
+    let key = (file_path.clone(), max_size);
+    let stamp = FileStamp::of(Path::new(&file_path));
+    if let Some((cached_stamp, png)) = lock_cache(&THUMBNAIL_CACHE)?.get(&key) {
+        if cached_stamp.is_some() && *cached_stamp == stamp {
+            return Ok(png.clone());
         }
-        
-        arc_file
-    };
-    
-    if image_index >= sti_file.images.len() {
-        return Err("Image index out of bounds".to_string());
     }
     
-    let image = &sti_file.images[image_index];
-    let pixel_data = image.decompressed_data.as_ref()
-        .ok_or("Image data not decompressed")?;
-    
-    // Convert to RGB format for export
-    let rgb_data = if sti_file.is_8bit() {
-        let palette = sti_file.palette.as_ref()
-            .ok_or("8-bit image missing palette")?;
-        
-        let mut rgb = Vec::with_capacity(pixel_data.len() * 3);
-        for &pixel in pixel_data {
-            let color = palette[pixel as usize];
-            rgb.extend_from_slice(&color);
-        }
-        rgb
-    } else {
-        // Convert 16-bit RGB565 to 24-bit RGB
-        let mut rgb = Vec::with_capacity(pixel_data.len() / 2 * 3);
-        for chunk in pixel_data.chunks(2) {
-            if chunk.len() == 2 {
-                let rgb565 = u16::from_le_bytes([chunk[0], chunk[1]]);
-                let r = ((rgb565 >> 11) & 0x1F) << 3;
-                let g = ((rgb565 >> 5) & 0x3F) << 2;
-                let b = (rgb565 & 0x1F) << 3;
-                rgb.push(r as u8);
-                rgb.push(g as u8);
-                rgb.push(b as u8);
-            }
+    // Folder views ask for every file, so an uncached one only decodes frame 0 and stays out of the cache
+    let sti_file = match cached_sti_file(&file_path)? {
+        Some(cached) => cached,
+        None => {
+            let file_data = fs::read(&file_path)
+                .map_err(|e| match e.kind() {
+                    std::io::ErrorKind::NotFound => CommandError::NotFound(format!("Failed to read file: {}", e)),
+                    _ => CommandError::Io(format!("Failed to read file: {}", e)),
+                })?;
+            let parsed_file = StiParser::parse_with_range(&file_data, Some(0..1))
+                .map_err(|e| CommandError::InvalidFormat(format!("Failed to parse STI file: {}", e)))?;
+            Arc::new(parsed_file)
         }
-        rgb
     };
+    let rgba = frame_to_rgba(&sti_file, 0)?;
     
-    // Use the image crate to save the file
-    let img = image::RgbImage::from_raw(image.width as u32, image.height as u32, rgb_data)
-        .ok_or("Failed to create image from data")?;
+    // Nearest-neighbor keeps pixel art crisp and is cheap enough for whole folders
+    let (width, height) = rgba.dimensions();
+    let scale = (max_size as f32 / width.max(height) as f32).min(1.0);
+    let thumb_width = ((width as f32 * scale).round() as u32).max(1);
+    let thumb_height = ((height as f32 * scale).round() as u32).max(1);
+    let thumbnail = image::imageops::resize(&rgba, thumb_width, thumb_height, image::imageops::FilterType::Nearest);
     
-    match format.to_lowercase().as_str() {
-        "png" => img.save_with_format(&output_path, image::ImageFormat::Png),
-        "jpeg" | "jpg" => img.save_with_format(&output_path, image::ImageFormat::Jpeg),
-        "bmp" => img.save_with_format(&output_path, image::ImageFormat::Bmp),
-        "tiff" | "tif" => img.save_with_format(&output_path, image::ImageFormat::Tiff),
-        _ => return Err(format!("Unsupported export format: {}", format)),
+    let mut png_data = Vec::new();
+    image::DynamicImage::ImageRgba8(thumbnail)
+        .write_to(&mut std::io::Cursor::new(&mut png_data), image::ImageOutputFormat::Png)
+        .map_err(|e| format!("Failed to encode thumbnail: {}", e))?;
+    
+    let mut cache = lock_cache(&THUMBNAIL_CACHE)?;
+    // Limit cache size to prevent memory issues
+    if cache.len() > 500 {
+        cache.clear(); // Simple eviction strategy
     }
-    .map_err(|e| format!("Failed to save image: {}", e))?;
+    cache.insert(key, (stamp, png_data.clone()));
     
-    Ok(())
+    Ok(png_data)
 }
 
-// Helper functions for STI file saving
-
-fn convert_editable_to_sti_file(editable: &EditableStiFile) -> Result<StiFile, String> {
-    use sti::{StiFile, StiImage, StiHeader, StiFlags, StiSubImageHeader};
-    
-    let mut sti_file = StiFile::new();
+// Frame as a PNG data URL ready for an <img src>, with the palette and transparency already applied
+#[tauri::command]
+async fn get_frame_data_url(file_path: String, image_index: usize) -> Result<String, CommandError> {
+    use base64::Engine;
     
-    // Convert header
-    let mut header = StiHeader::default();
-    header.signature = [b'S', b'T', b'C', b'I'];
-    header.transparent_color = editable.transparent_color;
-    header.flags = StiFlags::from(editable.flags);
-    
-    if editable.is_8bit {
-        header.flags.indexed = true;
-        header.flags.rgb = false;
-        header.flags.etrle_compressed = true; // Enable ETRLE compression for 8-bit
-        header.palette_colors = 256;
-        header.num_images = editable.images.len() as u16;
-        header.color_depth = 8;
-        
-        // For 8-bit multi-image files, DON'T set width/height in main header
-        // These are stored in individual sub-image headers
-        if header.num_images == 1 {
-            // Single image 8-bit files can have width/height in main header
-            if let Some(first_image) = editable.images.first() {
-                header.width = first_image.width;
-                header.height = first_image.height;
-            }
-        } else {
-            // Multi-image files: width/height should be 0 in main header
-            header.width = 0;
-            header.height = 0;
-        }
-    } else if editable.is_16bit {
-        header.flags.rgb = true;
-        header.flags.indexed = false;
-        header.color_depth = 16;
-        header.num_images = 1; // 16-bit files typically have one image
-        
-        if let Some(first_image) = editable.images.first() {
-            header.width = first_image.width;
-            header.height = first_image.height;
-        }
+    let sti_file = load_sti_file(&file_path)?;
+    if image_index >= sti_file.images.len() {
+        return Err(CommandError::OutOfBounds(format!("Image index {} out of bounds", image_index)));
     }
+    let rgba = frame_to_rgba(&sti_file, image_index)?;
     
-    sti_file.header = header;
-    
-    // Convert palette
-    if editable.is_8bit {
-        if let Some(palette_data) = &editable.palette {
-            let mut palette = [[0u8; 3]; 256];
-            for (i, color) in palette_data.iter().enumerate() {
-                if i < 256 {
-                    palette[i] = *color;
-                }
-            }
-            sti_file.palette = Some(palette);
-        } else {
-            return Err("8-bit STI file requires a palette".to_string());
-        }
-    }
+    let mut png_data = Vec::new();
+    image::DynamicImage::ImageRgba8(rgba)
+        .write_to(&mut std::io::Cursor::new(&mut png_data), image::ImageOutputFormat::Png)
+        .map_err(|e| format!("Failed to encode frame {}: {}", image_index, e))?;
     
-    // Convert images
-    for editable_image in editable.images.iter() {
-        let mut image = if editable.is_8bit {
-            // Create sub-image header for 8-bit images
-            let sub_header = StiSubImageHeader {
-                data_offset: 0, // Will be set properly in compress_sti_images
-                data_size: 0, // Will be set after compression
-                offset_x: 0,
-                offset_y: 0,
-                height: editable_image.height,
-                width: editable_image.width,
-            };
-            
-            StiImage::with_header(sub_header)
-        } else {
-            StiImage::new(editable_image.width, editable_image.height)
+    Ok(format!("data:image/png;base64,{}", base64::engine::general_purpose::STANDARD.encode(png_data)))
+}
+
+// Expand {stem}, {ext} and {index} / {index:0N} placeholders in an export filename pattern
+fn format_export_filename(pattern: &str, stem: &str, index: usize, ext: &str) -> String {
+    let mut name = pattern.replace("{stem}", stem).replace("{ext}", ext);
+    
+    while let Some(start) = name.find("{index") {
+        let end = match name[start..].find('}') {
+            Some(offset) => start + offset,
+            None => break,
         };
-        
-        // Set decompressed data (will be compressed later if needed)
-        image.decompressed_data = Some(editable_image.data.clone());
-        image.width = editable_image.width;
-        image.height = editable_image.height;
-        
-        sti_file.images.push(image);
+        let width = name[start + "{index".len()..end]
+            .trim_start_matches(':')
+            .trim_start_matches('0')
+            .parse::<usize>()
+            .unwrap_or(0);
+        name.replace_range(start..=end, &format!("{:0width$}", index, width = width));
     }
     
-    Ok(sti_file)
+    name
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BatchExportResult {
+    pub written: Vec<String>,
+    pub failures: Vec<String>, // "Image N: reason" for every frame that was skipped
 }
 
-fn compress_sti_images(sti_file: &mut StiFile) -> Result<(), String> {
-    use sti::etrle::EtrleDecoder;
+#[tauri::command]
+async fn batch_export_images(
+    file_path: String,
+    output_dir: String,
+    format: String,
+    filename_pattern: Option<String>,
+    options: Option<ExportOptions>
+) -> Result<BatchExportResult, CommandError> {
+    check_write_allowed(&output_dir)?;
+    let sti_file = load_sti_file(&file_path)?;
+    let options = options.unwrap_or_default();
+    
+    let output_dir = Path::new(&output_dir);
+    if !output_dir.is_dir() {
+        return Err(CommandError::NotFound("Output directory does not exist".to_string()));
+    }
     
-    if sti_file.is_8bit() && sti_file.header.flags.etrle_compressed {
-        // Compress 8-bit ETRLE images with proper offset calculation
-        let mut cumulative_data_offset = 0u32;
+    let stem = Path::new(&file_path)
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("image")
+        .to_string();
+    let ext = format.to_lowercase();
+    let pattern = filename_pattern.unwrap_or_else(|| "{stem}_{index:03}.{ext}".to_string());
+    
+    let mut written = Vec::new();
+    let mut failures = Vec::new();
+    
+    for index in 0..sti_file.images.len() {
+        let file_name = format_export_filename(&pattern, &stem, index, &ext);
+        let output_path = output_dir.join(file_name).to_string_lossy().to_string();
         
-        for (_index, image) in sti_file.images.iter_mut().enumerate() {
-            if let Some(decompressed_data) = &image.decompressed_data {
-                let encoder = EtrleDecoder::new(image.width, image.height);
-                let compressed_data = encoder.compress(decompressed_data)
-                    .map_err(|e| format!("Failed to compress image data: {}", e))?;
-                
-                image.raw_data = compressed_data;
-                
-                // Update sub-header with compressed size and cumulative offset
-                if let Some(header) = &mut image.header {
-                    header.data_size = image.raw_data.len() as u32;
-                    
-                    // data_offset is cumulative from the start of image data section
-                    header.data_offset = cumulative_data_offset;
-                }
-                
-                // Add this image's size to the cumulative offset for next image
-                cumulative_data_offset += image.raw_data.len() as u32;
-            }
-        }
-    } else if sti_file.is_16bit() {
-        // For 16-bit files, raw_data = decompressed_data (no compression)
-        for image in &mut sti_file.images {
-            if let Some(decompressed_data) = &image.decompressed_data {
-                image.raw_data = decompressed_data.clone();
-            }
-        }
-    } else {
-        // For uncompressed 8-bit files, raw_data = decompressed_data
-        let mut cumulative_data_offset = 0u32;
-        
-        for (_index, image) in sti_file.images.iter_mut().enumerate() {
-            if let Some(decompressed_data) = &image.decompressed_data {
-                image.raw_data = decompressed_data.clone();
-                
-                // Update sub-header with data size and cumulative offset
-                if let Some(header) = &mut image.header {
-                    header.data_size = image.raw_data.len() as u32;
-                    header.data_offset = cumulative_data_offset;
-                }
-                
-                // Add this image's size to the cumulative offset for next image
-                cumulative_data_offset += image.raw_data.len() as u32;
-            }
+        // One bad frame shouldn't abort the rest of the sheet
+        match export_frame(&sti_file, index, &output_path, &format, false, &options) {
+            Ok(()) => written.push(output_path),
+            Err(e) => failures.push(format!("Image {}: {}", index, e)),
         }
     }
     
-    Ok(())
+    if written.is_empty() && !failures.is_empty() {
+        return Err(CommandError::Io(format!("No images were exported: {}", failures.join("; "))));
+    }
+    
+    for failure in &failures {
+        log::warn!("batch_export_images skipped {}", failure);
+    }
+    
+    Ok(BatchExportResult { written, failures })
 }
 
 // Helper function to save modified STI files with proper compression and validation
 async fn save_modified_sti_file(file_path: &str, sti_file: &StiFile) -> Result<(), String> {
+    // Remember the state being replaced so the edit can be undone
+    if let Ok(previous) = load_sti_file(file_path) {
+        let mut history = lock_cache(&EDIT_HISTORY)?;
+        let entry = history.entry(file_path.to_string()).or_default();
+        entry.undo.push(previous);
+        if entry.undo.len() > MAX_EDIT_HISTORY {
+            entry.undo.remove(0);
+        }
+        entry.redo.clear();
+    }
+    
+    // Files opened from bytes keep the edit in the cache until they are saved to a real path
+    if lock_cache(&STI_CACHE)?.get(file_path).is_some_and(|entry| entry.in_memory) {
+        return store_edited_sti_file(file_path, sti_file.clone());
+    }
+    
     // Convert to editable format first
-    let editable_sti = convert_sti_to_editable(sti_file)?;
+    let editable_sti = sti::ops::editable_from_sti(sti_file).map_err(|e| e.to_string())?;
     
     // Use existing save function
-    save_sti_file(file_path.to_string(), editable_sti).await
+    write_editable_sti_file(file_path, &editable_sti)
 }
 
-fn convert_sti_to_editable(sti_file: &StiFile) -> Result<EditableStiFile, String> {
-    let mut editable_images = Vec::new();
+// Swap the file with the newest snapshot from one history stack, pushing the current state onto the other
+fn step_edit_history(file_path: &str, undo: bool) -> Result<bool, String> {
+    let current = load_sti_file(file_path)?;
+    let snapshot = {
+        let mut history = lock_cache(&EDIT_HISTORY)?;
+        let entry = history.entry(file_path.to_string()).or_default();
+        let stack = if undo { &mut entry.undo } else { &mut entry.redo };
+        match stack.pop() {
+            Some(snapshot) => snapshot,
+            None => return Ok(false),
+        }
+    };
     
-    for image in &sti_file.images {
-        let pixel_data = image.decompressed_data.as_ref()
-            .ok_or("Image data not decompressed")?;
-        
-        editable_images.push(EditableImage {
-            width: image.width,
-            height: image.height,
-            data: pixel_data.clone(),
-        });
+    if let Err(e) = write_sti_file(file_path, &mut (*snapshot).clone()) {
+        // Put the snapshot back so a failed write doesn't lose history
+        let mut history = lock_cache(&EDIT_HISTORY)?;
+        let entry = history.entry(file_path.to_string()).or_default();
+        let stack = if undo { &mut entry.undo } else { &mut entry.redo };
+        stack.push(snapshot);
+        return Err(e);
     }
     
-    Ok(EditableStiFile {
-        file_path: String::new(), // Will be set by caller
-        is_8bit: sti_file.is_8bit(),
-        is_16bit: sti_file.is_16bit(),
-        palette: sti_file.palette.map(|p| p.to_vec()),
-        images: editable_images,
-        transparent_color: sti_file.header.transparent_color,
-        flags: sti_file.header.flags.into(),
-    })
+    let mut history = lock_cache(&EDIT_HISTORY)?;
+    let entry = history.entry(file_path.to_string()).or_default();
+    let stack = if undo { &mut entry.redo } else { &mut entry.undo };
+    stack.push(current);
+    if stack.len() > MAX_EDIT_HISTORY {
+        stack.remove(0);
+    }
+    
+    Ok(true)
 }
 
-fn update_sti_header_sizes(sti_file: &mut StiFile) -> Result<(), String> {
-    if sti_file.is_8bit() {
-        // Calculate total compressed size for 8-bit files
-        let mut total_compressed_size = 0u32;
-        let mut total_original_size = 0u32;
-        
-        for image in &sti_file.images {
-            total_compressed_size += image.raw_data.len() as u32;
-            if let Some(decompressed) = &image.decompressed_data {
-                total_original_size += decompressed.len() as u32;
-            }
-        }
-        
-        sti_file.header.compressed_size = total_compressed_size;
-        sti_file.header.original_size = total_original_size;
-    } else if sti_file.is_16bit() {
-        // For 16-bit files, raw data = decompressed data
-        if let Some(first_image) = sti_file.images.first() {
-            let data_size = first_image.raw_data.len() as u32;
-            sti_file.header.compressed_size = data_size;
-            sti_file.header.original_size = data_size;
-        }
+#[tauri::command]
+async fn undo_edit(app: tauri::AppHandle, file_path: String) -> Result<bool, CommandError> {
+    let changed = step_edit_history(&file_path, true)?;
+    if changed {
+        notify_cache_invalidated(&app, &file_path);
+    }
+    Ok(changed)
+}
+
+#[tauri::command]
+async fn redo_edit(app: tauri::AppHandle, file_path: String) -> Result<bool, CommandError> {
+    let changed = step_edit_history(&file_path, false)?;
+    if changed {
+        notify_cache_invalidated(&app, &file_path);
+    }
+    Ok(changed)
+}
+
+// Write a fully populated StiFile to disk, keeping sub-image offsets and animation data
+fn write_sti_file(file_path: &str, sti_file: &mut StiFile) -> Result<(), String> {
+    check_write_allowed(file_path)?;
+    check_backed_by_disk(file_path)?;
+    let written = sti::ops::write_file(Path::new(file_path), sti_file)
+        .map_err(|e| format!("Error writing '{}': {}", file_path, e))?;
+    log::debug!("Wrote {} bytes to {}", written, file_path);
+    
+    // Clear the cache to force reload from disk
+    {
+        let mut cache = lock_cache(&STI_CACHE)?;
+        cache.remove(file_path);
     }
     
     Ok(())
 }
 
+// Files with unsaved edits that changed on disk since, so the UI can ask which version to keep
+#[tauri::command]
+async fn list_edit_conflicts() -> Result<Vec<String>, CommandError> {
+    let cache = lock_cache(&STI_CACHE)?;
+    let mut conflicts: Vec<String> = cache.iter()
+        .filter(|(path, entry)| has_edit_conflict(entry, &FileStamp::of(Path::new(path.as_str()))))
+        .map(|(path, _)| path.clone())
+        .collect();
+    conflicts.sort();
+    Ok(conflicts)
+}
+
 #[tauri::command]
-async fn clear_sti_cache() -> Result<(), String> {
-    let mut sti_cache = STI_CACHE.lock().unwrap();
-    let mut dir_cache = DIRECTORY_CACHE.lock().unwrap();
+async fn clear_sti_cache() -> Result<(), CommandError> {
+    let mut sti_cache = lock_cache(&STI_CACHE)?;
+    let mut dir_cache = lock_cache(&DIRECTORY_CACHE)?;
+    let mut thumbnail_cache = lock_cache(&THUMBNAIL_CACHE)?;
     sti_cache.clear();
     dir_cache.clear();
+    thumbnail_cache.clear();
+    Ok(())
+}
+
+#[tauri::command]
+async fn set_cache_limits(sti_max: usize, dir_max: usize) -> Result<(), CommandError> {
+    if sti_max == 0 || dir_max == 0 {
+        return Err(CommandError::OutOfBounds("Cache limits must be at least 1".to_string()));
+    }
+    *lock_cache(&CACHE_CONFIG)? = CacheConfig { sti_max, dir_max };
+    Ok(())
+}
+
+// Confine writes and backups to these directories; an empty list lifts the restriction
+#[tauri::command]
+async fn set_allowed_roots(roots: Vec<String>) -> Result<(), CommandError> {
+    let mut canonical = Vec::with_capacity(roots.len());
+    for root in &roots {
+        let path = Path::new(root).canonicalize()
+            .map_err(|e| CommandError::NotFound(format!("Allowed root '{}' cannot be resolved: {}", root, e)))?;
+        if !path.is_dir() {
+            return Err(CommandError::InvalidFormat(format!("Allowed root '{}' is not a directory", root)));
+        }
+        canonical.push(path);
+    }
+    *lock_cache(&ALLOWED_ROOTS)? = canonical;
+    Ok(())
+}
+
+// Raise or lower the parse/save trace level at runtime: off, error, warn, info, debug or trace
+#[tauri::command]
+async fn set_log_level(level: String) -> Result<(), CommandError> {
+    logging::set_level(&level).map_err(CommandError::InvalidFormat)
+}
+
+// Re-key a cached file after a rename or save-as instead of parsing it again
+#[tauri::command]
+async fn move_cache_entry(old_path: String, new_path: String) -> Result<(), CommandError> {
+    if old_path == new_path {
+        return Ok(());
+    }
+    let mut cache = lock_cache(&STI_CACHE)?;
+    if !rekey_cache_entry(&mut cache, &old_path, &new_path) {
+        return Err(CommandError::NotFound(format!("'{}' is not cached", old_path)));
+    }
+    drop(cache);
+    
+    let mut history = lock_cache(&EDIT_HISTORY)?;
+    if let Some(entry) = history.remove(&old_path) {
+        history.insert(new_path.clone(), entry);
+    }
+    lock_cache(&THUMBNAIL_CACHE)?.retain(|(path, _), _| path != &old_path && path != &new_path);
+    
     Ok(())
 }
 
 #[tauri::command]
-async fn check_file_exists(file_path: String) -> Result<bool, String> {
+async fn get_cache_stats() -> Result<serde_json::Value, CommandError> {
+    // Snapshot the entries so the size walk happens outside the lock
+    let sti_entries: Vec<(Arc<StiFile>, bool)> = {
+        let cache = lock_cache(&STI_CACHE)?;
+        cache.values().map(|entry| (entry.file.clone(), entry.dirty)).collect()
+    };
+    let directory_entries = lock_cache(&DIRECTORY_CACHE)?.len();
+    let thumbnail_entries = lock_cache(&THUMBNAIL_CACHE)?.len();
+    let config = cache_config();
+    
+    let approximate_bytes: usize = sti_entries.iter()
+        .flat_map(|(file, _)| file.images.iter())
+        .map(|image| image.raw_data.len() + image.decompressed_data.as_ref().map_or(0, |data| data.len()))
+        .sum();
+    let dirty_entries = sti_entries.iter().filter(|(_, dirty)| *dirty).count();
+    
+    Ok(serde_json::json!({
+        "sti_cache_entries": sti_entries.len(),
+        "sti_cache_dirty_entries": dirty_entries,
+        "sti_cache_bytes": approximate_bytes,
+        "directory_cache_entries": directory_entries,
+        "thumbnail_cache_entries": thumbnail_entries,
+        "sti_cache_limit": config.sti_max,
+        "directory_cache_limit": config.dir_max,
+    }))
+}
+
+#[tauri::command]
+async fn check_file_exists(file_path: String) -> Result<bool, CommandError> {
     let path = Path::new(&file_path);
     Ok(path.exists())
 }
 
 #[tauri::command]
-async fn analyze_image_for_import(image_path: String) -> Result<ImageAnalysisResult, String> {
+async fn analyze_image_for_import(image_path: String) -> Result<ImageAnalysisResult, CommandError> {
     use std::io::Cursor;
     
     let path = Path::new(&image_path);
     if !path.exists() {
-        return Err("Image file does not exist".to_string());
+        return Err(CommandError::NotFound("Image file does not exist".to_string()));
     }
 
     let file_data = fs::read(path)
-        .map_err(|e| format!("Failed to read image file: {}", e))?;
+        .map_err(|e| CommandError::Io(format!("Failed to read image file: {}", e)))?;
     
     let file_size = file_data.len() as u64;
 
     // Use the image crate to decode the image
     let img = image::load_from_memory(&file_data)
-        .map_err(|e| format!("Failed to decode image: {}", e))?;
+        .map_err(|e| CommandError::InvalidFormat(format!("Failed to decode image: {}", e)))?;
 
     let format = match image::guess_format(&file_data) {
         Ok(fmt) => format!("{:?}", fmt),
@@ -1251,17 +2431,17 @@ async fn analyze_image_for_import(image_path: String) -> Result<ImageAnalysisRes
 
 #[tauri::command]
 async fn import_image_to_new_sti(
-    _app: tauri::AppHandle,
+    app: tauri::AppHandle,
     source_path: String,
     destination_path: String,
     options: ImportOptions
-) -> Result<(), String> {
+) -> Result<(), CommandError> {
     // Load and decode the source image
     let source_data = fs::read(&source_path)
-        .map_err(|e| format!("Failed to read source image: {}", e))?;
+        .map_err(|e| CommandError::Io(format!("Failed to read source image: {}", e)))?;
     
     let img = image::load_from_memory(&source_data)
-        .map_err(|e| format!("Failed to decode source image: {}", e))?;
+        .map_err(|e| CommandError::InvalidFormat(format!("Failed to decode source image: {}", e)))?;
 
     let rgb_img = img.to_rgb8();
     let (width, height) = rgb_img.dimensions();
@@ -1282,7 +2462,8 @@ async fn import_image_to_new_sti(
     sti_file.header.color_depth = 8;
     sti_file.header.width = width as u16;
     sti_file.header.height = height as u16;
-    sti_file.header.transparent_color = options.transparent_color.unwrap_or(0) as u32;
+    // quantize_image_to_palette reserves index 0 for transparency, so ETRLE must run-encode index 0
+    sti_file.header.transparent_color = 0;
     
     // Set palette
     sti_file.palette = Some(palette_data);
@@ -1305,51 +2486,39 @@ async fn import_image_to_new_sti(
     sti_file.images.push(image);
     
     // Convert to editable format for saving
-    let editable_sti = convert_sti_to_editable(&sti_file)?;
+    let editable_sti = sti::ops::editable_from_sti(&sti_file)?;
     
     // Save the new STI file
-    save_sti_file(destination_path, editable_sti).await?;
+    save_sti_file(app, destination_path, editable_sti).await?;
     
     Ok(())
 }
 
 #[tauri::command]
 async fn import_image_to_existing_sti(
+    app: tauri::AppHandle,
     source_path: String,
     sti_path: String,
     insert_position: Option<usize>,
     options: ImportOptions
-) -> Result<(), String> {
+) -> Result<(), CommandError> {
     // Create backup first
     let _backup_path = create_sti_backup(sti_path.clone()).await?;
     
     // Load the existing STI file
-    let cached_file = {
-        let cache = STI_CACHE.lock().unwrap();
-        cache.get(&sti_path).cloned()
-    };
-    
-    let mut sti_file = if let Some(cached) = cached_file {
-        (*cached).clone()
-    } else {
-        let path = Path::new(&sti_path);
-        let file_data = fs::read(path)
-            .map_err(|e| format!("Failed to read STI file: {}", e))?;
-        sti::StiParser::parse(&file_data)
-            .map_err(|e| format!("Failed to parse STI file: {}", e))?
-    };
+    let mut sti_file = (*load_sti_file(&sti_path)?).clone();
     
     // Only support adding to 8-bit STI files for now
     if !sti_file.is_8bit() {
-        return Err("Can only import images into 8-bit STI files".to_string());
+        return Err(CommandError::Unsupported("Can only import images into 8-bit STI files".to_string()));
     }
     
     // Load and decode the source image
     let source_data = fs::read(&source_path)
-        .map_err(|e| format!("Failed to read source image: {}", e))?;
+        .map_err(|e| CommandError::Io(format!("Failed to read source image: {}", e)))?;
 
     let img = image::load_from_memory(&source_data)
-        .map_err(|e| format!("Failed to decode source image: {}", e))?;
+        .map_err(|e| CommandError::InvalidFormat(format!("Failed to decode source image: {}", e)))?;
 
     let rgb_img = img.to_rgb8();
     let (width, height) = rgb_img.dimensions();
@@ -1364,7 +2533,7 @@ async fn import_image_to_existing_sti(
             quantize_to_existing_palette(&rgb_img, existing_palette)?
         },
         "regenerate" => {
-            return Err("Palette regeneration not implemented yet".to_string());
+            return Err(CommandError::Unsupported("Palette regeneration not implemented yet".to_string()));
         },
         _ => {
             // Auto: use match strategy for existing files
@@ -1396,6 +2565,7 @@ async fn import_image_to_existing_sti(
     
     // Save the modified STI file
     save_modified_sti_file(&sti_path, &sti_file).await?;
+    notify_cache_invalidated(&app, &sti_path);
     
     Ok(())
 }
@@ -1416,7 +2586,7 @@ fn quantize_image_to_palette(rgb_img: &image::RgbImage, transparent_color: Optio
     
     // Sort colors by frequency (most used first)
     let mut sorted_colors: Vec<_> = color_histogram.into_iter().collect();
-    sorted_colors.sort_by(|a, b| b.1.cmp(&a.1));
+    sorted_colors.sort_by_key(|&(_, count)| std::cmp::Reverse(count));
     
     // Create palette (limit to 256 colors)
     let mut palette = [[0u8; 3]; 256];
@@ -1448,7 +2618,7 @@ fn quantize_image_to_palette(rgb_img: &image::RgbImage, transparent_color: Optio
             index
         } else {
             // Find closest color in palette
-            find_closest_palette_color(&palette, rgb)
+            sti::palette::nearest_index(&palette, [rgb.0, rgb.1, rgb.2])
         };
         
         indexed_data.push(palette_index);
@@ -1460,68 +2630,3226 @@ fn quantize_image_to_palette(rgb_img: &image::RgbImage, transparent_color: Optio
 fn quantize_to_existing_palette(rgb_img: &image::RgbImage, palette: &[[u8; 3]; 256]) -> Result<Vec<u8>, String> {
     let (width, height) = rgb_img.dimensions();
     let mut indexed_data = Vec::with_capacity((width * height) as usize);
+    let mut nearest = sti::palette::NearestColorCache::new(palette);
     
     for pixel in rgb_img.pixels() {
-        let rgb = (pixel[0], pixel[1], pixel[2]);
-        let palette_index = find_closest_palette_color(palette, rgb);
-        indexed_data.push(palette_index);
+        indexed_data.push(nearest.nearest([pixel[0], pixel[1], pixel[2]]));
     }
     
     Ok(indexed_data)
 }
 
-fn find_closest_palette_color(palette: &[[u8; 3]; 256], target_rgb: (u8, u8, u8)) -> u8 {
-    let mut best_index = 0;
-    let mut best_distance = u32::MAX;
+// 8-bit files need an index that fits the palette, since ETRLE encodes runs of it;
+// 16-bit files compare against the raw pixel value, so it must fit in 16 bits
+#[tauri::command]
+async fn set_transparent_color(app: tauri::AppHandle, file_path: String, color: u32) -> Result<(), CommandError> {
+    let mut sti_file = (*load_sti_file(&file_path)?).clone();
     
-    for (i, &color) in palette.iter().enumerate() {
-        let dr = (color[0] as i32 - target_rgb.0 as i32).abs() as u32;
-        let dg = (color[1] as i32 - target_rgb.1 as i32).abs() as u32;
-        let db = (color[2] as i32 - target_rgb.2 as i32).abs() as u32;
-        
-        // Simple Euclidean distance
-        let distance = dr * dr + dg * dg + db * db;
-        
-        if distance < best_distance {
-            best_distance = distance;
-            best_index = i;
+    if sti_file.is_8bit() && color > 255 {
+        return Err(CommandError::OutOfBounds(format!("Transparent color {} is not a palette index (0-255)", color)));
+    }
+    if sti_file.is_16bit() && color > 0xFFFF {
+        return Err(CommandError::OutOfBounds(format!("Transparent color {} does not fit a 16-bit pixel", color)));
+    }
+    
+    sti_file.header.transparent_color = color;
+    store_edited_sti_file(&file_path, sti_file)?;
+    notify_cache_invalidated(&app, &file_path);
+    
+    Ok(())
+}
+
+// Guess the background from the first frame: the most common index along its border for
+// 8-bit files, the most common of the four corner pixels for 16-bit. Ties go to the lower value
+fn detect_transparent_value(sti_file: &StiFile) -> Result<u32, String> {
+    let image = sti_file.images.first()
+        .ok_or("File has no images")?;
+    let pixel_data = image.decompressed_data.as_ref()
+        .ok_or("Image data not decompressed")?;
+    let (width, height) = (image.width as usize, image.height as usize);
+    let bytes_per_pixel = if sti_file.is_16bit() { 2 } else { 1 };
+    if width == 0 || height == 0 || pixel_data.len() < width * height * bytes_per_pixel {
+        return Err("First frame has no pixel data to sample".to_string());
+    }
+    
+    let mut positions = Vec::new();
+    if sti_file.is_16bit() {
+        positions.extend([(0, 0), (width - 1, 0), (0, height - 1), (width - 1, height - 1)]);
+    } else {
+        for x in 0..width {
+            positions.push((x, 0));
+            if height > 1 {
+                positions.push((x, height - 1));
+            }
         }
+        for y in 1..height.saturating_sub(1) {
+            positions.push((0, y));
+            if width > 1 {
+                positions.push((width - 1, y));
+            }
+        }
+    }
+    
+    let mut counts: HashMap<u32, usize> = HashMap::new();
+    for (x, y) in positions {
+        let offset = (y * width + x) * bytes_per_pixel;
+        let value = if sti_file.is_16bit() {
+            u16::from_le_bytes([pixel_data[offset], pixel_data[offset + 1]]) as u32
+        } else {
+            pixel_data[offset] as u32
+        };
+        *counts.entry(value).or_insert(0) += 1;
     }
     
-    best_index as u8
+    counts.into_iter()
+        .max_by(|a, b| a.1.cmp(&b.1).then(b.0.cmp(&a.0)))
+        .map(|(value, _)| value)
+        .ok_or_else(|| "First frame has no pixel data to sample".to_string())
 }
 
-#[cfg_attr(mobile, tauri::mobile_entry_point)]
-pub fn run() {
-    tauri::Builder::default()
-        .plugin(tauri_plugin_opener::init())
-        .plugin(tauri_plugin_dialog::init())
-        .plugin(tauri_plugin_fs::init())
-        .invoke_handler(tauri::generate_handler![
-            open_sti_file,
-            get_sti_image,
-            get_sti_metadata,
-            save_sti_file,
-            export_image,
-            select_directory,
-            browse_directory,
-            scan_for_sti_files,
-            debug_sti_file,
-            clear_sti_cache,
-            enter_edit_mode,
-            update_image_data,
-            add_new_image,
-            reorder_images,
-            delete_image,
-            remove_images_from_sti,
-            create_sti_backup,
-            validate_sti_integrity,
-            restore_sti_from_backup,
-            check_file_exists,
-            analyze_image_for_import,
-            import_image_to_new_sti,
-            import_image_to_existing_sti
-        ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+// Propose a transparent color for files that render with a solid background, and
+// record it through set_transparent_color when `apply` is set
+#[tauri::command]
+async fn detect_transparent_color(app: tauri::AppHandle, file_path: String, apply: Option<bool>) -> Result<u32, CommandError> {
+    let sti_file = load_sti_file(&file_path)?;
+    let color = detect_transparent_value(&sti_file)?;
+    if apply.unwrap_or(false) {
+        set_transparent_color(app, file_path, color).await?;
+    }
+    
+    Ok(color)
+}
+
+#[tauri::command]
+async fn get_animation_data(file_path: String) -> Result<Vec<u8>, CommandError> {
+    let sti_file = load_sti_file(&file_path)?;
+    
+    Ok(sti_file.animation_data.iter().map(|anim| anim.frame_count).collect())
+}
+
+// Files without animation data get one empty record per image before the edit
+#[tauri::command]
+async fn set_animation_frame_count(app: tauri::AppHandle, file_path: String, index: usize, count: u8) -> Result<(), CommandError> {
+    let mut sti_file = (*load_sti_file(&file_path)?).clone();
+    
+    if !sti_file.is_8bit() {
+        return Err(CommandError::Unsupported("Animation data is only supported for 8-bit files".to_string()));
+    }
+    if index >= sti_file.images.len() {
+        return Err(CommandError::OutOfBounds("Image index out of bounds".to_string()));
+    }
+    
+    if sti_file.animation_data.is_empty() && !sti_file.app_data.is_empty() {
+        return Err(CommandError::Unsupported("File carries application data that is not animation records".to_string()));
+    }
+    
+    if sti_file.animation_data.len() < sti_file.images.len() {
+        sti_file.animation_data.resize(sti_file.images.len(), sti::StiAnimationData::default());
+    }
+    sti_file.animation_data[index].frame_count = count;
+    sti_file.header.app_data_size = sti_file.app_data_bytes().len() as u32;
+    
+    store_edited_sti_file(&file_path, sti_file)?;
+    notify_cache_invalidated(&app, &file_path);
+    
+    Ok(())
+}
+
+// Why a file has no palette to work on: 16-bit files never carry one, an 8-bit file lost it
+fn missing_palette_message(sti_file: &StiFile) -> String {
+    if sti_file.is_16bit() {
+        "16-bit files store colors directly and have no palette".to_string()
+    } else {
+        "File has no palette, palette operations need an 8-bit file".to_string()
+    }
+}
+
+fn palette_of(sti_file: &StiFile) -> Result<&sti::StiPalette, CommandError> {
+    match (&sti_file.palette, sti_file.is_8bit()) {
+        (Some(palette), true) => Ok(palette),
+        _ => Err(CommandError::Unsupported(missing_palette_message(sti_file))),
+    }
+}
+
+fn palette_of_mut(sti_file: &mut StiFile) -> Result<&mut sti::StiPalette, CommandError> {
+    if !sti_file.is_8bit() {
+        return Err(CommandError::Unsupported(missing_palette_message(sti_file)));
+    }
+    match sti_file.palette {
+        Some(ref mut palette) => Ok(palette),
+        None => Err(CommandError::Unsupported(missing_palette_message(sti_file))),
+    }
+}
+
+#[tauri::command]
+async fn get_palette(file_path: String) -> Result<Vec<[u8; 3]>, CommandError> {
+    let sti_file = load_sti_file(&file_path)?;
+    
+    let palette = palette_of(&sti_file)?;
+    
+    Ok(palette.to_vec())
+}
+
+#[tauri::command]
+async fn update_palette_color(app: tauri::AppHandle, file_path: String, index: usize, color: [u8; 3]) -> Result<(), CommandError> {
+    let mut sti_file = (*load_sti_file(&file_path)?).clone();
+    
+    let palette = palette_of_mut(&mut sti_file)?;
+    
+    if index >= palette.len() {
+        return Err(CommandError::OutOfBounds(format!("Palette index {} out of range (0-{})", index, palette.len() - 1)));
+    }
+    
+    palette[index] = color;
+    store_edited_sti_file(&file_path, sti_file)?;
+    notify_cache_invalidated(&app, &file_path);
+    
+    Ok(())
+}
+
+// Recolor a contiguous palette range, e.g. a team or uniform color ramp, by rotating its hue.
+// Only the palette changes, the frames keep their indices
+#[tauri::command]
+async fn shift_palette_range(app: tauri::AppHandle, file_path: String, start: u8, count: u8, hue_degrees: f32) -> Result<(), CommandError> {
+    if !hue_degrees.is_finite() {
+        return Err(CommandError::OutOfBounds(format!("Hue shift must be a finite number of degrees, got {}", hue_degrees)));
+    }
+    let end = start as usize + count as usize;
+    if count == 0 || end > 256 {
+        return Err(CommandError::OutOfBounds(format!(
+            "Palette range of {} entries starting at {} must lie within 0-255", count, start
+        )));
+    }
+    
+    let mut sti_file = (*load_sti_file(&file_path)?).clone();
+    let palette = palette_of_mut(&mut sti_file)?;
+    
+    for color in &mut palette[start as usize..end] {
+        *color = sti::palette::shift_hue(*color, hue_degrees);
+    }
+    store_edited_sti_file(&file_path, sti_file)?;
+    notify_cache_invalidated(&app, &file_path);
+    
+    Ok(())
+}
+
+// Rotate the pixel indices inside [start, start + count) by `shift` places, wrapping within
+// the range, the way the engine offsets team colors. The palette table stays as it is
+fn cycle_pixel_indices(sti_file: &mut StiFile, start: u8, count: u8, shift: i16) {
+    let start = start as usize;
+    let count = count as usize;
+    let step = (shift as i32).rem_euclid(count as i32) as usize;
+    if step == 0 {
+        return;
+    }
+    
+    for image in &mut sti_file.images {
+        if let Some(pixels) = &mut image.decompressed_data {
+            for pixel in pixels.iter_mut() {
+                let index = *pixel as usize;
+                if index >= start && index < start + count {
+                    *pixel = (start + (index - start + step) % count) as u8;
+                }
+            }
+        }
+    }
+}
+
+#[tauri::command]
+async fn cycle_team_colors(app: tauri::AppHandle, file_path: String, start: u8, count: u8, shift: i16) -> Result<(), CommandError> {
+    let end = start as usize + count as usize;
+    if count == 0 || end > 256 {
+        return Err(CommandError::OutOfBounds(format!(
+            "Palette range of {} entries starting at {} must lie within 0-255", count, start
+        )));
+    }
+    
+    let mut sti_file = (*load_sti_file(&file_path)?).clone();
+    if !sti_file.is_8bit() {
+        return Err(CommandError::Unsupported("Team color cycling only applies to 8-bit indexed files".to_string()));
+    }
+    
+    cycle_pixel_indices(&mut sti_file, start, count, shift);
+    store_edited_sti_file(&file_path, sti_file)?;
+    notify_cache_invalidated(&app, &file_path);
+    
+    Ok(())
+}
+
+fn palette_format_for(format: &str) -> Result<sti::palette::PaletteFormat, String> {
+    sti::palette::PaletteFormat::from_name(format)
+        .ok_or_else(|| format!("Unsupported palette format: {}", format))
+}
+
+#[tauri::command]
+async fn export_palette(file_path: String, output_path: String, format: String) -> Result<(), CommandError> {
+    check_write_allowed(&output_path)?;
+    let palette_format = palette_format_for(&format)?;
+    let sti_file = load_sti_file(&file_path)?;
+    
+    let palette = palette_of(&sti_file)?;
+    
+    fs::write(&output_path, sti::palette::write_palette(palette, palette_format))
+        .map_err(|e| CommandError::Io(format!("Failed to write palette: {}", e)))
+}
+
+// Swatch sheet for documenting a palette, 16 cells to a row
+#[tauri::command]
+async fn export_palette_image(file_path: String, output_path: String, swatch_size: u32) -> Result<(), CommandError> {
+    check_write_allowed(&output_path)?;
+    if !(1..=256).contains(&swatch_size) {
+        return Err(CommandError::OutOfBounds(format!("Swatch size {} must be between 1 and 256 pixels", swatch_size)));
+    }
+    let sti_file = load_sti_file(&file_path)?;
+    let palette = palette_of(&sti_file)?;
+    
+    sti::palette::swatch_image(palette, swatch_size, sti_file.header.etrle_transparent_index())
+        .save_with_format(&output_path, image::ImageFormat::Png)
+        .map_err(|e| CommandError::Io(format!("Failed to write palette image '{}': {}", output_path, e)))
+}
+
+#[tauri::command]
+async fn import_palette(app: tauri::AppHandle, file_path: String, palette_path: String, remap_existing: bool) -> Result<(), CommandError> {
+    let extension = Path::new(&palette_path).extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or("");
+    let palette_format = palette_format_for(extension)?;
+    
+    let palette_bytes = fs::read(&palette_path)
+        .map_err(|e| CommandError::Io(format!("Failed to read palette file: {}", e)))?;
+    let new_palette = sti::palette::read_palette(&palette_bytes, palette_format)
+        .map_err(|e| CommandError::InvalidFormat(format!("Failed to parse palette file: {}", e)))?;
+    
+    let mut sti_file = (*load_sti_file(&file_path)?).clone();
+    replace_palette(&mut sti_file, new_palette, remap_existing)?;
+    store_edited_sti_file(&file_path, sti_file)?;
+    notify_cache_invalidated(&app, &file_path);
+    
+    Ok(())
+}
+
+// Swap in `new_palette`, optionally remapping every pixel to the nearest new color first
+fn replace_palette(sti_file: &mut StiFile, new_palette: sti::StiPalette, remap_existing: bool) -> Result<(), String> {
+    let old_palette = sti_file.palette
+        .ok_or_else(|| missing_palette_message(sti_file))?;
+    
+    if remap_existing {
+        // Map every old index to the nearest new color, keeping the transparent index in place
+        let transparent_index = sti_file.header.etrle_transparent_index();
+        let index_map = sti::palette::remap_table(&old_palette, &new_palette, transparent_index);
+        
+        for image in &mut sti_file.images {
+            if let Some(pixel_data) = &mut image.decompressed_data {
+                for index in pixel_data.iter_mut() {
+                    *index = index_map[*index as usize];
+                }
+            }
+        }
+    }
+    
+    sti_file.palette = Some(new_palette);
+    Ok(())
+}
+
+// Reskin every 8-bit file under a directory with one palette file, backing each up first.
+// A file that fails is reported and the sweep moves on
+#[tauri::command]
+async fn apply_palette_to_directory(app: tauri::AppHandle, directory_path: String, palette_path: String, recursive: bool, remap: bool) -> Result<serde_json::Value, CommandError> {
+    use serde_json::json;
+    
+    let extension = Path::new(&palette_path).extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or("");
+    let palette_format = palette_format_for(extension)?;
+    let palette_bytes = fs::read(&palette_path)
+        .map_err(|e| CommandError::Io(format!("Failed to read palette file: {}", e)))?;
+    let new_palette = sti::palette::read_palette(&palette_bytes, palette_format)
+        .map_err(|e| CommandError::InvalidFormat(format!("Failed to parse palette file: {}", e)))?;
+    
+    let mut sti_paths = Vec::new();
+    scan_directory_for_sti(Path::new(&directory_path), &mut sti_paths, recursive)?;
+    sti_paths.sort();
+    
+    let mut results = Vec::with_capacity(sti_paths.len());
+    for sti_path in sti_paths {
+        // Writing would drop the cached copy, and with it the unsaved edits
+        if has_unsaved_edits(&sti_path)? {
+            results.push(json!({ "path": sti_path, "status": "skipped", "note": "File has unsaved edits" }));
+            continue;
+        }
+        let mut sti_file = match sti::ops::read_file(Path::new(&sti_path)) {
+            Ok(sti_file) => sti_file,
+            Err(e) => {
+                results.push(json!({ "path": sti_path, "status": "failed", "error": e.to_string() }));
+                continue;
+            }
+        };
+        if !sti_file.is_8bit() {
+            results.push(json!({ "path": sti_path, "status": "skipped", "note": missing_palette_message(&sti_file) }));
+            continue;
+        }
+        
+        let applied = async {
+            replace_palette(&mut sti_file, new_palette, remap)?;
+            let backup_path = create_sti_backup(sti_path.clone()).await?;
+            write_sti_file(&sti_path, &mut sti_file)?;
+            Ok::<String, CommandError>(backup_path)
+        }.await;
+        
+        match applied {
+            Ok(backup_path) => {
+                notify_cache_invalidated(&app, &sti_path);
+                results.push(json!({ "path": sti_path, "status": "applied", "backup_path": backup_path }));
+            }
+            Err(e) => results.push(json!({ "path": sti_path, "status": "failed", "error": e.to_string() })),
+        }
+    }
+    
+    let count = |status: &str| results.iter().filter(|result| result["status"] == status).count();
+    Ok(json!({
+        "applied": count("applied"),
+        "skipped": count("skipped"),
+        "failed": count("failed"),
+        "files": results,
+    }))
+}
+
+// Deduplicate and sort the palette of a cached file, remapping every frame; a later save persists it
+#[tauri::command]
+async fn normalize_palette(app: tauri::AppHandle, file_path: String) -> Result<serde_json::Value, CommandError> {
+    let mut sti_file = (*load_sti_file(&file_path)?).clone();
+    if !sti_file.is_8bit() {
+        return Err(CommandError::Unsupported("Palette normalization is only supported for 8-bit files".to_string()));
+    }
+    let old_palette = sti_file.palette
+        .ok_or("8-bit image missing palette")?;
+    
+    let transparent = sti_file.header.etrle_transparent_index();
+    let (new_palette, index_map, collapsed) = sti::palette::normalize_palette(&old_palette, transparent);
+    for (index, image) in sti_file.images.iter_mut().enumerate() {
+        let pixel_data = image.decompressed_data.as_mut()
+            .ok_or_else(|| CommandError::InvalidFormat(format!("Image {} failed to decompress", index)))?;
+        for pixel in pixel_data.iter_mut() {
+            *pixel = index_map[*pixel as usize];
+        }
+    }
+    
+    sti_file.palette = Some(new_palette);
+    store_edited_sti_file(&file_path, sti_file)?;
+    notify_cache_invalidated(&app, &file_path);
+    
+    Ok(serde_json::json!({
+        "collapsed": collapsed,
+        "unique_colors": 255 - collapsed,
+    }))
+}
+
+#[tauri::command]
+async fn analyze_palette_usage(file_path: String) -> Result<serde_json::Value, CommandError> {
+    let sti_file = load_sti_file(&file_path)?;
+    if !sti_file.is_8bit() {
+        return Err(CommandError::Unsupported("Palette usage is only available for 8-bit files".to_string()));
+    }
+    
+    let histogram = palette_index_counts(&sti_file);
+    let unused = histogram.iter().filter(|&&count| count == 0).count();
+    Ok(serde_json::json!({
+        "histogram": histogram,
+        "used": 256 - unused,
+        "unused": unused,
+    }))
+}
+
+// Most used 16-bit pixel values and their counts, busiest first
+fn rgb16_color_counts(images: &[sti::StiImage], limit: usize) -> Vec<(u16, u64)> {
+    let mut counts: HashMap<u16, u64> = HashMap::new();
+    for image in images {
+        if let Some(pixel_data) = &image.decompressed_data {
+            for pixel in pixel_data.chunks_exact(2) {
+                *counts.entry(u16::from_le_bytes([pixel[0], pixel[1]])).or_insert(0) += 1;
+            }
+        }
+    }
+    
+    let mut colors: Vec<(u16, u64)> = counts.into_iter().collect();
+    colors.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    colors.truncate(limit);
+    colors
+}
+
+// Color usage of one frame, or of every frame when image_index is omitted: counts per palette
+// index for 8-bit files, the most used colors for 16-bit files
+#[tauri::command]
+async fn color_histogram(file_path: String, image_index: Option<usize>) -> Result<serde_json::Value, CommandError> {
+    let sti_file = load_sti_file(&file_path)?;
+    let images = match image_index {
+        Some(index) => {
+            if index >= sti_file.images.len() {
+                return Err(CommandError::OutOfBounds("Image index out of bounds".to_string()));
+            }
+            &sti_file.images[index..=index]
+        }
+        None => &sti_file.images[..],
+    };
+    
+    match sti_file.format() {
+        sti::StiFormat::Indexed8 => Ok(serde_json::json!({
+            "format": "indexed",
+            "counts": frame_index_counts(images),
+        })),
+        sti::StiFormat::Rgb16 => {
+            let colors: Vec<serde_json::Value> = rgb16_color_counts(images, RGB_HISTOGRAM_TOP_COLORS).into_iter()
+                .map(|(pixel, count)| serde_json::json!({
+                    "pixel": pixel,
+                    "rgb": sti_file.header.decode_rgb16(pixel),
+                    "count": count,
+                }))
+                .collect();
+            Ok(serde_json::json!({ "format": "rgb16", "colors": colors }))
+        }
+        sti::StiFormat::Unknown => Err(ambiguous_format_error(&sti_file)),
+    }
+}
+
+#[tauri::command]
+async fn compact_palette(app: tauri::AppHandle, file_path: String) -> Result<serde_json::Value, CommandError> {
+    let mut sti_file = (*load_sti_file(&file_path)?).clone();
+    if !sti_file.is_8bit() {
+        return Err(CommandError::Unsupported("Palette compaction is only supported for 8-bit files".to_string()));
+    }
+    let old_palette = sti_file.palette
+        .ok_or("8-bit image missing palette")?;
+    if let Some(index) = sti_file.images.iter().position(|image| image.decompressed_data.is_none()) {
+        return Err(CommandError::InvalidFormat(format!("Image {} failed to decompress", index)));
+    }
+    
+    let used: Vec<bool> = palette_index_counts(&sti_file).iter().map(|&count| count > 0).collect();
+    let transparent = sti_file.header.transparent_color.min(255) as u8;
+    let (new_palette, index_map, used_colors) = sti::palette::compact_palette(&old_palette, &used, transparent);
+    for image in sti_file.images.iter_mut() {
+        if let Some(pixel_data) = image.decompressed_data.as_mut() {
+            for pixel in pixel_data.iter_mut() {
+                *pixel = index_map[*pixel as usize];
+            }
+        }
+    }
+    
+    sti_file.palette = Some(new_palette);
+    store_edited_sti_file(&file_path, sti_file)?;
+    notify_cache_invalidated(&app, &file_path);
+    
+    Ok(serde_json::json!({
+        "used_colors": used_colors,
+        "freed_slots": 256 - used_colors,
+    }))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PickedColor {
+    pub local_x: i32,          // Coordinate inside the frame after removing offsets
+    pub local_y: i32,
+    pub inside: bool,          // False if the point lies outside the frame
+    pub transparent: bool,
+    pub palette_index: Option<u8>, // 8-bit only
+    pub rgb565: Option<u16>,       // 16-bit only
+    pub rgb: Option<[u8; 3]>,
+}
+
+#[tauri::command]
+async fn pick_color_at(file_path: String, image_index: usize, composite_x: i32, composite_y: i32) -> Result<PickedColor, CommandError> {
+    let sti_file = load_sti_file(&file_path)?;
+    
+    if image_index >= sti_file.images.len() {
+        return Err(CommandError::OutOfBounds("Image index out of bounds".to_string()));
+    }
+    
+    let image = &sti_file.images[image_index];
+    
+    // Map the composite coordinate into frame space
+    let (offset_x, offset_y) = image.header.as_ref()
+        .map(|h| (h.offset_x as i32, h.offset_y as i32))
+        .unwrap_or((0, 0));
+    let local_x = composite_x - offset_x;
+    let local_y = composite_y - offset_y;
+    
+    let mut picked = PickedColor {
+        local_x,
+        local_y,
+        inside: false,
+        transparent: true,
+        palette_index: None,
+        rgb565: None,
+        rgb: None,
+    };
+    
+    if local_x < 0 || local_y < 0 || local_x >= image.width as i32 || local_y >= image.height as i32 {
+        return Ok(picked);
+    }
+    picked.inside = true;
+    
+    let pixel_data = image.decompressed_data.as_ref()
+        .ok_or("Image data not decompressed")?;
+    let pixel_pos = local_y as usize * image.width as usize + local_x as usize;
+    
+    if sti_file.is_8bit() {
+        let palette = sti_file.palette.as_ref()
+            .ok_or("8-bit image missing palette")?;
+        let index = *pixel_data.get(pixel_pos)
+            .ok_or("Pixel outside decoded data")?;
+        
+        // ETRLE encodes transparency as the header's transparent index
+        picked.transparent = index == sti_file.header.etrle_transparent_index();
+        picked.palette_index = Some(index);
+        picked.rgb = Some(palette[index as usize]);
+    } else {
+        let byte_pos = pixel_pos * 2;
+        if byte_pos + 1 >= pixel_data.len() {
+            return Err(CommandError::OutOfBounds("Pixel outside decoded data".to_string()));
+        }
+        let value = u16::from_le_bytes([pixel_data[byte_pos], pixel_data[byte_pos + 1]]);
+        
+        picked.transparent = sti_file.header.flags.transparent
+            && value as u32 == sti_file.header.transparent_color;
+        picked.rgb565 = Some(value);
+        picked.rgb = Some(sti_file.header.decode_rgb16(value));
+    }
+    
+    Ok(picked)
+}
+
+// Byte offset of a pixel inside a frame's decoded data
+fn pixel_offset(sti_file: &StiFile, image: &sti::StiImage, x: u16, y: u16) -> Result<usize, CommandError> {
+    if x >= image.width || y >= image.height {
+        return Err(CommandError::OutOfBounds(format!(
+            "Pixel ({}, {}) is outside the {}x{} frame",
+            x, y, image.width, image.height
+        )));
+    }
+    let bytes_per_pixel = if sti_file.is_16bit() { 2 } else { 1 };
+    let offset = (y as usize * image.width as usize + x as usize) * bytes_per_pixel;
+    
+    let data_len = image.decompressed_data.as_ref().map_or(0, |data| data.len());
+    if offset + bytes_per_pixel > data_len {
+        return Err(CommandError::InvalidFormat("Decoded image data is smaller than the frame dimensions".to_string()));
+    }
+    Ok(offset)
+}
+
+// Edit a cached file in place, cloning it only if another reader still holds the previous state
+fn edit_cached_sti_file<T>(file_path: &str, edit: impl FnOnce(&mut StiFile) -> Result<T, CommandError>) -> Result<T, CommandError> {
+    // Make sure a fresh parse is cached before taking the lock
+    load_sti_file(file_path)?;
+    
+    let mut cache = lock_cache(&STI_CACHE)?;
+    let entry = cache.get_mut(file_path)
+        .ok_or_else(|| CommandError::Internal(format!("'{}' was evicted from the cache while editing", file_path)))?;
+    let result = edit(Arc::make_mut(&mut entry.file))?;
+    entry.dirty = true;
+    Ok(result)
+}
+
+// Value written by set_pixel: a palette index for 8-bit files, a packed RGB565 pixel for 16-bit
+fn write_pixel(sti_file: &mut StiFile, image_index: usize, x: u16, y: u16, value: u16) -> Result<(), CommandError> {
+    let is_16bit = sti_file.is_16bit();
+    let image = sti_file.images.get(image_index)
+        .ok_or_else(|| CommandError::OutOfBounds("Image index out of bounds".to_string()))?;
+    let offset = pixel_offset(sti_file, image, x, y)?;
+    if !is_16bit && value > u8::MAX as u16 {
+        return Err(CommandError::OutOfBounds(format!("Palette index {} out of range (0-255)", value)));
+    }
+    
+    let pixel_data = sti_file.images[image_index].decompressed_data.as_mut()
+        .ok_or("Image data not decompressed")?;
+    if is_16bit {
+        pixel_data[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+    } else {
+        pixel_data[offset] = value as u8;
+    }
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_pixel(file_path: String, image_index: usize, x: u16, y: u16) -> Result<serde_json::Value, CommandError> {
+    let sti_file = load_sti_file(&file_path)?;
+    let image = sti_file.images.get(image_index)
+        .ok_or_else(|| CommandError::OutOfBounds("Image index out of bounds".to_string()))?;
+    let offset = pixel_offset(&sti_file, image, x, y)?;
+    let pixel_data = image.decompressed_data.as_ref()
+        .ok_or("Image data not decompressed")?;
+    
+    if sti_file.is_8bit() {
+        let palette = sti_file.palette.as_ref()
+            .ok_or("8-bit image missing palette")?;
+        let index = pixel_data[offset];
+        Ok(serde_json::json!({
+            "index": index,
+            "rgb": palette[index as usize],
+        }))
+    } else {
+        let value = u16::from_le_bytes([pixel_data[offset], pixel_data[offset + 1]]);
+        Ok(serde_json::json!({
+            "rgb565": value,
+            "rgb": sti_file.header.decode_rgb16(value),
+        }))
+    }
+}
+
+#[tauri::command]
+async fn set_pixel(app: tauri::AppHandle, file_path: String, image_index: usize, x: u16, y: u16, value: u16) -> Result<(), CommandError> {
+    edit_cached_sti_file(&file_path, |sti_file| write_pixel(sti_file, image_index, x, y, value))?;
+    notify_cache_invalidated(&app, &file_path);
+    Ok(())
+}
+
+// Move a frame without touching its pixels; raw_data stays as read, so a save only changes the offset bytes
+#[tauri::command]
+async fn set_frame_offset(app: tauri::AppHandle, file_path: String, image_index: usize, offset_x: i16, offset_y: i16) -> Result<(), CommandError> {
+    edit_cached_sti_file(&file_path, |sti_file| {
+        let image = sti_file.images.get_mut(image_index)
+            .ok_or_else(|| CommandError::OutOfBounds("Image index out of bounds".to_string()))?;
+        let header = image.header.as_mut()
+            .ok_or_else(|| CommandError::Unsupported("Single-image 16-bit files have no sub-header to hold an offset".to_string()))?;
+        header.offset_x = offset_x;
+        header.offset_y = offset_y;
+        Ok(())
+    })?;
+    notify_cache_invalidated(&app, &file_path);
+    Ok(())
+}
+
+// Apply a whole brush stroke under one cache lock; nothing is written if any point is invalid
+#[tauri::command]
+async fn set_pixels(app: tauri::AppHandle, file_path: String, image_index: usize, points: Vec<(u16, u16, u16)>) -> Result<(), CommandError> {
+    edit_cached_sti_file(&file_path, |sti_file| {
+        let is_16bit = sti_file.is_16bit();
+        let image = sti_file.images.get(image_index)
+            .ok_or_else(|| CommandError::OutOfBounds("Image index out of bounds".to_string()))?;
+        
+        let mut offsets = Vec::with_capacity(points.len());
+        for (position, &(x, y, value)) in points.iter().enumerate() {
+            let offset = pixel_offset(sti_file, image, x, y)
+                .map_err(|e| CommandError::OutOfBounds(format!("Point {}: {}", position, e)))?;
+            if !is_16bit && value > u8::MAX as u16 {
+                return Err(CommandError::OutOfBounds(format!(
+                    "Point {}: palette index {} out of range (0-255)",
+                    position, value
+                )));
+            }
+            offsets.push((offset, value));
+        }
+        
+        let pixel_data = sti_file.images[image_index].decompressed_data.as_mut()
+            .ok_or("Image data not decompressed")?;
+        for (offset, value) in offsets {
+            if is_16bit {
+                pixel_data[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+            } else {
+                pixel_data[offset] = value as u8;
+            }
+        }
+        Ok(())
+    })?;
+    notify_cache_invalidated(&app, &file_path);
+    Ok(())
+}
+
+#[tauri::command]
+async fn export_svg(file_path: String, image_index: usize, output_path: String, pixel_size: u32) -> Result<(), CommandError> {
+    use std::fmt::Write;
+    
+    check_write_allowed(&output_path)?;
+    if pixel_size == 0 {
+        return Err(CommandError::OutOfBounds("Pixel size must be at least 1".to_string()));
+    }
+    
+    let sti_file = load_sti_file(&file_path)?;
+    
+    if image_index >= sti_file.images.len() {
+        return Err(CommandError::OutOfBounds("Image index out of bounds".to_string()));
+    }
+    
+    let image = &sti_file.images[image_index];
+    let colors = decode_frame_colors(&sti_file, image)?;
+    let width = image.width as usize;
+    let height = image.height as usize;
+    
+    if colors.len() < width * height {
+        return Err(CommandError::InvalidFormat("Decoded image data is smaller than the frame dimensions".to_string()));
+    }
+    
+    let mut svg = String::new();
+    let _ = writeln!(svg, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>");
+    let _ = writeln!(
+        svg,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\" shape-rendering=\"crispEdges\">",
+        width as u32 * pixel_size,
+        height as u32 * pixel_size,
+        width as u32 * pixel_size,
+        height as u32 * pixel_size
+    );
+    
+    for y in 0..height {
+        let row = &colors[y * width..(y + 1) * width];
+        let mut x = 0;
+        
+        while x < width {
+            let color = match row[x] {
+                Some(color) => color,
+                None => {
+                    x += 1;
+                    continue;
+                }
+            };
+            
+            // Merge horizontally adjacent pixels of the same color into one rect
+            let mut run = 1;
+            while x + run < width && row[x + run] == Some(color) {
+                run += 1;
+            }
+            
+            let _ = writeln!(
+                svg,
+                "  <rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"#{:02x}{:02x}{:02x}\"/>",
+                x as u32 * pixel_size,
+                y as u32 * pixel_size,
+                run as u32 * pixel_size,
+                pixel_size,
+                color[0],
+                color[1],
+                color[2]
+            );
+            
+            x += run;
+        }
+    }
+    
+    svg.push_str("</svg>\n");
+    
+    fs::write(&output_path, svg)
+        .map_err(|e| CommandError::Io(format!("Failed to write SVG '{}': {}", output_path, e)))?;
+    
+    Ok(())
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FrameManifestEntry {
+    pub index: Option<usize>, // Defaults to the entry's position in the manifest
+    pub offset_x: i16,
+    pub offset_y: i16,
+    pub name: Option<String>, // Informational only, STI files have no frame names
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FrameManifestResult {
+    pub frames_updated: usize,
+    pub frames_changed: usize,
+}
+
+fn parse_frame_manifest(manifest_path: &str, contents: &str) -> Result<Vec<FrameManifestEntry>, String> {
+    let is_csv = Path::new(manifest_path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.eq_ignore_ascii_case("csv"))
+        .unwrap_or(false);
+    
+    if !is_csv {
+        // Accept either a bare array or an object with a "frames" array
+        let value: serde_json::Value = serde_json::from_str(contents)
+            .map_err(|e| format!("Invalid JSON manifest: {}", e))?;
+        let frames = match value.get("frames") {
+            Some(frames) => frames.clone(),
+            None => value,
+        };
+        return serde_json::from_value(frames)
+            .map_err(|e| format!("Invalid frame entries in manifest: {}", e));
+    }
+    
+    let mut lines = contents.lines().filter(|line| !line.trim().is_empty());
+    let header: Vec<String> = lines.next()
+        .ok_or("CSV manifest is empty")?
+        .split(',')
+        .map(|column| column.trim().to_lowercase())
+        .collect();
+    
+    let column = |name: &str| header.iter().position(|c| c == name);
+    let index_col = column("index");
+    let name_col = column("name");
+    let offset_x_col = column("offset_x").ok_or("CSV manifest is missing an offset_x column")?;
+    let offset_y_col = column("offset_y").ok_or("CSV manifest is missing an offset_y column")?;
+    
+    let mut entries = Vec::new();
+    for (line_number, line) in lines.enumerate() {
+        let fields: Vec<&str> = line.split(',').map(|f| f.trim()).collect();
+        let field = |col: usize| fields.get(col).copied()
+            .ok_or(format!("CSV row {} has too few columns", line_number + 2));
+        
+        let index = match index_col {
+            Some(col) => Some(field(col)?.parse::<usize>()
+                .map_err(|e| format!("CSV row {}: invalid index: {}", line_number + 2, e))?),
+            None => None,
+        };
+        
+        entries.push(FrameManifestEntry {
+            index,
+            offset_x: field(offset_x_col)?.parse::<i16>()
+                .map_err(|e| format!("CSV row {}: invalid offset_x: {}", line_number + 2, e))?,
+            offset_y: field(offset_y_col)?.parse::<i16>()
+                .map_err(|e| format!("CSV row {}: invalid offset_y: {}", line_number + 2, e))?,
+            name: name_col.and_then(|col| fields.get(col)).map(|n| n.to_string()),
+        });
+    }
+    
+    Ok(entries)
+}
+
+#[tauri::command]
+async fn apply_frame_manifest(app: tauri::AppHandle, file_path: String, manifest_path: String) -> Result<FrameManifestResult, CommandError> {
+    let contents = fs::read_to_string(&manifest_path)
+        .map_err(|e| CommandError::Io(format!("Failed to read manifest: {}", e)))?;
+    let entries = parse_frame_manifest(&manifest_path, &contents)?;
+    
+    let mut sti_file = (*load_sti_file(&file_path)?).clone();
+    
+    if !sti_file.is_8bit() {
+        return Err(CommandError::Unsupported("Frame offsets are only stored in 8-bit STI files".to_string()));
+    }
+    
+    if entries.len() != sti_file.images.len() {
+        return Err(CommandError::InvalidFormat(format!(
+            "Manifest describes {} frames but the file contains {}",
+            entries.len(),
+            sti_file.images.len()
+        )));
+    }
+    
+    // Validate every entry before touching the file
+    let mut seen = vec![false; sti_file.images.len()];
+    for (position, entry) in entries.iter().enumerate() {
+        let index = entry.index.unwrap_or(position);
+        if index >= sti_file.images.len() {
+            return Err(CommandError::InvalidFormat(format!("Manifest entry {} refers to missing frame {}", position, index)));
+        }
+        if seen[index] {
+            return Err(CommandError::InvalidFormat(format!("Manifest lists frame {} more than once", index)));
+        }
+        seen[index] = true;
+    }
+    
+    let mut frames_changed = 0;
+    for (position, entry) in entries.iter().enumerate() {
+        let index = entry.index.unwrap_or(position);
+        if let Some(header) = &mut sti_file.images[index].header {
+            if header.offset_x != entry.offset_x || header.offset_y != entry.offset_y {
+                header.offset_x = entry.offset_x;
+                header.offset_y = entry.offset_y;
+                frames_changed += 1;
+            }
+        }
+    }
+    
+    if frames_changed > 0 {
+        write_sti_file(&file_path, &mut sti_file)?;
+        notify_cache_invalidated(&app, &file_path);
+    }
+    
+    Ok(FrameManifestResult {
+        frames_updated: entries.len(),
+        frames_changed,
+    })
+}
+
+// Look up (or register) the cancel flag for a batch operation
+fn batch_cancel_flag(operation_id: &Option<String>) -> Option<Arc<AtomicBool>> {
+    let operation_id = operation_id.as_ref()?;
+    let mut operations = lock_cache(&BATCH_OPERATIONS).ok()?;
+    Some(operations
+        .entry(operation_id.clone())
+        .or_insert_with(|| Arc::new(AtomicBool::new(false)))
+        .clone())
+}
+
+fn is_batch_cancelled(flag: &Option<Arc<AtomicBool>>) -> bool {
+    flag.as_ref().map(|f| f.load(Ordering::SeqCst)).unwrap_or(false)
+}
+
+fn finish_batch_operation(operation_id: &Option<String>) {
+    if let Some(operation_id) = operation_id {
+        if let Ok(mut operations) = lock_cache(&BATCH_OPERATIONS) {
+            operations.remove(operation_id);
+        }
+    }
+}
+
+// Unregisters a batch operation when the command returns, on errors as well as on success
+struct BatchOperationGuard(Option<String>);
+
+impl Drop for BatchOperationGuard {
+    fn drop(&mut self) {
+        finish_batch_operation(&self.0);
+    }
+}
+
+#[tauri::command]
+async fn start_batch_operation() -> Result<String, CommandError> {
+    let id = NEXT_OPERATION_ID.fetch_add(1, Ordering::SeqCst);
+    let operation_id = format!("batch-{}", id);
+    
+    let mut operations = lock_cache(&BATCH_OPERATIONS)?;
+    operations.insert(operation_id.clone(), Arc::new(AtomicBool::new(false)));
+    
+    Ok(operation_id)
+}
+
+#[tauri::command]
+async fn cancel_batch(operation_id: String) -> Result<bool, CommandError> {
+    let operations = lock_cache(&BATCH_OPERATIONS)?;
+    match operations.get(&operation_id) {
+        Some(flag) => {
+            flag.store(true, Ordering::SeqCst);
+            Ok(true)
+        }
+        None => Ok(false), // Already finished or never started
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PaletteUsageGroup {
+    pub palette_hash: String,
+    pub files: Vec<String>,
+    pub index_counts: Vec<u64>, // Pixel count per palette index across the group
+    pub unused_indices: Vec<u8>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DirectoryPaletteUsage {
+    pub groups: Vec<PaletteUsageGroup>,
+    pub skipped_files: Vec<String>, // Unreadable or non 8-bit files
+    pub cancelled: bool,            // True if stopped early, results are partial
+}
+
+fn palette_hash(palette: &sti::StiPalette) -> u64 {
+    use std::hash::{Hash, Hasher};
+    
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    palette.hash(&mut hasher);
+    hasher.finish()
+}
+
+// Count how many pixels use each palette index across all frames of an 8-bit file
+fn palette_index_counts(sti_file: &StiFile) -> Vec<u64> {
+    frame_index_counts(&sti_file.images)
+}
+
+// Number of distinct colors reported by color_histogram for 16-bit files
+const RGB_HISTOGRAM_TOP_COLORS: usize = 256;
+
+fn frame_index_counts(images: &[sti::StiImage]) -> Vec<u64> {
+    let mut counts = vec![0u64; 256];
+    for image in images {
+        if let Some(pixel_data) = &image.decompressed_data {
+            for &index in pixel_data {
+                counts[index as usize] += 1;
+            }
+        }
+    }
+    counts
+}
+
+#[tauri::command]
+async fn directory_palette_usage(directory_path: String, recursive: bool, operation_id: Option<String>) -> Result<DirectoryPaletteUsage, CommandError> {
+    let cancel_flag = batch_cancel_flag(&operation_id);
+    let _batch = BatchOperationGuard(operation_id);
+    
+    let mut sti_paths = Vec::new();
+    scan_directory_for_sti(Path::new(&directory_path), &mut sti_paths, recursive)?;
+    sti_paths.sort();
+    
+    let mut groups: HashMap<u64, PaletteUsageGroup> = HashMap::new();
+    let mut skipped_files = Vec::new();
+    let mut cancelled = false;
+    
+    for sti_path in sti_paths {
+        if is_batch_cancelled(&cancel_flag) {
+            cancelled = true;
+            break;
+        }
+        
+        // Parse directly so a directory sweep doesn't flush the viewer cache
+        let parsed = fs::read(&sti_path)
+            .ok()
+            .and_then(|data| StiParser::parse(&data).ok());
+        
+        let sti_file = match parsed {
+            Some(sti_file) if sti_file.is_8bit() => sti_file,
+            _ => {
+                skipped_files.push(sti_path);
+                continue;
+            }
+        };
+        
+        let palette = match &sti_file.palette {
+            Some(palette) => palette,
+            None => {
+                skipped_files.push(sti_path);
+                continue;
+            }
+        };
+        
+        let hash = palette_hash(palette);
+        let group = groups.entry(hash).or_insert_with(|| PaletteUsageGroup {
+            palette_hash: format!("{:016x}", hash),
+            files: Vec::new(),
+            index_counts: vec![0; 256],
+            unused_indices: Vec::new(),
+        });
+        
+        for (index, count) in palette_index_counts(&sti_file).into_iter().enumerate() {
+            group.index_counts[index] += count;
+        }
+        
+        group.files.push(sti_path);
+    }
+    
+    let mut groups: Vec<PaletteUsageGroup> = groups.into_values().collect();
+    for group in &mut groups {
+        group.unused_indices = group.index_counts.iter()
+            .enumerate()
+            .filter(|&(_, &count)| count == 0)
+            .map(|(index, _)| index as u8)
+            .collect();
+    }
+    
+    // Largest groups first
+    groups.sort_by(|a, b| b.files.len().cmp(&a.files.len()).then(a.palette_hash.cmp(&b.palette_hash)));
+    
+    Ok(DirectoryPaletteUsage {
+        groups,
+        skipped_files,
+        cancelled,
+    })
+}
+
+#[tauri::command]
+async fn export_usage_histogram(file_path: String, output_path: String, log_scale: Option<bool>) -> Result<(), CommandError> {
+    const BAR_WIDTH: u32 = 2;
+    const CHART_HEIGHT: u32 = 256;
+    
+    check_write_allowed(&output_path)?;
+    let sti_file = load_sti_file(&file_path)?;
+    
+    if !sti_file.is_8bit() {
+        return Err(CommandError::Unsupported("Palette usage histograms require an 8-bit STI file".to_string()));
+    }
+    
+    let palette = sti_file.palette.as_ref()
+        .ok_or("8-bit image missing palette")?;
+    let counts = palette_index_counts(&sti_file);
+    let log_scale = log_scale.unwrap_or(false);
+    
+    let scale = |count: u64| -> f64 {
+        if log_scale { (count as f64).ln_1p() } else { count as f64 }
+    };
+    let max_value = counts.iter().map(|&c| scale(c)).fold(0.0, f64::max);
+    
+    let mut img = image::RgbImage::from_pixel(256 * BAR_WIDTH, CHART_HEIGHT, image::Rgb([32, 32, 32]));
+    
+    if max_value > 0.0 {
+        for (index, &count) in counts.iter().enumerate() {
+            if count == 0 {
+                continue;
+            }
+            
+            // Every used index gets at least one pixel so it stays visible
+            let bar_height = ((scale(count) / max_value) * CHART_HEIGHT as f64).round() as u32;
+            let bar_height = bar_height.clamp(1, CHART_HEIGHT);
+            let color = image::Rgb(palette[index]);
+            
+            for x in 0..BAR_WIDTH {
+                for y in (CHART_HEIGHT - bar_height)..CHART_HEIGHT {
+                    img.put_pixel(index as u32 * BAR_WIDTH + x, y, color);
+                }
+            }
+        }
+    }
+    
+    img.save_with_format(&output_path, image::ImageFormat::Png)
+        .map_err(|e| CommandError::Io(format!("Failed to save histogram: {}", e)))?;
+    
+    Ok(())
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EquivalenceReport {
+    pub equivalent: bool,
+    pub first_divergence: Option<String>,
+    pub frames_compared: usize,
+}
+
+// Find the first semantic difference between two parsed files, ignoring byte layout
+fn first_semantic_divergence(a: &StiFile, b: &StiFile) -> (Option<String>, usize) {
+    if a.is_8bit() != b.is_8bit() || a.is_16bit() != b.is_16bit() {
+        return (Some("Files use different color formats (8-bit vs 16-bit)".to_string()), 0);
+    }
+    
+    if a.palette != b.palette {
+        let index = match (&a.palette, &b.palette) {
+            (Some(pa), Some(pb)) => pa.iter().zip(pb.iter()).position(|(ca, cb)| ca != cb),
+            _ => None,
+        };
+        return match index {
+            Some(index) => (Some(format!(
+                "Palette entry {} differs: {:?} vs {:?}",
+                index,
+                a.palette.as_ref().map(|p| p[index]).unwrap_or_default(),
+                b.palette.as_ref().map(|p| p[index]).unwrap_or_default()
+            )), 0),
+            None => (Some("Only one file has a palette".to_string()), 0),
+        };
+    }
+    
+    if a.images.len() != b.images.len() {
+        return (Some(format!("Frame count differs: {} vs {}", a.images.len(), b.images.len())), 0);
+    }
+    
+    for (index, (image_a, image_b)) in a.images.iter().zip(b.images.iter()).enumerate() {
+        if image_a.width != image_b.width || image_a.height != image_b.height {
+            return (Some(format!(
+                "Frame {} dimensions differ: {}x{} vs {}x{}",
+                index, image_a.width, image_a.height, image_b.width, image_b.height
+            )), index);
+        }
+        
+        let offsets_a = image_a.header.as_ref().map(|h| (h.offset_x, h.offset_y)).unwrap_or((0, 0));
+        let offsets_b = image_b.header.as_ref().map(|h| (h.offset_x, h.offset_y)).unwrap_or((0, 0));
+        if offsets_a != offsets_b {
+            return (Some(format!(
+                "Frame {} offsets differ: {:?} vs {:?}",
+                index, offsets_a, offsets_b
+            )), index);
+        }
+        
+        // Uncompressed 8-bit frames keep their pixels in raw_data
+        let pixels_a = image_a.decompressed_data.as_ref().unwrap_or(&image_a.raw_data);
+        let pixels_b = image_b.decompressed_data.as_ref().unwrap_or(&image_b.raw_data);
+        if pixels_a != pixels_b {
+            let bytes_per_pixel = if a.is_16bit() { 2 } else { 1 };
+            let divergence = match pixels_a.iter().zip(pixels_b.iter()).position(|(pa, pb)| pa != pb) {
+                Some(byte_pos) => {
+                    let pixel = byte_pos / bytes_per_pixel;
+                    let width = (image_a.width as usize).max(1);
+                    format!("Frame {} pixel ({}, {}) differs", index, pixel % width, pixel / width)
+                }
+                None => format!(
+                    "Frame {} decoded data length differs: {} vs {} bytes",
+                    index, pixels_a.len(), pixels_b.len()
+                ),
+            };
+            return (Some(divergence), index);
+        }
+    }
+    
+    (None, a.images.len())
+}
+
+#[tauri::command]
+async fn assert_equivalent(path_a: String, path_b: String) -> Result<EquivalenceReport, CommandError> {
+    // Parse fresh from disk, the point is to see what another tool wrote
+    let parse = |path: &str| -> Result<StiFile, CommandError> {
+        let file_data = fs::read(path)
+            .map_err(|e| CommandError::Io(format!("Failed to read '{}': {}", path, e)))?;
+        StiParser::parse(&file_data)
+            .map_err(|e| CommandError::InvalidFormat(format!("Failed to parse '{}': {}", path, e)))
+    };
+    
+    let file_a = parse(&path_a)?;
+    let file_b = parse(&path_b)?;
+    
+    let (first_divergence, frames_compared) = first_semantic_divergence(&file_a, &file_b);
+    
+    Ok(EquivalenceReport {
+        equivalent: first_divergence.is_none(),
+        first_divergence,
+        frames_compared,
+    })
+}
+
+// Collect PNG files in a folder ordered by the number embedded in their names
+fn collect_numbered_pngs(folder: &Path) -> Result<Vec<std::path::PathBuf>, String> {
+    let entries = fs::read_dir(folder)
+        .map_err(|e| format!("Failed to read directory {}: {}", folder.display(), e))?;
+    
+    let mut frames = Vec::new();
+    for entry in entries {
+        let entry = entry.map_err(|e| format!("Failed to read directory entry: {}", e))?;
+        let path = entry.path();
+        
+        let is_png = path.extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.eq_ignore_ascii_case("png"))
+            .unwrap_or(false);
+        if !path.is_file() || !is_png {
+            continue;
+        }
+        
+        let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("").to_string();
+        let digits: String = stem.chars()
+            .rev()
+            .skip_while(|c| !c.is_ascii_digit())
+            .take_while(|c| c.is_ascii_digit())
+            .collect::<Vec<_>>()
+            .into_iter()
+            .rev()
+            .collect();
+        let number = digits.parse::<u64>().unwrap_or(u64::MAX);
+        
+        frames.push((number, stem.to_lowercase(), path));
+    }
+    
+    // Numeric order so frame_2 sorts before frame_10
+    frames.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.cmp(&b.1)));
+    Ok(frames.into_iter().map(|(_, _, path)| path).collect())
+}
+
+// Build a palette shared by several frames, index 0 is reserved for transparency
+fn build_shared_palette(frames: &[image::RgbaImage]) -> [[u8; 3]; 256] {
+    let mut color_histogram: HashMap<[u8; 3], u64> = HashMap::new();
+    for frame in frames {
+        for pixel in frame.pixels() {
+            if pixel[3] >= 128 {
+                *color_histogram.entry([pixel[0], pixel[1], pixel[2]]).or_insert(0) += 1;
+            }
+        }
+    }
+    
+    let mut sorted_colors: Vec<_> = color_histogram.into_iter().collect();
+    sorted_colors.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    
+    let mut palette = [[0u8; 3]; 256];
+    for (i, (color, _)) in sorted_colors.into_iter().take(255).enumerate() {
+        palette[i + 1] = color;
+    }
+    palette
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PngFolderBuildResult {
+    pub frame_count: usize,
+    pub frame_files: Vec<String>,
+    pub cancelled: bool,
+}
+
+#[tauri::command]
+async fn build_from_png_folder(
+    folder_path: String,
+    output_path: String,
+    is_8bit: bool,
+    palette: Option<Vec<[u8; 3]>>,
+    manifest_path: Option<String>,
+    operation_id: Option<String>
+) -> Result<PngFolderBuildResult, CommandError> {
+    let cancel_flag = batch_cancel_flag(&operation_id);
+    let _batch = BatchOperationGuard(operation_id);
+    let frame_paths = collect_numbered_pngs(Path::new(&folder_path))?;
+    if frame_paths.is_empty() {
+        return Err(CommandError::NotFound("No PNG frames found in folder".to_string()));
+    }
+    
+    if !is_8bit && frame_paths.len() > 1 {
+        return Err(CommandError::Unsupported("16-bit STI files can only hold a single image".to_string()));
+    }
+    
+    let mut frames = Vec::with_capacity(frame_paths.len());
+    for frame_path in &frame_paths {
+        // A partial build is useless, so cancelling writes nothing
+        if is_batch_cancelled(&cancel_flag) {
+            return Ok(PngFolderBuildResult {
+                frame_count: 0,
+                frame_files: Vec::new(),
+                cancelled: true,
+            });
+        }
+        
+        let img = image::open(frame_path)
+            .map_err(|e| CommandError::InvalidFormat(format!("Failed to decode {}: {}", frame_path.display(), e)))?;
+        let (width, height) = img.dimensions();
+        if width > u16::MAX as u32 || height > u16::MAX as u32 {
+            return Err(CommandError::OutOfBounds(format!("{} is too large for an STI frame", frame_path.display())));
+        }
+        frames.push(img.to_rgba8());
+    }
+    
+    // Optional per-frame offsets
+    let offsets = match &manifest_path {
+        Some(manifest_path) => {
+            let contents = fs::read_to_string(manifest_path)
+                .map_err(|e| CommandError::Io(format!("Failed to read manifest: {}", e)))?;
+            let entries = parse_frame_manifest(manifest_path, &contents)?;
+            if entries.len() != frames.len() {
+                return Err(CommandError::InvalidFormat(format!(
+                    "Manifest describes {} frames but the folder contains {}",
+                    entries.len(),
+                    frames.len()
+                )));
+            }
+            let mut offsets = vec![(0i16, 0i16); frames.len()];
+            for (position, entry) in entries.iter().enumerate() {
+                let index = entry.index.unwrap_or(position);
+                if index >= offsets.len() {
+                    return Err(CommandError::InvalidFormat(format!("Manifest entry {} refers to missing frame {}", position, index)));
+                }
+                offsets[index] = (entry.offset_x, entry.offset_y);
+            }
+            offsets
+        }
+        None => vec![(0, 0); frames.len()],
+    };
+    
+    let mut sti_file = sti::StiFile::new();
+    sti_file.header.signature = [b'S', b'T', b'C', b'I'];
+    
+    if is_8bit {
+        let palette = match palette {
+            Some(colors) => {
+                let mut table = [[0u8; 3]; 256];
+                for (i, color) in colors.iter().take(256).enumerate() {
+                    table[i] = *color;
+                }
+                table
+            }
+            None => build_shared_palette(&frames),
+        };
+        
+        sti_file.header.flags.indexed = true;
+        sti_file.header.flags.etrle_compressed = true;
+        sti_file.header.palette_colors = 256;
+        sti_file.header.num_images = frames.len() as u16;
+        sti_file.header.color_depth = 8;
+        sti_file.palette = Some(palette);
+        
+        let transparent_index = sti_file.header.etrle_transparent_index();
+        let mut nearest = sti::palette::NearestColorCache::opaque(&palette, transparent_index);
+        for (frame, &(offset_x, offset_y)) in frames.iter().zip(offsets.iter()) {
+            let (width, height) = frame.dimensions();
+            
+            let indexed_data: Vec<u8> = frame.pixels()
+                .map(|pixel| {
+                    if pixel[3] < 128 {
+                        transparent_index
+                    } else {
+                        nearest.nearest([pixel[0], pixel[1], pixel[2]])
+                    }
+                })
+                .collect();
+            
+            let sub_header = sti::StiSubImageHeader {
+                data_offset: 0,
+                data_size: 0, // Will be set during compression
+                offset_x,
+                offset_y,
+                height: height as u16,
+                width: width as u16,
+            };
+            
+            let mut image = sti::StiImage::with_header(sub_header);
+            image.decompressed_data = Some(indexed_data);
+            sti_file.images.push(image);
+        }
+        
+        if let Some(first) = sti_file.images.first() {
+            if sti_file.images.len() == 1 {
+                sti_file.header.width = first.width;
+                sti_file.header.height = first.height;
+            }
+        }
+    } else {
+        let frame = &frames[0];
+        let (width, height) = frame.dimensions();
+        
+        sti_file.header.flags.rgb = true;
+        sti_file.header.num_images = 1;
+        sti_file.header.color_depth = 16;
+        sti_file.header.width = width as u16;
+        sti_file.header.height = height as u16;
+        sti_file.header.red_mask = 0xF800;
+        sti_file.header.green_mask = 0x07E0;
+        sti_file.header.blue_mask = 0x001F;
+        sti_file.header.red_depth = 5;
+        sti_file.header.green_depth = 6;
+        sti_file.header.blue_depth = 5;
+        
+        let mut pixel_data = Vec::with_capacity((width * height * 2) as usize);
+        for pixel in frame.pixels() {
+            let value = sti_file.header.encode_rgb16([pixel[0], pixel[1], pixel[2]]);
+            pixel_data.extend_from_slice(&value.to_le_bytes());
+        }
+        
+        let mut image = sti::StiImage::new(width as u16, height as u16);
+        image.decompressed_data = Some(pixel_data);
+        sti_file.images.push(image);
+    }
+    
+    write_sti_file(&output_path, &mut sti_file)?;
+    
+    Ok(PngFolderBuildResult {
+        frame_count: frame_paths.len(),
+        frame_files: frame_paths.iter().map(|p| p.to_string_lossy().to_string()).collect(),
+        cancelled: false,
+    })
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BackendCapabilities {
+    pub codec_version: String,
+    pub app_version: String,
+    pub decompression: Vec<String>,   // Compression schemes that can be read
+    pub compression: Vec<String>,     // Compression schemes that can be written
+    pub bit_depths: Vec<u8>,
+    pub export_formats: Vec<String>,
+    pub multi_image_16bit: bool,
+    pub slf_archives: bool,
+    pub file_watching: bool,
+    pub clipboard: bool,
+}
+
+// Bump when the decoder or writer changes behavior the frontend may depend on
+const CODEC_VERSION: &str = "1";
+
+#[tauri::command]
+async fn get_capabilities() -> Result<BackendCapabilities, CommandError> {
+    Ok(BackendCapabilities {
+        codec_version: CODEC_VERSION.to_string(),
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        decompression: vec!["etrle".to_string(), "zlib".to_string()],
+        compression: vec!["etrle".to_string(), "zlib".to_string()],
+        bit_depths: vec![8, 16],
+        export_formats: ["png", "jpeg", "bmp", "tiff", "tga", "webp", "svg", "gif"].iter().map(|f| f.to_string()).collect(),
+        multi_image_16bit: true,
+        slf_archives: true,
+        file_watching: false,
+        clipboard: false,
+    })
+}
+
+#[tauri::command]
+async fn import_image_as_frame(app: tauri::AppHandle, file_path: String, image_path: String, position: Option<usize>) -> Result<usize, CommandError> {
+    let mut sti_file = (*load_sti_file(&file_path)?).clone();
+    
+    if sti_file.is_16bit() {
+        return Err(CommandError::Unsupported("Importing frames is only supported for 8-bit STI files".to_string()));
+    }
+    
+    let img = image::open(&image_path)
+        .map_err(|e| CommandError::InvalidFormat(format!("Failed to decode image '{}': {}", image_path, e)))?;
+    let (width, height) = img.dimensions();
+    if width > u16::MAX as u32 || height > u16::MAX as u32 {
+        return Err(CommandError::OutOfBounds("Image is too large for an STI frame".to_string()));
+    }
+    let rgba = img.to_rgba8();
+    // A PNG from export_image knows where the frame sat; anything else starts at the origin
+    let (offset_x, offset_y) = read_png_frame_offsets(&image_path).unwrap_or((0, 0));
+    
+    let indexed_data: Vec<u8> = match sti_file.palette {
+        Some(palette) => {
+            // Remap every opaque pixel to the nearest existing palette color
+            let transparent_index = sti_file.header.etrle_transparent_index();
+            let mut nearest = sti::palette::NearestColorCache::opaque(&palette, transparent_index);
+            rgba.pixels()
+                .map(|pixel| {
+                    if pixel[3] < 128 {
+                        transparent_index
+                    } else {
+                        nearest.nearest([pixel[0], pixel[1], pixel[2]])
+                    }
+                })
+                .collect()
+        }
+        None => {
+            // No palette to map onto yet, so derive one from the image itself
+            let transparent_index = sti_file.header.etrle_transparent_index();
+            let opaque: Vec<[u8; 3]> = rgba.pixels()
+                .filter(|pixel| pixel[3] >= 128)
+                .map(|pixel| [pixel[0], pixel[1], pixel[2]])
+                .collect();
+            let (palette, opaque_indices) = sti::palette::generate_palette_reserving(&opaque, 255, transparent_index);
+            sti_file.palette = Some(palette);
+            sti_file.header.palette_colors = 256;
+            
+            let mut opaque_indices = opaque_indices.into_iter();
+            rgba.pixels()
+                .map(|pixel| {
+                    if pixel[3] < 128 {
+                        transparent_index
+                    } else {
+                        opaque_indices.next().unwrap_or(transparent_index)
+                    }
+                })
+                .collect()
+        }
+    };
+    
+    let sub_header = sti::StiSubImageHeader {
+        data_offset: 0,
+        data_size: 0, // Will be set during compression
+        offset_x,
+        offset_y,
+        height: height as u16,
+        width: width as u16,
+    };
+    
+    let mut new_image = sti::StiImage::with_header(sub_header);
+    new_image.decompressed_data = Some(indexed_data);
+    
+    // Insert at specified position or at the end
+    let insert_pos = position.unwrap_or(sti_file.images.len()).min(sti_file.images.len());
+    
+    sti_file.images.insert(insert_pos, new_image);
+    sti_file.header.num_images = sti_file.images.len() as u16;
+    
+    save_modified_sti_file(&file_path, &sti_file).await?;
+    notify_cache_invalidated(&app, &file_path);
+    
+    Ok(insert_pos)
+}
+
+#[tauri::command]
+async fn composite_frames(file_path: String) -> Result<serde_json::Value, CommandError> {
+    let sti_file = load_sti_file(&file_path)?;
+    let canvas = sti::gif::AnimationCanvas::for_file(&sti_file)
+        .map_err(|e| format!("Failed to compute canvas: {}", e))?;
+    
+    let frames: Vec<serde_json::Value> = sti_file.images.iter().enumerate()
+        .map(|(index, image)| {
+            let (dest_x, dest_y) = canvas.frame_position(image);
+            serde_json::json!({
+                "index": index,
+                "dest_x": dest_x,
+                "dest_y": dest_y,
+                "width": image.width,
+                "height": image.height,
+            })
+        })
+        .collect();
+    
+    Ok(serde_json::json!({
+        "canvas_width": canvas.width,
+        "canvas_height": canvas.height,
+        "frames": frames,
+    }))
+}
+
+#[tauri::command]
+async fn export_animated_gif(file_path: String, output_path: String, frame_delay_ms: u16) -> Result<(), CommandError> {
+    check_write_allowed(&output_path)?;
+    let sti_file = load_sti_file(&file_path)?;
+    
+    let gif_data = sti::gif::encode_animated_gif(&sti_file, frame_delay_ms)
+        .map_err(|e| format!("Failed to build GIF: {}", e))?;
+    
+    fs::write(&output_path, gif_data)
+        .map_err(|e| CommandError::Io(format!("Failed to write GIF '{}': {}", output_path, e)))?;
+    
+    Ok(())
+}
+
+// All frames of an animation in one PNG, plus a JSON atlas next to it (same name, .json) giving each
+// frame's cell and frame_count so engines that take strips can import the sprite
+#[tauri::command]
+async fn export_animation_strip(file_path: String, output_path: String, horizontal: bool) -> Result<(), CommandError> {
+    check_write_allowed(&output_path)?;
+    let sti_file = load_sti_file(&file_path)?;
+    if sti_file.is_16bit() {
+        return Err(CommandError::Unsupported("Animation strips can only be exported from 8-bit files".to_string()));
+    }
+    if sti_file.animation_data.is_empty() {
+        return Err(CommandError::Unsupported(
+            "File has no animation data; use batch export to save its frames individually".to_string()
+        ));
+    }
+    
+    let (strip, canvas, cells) = sti::gif::compose_strip(&sti_file, horizontal)
+        .map_err(|e| format!("Failed to build strip: {}", e))?;
+    strip.save_with_format(&output_path, image::ImageFormat::Png)
+        .map_err(|e| CommandError::Io(format!("Failed to write strip '{}': {}", output_path, e)))?;
+    
+    let frames: Vec<serde_json::Value> = cells.iter().enumerate()
+        .map(|(index, &(x, y))| serde_json::json!({
+            "index": index,
+            "x": x,
+            "y": y,
+            "width": canvas.width,
+            "height": canvas.height,
+            "frame_count": sti_file.animation_data.get(index).map_or(0, |anim| anim.frame_count),
+        }))
+        .collect();
+    let atlas = serde_json::json!({
+        "image": Path::new(&output_path).file_name().and_then(|name| name.to_str()),
+        "horizontal": horizontal,
+        "origin_x": canvas.origin_x,
+        "origin_y": canvas.origin_y,
+        "frames": frames,
+    });
+    
+    let atlas_path = Path::new(&output_path).with_extension("json");
+    let atlas_json = serde_json::to_string_pretty(&atlas)
+        .map_err(|e| format!("Failed to serialize atlas: {}", e))?;
+    fs::write(&atlas_path, atlas_json)
+        .map_err(|e| CommandError::Io(format!("Failed to write atlas '{}': {}", atlas_path.display(), e)))?;
+    
+    Ok(())
+}
+
+// Swap in new pixel data and dimensions for a frame, keeping headers in sync
+fn replace_frame_pixels(sti_file: &mut StiFile, image_index: usize, width: u16, height: u16, pixel_data: Vec<u8>) {
+    let is_16bit = sti_file.is_16bit();
+    let image = &mut sti_file.images[image_index];
+    image.width = width;
+    image.height = height;
+    image.decompressed_data = Some(pixel_data);
+    
+    if let Some(header) = &mut image.header {
+        header.width = width;
+        header.height = height;
+    }
+    
+    // The main header of a 16-bit file describes its first image
+    if is_16bit && image_index == 0 {
+        sti_file.header.width = width;
+        sti_file.header.height = height;
+    }
+}
+
+#[tauri::command]
+async fn resize_image(app: tauri::AppHandle, file_path: String, image_index: usize, new_width: u16, new_height: u16, mode: String) -> Result<(), CommandError> {
+    if new_width == 0 || new_height == 0 {
+        return Err(CommandError::OutOfBounds("New dimensions must be at least 1x1".to_string()));
+    }
+    
+    let mut sti_file = (*load_sti_file(&file_path)?).clone();
+    
+    if image_index >= sti_file.images.len() {
+        return Err(CommandError::OutOfBounds("Image index out of bounds".to_string()));
+    }
+    
+    let image = &sti_file.images[image_index];
+    let pixel_data = image.decompressed_data.as_ref()
+        .ok_or("Image data not decompressed")?;
+    let bytes_per_pixel = if sti_file.is_16bit() { 2 } else { 1 };
+    let (src_width, src_height) = (image.width as u32, image.height as u32);
+    
+    if pixel_data.len() < (src_width * src_height) as usize * bytes_per_pixel {
+        return Err(CommandError::InvalidFormat("Decoded image data is smaller than the frame dimensions".to_string()));
+    }
+    
+    let resized = match mode.to_lowercase().as_str() {
+        "nearest" => {
+            // Copies source pixels verbatim, so no new palette indices can appear
+            let mut out = Vec::with_capacity(new_width as usize * new_height as usize * bytes_per_pixel);
+            for y in 0..new_height as u32 {
+                let src_y = (y * src_height / new_height as u32) as usize;
+                for x in 0..new_width as u32 {
+                    let src_x = (x * src_width / new_width as u32) as usize;
+                    let pos = (src_y * src_width as usize + src_x) * bytes_per_pixel;
+                    out.extend_from_slice(&pixel_data[pos..pos + bytes_per_pixel]);
+                }
+            }
+            out
+        }
+        "bilinear" => {
+            if !sti_file.is_16bit() {
+                return Err(CommandError::Unsupported("Bilinear resizing would invent palette indices, use nearest for 8-bit files".to_string()));
+            }
+            
+            let mut rgb = image::RgbImage::new(src_width, src_height);
+            for (pos, chunk) in pixel_data.chunks_exact(2).take((src_width * src_height) as usize).enumerate() {
+                let value = u16::from_le_bytes([chunk[0], chunk[1]]);
+                let x = pos as u32 % src_width;
+                let y = pos as u32 / src_width;
+                rgb.put_pixel(x, y, image::Rgb(sti_file.header.decode_rgb16(value)));
+            }
+            
+            let scaled = image::imageops::resize(&rgb, new_width as u32, new_height as u32, image::imageops::FilterType::Triangle);
+            let mut out = Vec::with_capacity(new_width as usize * new_height as usize * 2);
+            for pixel in scaled.pixels() {
+                let value = sti_file.header.encode_rgb16([pixel[0], pixel[1], pixel[2]]);
+                out.extend_from_slice(&value.to_le_bytes());
+            }
+            out
+        }
+        _ => return Err(CommandError::Unsupported(format!("Unsupported resize mode: {}", mode))),
+    };
+    
+    replace_frame_pixels(&mut sti_file, image_index, new_width, new_height, resized);
+    store_edited_sti_file(&file_path, sti_file)?;
+    notify_cache_invalidated(&app, &file_path);
+    
+    Ok(())
+}
+
+#[tauri::command]
+async fn crop_image(app: tauri::AppHandle, file_path: String, image_index: usize, x: u16, y: u16, width: u16, height: u16) -> Result<(), CommandError> {
+    if width == 0 || height == 0 {
+        return Err(CommandError::OutOfBounds("Crop area must be at least 1x1".to_string()));
+    }
+    
+    let mut sti_file = (*load_sti_file(&file_path)?).clone();
+    
+    if image_index >= sti_file.images.len() {
+        return Err(CommandError::OutOfBounds("Image index out of bounds".to_string()));
+    }
+    
+    crop_frame(&mut sti_file, image_index, x, y, width, height)?;
+    store_edited_sti_file(&file_path, sti_file)?;
+    notify_cache_invalidated(&app, &file_path);
+    
+    Ok(())
+}
+
+// Cut a frame down to the given area, shifting its offset to keep on-screen placement
+fn crop_frame(sti_file: &mut StiFile, image_index: usize, x: u16, y: u16, width: u16, height: u16) -> Result<(), CommandError> {
+    let image = &sti_file.images[image_index];
+    if x as u32 + width as u32 > image.width as u32 || y as u32 + height as u32 > image.height as u32 {
+        return Err(CommandError::OutOfBounds("Crop area extends outside the frame".to_string()));
+    }
+    
+    let pixel_data = image.decompressed_data.as_ref()
+        .ok_or("Image data not decompressed")?;
+    let bytes_per_pixel = if sti_file.is_16bit() { 2 } else { 1 };
+    let row_bytes = image.width as usize * bytes_per_pixel;
+    
+    if pixel_data.len() < row_bytes * image.height as usize {
+        return Err(CommandError::InvalidFormat("Decoded image data is smaller than the frame dimensions".to_string()));
+    }
+    
+    let mut cropped = Vec::with_capacity(width as usize * height as usize * bytes_per_pixel);
+    for row in y as usize..(y + height) as usize {
+        let start = row * row_bytes + x as usize * bytes_per_pixel;
+        cropped.extend_from_slice(&pixel_data[start..start + width as usize * bytes_per_pixel]);
+    }
+    
+    // Shift the offset so the remaining pixels stay where they were on screen
+    if let Some(header) = &mut sti_file.images[image_index].header {
+        header.offset_x = shift_offset(header.offset_x, x)?;
+        header.offset_y = shift_offset(header.offset_y, y)?;
+    }
+    
+    replace_frame_pixels(sti_file, image_index, width, height, cropped);
+    
+    Ok(())
+}
+
+// A box inside a frame as (x, y, width, height)
+type FrameBounds = (u16, u16, u16, u16);
+
+// Find the tight box around a frame's opaque pixels
+fn opaque_bounds(sti_file: &StiFile, image: &sti::StiImage) -> Result<Option<FrameBounds>, String> {
+    opaque_extent(sti_file, image).map(|(bounds, _)| bounds)
+}
+
+// opaque_bounds plus the number of opaque pixels inside it
+fn opaque_extent(sti_file: &StiFile, image: &sti::StiImage) -> Result<(Option<FrameBounds>, usize), String> {
+    let pixel_data = image.decompressed_data.as_ref()
+        .ok_or("Image data not decompressed")?;
+    let bytes_per_pixel = if sti_file.is_16bit() { 2 } else { 1 };
+    let (width, height) = (image.width as usize, image.height as usize);
+    
+    if pixel_data.len() < width * height * bytes_per_pixel {
+        return Err("Decoded image data is smaller than the frame dimensions".to_string());
+    }
+    
+    let transparent_value = sti_file.header.transparent_color;
+    let has_transparency = sti_file.is_8bit() || sti_file.header.flags.transparent;
+    
+    let mut bounds: Option<(usize, usize, usize, usize)> = None;
+    let mut opaque_pixels = 0;
+    for (pos, chunk) in pixel_data.chunks_exact(bytes_per_pixel).take(width * height).enumerate() {
+        let value = if bytes_per_pixel == 2 { u16::from_le_bytes([chunk[0], chunk[1]]) as u32 } else { chunk[0] as u32 };
+        if has_transparency && value == transparent_value {
+            continue;
+        }
+        
+        opaque_pixels += 1;
+        let (x, y) = (pos % width, pos / width);
+        bounds = Some(match bounds {
+            Some((min_x, min_y, max_x, max_y)) => (min_x.min(x), min_y.min(y), max_x.max(x), max_y.max(y)),
+            None => (x, y, x, y),
+        });
+    }
+    
+    let bounds = bounds.map(|(min_x, min_y, max_x, max_y)| {
+        (min_x as u16, min_y as u16, (max_x - min_x + 1) as u16, (max_y - min_y + 1) as u16)
+    });
+    Ok((bounds, opaque_pixels))
+}
+
+// Read-only counterpart of autocrop_image for every frame: blank frames report "empty"
+#[tauri::command]
+async fn frame_content_bounds(file_path: String) -> Result<serde_json::Value, CommandError> {
+    let sti_file = load_sti_file(&file_path)?;
+    
+    let frames = sti_file.images.iter().enumerate()
+        .map(|(index, image)| {
+            let (bounds, opaque_pixels) = opaque_extent(&sti_file, image)
+                .map_err(|e| format!("Image {}: {}", index, e))?;
+            Ok(match bounds {
+                Some((x, y, width, height)) => serde_json::json!({
+                    "index": index,
+                    "empty": false,
+                    "min_x": x,
+                    "min_y": y,
+                    "max_x": x + width - 1,
+                    "max_y": y + height - 1,
+                    "opaque_pixels": opaque_pixels,
+                }),
+                None => serde_json::json!({
+                    "index": index,
+                    "empty": true,
+                    "opaque_pixels": 0,
+                }),
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    
+    Ok(serde_json::json!({ "frames": frames }))
+}
+
+// What a sprite costs once loaded: every frame decompressed (one index per pixel for
+// 8-bit, two bytes per pixel for 16-bit) plus the palette, against the stored frame bytes
+fn memory_estimate(sti_file: &StiFile) -> serde_json::Value {
+    let bytes_per_pixel = if sti_file.is_16bit() { 2 } else { 1 };
+    let ratio = |decompressed: usize, stored: usize| {
+        if stored == 0 { None } else { Some(decompressed as f64 / stored as f64) }
+    };
+
+    let mut decompressed_size = 0;
+    let mut compressed_size = 0;
+    let frames: Vec<serde_json::Value> = sti_file.images.iter().enumerate()
+        .map(|(index, image)| {
+            let decompressed = image.width as usize * image.height as usize * bytes_per_pixel;
+            let stored = image.raw_data.len();
+            decompressed_size += decompressed;
+            compressed_size += stored;
+            serde_json::json!({
+                "index": index,
+                "width": image.width,
+                "height": image.height,
+                "decompressed_size": decompressed,
+                "compressed_size": stored,
+                "compression_ratio": ratio(decompressed, stored),
+            })
+        })
+        .collect();
+    let palette_size = sti_file.palette.as_ref().map_or(0, |palette| palette.len() * 3);
+
+    serde_json::json!({
+        "decompressed_size": decompressed_size,
+        "palette_size": palette_size,
+        "total_memory": decompressed_size + palette_size,
+        "compressed_size": compressed_size,
+        "compression_ratio": ratio(decompressed_size, compressed_size),
+        "frames": frames,
+    })
+}
+
+#[tauri::command]
+async fn estimate_memory(file_path: String) -> Result<serde_json::Value, CommandError> {
+    let sti_file = load_sti_file(&file_path)?;
+    let mut estimate = memory_estimate(&sti_file);
+    // Whole file including headers; the cached copy may hold unsaved edits
+    estimate["file_size"] = serde_json::json!(fs::metadata(&file_path).ok().map(|metadata| metadata.len()));
+    Ok(estimate)
+}
+
+#[tauri::command]
+async fn autocrop_image(app: tauri::AppHandle, file_path: String, image_index: usize) -> Result<serde_json::Value, CommandError> {
+    let mut sti_file = (*load_sti_file(&file_path)?).clone();
+    
+    if image_index >= sti_file.images.len() {
+        return Err(CommandError::OutOfBounds("Image index out of bounds".to_string()));
+    }
+    
+    let image = &sti_file.images[image_index];
+    let (old_width, old_height) = (image.width, image.height);
+    
+    let (x, y, width, height) = match opaque_bounds(&sti_file, image)? {
+        Some(bounds) => bounds,
+        // Nothing to keep, leave fully transparent frames alone
+        None => return Ok(serde_json::json!({
+            "cropped": false,
+            "empty": true,
+            "width": old_width,
+            "height": old_height,
+            "offset_dx": 0,
+            "offset_dy": 0,
+        })),
+    };
+    
+    let cropped = (x, y, width, height) != (0, 0, old_width, old_height);
+    if cropped {
+        crop_frame(&mut sti_file, image_index, x, y, width, height)?;
+        store_edited_sti_file(&file_path, sti_file)?;
+        notify_cache_invalidated(&app, &file_path);
+    }
+    
+    Ok(serde_json::json!({
+        "cropped": cropped,
+        "empty": false,
+        "width": width,
+        "height": height,
+        "offset_dx": x,
+        "offset_dy": y,
+    }))
+}
+
+#[tauri::command]
+async fn transform_image(app: tauri::AppHandle, file_path: String, image_index: usize, op: String) -> Result<(), CommandError> {
+    let transform = sti::transform::FrameTransform::from_name(&op)
+        .ok_or_else(|| format!("Unsupported transform: {}", op))?;
+    
+    let mut sti_file = (*load_sti_file(&file_path)?).clone();
+    
+    if image_index >= sti_file.images.len() {
+        return Err(CommandError::OutOfBounds("Image index out of bounds".to_string()));
+    }
+    
+    let image = &sti_file.images[image_index];
+    let pixel_data = image.decompressed_data.as_ref()
+        .ok_or("Image data not decompressed")?;
+    let bytes_per_pixel = if sti_file.is_16bit() { 2 } else { 1 };
+    
+    let (transformed, width, height) = sti::transform::transform_pixels(pixel_data, image.width, image.height, bytes_per_pixel, transform)
+        .map_err(|e| format!("Failed to transform image {}: {}", image_index, e))?;
+    
+    replace_frame_pixels(&mut sti_file, image_index, width, height, transformed);
+    store_edited_sti_file(&file_path, sti_file)?;
+    notify_cache_invalidated(&app, &file_path);
+    
+    Ok(())
+}
+
+// Swap one palette index for another in a frame, or in every frame when no index is given
+#[tauri::command]
+async fn replace_index(app: tauri::AppHandle, file_path: String, image_index: Option<usize>, from_index: u8, to_index: u8) -> Result<usize, CommandError> {
+    let mut sti_file = (*load_sti_file(&file_path)?).clone();
+    
+    if !sti_file.is_8bit() {
+        return Err(CommandError::Unsupported("Replacing palette indices is only supported for 8-bit files".to_string()));
+    }
+    
+    let targets = match image_index {
+        Some(index) if index >= sti_file.images.len() => return Err(CommandError::OutOfBounds("Image index out of bounds".to_string())),
+        Some(index) => index..index + 1,
+        None => 0..sti_file.images.len(),
+    };
+    
+    let mut changed = 0;
+    for index in targets {
+        let image = &mut sti_file.images[index];
+        let pixel_count = image.width as usize * image.height as usize;
+        let pixel_data = image.decompressed_data.as_mut()
+            .ok_or_else(|| format!("Image {} is not decompressed", index))?;
+        
+        for pixel in pixel_data.iter_mut().take(pixel_count) {
+            if *pixel == from_index {
+                *pixel = to_index;
+                changed += 1;
+            }
+        }
+    }
+    
+    if changed > 0 {
+        store_edited_sti_file(&file_path, sti_file)?;
+        notify_cache_invalidated(&app, &file_path);
+    }
+    
+    Ok(changed)
+}
+
+// Copy the source frame onto the destination frame with its top-left corner at (x, y), clipped to
+// the destination. With skip_transparent, source pixels of the transparent color leave the destination as is
+fn paste_pixels(sti_file: &mut StiFile, dest_index: usize, src_index: usize, x: i16, y: i16, skip_transparent: bool) -> Result<(), String> {
+    let bytes_per_pixel = if sti_file.is_16bit() { 2 } else { 1 };
+    let transparent_value = sti_file.header.transparent_color;
+    let has_transparency = sti_file.is_8bit() || sti_file.header.flags.transparent;
+    
+    let src = &sti_file.images[src_index];
+    let (src_width, src_height) = (src.width as i32, src.height as i32);
+    let src_data = src.decompressed_data.clone()
+        .ok_or_else(|| format!("Image {} is not decompressed", src_index))?;
+    
+    let dest = &mut sti_file.images[dest_index];
+    let (dest_width, dest_height) = (dest.width as i32, dest.height as i32);
+    let dest_data = dest.decompressed_data.as_mut()
+        .ok_or_else(|| format!("Image {} is not decompressed", dest_index))?;
+    if src_data.len() < (src_width * src_height) as usize * bytes_per_pixel
+        || dest_data.len() < (dest_width * dest_height) as usize * bytes_per_pixel {
+        return Err("Decoded image data is smaller than the frame dimensions".to_string());
+    }
+    
+    // Rows and columns of the source that land inside the destination
+    let (x, y) = (x as i32, y as i32);
+    let (first_col, last_col) = ((-x).max(0), src_width.min(dest_width - x));
+    let (first_row, last_row) = ((-y).max(0), src_height.min(dest_height - y));
+    
+    for row in first_row..last_row {
+        for col in first_col..last_col {
+            let src_pos = (row * src_width + col) as usize * bytes_per_pixel;
+            let pixel = &src_data[src_pos..src_pos + bytes_per_pixel];
+            if skip_transparent && has_transparency {
+                let value = if bytes_per_pixel == 2 { u16::from_le_bytes([pixel[0], pixel[1]]) as u32 } else { pixel[0] as u32 };
+                if value == transparent_value {
+                    continue;
+                }
+            }
+            
+            let dest_pos = ((row + y) * dest_width + col + x) as usize * bytes_per_pixel;
+            dest_data[dest_pos..dest_pos + bytes_per_pixel].copy_from_slice(pixel);
+        }
+    }
+    
+    Ok(())
+}
+
+#[tauri::command]
+async fn paste_frame(app: tauri::AppHandle, file_path: String, dest_index: usize, src_index: usize, x: i16, y: i16, skip_transparent: bool) -> Result<(), CommandError> {
+    let mut sti_file = (*load_sti_file(&file_path)?).clone();
+    
+    if dest_index >= sti_file.images.len() || src_index >= sti_file.images.len() {
+        return Err(CommandError::OutOfBounds("Image index out of bounds".to_string()));
+    }
+    if sti_file.format() == sti::StiFormat::Unknown {
+        return Err(ambiguous_format_error(&sti_file));
+    }
+    
+    paste_pixels(&mut sti_file, dest_index, src_index, x, y, skip_transparent)?;
+    store_edited_sti_file(&file_path, sti_file)?;
+    notify_cache_invalidated(&app, &file_path);
+    
+    Ok(())
+}
+
+// Indices of byte-identical frames (same size and pixels), in groups of two or more
+fn duplicate_frame_groups(sti_file: &StiFile) -> Vec<Vec<usize>> {
+    let mut groups: HashMap<blake3::Hash, Vec<usize>> = HashMap::new();
+    for (index, image) in sti_file.images.iter().enumerate() {
+        let pixel_data = match &image.decompressed_data {
+            Some(pixel_data) => pixel_data,
+            None => continue,
+        };
+        // Identical pixels drawn at another offset are a different frame of the animation
+        let (offset_x, offset_y) = image.header.as_ref()
+            .map(|header| (header.offset_x, header.offset_y))
+            .unwrap_or((0, 0));
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(&image.width.to_le_bytes());
+        hasher.update(&image.height.to_le_bytes());
+        hasher.update(&offset_x.to_le_bytes());
+        hasher.update(&offset_y.to_le_bytes());
+        hasher.update(pixel_data);
+        groups.entry(hasher.finalize()).or_default().push(index);
+    }
+    
+    let mut groups: Vec<Vec<usize>> = groups.into_values().filter(|group| group.len() > 1).collect();
+    groups.sort();
+    groups
+}
+
+#[tauri::command]
+async fn find_duplicate_frames(file_path: String) -> Result<serde_json::Value, CommandError> {
+    let sti_file = load_sti_file(&file_path)?;
+    let groups = duplicate_frame_groups(&sti_file);
+    let duplicates: usize = groups.iter().map(|group| group.len() - 1).sum();
+    
+    Ok(serde_json::json!({
+        "groups": groups,
+        "duplicate_frames": duplicates,
+    }))
+}
+
+// Remove every frame identical to an earlier one. Duplicates inside an animation sequence are
+// refused unless update_animation shortens the sequences they belonged to
+#[tauri::command]
+async fn dedupe_frames(app: tauri::AppHandle, file_path: String, update_animation: bool) -> Result<usize, CommandError> {
+    let mut sti_file = (*load_sti_file(&file_path)?).clone();
+    let duplicates: Vec<usize> = duplicate_frame_groups(&sti_file).into_iter()
+        .flat_map(|group| group.into_iter().skip(1))
+        .collect();
+    if duplicates.is_empty() {
+        return Ok(0);
+    }
+    
+    remove_images(&mut sti_file, &duplicates, update_animation)?;
+    save_modified_sti_file(&file_path, &sti_file).await?;
+    notify_cache_invalidated(&app, &file_path);
+    
+    Ok(duplicates.len())
+}
+
+#[tauri::command]
+async fn diff_sti_files(left_path: String, right_path: String) -> Result<serde_json::Value, CommandError> {
+    use serde_json::json;
+    
+    let left = load_sti_file(&left_path)?;
+    let right = load_sti_file(&right_path)?;
+    
+    let left_flags = left.header.flags_value();
+    let right_flags = right.header.flags_value();
+    let bytes_per_pixel = if left.is_16bit() { 2 } else { 1 };
+    
+    // Per-image comparison across the union of both image lists
+    let image_count = left.images.len().max(right.images.len());
+    let mut images = Vec::with_capacity(image_count);
+    for index in 0..image_count {
+        let left_image = left.images.get(index);
+        let right_image = right.images.get(index);
+        let dims = |image: Option<&sti::StiImage>| image.map(|img| json!({ "width": img.width, "height": img.height }));
+        
+        let mut entry = json!({
+            "index": index,
+            "left": dims(left_image),
+            "right": dims(right_image),
+        });
+        
+        if let (Some(a), Some(b)) = (left_image, right_image) {
+            let same_dimensions = a.width == b.width && a.height == b.height;
+            entry["dimensions_differ"] = json!(!same_dimensions);
+            
+            let pixels_a = a.decompressed_data.as_ref().unwrap_or(&a.raw_data);
+            let pixels_b = b.decompressed_data.as_ref().unwrap_or(&b.raw_data);
+            entry["differing_pixels"] = if same_dimensions && left.is_16bit() == right.is_16bit() {
+                json!(pixels_a.chunks(bytes_per_pixel)
+                    .zip(pixels_b.chunks(bytes_per_pixel))
+                    .filter(|(pa, pb)| pa != pb)
+                    .count())
+            } else {
+                serde_json::Value::Null
+            };
+        }
+        
+        images.push(entry);
+    }
+    
+    // Palette slots whose RGB changed
+    let palette_differences: Vec<serde_json::Value> = match (&left.palette, &right.palette) {
+        (Some(pa), Some(pb)) => pa.iter()
+            .zip(pb.iter())
+            .enumerate()
+            .filter(|(_, (ca, cb))| ca != cb)
+            .map(|(index, (ca, cb))| json!({ "index": index, "left": ca, "right": cb }))
+            .collect(),
+        _ => Vec::new(),
+    };
+    
+    Ok(json!({
+        "left_path": left_path,
+        "right_path": right_path,
+        "bit_depth_differs": left.is_8bit() != right.is_8bit() || left.is_16bit() != right.is_16bit(),
+        "left_flags": left_flags,
+        "right_flags": right_flags,
+        "flags_differ": left_flags != right_flags,
+        "left_image_count": left.images.len(),
+        "right_image_count": right.images.len(),
+        "image_count_delta": right.images.len() as i64 - left.images.len() as i64,
+        "images": images,
+        "palette_presence_differs": left.palette.is_some() != right.palette.is_some(),
+        "palette_differences": palette_differences,
+    }))
+}
+
+// Visual companion to diff_sti_files for one frame; the diff PNG covers the overlapping region
+#[tauri::command]
+async fn diff_frame_image(left_path: String, right_path: String, index: usize, output_path: String) -> Result<serde_json::Value, CommandError> {
+    use serde_json::json;
+    
+    check_write_allowed(&output_path)?;
+    let left = load_sti_file(&left_path)?;
+    let right = load_sti_file(&right_path)?;
+    for (sti_file, path) in [(&left, &left_path), (&right, &right_path)] {
+        if index >= sti_file.images.len() {
+            return Err(CommandError::OutOfBounds(format!(
+                "Image index {} out of bounds for '{}' ({} images)", index, path, sti_file.images.len()
+            )));
+        }
+    }
+    
+    let left_rgba = frame_to_rgba(&left, index)?;
+    let right_rgba = frame_to_rgba(&right, index)?;
+    let (diff, differing_pixels) = sti::ops::diff_rgba(&left_rgba, &right_rgba);
+    diff.save_with_format(&output_path, image::ImageFormat::Png)
+        .map_err(|e| CommandError::Io(format!("Failed to write diff '{}': {}", output_path, e)))?;
+    
+    let compared_pixels = diff.width() as usize * diff.height() as usize;
+    let differing_percent = if compared_pixels == 0 {
+        0.0
+    } else {
+        differing_pixels as f64 * 100.0 / compared_pixels as f64
+    };
+    
+    Ok(json!({
+        "index": index,
+        "left": { "width": left_rgba.width(), "height": left_rgba.height() },
+        "right": { "width": right_rgba.width(), "height": right_rgba.height() },
+        "size_mismatch": left_rgba.dimensions() != right_rgba.dimensions(),
+        "compared": { "width": diff.width(), "height": diff.height() },
+        "differing_pixels": differing_pixels,
+        "differing_percent": differing_percent,
+        "output_path": output_path,
+    }))
+}
+
+// Palette slots whose colors differ between two palettes
+fn differing_palette_indices(a: &sti::StiPalette, b: &sti::StiPalette) -> Vec<usize> {
+    a.iter().zip(b.iter())
+        .enumerate()
+        .filter(|(_, (ca, cb))| ca != cb)
+        .map(|(index, _)| index)
+        .collect()
+}
+
+// Pre-flight for merge_sti_files: every file compared against the first one's palette
+#[tauri::command]
+async fn check_palette_compatibility(file_paths: Vec<String>) -> Result<serde_json::Value, CommandError> {
+    use serde_json::json;
+    
+    let mut palettes = Vec::with_capacity(file_paths.len());
+    for path in &file_paths {
+        let sti_file = load_sti_file(path)?;
+        match sti_file.palette {
+            Some(palette) if sti_file.is_8bit() => palettes.push(palette),
+            _ => return Err(CommandError::Unsupported(format!("{} is not an 8-bit file with a palette", path))),
+        }
+    }
+    
+    let reference = match palettes.first() {
+        Some(palette) => *palette,
+        None => return Err(CommandError::InvalidFormat("No files to compare".to_string())),
+    };
+    let files: Vec<serde_json::Value> = file_paths.iter().zip(palettes.iter()).skip(1)
+        .map(|(path, palette)| {
+            let differing = differing_palette_indices(&reference, palette);
+            json!({
+                "path": path,
+                "identical": differing.is_empty(),
+                "differing_indices": differing,
+            })
+        })
+        .collect();
+    
+    Ok(json!({
+        "reference": file_paths[0],
+        "all_identical": files.iter().all(|file| file["identical"] == json!(true)),
+        "files": files,
+    }))
+}
+
+#[tauri::command]
+async fn merge_sti_files(source_paths: Vec<String>, output_path: String, remap: bool) -> Result<(), CommandError> {
+    if source_paths.is_empty() {
+        return Err(CommandError::InvalidFormat("No source files to merge".to_string()));
+    }
+    
+    let sources = source_paths.iter()
+        .map(|path| load_sti_file(path).map_err(|e| format!("{}: {}", path, e)))
+        .collect::<Result<Vec<_>, String>>()?;
+    
+    let first = &sources[0];
+    for (path, source) in source_paths.iter().zip(sources.iter()).skip(1) {
+        if source.is_8bit() != first.is_8bit() || source.is_16bit() != first.is_16bit() {
+            return Err(CommandError::Unsupported(format!("{} has a different bit depth than {}", path, source_paths[0])));
+        }
+    }
+    
+    let total_images: usize = sources.iter().map(|s| s.images.len()).sum();
+    if first.is_16bit() && total_images > 1 {
+        return Err(CommandError::Unsupported("16-bit STI files can only hold a single image".to_string()));
+    }
+    
+    let mut merged = StiFile::new();
+    merged.header = first.header.clone();
+    merged.palette = first.palette;
+    
+    let keep_animation = sources.iter()
+        .all(|s| !s.animation_data.is_empty() && s.animation_data.len() == s.images.len());
+    
+    for (path, source) in source_paths.iter().zip(sources.iter()) {
+        // Map every index onto the first file's palette when palettes differ
+        let index_map = match (&merged.palette, &source.palette) {
+            (Some(target), Some(palette)) if target != palette => {
+                if !remap {
+                    return Err(CommandError::Unsupported(format!(
+                        "{} uses a different palette than {} ({} indices differ)",
+                        path, source_paths[0], differing_palette_indices(target, palette).len()
+                    )));
+                }
+                Some(sti::palette::remap_table(palette, target, merged.header.etrle_transparent_index()))
+            }
+            _ => None,
+        };
+        
+        for image in &source.images {
+            let mut image = image.clone();
+            if let (Some(map), Some(pixel_data)) = (&index_map, &mut image.decompressed_data) {
+                for pixel in pixel_data.iter_mut() {
+                    *pixel = map[*pixel as usize];
+                }
+            }
+            merged.images.push(image);
+        }
+        
+        if keep_animation {
+            merged.animation_data.extend(source.animation_data.iter().cloned());
+        }
+    }
+    
+    merged.header.num_images = merged.images.len() as u16;
+    if merged.is_8bit() {
+        merged.header.flags.etrle_compressed = true;
+        merged.header.flags.zlib_compressed = false;
+        if merged.images.len() > 1 {
+            merged.header.width = 0;
+            merged.header.height = 0;
+        }
+    }
+    
+    if merged.images.iter().any(|image| image.decompressed_data.is_none()) {
+        return Err(CommandError::InvalidFormat("Cannot merge images that failed to decompress".to_string()));
+    }
+    
+    if Path::new(&output_path).exists() {
+        create_sti_backup(output_path.clone()).await?;
+    }
+    
+    Ok(write_sti_file(&output_path, &mut merged)?)
+}
+
+// Offsets are reset to 0 unless `keep_offsets` is set, so the sprite stands on its own
+#[tauri::command]
+async fn extract_frame_to_file(source_path: String, image_index: usize, output_path: String, keep_offsets: Option<bool>) -> Result<(), CommandError> {
+    let source = load_sti_file(&source_path)?;
+    
+    let mut image = source.images.get(image_index)
+        .ok_or("Image index out of bounds")?
+        .clone();
+    if image.decompressed_data.is_none() {
+        return Err(CommandError::InvalidFormat(format!("Image {} failed to decompress", image_index)));
+    }
+    
+    if !keep_offsets.unwrap_or(false) {
+        if let Some(header) = &mut image.header {
+            header.offset_x = 0;
+            header.offset_y = 0;
+        }
+    }
+    
+    let mut extracted = StiFile::new();
+    extracted.header = source.header.clone();
+    extracted.header.num_images = 1;
+    extracted.header.width = image.width;
+    extracted.header.height = image.height;
+    extracted.palette = source.palette;
+    
+    // A lone 16-bit image is stored without a sub-header
+    if extracted.is_16bit() {
+        image.header = None;
+    }
+    extracted.images.push(image);
+    
+    if Path::new(&output_path).exists() {
+        create_sti_backup(output_path.clone()).await?;
+    }
+    
+    Ok(write_sti_file(&output_path, &mut extracted)?)
+}
+
+#[tauri::command]
+async fn convert_bit_depth(file_path: String, target_depth: u8, output_path: String) -> Result<(), CommandError> {
+    let sti_file = load_sti_file(&file_path)?;
+    
+    let mut converted = match target_depth {
+        8 => sti::convert::convert_to_8bit(&sti_file),
+        16 => sti::convert::convert_to_16bit(&sti_file),
+        _ => return Err(CommandError::Unsupported(format!("Unsupported target bit depth: {}", target_depth))),
+    }
+    .map_err(|e| format!("Failed to convert '{}': {}", file_path, e))?;
+    
+    Ok(write_sti_file(&output_path, &mut converted)?)
+}
+
+const BUNDLE_MANIFEST: &str = "manifest.json";
+const BUNDLE_VERSION: u32 = 1;
+
+/// One frame of an exported bundle
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BundleFrame {
+    pub file: String, // PNG next to the manifest
+    pub width: u16,
+    pub height: u16,
+    pub offset_x: i16,
+    pub offset_y: i16,
+}
+
+/// Text side of a JSON+PNG bundle: everything about the file except the pixels
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BundleManifest {
+    pub version: u32,
+    pub header: sti::StiHeader,
+    pub palette: Option<Vec<[u8; 3]>>,
+    pub frames: Vec<BundleFrame>,
+    #[serde(default)]
+    pub animation_data: Vec<sti::StiAnimationData>,
+    #[serde(default)]
+    pub app_data: Vec<u8>,
+}
+
+#[tauri::command]
+async fn export_bundle(file_path: String, output_dir: String) -> Result<usize, CommandError> {
+    check_write_allowed(&output_dir)?;
+    let sti_file = load_sti_file(&file_path)?;
+    if sti_file.format() == sti::StiFormat::Unknown {
+        return Err(ambiguous_format_error(&sti_file));
+    }
+    
+    let output = Path::new(&output_dir);
+    fs::create_dir_all(output)
+        .map_err(|e| CommandError::Io(format!("Failed to create directory {}: {}", output_dir, e)))?;
+    
+    let mut frames = Vec::with_capacity(sti_file.images.len());
+    for (index, image) in sti_file.images.iter().enumerate() {
+        let file = format!("frame_{:04}.png", index);
+        frame_to_rgba(&sti_file, index)?
+            .save_with_format(output.join(&file), image::ImageFormat::Png)
+            .map_err(|e| CommandError::Io(format!("Failed to write {}: {}", file, e)))?;
+        
+        let (offset_x, offset_y) = image.header.as_ref()
+            .map(|h| (h.offset_x, h.offset_y))
+            .unwrap_or((0, 0));
+        frames.push(BundleFrame { file, width: image.width, height: image.height, offset_x, offset_y });
+    }
+    
+    // Sizes are recomputed on import, leaving them out keeps the manifest stable across edits
+    let mut header = sti_file.header.clone();
+    header.original_size = 0;
+    header.compressed_size = 0;
+    
+    let manifest = BundleManifest {
+        version: BUNDLE_VERSION,
+        header,
+        palette: sti_file.palette.map(|p| p.to_vec()),
+        frames,
+        animation_data: sti_file.animation_data.clone(),
+        app_data: sti_file.app_data.clone(),
+    };
+    let json = serde_json::to_string_pretty(&manifest)
+        .map_err(|e| format!("Failed to serialize manifest: {}", e))?;
+    fs::write(output.join(BUNDLE_MANIFEST), json)
+        .map_err(|e| CommandError::Io(format!("Failed to write manifest: {}", e)))?;
+    
+    Ok(manifest.frames.len())
+}
+
+// Turn a bundle PNG back into stored pixels: palette indices for 8-bit files (transparent
+// pixels become the transparent index, other colors their first exact palette match or the
+// nearest opaque entry), header-mask encoded values for 16-bit files
+fn bundle_frame_pixels(header: &sti::StiHeader, palette: Option<&sti::StiPalette>, rgba: &image::RgbaImage) -> Vec<u8> {
+    match palette {
+        Some(palette) => {
+            let transparent = header.etrle_transparent_index();
+            let mut exact: HashMap<[u8; 3], u8> = HashMap::new();
+            for (index, color) in palette.iter().enumerate().rev() {
+                if index as u8 != transparent {
+                    exact.insert(*color, index as u8);
+                }
+            }
+            let mut nearest = sti::palette::NearestColorCache::opaque(palette, transparent);
+            rgba.pixels()
+                .map(|pixel| {
+                    let color = [pixel[0], pixel[1], pixel[2]];
+                    if pixel[3] < 128 {
+                        transparent
+                    } else {
+                        exact.get(&color).copied().unwrap_or_else(|| nearest.nearest(color))
+                    }
+                })
+                .collect()
+        }
+        None => rgba.pixels()
+            .flat_map(|pixel| {
+                let value = header.encode_rgb16([pixel[0], pixel[1], pixel[2]]) | header.encode_alpha16(pixel[3]);
+                value.to_le_bytes()
+            })
+            .collect(),
+    }
+}
+
+#[tauri::command]
+async fn import_bundle(bundle_dir: String, output_path: String) -> Result<usize, CommandError> {
+    let bundle = Path::new(&bundle_dir);
+    let json = fs::read_to_string(bundle.join(BUNDLE_MANIFEST))
+        .map_err(CommandError::from)?;
+    let manifest: BundleManifest = serde_json::from_str(&json)
+        .map_err(|e| CommandError::InvalidFormat(format!("Invalid bundle manifest: {}", e)))?;
+    if manifest.version != BUNDLE_VERSION {
+        return Err(CommandError::Unsupported(format!("Unsupported bundle version {}", manifest.version)));
+    }
+    
+    let mut sti_file = StiFile::new();
+    sti_file.header = manifest.header;
+    sti_file.header.num_images = manifest.frames.len() as u16;
+    if sti_file.format() == sti::StiFormat::Unknown {
+        return Err(ambiguous_format_error(&sti_file));
+    }
+    if sti_file.is_8bit() {
+        let colors = manifest.palette
+            .ok_or_else(|| CommandError::InvalidFormat("8-bit bundle has no palette".to_string()))?;
+        if colors.len() != 256 {
+            return Err(CommandError::InvalidFormat(format!("Bundle palette has {} colors, expected 256", colors.len())));
+        }
+        let mut palette = [[0u8; 3]; 256];
+        palette.copy_from_slice(&colors);
+        sti_file.palette = Some(palette);
+    }
+    
+    let with_sub_headers = sti_file.is_8bit() || manifest.frames.len() > 1;
+    for frame in &manifest.frames {
+        let rgba = image::open(bundle.join(&frame.file))
+            .map_err(|e| CommandError::InvalidFormat(format!("Failed to read {}: {}", frame.file, e)))?
+            .to_rgba8();
+        if rgba.dimensions() != (frame.width as u32, frame.height as u32) {
+            return Err(CommandError::InvalidFormat(format!(
+                "{} is {}x{} but the manifest says {}x{}",
+                frame.file, rgba.width(), rgba.height(), frame.width, frame.height
+            )));
+        }
+        
+        let mut image = if with_sub_headers {
+            sti::StiImage::with_header(sti::StiSubImageHeader {
+                data_offset: 0,
+                data_size: 0,
+                offset_x: frame.offset_x,
+                offset_y: frame.offset_y,
+                height: frame.height,
+                width: frame.width,
+            })
+        } else {
+            sti::StiImage::new(frame.width, frame.height)
+        };
+        image.decompressed_data = Some(bundle_frame_pixels(&sti_file.header, sti_file.palette.as_ref(), &rgba));
+        sti_file.images.push(image);
+    }
+    
+    sti_file.animation_data = manifest.animation_data;
+    sti_file.app_data = manifest.app_data;
+    
+    if Path::new(&output_path).exists() {
+        create_sti_backup(output_path.clone()).await?;
+    }
+    write_sti_file(&output_path, &mut sti_file)?;
+    
+    Ok(sti_file.images.len())
+}
+
+#[cfg_attr(mobile, tauri::mobile_entry_point)]
+pub fn run() {
+    logging::init();
+    tauri::Builder::default()
+        .plugin(tauri_plugin_opener::init())
+        .plugin(tauri_plugin_dialog::init())
+        .plugin(tauri_plugin_fs::init())
+        .invoke_handler(tauri::generate_handler![
+            open_sti_file,
+            open_sti_bytes,
+            open_sti_file_fast,
+            open_sti_file_salvage,
+            browse_slf,
+            open_sti_from_slf,
+            get_sti_image,
+            get_sti_images,
+            get_region,
+            get_sti_metadata,
+            save_sti_file,
+            save_sti_file_as,
+            create_new_sti,
+            export_image,
+            select_directory,
+            browse_directory,
+            build_directory_tree,
+            scan_for_sti_files,
+            debug_sti_file,
+            identify_sti,
+            analyze_frame,
+            get_raw_frame_bytes,
+            clear_sti_cache,
+            list_edit_conflicts,
+            move_cache_entry,
+            get_cache_stats,
+            set_cache_limits,
+            set_allowed_roots,
+            set_log_level,
+            enter_edit_mode,
+            update_image_data,
+            add_new_image,
+            add_blank_frame,
+            add_new_image_preview,
+            reorder_images,
+            reorder_images_preview,
+            swap_frames,
+            delete_image,
+            trim_blank_frames,
+            undo_edit,
+            redo_edit,
+            remove_images_from_sti,
+            remove_images_from_sti_preview,
+            create_sti_backup,
+            validate_sti_integrity,
+            check_sti_integrity,
+            validate_directory,
+            restore_sti_from_backup,
+            repair_offsets,
+            optimize_compression,
+            list_backups,
+            prune_backups,
+            check_file_exists,
+            analyze_image_for_import,
+            import_image_to_new_sti,
+            import_image_to_existing_sti,
+            set_transparent_color,
+            detect_transparent_color,
+            get_animation_data,
+            set_animation_frame_count,
+            get_palette,
+            update_palette_color,
+            shift_palette_range,
+            cycle_team_colors,
+            export_palette,
+            export_palette_image,
+            import_palette,
+            apply_palette_to_directory,
+            normalize_palette,
+            analyze_palette_usage,
+            color_histogram,
+            compact_palette,
+            pick_color_at,
+            get_pixel,
+            set_pixel,
+            set_pixels,
+            set_frame_offset,
+            export_svg,
+            apply_frame_manifest,
+            directory_palette_usage,
+            export_usage_histogram,
+            assert_equivalent,
+            build_from_png_folder,
+            get_capabilities,
+            start_batch_operation,
+            cancel_batch,
+            cancel_scan,
+            batch_export_images,
+            generate_thumbnail,
+            get_frame_data_url,
+            import_image_as_frame,
+            composite_frames,
+            export_animated_gif,
+            export_animation_strip,
+            resize_image,
+            crop_image,
+            autocrop_image,
+            frame_content_bounds,
+            estimate_memory,
+            transform_image,
+            replace_index,
+            paste_frame,
+            diff_sti_files,
+            diff_frame_image,
+            find_duplicate_frames,
+            dedupe_frames,
+            merge_sti_files,
+            check_palette_compatibility,
+            extract_frame_to_file,
+            convert_bit_depth,
+            export_bundle,
+            import_bundle
+        ])
+        .run(tauri::generate_context!())
+        .expect("error while running tauri application");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_differing_palette_indices() {
+        let a = [[0u8; 3]; 256];
+        let mut b = a;
+        b[3] = [1, 0, 0];
+        b[255] = [0, 0, 9];
+        assert_eq!(differing_palette_indices(&a, &b), vec![3, 255]);
+        assert!(differing_palette_indices(&a, &a).is_empty());
+    }
+    
+    #[test]
+    fn test_rekey_cache_entry() {
+        let sti_file = Arc::new(sti::builder::StiBuilder::new_indexed([[0u8; 3]; 256])
+            .add_frame(1, 1, vec![0], 0, 0)
+            .build()
+            .unwrap());
+        let entry = |dirty: bool| CachedStiFile {
+            file: sti_file.clone(),
+            stamp: Some(FileStamp { modified: None, len: 7 }),
+            dirty,
+            in_memory: false,
+        };
+        let mut cache = HashMap::new();
+        cache.insert("old.sti".to_string(), entry(false));
+        cache.insert("edited.sti".to_string(), entry(true));
+        
+        assert!(rekey_cache_entry(&mut cache, "old.sti", "new.sti"));
+        assert!(!cache.contains_key("old.sti"));
+        assert_eq!(cache["new.sti"].stamp, Some(FileStamp { modified: None, len: 7 }));
+        
+        // Unsaved edits take the new path's stamp (none here, the file doesn't exist)
+        assert!(rekey_cache_entry(&mut cache, "edited.sti", "/nonexistent/moved.sti"));
+        assert!(cache["/nonexistent/moved.sti"].stamp.is_none());
+        
+        assert!(!rekey_cache_entry(&mut cache, "missing.sti", "other.sti"));
+    }
+    
+    #[test]
+    fn test_has_edit_conflict() {
+        let entry = |dirty: bool, in_memory: bool| CachedStiFile {
+            file: Arc::new(StiFile::new()),
+            stamp: Some(FileStamp { modified: None, len: 7 }),
+            dirty,
+            in_memory,
+        };
+        let same = Some(FileStamp { modified: None, len: 7 });
+        let changed = Some(FileStamp { modified: None, len: 9 });
+        
+        assert!(has_edit_conflict(&entry(true, false), &changed));
+        assert!(has_edit_conflict(&entry(true, false), &None));
+        assert!(!has_edit_conflict(&entry(true, false), &same));
+        assert!(!has_edit_conflict(&entry(false, false), &changed));
+        assert!(!has_edit_conflict(&entry(true, true), &changed));
+    }
+    
+    #[test]
+    fn test_in_memory_entries_are_not_written_to_disk() {
+        let dir = std::env::temp_dir().join(format!("sti_in_memory_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let name = dir.join("dropped.sti").to_string_lossy().to_string();
+        insert_cache_entry(&name, CachedStiFile {
+            file: Arc::new(StiFile::new()),
+            stamp: None,
+            dirty: false,
+            in_memory: true,
+        }).unwrap();
+        
+        let mut sti_file = sti::builder::StiBuilder::new_indexed([[0u8; 3]; 256])
+            .add_frame(1, 1, vec![0], 0, 0)
+            .build()
+            .unwrap();
+        assert!(write_sti_file(&name, &mut sti_file).is_err());
+        assert!(!Path::new(&name).exists());
+        
+        lock_cache(&STI_CACHE).unwrap().remove(&name);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+    
+    #[test]
+    fn test_has_unsaved_edits() {
+        let name = "unsaved_edits_test.sti";
+        assert!(!has_unsaved_edits(name).unwrap());
+        for dirty in [false, true] {
+            insert_cache_entry(name, CachedStiFile {
+                file: Arc::new(StiFile::new()),
+                stamp: None,
+                dirty,
+                in_memory: false,
+            }).unwrap();
+            assert_eq!(has_unsaved_edits(name).unwrap(), dirty);
+        }
+        lock_cache(&STI_CACHE).unwrap().remove(name);
+    }
+    
+    #[test]
+    fn test_batch_operation_guard_unregisters() {
+        fn failing_batch(operation_id: Option<String>) -> Result<(), CommandError> {
+            let _flag = batch_cancel_flag(&operation_id);
+            let _batch = BatchOperationGuard(operation_id);
+            Err(CommandError::NotFound("no frames".to_string()))
+        }
+        
+        assert!(failing_batch(Some("batch-guard-test".to_string())).is_err());
+        assert!(!lock_cache(&BATCH_OPERATIONS).unwrap().contains_key("batch-guard-test"));
+    }
+    
+    #[test]
+    fn test_opaque_extent() {
+        let indexed = sti::builder::StiBuilder::new_indexed([[0u8; 3]; 256])
+            .add_frame(4, 3, vec![0, 0, 0, 0, 0, 5, 0, 0, 0, 6, 7, 0], 0, 0)
+            .add_frame(2, 1, vec![0, 0], 0, 0)
+            .build()
+            .unwrap();
+        assert_eq!(opaque_extent(&indexed, &indexed.images[0]).unwrap(), (Some((1, 1, 2, 2)), 3));
+        assert_eq!(opaque_extent(&indexed, &indexed.images[1]).unwrap(), (None, 0));
+        
+        let pixels: Vec<u8> = [0x001Fu16, 0xF800, 0x001F].iter().flat_map(|value| value.to_le_bytes()).collect();
+        let rgb = sti::builder::StiBuilder::new_rgb16((0xF800, 0x07E0, 0x001F))
+            .transparent_color(0x001F)
+            .add_frame(3, 1, pixels, 0, 0)
+            .build()
+            .unwrap();
+        assert_eq!(opaque_extent(&rgb, &rgb.images[0]).unwrap(), (Some((1, 0, 1, 1)), 1));
+    }
+    
+    #[test]
+    fn test_detect_transparent_value() {
+        // Index 7 fills the border apart from one stray pixel, 0 only appears inside
+        let indexed = sti::builder::StiBuilder::new_indexed([[0u8; 3]; 256])
+            .add_frame(3, 3, vec![7, 7, 7, 7, 0, 2, 7, 7, 7], 0, 0)
+            .build()
+            .unwrap();
+        assert_eq!(detect_transparent_value(&indexed).unwrap(), 7);
+        
+        let single = sti::builder::StiBuilder::new_indexed([[0u8; 3]; 256])
+            .add_frame(2, 1, vec![9, 4], 0, 0)
+            .build()
+            .unwrap();
+        assert_eq!(detect_transparent_value(&single).unwrap(), 4);
+        
+        let pixels: Vec<u8> = [0xF81Fu16, 0x0000, 0xF81F, 0x07E0, 0x0000, 0xF81F]
+            .iter().flat_map(|value| value.to_le_bytes()).collect();
+        let rgb = sti::builder::StiBuilder::new_rgb16((0xF800, 0x07E0, 0x001F))
+            .add_frame(3, 2, pixels, 0, 0)
+            .build()
+            .unwrap();
+        assert_eq!(detect_transparent_value(&rgb).unwrap(), 0xF81F);
+    }
+    
+    #[test]
+    fn test_blank_edge_frames() {
+        let sti_file = sti::builder::StiBuilder::new_indexed([[0u8; 3]; 256])
+            .add_frame(1, 1, vec![0], 0, 0)
+            .add_frame(2, 1, vec![0, 4], 0, 0)
+            .add_frame(1, 1, vec![0], 0, 0)
+            .add_frame(1, 1, vec![3], 0, 0)
+            .add_frame(1, 1, vec![0], 0, 0)
+            .add_frame(2, 1, vec![0, 0], 0, 0)
+            .build()
+            .unwrap();
+        assert_eq!(blank_edge_frames(&sti_file, true, true).unwrap(), vec![0, 4, 5]);
+        assert_eq!(blank_edge_frames(&sti_file, true, false).unwrap(), vec![0]);
+        assert_eq!(blank_edge_frames(&sti_file, false, true).unwrap(), vec![4, 5]);
+        assert!(blank_edge_frames(&sti_file, false, false).unwrap().is_empty());
+        
+        // Every frame is listed once, and removing them all is refused
+        let mut empty = sti::builder::StiBuilder::new_indexed([[0u8; 3]; 256])
+            .add_frame(1, 1, vec![0], 0, 0)
+            .add_frame(1, 1, vec![0], 0, 0)
+            .build()
+            .unwrap();
+        let indices = blank_edge_frames(&empty, true, true).unwrap();
+        assert_eq!(indices, vec![0, 1]);
+        assert!(matches!(remove_images(&mut empty, &indices, true), Err(CommandError::Unsupported(_))));
+    }
+    
+    #[test]
+    fn test_cycle_pixel_indices() {
+        let mut sti_file = sti::builder::StiBuilder::new_indexed([[0u8; 3]; 256])
+            .add_frame(3, 2, vec![0, 10, 11, 12, 13, 200], 0, 0)
+            .add_frame(1, 1, vec![12], 0, 0)
+            .build()
+            .unwrap();
+        let palette = sti_file.palette;
+        
+        cycle_pixel_indices(&mut sti_file, 10, 4, 1);
+        assert_eq!(sti_file.images[0].decompressed_data, Some(vec![0, 11, 12, 13, 10, 200]));
+        assert_eq!(sti_file.images[1].decompressed_data, Some(vec![13]));
+        assert_eq!(sti_file.palette, palette);
+        
+        // Shifting back, or by a whole multiple of the range, restores the pixels
+        cycle_pixel_indices(&mut sti_file, 10, 4, -5);
+        cycle_pixel_indices(&mut sti_file, 10, 4, 8);
+        assert_eq!(sti_file.images[0].decompressed_data, Some(vec![0, 10, 11, 12, 13, 200]));
+        assert_eq!(sti_file.images[1].decompressed_data, Some(vec![12]));
+    }
+    
+    #[test]
+    fn test_memory_estimate() {
+        let indexed = sti::builder::StiBuilder::new_indexed([[0u8; 3]; 256])
+            .add_frame(4, 2, vec![0; 8], 0, 0)
+            .add_frame(2, 2, vec![1, 2, 3, 4], 0, 0)
+            .build()
+            .unwrap();
+        let estimate = memory_estimate(&indexed);
+        let stored: usize = indexed.images.iter().map(|image| image.raw_data.len()).sum();
+        assert_eq!(estimate["decompressed_size"], 12);
+        assert_eq!(estimate["palette_size"], 768);
+        assert_eq!(estimate["total_memory"], 780);
+        assert_eq!(estimate["compressed_size"], stored);
+        assert_eq!(estimate["frames"][1]["decompressed_size"], 4);
+        
+        let rgb = sti::builder::StiBuilder::new_rgb16((0xF800, 0x07E0, 0x001F))
+            .add_frame(3, 2, vec![0; 12], 0, 0)
+            .build()
+            .unwrap();
+        let estimate = memory_estimate(&rgb);
+        assert_eq!(estimate["decompressed_size"], 12);
+        assert_eq!(estimate["palette_size"], 0);
+        assert_eq!(estimate["compression_ratio"], 1.0);
+    }
+    
+    #[test]
+    fn test_new_blank_sti() {
+        let indexed = new_blank_sti(true, 3, 2, None, 0).unwrap();
+        let parsed = StiParser::parse(&StiParser::write(&indexed).unwrap()).unwrap();
+        assert_eq!(parsed.images[0].decompressed_data, Some(vec![0; 6]));
+        assert_eq!(parsed.palette.unwrap()[200], [200, 200, 200]);
+        
+        let custom = new_blank_sti(true, 1, 1, Some(vec![[9, 8, 7], [1, 2, 3]]), 1).unwrap();
+        assert_eq!(custom.palette.unwrap()[..3], [[9, 8, 7], [1, 2, 3], [0, 0, 0]]);
+        
+        let rgb = new_blank_sti(false, 2, 1, None, 0x07E0).unwrap();
+        assert_eq!(rgb.images[0].raw_data, vec![0xE0, 0x07, 0xE0, 0x07]);
+        
+        assert!(matches!(new_blank_sti(true, 0, 4, None, 0), Err(CommandError::OutOfBounds(_))));
+        assert!(matches!(new_blank_sti(true, 1, 1, None, 300), Err(CommandError::OutOfBounds(_))));
+        assert!(new_blank_sti(true, 1, 1, Some(Vec::new()), 0).is_err());
+    }
+    
+    #[test]
+    fn test_path_within_roots_rejects_traversal() {
+        let base = std::env::temp_dir().join(format!("sti_roots_test_{}", std::process::id()));
+        let root = base.join("allowed");
+        fs::create_dir_all(root.join("sub")).unwrap();
+        fs::create_dir_all(base.join("other")).unwrap();
+        let roots = vec![root.canonicalize().unwrap()];
+        
+        let inside = path_within_roots(&root.join("sub").join("new.sti"), &roots);
+        let dotted_inside = path_within_roots(&root.join("sub").join("..").join("new.sti"), &roots);
+        let escaped = path_within_roots(&root.join("..").join("other").join("new.sti"), &roots);
+        let deep_escape = path_within_roots(&root.join("sub").join("..").join("..").join("..").join("x.sti"), &roots);
+        let dir_escape = path_within_roots(&root.join(".."), &roots);
+        let unrestricted = path_within_roots(&base.join("other").join("new.sti"), &[]);
+        let _ = fs::remove_dir_all(&base);
+        
+        assert!(inside.is_ok());
+        assert!(dotted_inside.unwrap().starts_with(&roots[0]));
+        assert!(escaped.unwrap_err().contains("outside the allowed roots"));
+        assert!(deep_escape.is_err());
+        assert!(dir_escape.is_err());
+        assert!(unrestricted.is_ok());
+    }
+
+    #[test]
+    fn test_hidden_entries_skipped_consistently() {
+        let base = std::env::temp_dir().join(format!("sti_hidden_test_{}", std::process::id()));
+        fs::create_dir_all(base.join(".cache")).unwrap();
+        fs::create_dir_all(base.join("only_hidden")).unwrap();
+        fs::write(base.join("visible.sti"), b"").unwrap();
+        fs::write(base.join(".backup.sti"), b"").unwrap();
+        fs::write(base.join(".cache").join("inner.sti"), b"").unwrap();
+        fs::write(base.join("only_hidden").join(".old.sti"), b"").unwrap();
+
+        let default_scan = collect_sti_files(&base, true, false, None).unwrap();
+        let full_scan = collect_sti_files(&base, true, true, None).unwrap();
+        let default_contains = directory_contains_sti_files_with_depth(&base.join("only_hidden"), 0, 1, false);
+        let hidden_contains = directory_contains_sti_files_with_depth(&base.join("only_hidden"), 0, 1, true);
+        let _ = fs::remove_dir_all(&base);
+
+        assert!(is_hidden(Path::new("dir/.backup.sti")));
+        assert!(!is_hidden(Path::new(".hidden/visible.sti")));
+
+        // Hidden files and hidden directories are treated alike
+        assert_eq!(default_scan.len(), 1);
+        assert!(default_scan[0].ends_with("visible.sti"));
+        assert_eq!(full_scan.len(), 4);
+        assert!(!default_contains);
+        assert!(hidden_contains);
+    }
+
+    #[test]
+    fn test_zlib_file_survives_save() {
+        let mut sti_file = StiFile::new();
+        sti_file.header.flags.indexed = true;
+        sti_file.header.flags.etrle_compressed = true;
+        sti_file.header.flags.zlib_compressed = true;
+        sti_file.header.palette_colors = 256;
+        sti_file.header.num_images = 2;
+        sti_file.header.color_depth = 8;
+        sti_file.palette = Some([[0u8; 3]; 256]);
+        
+        for frame in 0..2u8 {
+            let mut image = sti::StiImage::with_header(sti::StiSubImageHeader {
+                data_offset: 0,
+                data_size: 0,
+                offset_x: frame as i16,
+                offset_y: 0,
+                height: 3,
+                width: 4,
+            });
+            image.decompressed_data = Some((0..12u8).map(|i| (i + frame) % 5).collect());
+            sti_file.images.push(image);
+        }
+        let original_pixels: Vec<_> = sti_file.images.iter().map(|image| image.decompressed_data.clone()).collect();
+        
+        fn save(sti_file: &mut StiFile) -> Vec<u8> {
+            sti::ops::encode_file(sti_file).unwrap()
+        }
+        
+        // Save, re-parse, then save the parsed copy again
+        let mut parsed = StiParser::parse(&save(&mut sti_file)).unwrap();
+        let saved = save(&mut parsed);
+        let current = StiParser::parse(&saved).unwrap();
+        
+        assert!(current.header.flags.zlib_compressed);
+        let reparsed_pixels: Vec<_> = current.images.iter().map(|image| image.decompressed_data.clone()).collect();
+        assert_eq!(reparsed_pixels, original_pixels);
+        
+        let strict = sti::ParseOptions { strict: true, ..sti::ParseOptions::default() };
+        assert!(StiParser::parse_with_options(&saved, &strict).is_ok());
+    }
+    
+    #[test]
+    fn test_bundle_frame_round_trip() {
+        let mut indexed = StiFile::new();
+        indexed.header.flags.indexed = true;
+        let mut palette = [[0u8; 3]; 256];
+        palette[1] = [255, 0, 0];
+        palette[2] = [0, 128, 255];
+        indexed.palette = Some(palette);
+        let mut image = sti::StiImage::new(2, 2);
+        image.decompressed_data = Some(vec![0, 1, 2, 1]);
+        indexed.images.push(image);
+        
+        let rgba = frame_to_rgba(&indexed, 0).unwrap();
+        assert_eq!(bundle_frame_pixels(&indexed.header, indexed.palette.as_ref(), &rgba), vec![0, 1, 2, 1]);
+        
+        let mut rgb = StiFile::new();
+        rgb.header.flags.rgb = true;
+        rgb.header.red_mask = 0xF800;
+        rgb.header.green_mask = 0x07E0;
+        rgb.header.blue_mask = 0x001F;
+        rgb.header.transparent_color = 0xFFFF_FFFF;
+        let pixels: Vec<u8> = [0x0000u16, 0xF800, 0x1234, 0xFFFF].iter().flat_map(|v| v.to_le_bytes()).collect();
+        let mut image = sti::StiImage::new(2, 2);
+        image.decompressed_data = Some(pixels.clone());
+        rgb.images.push(image);
+        
+        let rgba = frame_to_rgba(&rgb, 0).unwrap();
+        assert_eq!(bundle_frame_pixels(&rgb.header, None, &rgba), pixels);
+    }
+    
+    #[test]
+    fn test_paste_pixels_clips_and_skips_transparent() {
+        let mut sti_file = sti::builder::StiBuilder::new_indexed([[0u8; 3]; 256])
+            .add_frame(3, 2, vec![1, 1, 1, 1, 1, 1], 0, 0)
+            .add_frame(2, 2, vec![5, 0, 6, 7], 0, 0)
+            .build()
+            .unwrap();
+        
+        // Only the source's bottom-left quarter lands at the destination's top-right corner
+        paste_pixels(&mut sti_file, 0, 1, 2, -1, false).unwrap();
+        assert_eq!(sti_file.images[0].decompressed_data, Some(vec![1, 1, 6, 1, 1, 1]));
+        
+        paste_pixels(&mut sti_file, 0, 1, 0, 0, true).unwrap();
+        assert_eq!(sti_file.images[0].decompressed_data, Some(vec![5, 1, 6, 6, 7, 1]));
+    }
+    
+    #[test]
+    fn test_crop_rows() {
+        // 3x2 frame, 2 bytes per pixel
+        let data: Vec<u8> = (0..12).collect();
+        assert_eq!(crop_rows(&data, 3, 2, 1, 0, 2, 2), vec![2, 3, 4, 5, 8, 9, 10, 11]);
+        assert_eq!(crop_rows(&data, 3, 2, 0, 1, 1, 1), vec![6, 7]);
+    }
+    
+    #[test]
+    fn test_shift_offset() {
+        assert_eq!(shift_offset(-5, 3).unwrap(), -2);
+        assert_eq!(shift_offset(-32768, 40000).unwrap(), 7232);
+        assert!(matches!(shift_offset(32000, 1000), Err(CommandError::OutOfBounds(_))));
+        assert!(shift_offset(0, 40000).is_err());
+    }
+    
+    #[test]
+    fn test_rgb16_color_counts() {
+        let mut image = sti::StiImage::new(4, 1);
+        image.decompressed_data = Some([7u16, 3, 7, 9].iter().flat_map(|pixel| pixel.to_le_bytes()).collect());
+        let images = vec![image];
+        
+        assert_eq!(rgb16_color_counts(&images, 10), vec![(7, 2), (3, 1), (9, 1)]);
+        assert_eq!(rgb16_color_counts(&images, 1), vec![(7, 2)]);
+    }
+    
+    #[test]
+    fn test_remove_animated_frames() {
+        let mut sti_file = StiFile::new();
+        sti_file.header.flags.indexed = true;
+        sti_file.images = (0..5).map(|_| sti::StiImage::new(1, 1)).collect();
+        sti_file.header.num_images = 5;
+        sti_file.animation_data = vec![sti::StiAnimationData::default(); 5];
+        sti_file.animation_data[0] = sti::StiAnimationData::single_frame(3);
+        sti_file.animation_data[3] = sti::StiAnimationData::single_frame(2);
+        
+        let mut rejected = sti_file.clone();
+        assert!(matches!(remove_images(&mut rejected, &[1], false), Err(CommandError::Unsupported(_))));
+        assert_eq!(rejected.images.len(), 5);
+        
+        remove_images(&mut sti_file, &[0, 4], true).unwrap();
+        let counts: Vec<u8> = sti_file.animation_data.iter().map(|anim| anim.frame_count).collect();
+        assert_eq!(counts, vec![2, 0, 1]);
+        assert!(sti_file.animation_data[0].starts_sequence());
+        assert_eq!(sti_file.header.app_data_size, 48);
+    }
+    
+    #[test]
+    fn test_remove_duplicate_indices() {
+        let mut sti_file = StiFile::new();
+        sti_file.header.flags.indexed = true;
+        sti_file.images = (0..5).map(|_| sti::StiImage::new(1, 1)).collect();
+        sti_file.header.num_images = 5;
+        sti_file.animation_data = vec![sti::StiAnimationData::default(); 5];
+        sti_file.animation_data[0] = sti::StiAnimationData::single_frame(3);
+        sti_file.animation_data[3] = sti::StiAnimationData::single_frame(2);
+    
+        // Three copies of the last frame of a 2-frame sequence remove it once
+        remove_images(&mut sti_file, &[4, 4, 4], true).unwrap();
+        assert_eq!(sti_file.images.len(), 4);
+        let counts: Vec<u8> = sti_file.animation_data.iter().map(|anim| anim.frame_count).collect();
+        assert_eq!(counts, vec![3, 0, 0, 1]);
+    }
+    
+    #[test]
+    fn test_duplicate_frame_groups() {
+        let mut builder = sti::builder::StiBuilder::new_indexed([[0u8; 3]; 256]);
+        for pixels in [vec![1, 2], vec![3, 4], vec![1, 2], vec![3, 4], vec![1, 2]] {
+            builder = builder.add_frame(2, 1, pixels, 0, 0);
+        }
+        // Same bytes at a different size or offset is a different frame
+        let sti_file = builder
+            .add_frame(1, 2, vec![1, 2], 0, 0)
+            .add_frame(2, 1, vec![1, 2], 3, 0)
+            .build()
+            .unwrap();
+        
+        assert_eq!(duplicate_frame_groups(&sti_file), vec![vec![0, 2, 4], vec![1, 3]]);
+    }
+    
+    #[test]
+    fn test_poisoned_cache_returns_error() {
+        // A local cache, poisoning the shared one would break tests running alongside
+        let cache: Mutex<HashMap<String, usize>> = Mutex::new(HashMap::new());
+        cache.lock().unwrap().insert("kept.sti".to_string(), 1);
+        let _ = std::thread::scope(|scope| scope.spawn(|| {
+            let _guard = cache.lock().unwrap();
+            panic!("poison the cache");
+        }).join());
+        assert!(cache.is_poisoned());
+        
+        assert!(lock_cache(&cache).is_err());
+        
+        // The cache is reset rather than staying unusable
+        assert!(lock_cache(&cache).unwrap().is_empty());
+        assert!(!cache.is_poisoned());
+    }
 }
@@ -0,0 +1,39 @@
+use log::{LevelFilter, Log, Metadata, Record};
+
+// Minimal stderr sink for the `log` facade, so parse and save traces show up in the
+// terminal or whatever file the app's stderr is redirected to
+struct StderrLogger;
+
+impl Log for StderrLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            eprintln!("[{} {}] {}", record.level(), record.target(), record.args());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: StderrLogger = StderrLogger;
+
+// Debug builds show info and up, release builds only warnings until set_log_level raises it
+pub fn default_level() -> LevelFilter {
+    if cfg!(debug_assertions) { LevelFilter::Info } else { LevelFilter::Warn }
+}
+
+// Install the stderr sink; a logger installed earlier (e.g. by a test harness) is kept
+pub fn init() {
+    let _ = log::set_logger(&LOGGER);
+    log::set_max_level(default_level());
+}
+
+pub fn set_level(level: &str) -> Result<(), String> {
+    let filter = level.parse::<LevelFilter>()
+        .map_err(|_| format!("Unknown log level '{}' (use off, error, warn, info, debug or trace)", level))?;
+    log::set_max_level(filter);
+    Ok(())
+}
@@ -0,0 +1,293 @@
+use crate::sti::etrle::EtrleDecoder;
+use crate::sti::parser::StiParser;
+use crate::sti::types::*;
+
+/// Fluent construction of a complete `StiFile` from frames of pixels
+///
+/// `build` fills in the flags, image count, sub-headers, stored frame bytes and
+/// header sizes, so the result can go straight to `StiParser::write`.
+#[derive(Debug, Clone)]
+pub struct StiBuilder {
+    sti_file: StiFile,
+}
+
+impl StiBuilder {
+    /// 8-bit ETRLE-compressed file drawn with `palette`
+    pub fn new_indexed(palette: StiPalette) -> Self {
+        let mut sti_file = StiFile::new();
+        let header = &mut sti_file.header;
+        header.flags.indexed = true;
+        header.flags.etrle_compressed = true;
+        header.palette_colors = 256;
+        header.color_depth = 8;
+        header.red_depth = 8;
+        header.green_depth = 8;
+        header.blue_depth = 8;
+        sti_file.palette = Some(palette);
+        Self { sti_file }
+    }
+    
+    /// 16-bit file with the given red, green and blue masks, e.g. `(0xF800, 0x07E0, 0x001F)`
+    pub fn new_rgb16(masks: (u32, u32, u32)) -> Self {
+        let mut sti_file = StiFile::new();
+        let header = &mut sti_file.header;
+        header.flags.rgb = true;
+        header.color_depth = 16;
+        header.red_mask = masks.0;
+        header.green_mask = masks.1;
+        header.blue_mask = masks.2;
+        header.red_depth = masks.0.count_ones() as u8;
+        header.green_depth = masks.1.count_ones() as u8;
+        header.blue_depth = masks.2.count_ones() as u8;
+        Self { sti_file }
+    }
+    
+    /// Palette index (8-bit) or pixel value (16-bit) that stands for transparency
+    pub fn transparent_color(mut self, color: u32) -> Self {
+        self.sti_file.header.transparent_color = color;
+        self.sti_file.header.flags.transparent = true;
+        self
+    }
+    
+    /// Append a frame: palette indices for 8-bit files, little-endian pixel values for 16-bit
+    pub fn add_frame(mut self, width: u16, height: u16, pixels: Vec<u8>, offset_x: i16, offset_y: i16) -> Self {
+        let mut image = StiImage::with_header(StiSubImageHeader {
+            data_offset: 0,
+            data_size: 0,
+            offset_x,
+            offset_y,
+            height,
+            width,
+        });
+        image.decompressed_data = Some(pixels);
+        self.sti_file.images.push(image);
+        self
+    }
+    
+    /// Animation records, one per frame, stored as the application data block
+    pub fn animation_data(mut self, records: Vec<StiAnimationData>) -> Self {
+        self.sti_file.animation_data = records;
+        self
+    }
+    
+    pub fn build(self) -> StiResult<StiFile> {
+        let mut sti_file = self.sti_file;
+        if sti_file.images.is_empty() {
+            return Err(StiError::InvalidFormat("STI file needs at least one frame".to_string()));
+        }
+        
+        let bytes_per_pixel = if sti_file.is_16bit() { 2 } else { 1 };
+        for (index, image) in sti_file.images.iter().enumerate() {
+            let expected = image.width as usize * image.height as usize * bytes_per_pixel;
+            let actual = image.decompressed_data.as_ref().map_or(0, |data| data.len());
+            if actual != expected {
+                return Err(StiError::InvalidFormat(format!(
+                    "Frame {} has {} bytes of pixels, expected {} for {}x{}",
+                    index, actual, expected, image.width, image.height
+                )));
+            }
+        }
+        
+        if !sti_file.animation_data.is_empty() && sti_file.animation_data.len() != sti_file.images.len() {
+            return Err(StiError::InvalidFormat(format!(
+                "{} animation records for {} frames",
+                sti_file.animation_data.len(), sti_file.images.len()
+            )));
+        }
+        
+        sti_file.header.num_images = sti_file.images.len() as u16;
+        if sti_file.is_16bit() {
+            // The main header carries the size; a lone 16-bit image has no sub-header
+            sti_file.header.width = sti_file.images[0].width;
+            sti_file.header.height = sti_file.images[0].height;
+            if sti_file.images.len() == 1 {
+                sti_file.images[0].header = None;
+            }
+        }
+        
+        encode_frames(&mut sti_file, &[])?;
+        update_header_sizes(&mut sti_file);
+        Ok(sti_file)
+    }
+}
+
+/// Store each frame's pixels the way the header's flags say and lay out the sub-headers
+///
+/// Frames marked in `clean` keep the stored bytes they already have.
+pub fn encode_frames(sti_file: &mut StiFile, clean: &[bool]) -> StiResult<()> {
+    if sti_file.is_8bit() {
+        let flags = sti_file.header.flags;
+        let transparent_index = sti_file.header.etrle_transparent_index();
+        if sti_file.header.transparent_color > u8::MAX as u32 {
+            log::warn!(
+                "Transparent color {} doesn't fit an 8-bit palette index, compressing with index {} instead",
+                sti_file.header.transparent_color, transparent_index
+            );
+        }
+        let mut cumulative_data_offset = 0u32;
+        
+        for (index, image) in sti_file.images.iter_mut().enumerate() {
+            if !clean.get(index).copied().unwrap_or(false) {
+                let decompressed_data = match &image.decompressed_data {
+                    Some(data) => data,
+                    None => continue,
+                };
+                
+                let mut stored_data = if flags.etrle_compressed {
+                    let encoder = EtrleDecoder::with_transparent(image.width, image.height, transparent_index)
+                        .with_row_end(image.etrle_row_end);
+                    encoder.compress(decompressed_data)
+                        .map_err(|e| StiError::Encoding(format!("Failed to compress image data: {}", e)))?
+                } else {
+                    decompressed_data.clone()
+                };
+                
+                // ZLIB wraps each frame's (ETRLE) data, matching how the parser inflates it
+                if flags.zlib_compressed {
+                    stored_data = StiParser::deflate_zlib(&stored_data)
+                        .map_err(|e| StiError::Encoding(format!("Failed to deflate image data: {}", e)))?;
+                }
+                
+                image.raw_data = stored_data;
+            }
+            
+            // Update sub-header with stored size and cumulative offset
+            if let Some(header) = &mut image.header {
+                header.data_size = image.raw_data.len() as u32;
+                
+                // data_offset is cumulative from the start of image data section
+                header.data_offset = cumulative_data_offset;
+            }
+            
+            // Add this image's size to the cumulative offset for next image
+            cumulative_data_offset += image.raw_data.len() as u32;
+        }
+    } else if sti_file.is_16bit() {
+        // For 16-bit files, raw_data = decompressed_data (no compression)
+        let mut cumulative_data_offset = 0u32;
+        for image in &mut sti_file.images {
+            if let Some(decompressed_data) = &image.decompressed_data {
+                image.raw_data = decompressed_data.clone();
+            }
+            
+            // Only multi-image files carry sub-headers
+            if let Some(header) = &mut image.header {
+                header.data_size = image.raw_data.len() as u32;
+                header.data_offset = cumulative_data_offset;
+            }
+            cumulative_data_offset += image.raw_data.len() as u32;
+        }
+    }
+    
+    Ok(())
+}
+
+/// Recompute the header's compressed, original and application data sizes from the frames
+pub fn update_header_sizes(sti_file: &mut StiFile) {
+    if sti_file.is_8bit() {
+        // Calculate total compressed size for 8-bit files
+        let mut total_compressed_size = 0u32;
+        let mut total_original_size = 0u32;
+        
+        for image in &sti_file.images {
+            total_compressed_size += image.raw_data.len() as u32;
+            if let Some(decompressed) = &image.decompressed_data {
+                total_original_size += decompressed.len() as u32;
+            }
+        }
+        
+        sti_file.header.compressed_size = total_compressed_size;
+        sti_file.header.original_size = total_original_size;
+        sti_file.header.app_data_size = sti_file.app_data_bytes().len() as u32;
+    } else if sti_file.is_16bit() {
+        // For 16-bit files, raw data = decompressed data
+        let data_size: u32 = sti_file.images.iter().map(|image| image.raw_data.len() as u32).sum();
+        sti_file.header.compressed_size = data_size;
+        sti_file.header.original_size = data_size;
+        sti_file.header.app_data_size = sti_file.app_data_bytes().len() as u32;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_build_indexed_file() {
+        let sti_file = StiBuilder::new_indexed([[3u8; 3]; 256])
+            .add_frame(2, 2, vec![0, 1, 1, 0], -4, 2)
+            .add_frame(1, 3, vec![5, 5, 0], 0, 0)
+            .animation_data(vec![StiAnimationData::single_frame(2), StiAnimationData::default()])
+            .build()
+            .unwrap();
+        
+        assert_eq!(sti_file.header.num_images, 2);
+        assert_eq!(sti_file.header.app_data_size, 32);
+        assert_eq!(
+            sti_file.header.compressed_size as usize,
+            sti_file.images.iter().map(|image| image.raw_data.len()).sum::<usize>()
+        );
+        assert_eq!(sti_file.images[1].header.as_ref().unwrap().data_offset as usize, sti_file.images[0].raw_data.len());
+        
+        let parsed = StiParser::parse(&StiParser::write(&sti_file).unwrap()).unwrap();
+        assert_eq!(parsed.images[0].decompressed_data, Some(vec![0, 1, 1, 0]));
+        assert_eq!(parsed.images[1].decompressed_data, Some(vec![5, 5, 0]));
+        assert_eq!(parsed.images[0].header.as_ref().unwrap().offset_x, -4);
+    }
+    
+    #[test]
+    fn test_build_rgb16_file() {
+        let pixels: Vec<u8> = [0xF800u16, 0x001F].iter().flat_map(|value| value.to_le_bytes()).collect();
+        let sti_file = StiBuilder::new_rgb16((0xF800, 0x07E0, 0x001F))
+            .add_frame(2, 1, pixels.clone(), 0, 0)
+            .build()
+            .unwrap();
+        
+        assert!(sti_file.images[0].header.is_none());
+        assert_eq!((sti_file.header.width, sti_file.header.height), (2, 1));
+        assert_eq!((sti_file.header.red_depth, sti_file.header.green_depth), (5, 6));
+        
+        let parsed = StiParser::parse(&StiParser::write(&sti_file).unwrap()).unwrap();
+        assert_eq!(parsed.images[0].decompressed_data, Some(pixels));
+    }
+    
+    #[test]
+    fn test_nonzero_transparent_index_round_trip() {
+        let pixels = vec![5, 5, 1, 0, 5, 2];
+        let sti_file = StiBuilder::new_indexed([[0u8; 3]; 256])
+            .transparent_color(5)
+            .add_frame(3, 2, pixels.clone(), 0, 0)
+            .build()
+            .unwrap();
+        
+        // Index 5 is stored as a transparent run, index 0 as an ordinary color
+        assert_eq!(sti_file.images[0].raw_data[0], 0x82);
+        
+        let parsed = StiParser::parse(&StiParser::write(&sti_file).unwrap()).unwrap();
+        assert_eq!(parsed.header.transparent_color, 5);
+        assert_eq!(parsed.images[0].decompressed_data, Some(pixels));
+    }
+    
+    #[test]
+    fn test_omitted_last_row_marker_survives_save() {
+        let mut sti_file = StiBuilder::new_indexed([[0u8; 3]; 256])
+            .add_frame(2, 2, vec![0, 1, 2, 3], 0, 0)
+            .build()
+            .unwrap();
+        sti_file.images[0].etrle_row_end = crate::sti::etrle::RowEndMode::OmitLast;
+        encode_frames(&mut sti_file, &[]).unwrap();
+        update_header_sizes(&mut sti_file);
+        let original = StiParser::write(&sti_file).unwrap();
+        
+        let mut parsed = StiParser::parse(&original).unwrap();
+        assert_eq!(parsed.images[0].etrle_row_end, crate::sti::etrle::RowEndMode::OmitLast);
+        encode_frames(&mut parsed, &[]).unwrap();
+        assert_eq!(StiParser::write(&parsed).unwrap(), original);
+    }
+    
+    #[test]
+    fn test_rejects_wrong_pixel_count() {
+        assert!(StiBuilder::new_indexed([[0u8; 3]; 256]).add_frame(2, 2, vec![0; 3], 0, 0).build().is_err());
+        assert!(StiBuilder::new_indexed([[0u8; 3]; 256]).build().is_err());
+    }
+}
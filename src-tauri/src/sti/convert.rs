@@ -0,0 +1,246 @@
+use crate::sti::quantize::{median_cut_palette, nearest_palette_index};
+use crate::sti::types::*;
+
+/// Expand an 8-bit file into a 16-bit file, the transparent palette index becomes the transparent color
+pub fn convert_to_16bit(sti_file: &StiFile) -> StiResult<StiFile> {
+    if sti_file.is_16bit() {
+        return Ok(sti_file.clone());
+    }
+    if !sti_file.is_8bit() {
+        return Err(StiError::UnsupportedFormat("Unknown STI format for conversion".to_string()));
+    }
+    if sti_file.images.len() != 1 {
+        return Err(StiError::UnsupportedFormat(
+            "16-bit STI files hold a single image, extract one frame before converting".to_string()
+        ));
+    }
+    
+    let palette = sti_file.palette.as_ref()
+        .ok_or_else(|| StiError::InvalidFormat("8-bit STI file requires palette".to_string()))?;
+    let image = &sti_file.images[0];
+    let pixel_data = image.decompressed_data.as_ref()
+        .ok_or_else(|| StiError::Decompression("Image data not decompressed".to_string()))?;
+    
+    let transparent_index = sti_file.header.etrle_transparent_index();
+    
+    let mut converted = StiFile::new();
+    let header = &mut converted.header;
+    header.flags.rgb = true;
+    header.flags.transparent = true;
+    header.color_depth = 16;
+    header.num_images = 1;
+    header.width = image.width;
+    header.height = image.height;
+    header.red_mask = 0xF800;
+    header.green_mask = 0x07E0;
+    header.blue_mask = 0x001F;
+    header.red_depth = 5;
+    header.green_depth = 6;
+    header.blue_depth = 5;
+    
+    let transparent_value = header.encode_rgb16(palette[transparent_index as usize]);
+    header.transparent_color = transparent_value as u32;
+    
+    let mut rgb16 = Vec::with_capacity(pixel_data.len() * 2);
+    for &index in pixel_data {
+        let value = if index == transparent_index {
+            transparent_value
+        } else {
+            // Keep opaque pixels from colliding with the transparent key
+            let value = header.encode_rgb16(palette[index as usize]);
+            if value == transparent_value { value ^ 0x0001 } else { value }
+        };
+        rgb16.extend_from_slice(&value.to_le_bytes());
+    }
+    
+    let mut new_image = StiImage::new(image.width, image.height);
+    new_image.decompressed_data = Some(rgb16);
+    converted.images.push(new_image);
+    
+    Ok(converted)
+}
+
+/// Reduce a 16-bit file to an 8-bit file with one median-cut palette shared by every frame,
+/// transparent pixels map to index 0
+pub fn convert_to_8bit(sti_file: &StiFile) -> StiResult<StiFile> {
+    if sti_file.is_8bit() {
+        return Ok(sti_file.clone());
+    }
+    if !sti_file.is_16bit() {
+        return Err(StiError::UnsupportedFormat("Unknown STI format for conversion".to_string()));
+    }
+    if sti_file.images.is_empty() {
+        return Err(StiError::InvalidFormat("16-bit STI file requires at least one image".to_string()));
+    }
+    
+    let source_header = &sti_file.header;
+    let is_transparent = |value: u16| {
+        source_header.flags.transparent && value as u32 == source_header.transparent_color
+    };
+    
+    let frame_values = sti_file.images.iter()
+        .map(|image| {
+            let pixel_data = image.decompressed_data.as_ref()
+                .ok_or_else(|| StiError::Decompression("Image data not decompressed".to_string()))?;
+            Ok(pixel_data.chunks_exact(2)
+                .map(|chunk| u16::from_le_bytes([chunk[0], chunk[1]]))
+                .collect::<Vec<u16>>())
+        })
+        .collect::<StiResult<Vec<_>>>()?;
+    let opaque_colors: Vec<[u8; 3]> = frame_values.iter().flatten()
+        .filter(|&&value| !is_transparent(value))
+        .map(|&value| source_header.decode_rgb16(value))
+        .collect();
+    
+    // Index 0 is reserved for transparency, the rest come from median cut
+    let mut palette = [[0u8; 3]; 256];
+    if source_header.flags.transparent {
+        palette[0] = source_header.decode_rgb16(source_header.transparent_color as u16);
+    }
+    for (slot, color) in median_cut_palette(&opaque_colors, 255).into_iter().enumerate() {
+        palette[slot + 1] = color;
+    }
+    
+    let mut converted = StiFile::new();
+    let header = &mut converted.header;
+    header.flags.indexed = true;
+    header.flags.transparent = true;
+    header.flags.etrle_compressed = true;
+    header.palette_colors = 256;
+    header.num_images = sti_file.images.len() as u16;
+    header.color_depth = 8;
+    header.red_depth = 8;
+    header.green_depth = 8;
+    header.blue_depth = 8;
+    // Like other 8-bit files, only a lone image repeats its size in the main header
+    if let [image] = sti_file.images.as_slice() {
+        header.width = image.width;
+        header.height = image.height;
+    }
+    converted.palette = Some(palette);
+    
+    for (image, values) in sti_file.images.iter().zip(&frame_values) {
+        let indexed: Vec<u8> = values.iter()
+            .map(|&value| {
+                if is_transparent(value) {
+                    0
+                } else {
+                    nearest_palette_index(&palette, source_header.decode_rgb16(value), 1) as u8
+                }
+            })
+            .collect();
+        
+        let (offset_x, offset_y) = image.header.as_ref()
+            .map_or((0, 0), |sub_header| (sub_header.offset_x, sub_header.offset_y));
+        let mut new_image = StiImage::with_header(StiSubImageHeader {
+            data_offset: 0,
+            data_size: 0,
+            offset_x,
+            offset_y,
+            height: image.height,
+            width: image.width,
+        });
+        new_image.decompressed_data = Some(indexed);
+        converted.images.push(new_image);
+    }
+    
+    Ok(converted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gradient_8bit() -> StiFile {
+        let mut sti_file = StiFile::new();
+        sti_file.header.flags.indexed = true;
+        sti_file.header.flags.etrle_compressed = true;
+        sti_file.header.num_images = 1;
+        
+        let mut palette = [[0u8; 3]; 256];
+        palette[0] = [255, 0, 255];
+        for (i, entry) in palette.iter_mut().enumerate().skip(1) {
+            *entry = [i as u8, (255 - i) as u8, (i / 2) as u8];
+        }
+        sti_file.palette = Some(palette);
+        
+        // A 16x16 gradient with the first pixel transparent
+        let pixels: Vec<u8> = (0..256u32).map(|i| i as u8).collect();
+        let mut image = StiImage::with_header(StiSubImageHeader {
+            data_offset: 0,
+            data_size: 0,
+            offset_x: 0,
+            offset_y: 0,
+            height: 16,
+            width: 16,
+        });
+        image.decompressed_data = Some(pixels);
+        sti_file.images.push(image);
+        sti_file
+    }
+
+    #[test]
+    fn test_gradient_round_trip() {
+        let original = gradient_8bit();
+        let palette = original.palette.unwrap();
+        
+        let rgb16 = convert_to_16bit(&original).unwrap();
+        assert!(rgb16.is_16bit());
+        assert!(rgb16.header.flags.transparent);
+        
+        let data16 = rgb16.images[0].decompressed_data.as_ref().unwrap();
+        assert_eq!(data16.len(), 256 * 2);
+        let first = u16::from_le_bytes([data16[0], data16[1]]);
+        assert_eq!(first as u32, rgb16.header.transparent_color);
+        
+        let back = convert_to_8bit(&rgb16).unwrap();
+        assert!(back.is_8bit());
+        let back_palette = back.palette.unwrap();
+        let indices = back.images[0].decompressed_data.as_ref().unwrap();
+        
+        assert_eq!(indices[0], 0);
+        for (pos, &index) in indices.iter().enumerate().skip(1) {
+            assert_ne!(index, 0, "opaque pixel {} became transparent", pos);
+            let expected = palette[pos];
+            let actual = back_palette[index as usize];
+            for (e, a) in expected.iter().zip(actual.iter()) {
+                // RGB565 quantization plus 255-color median cut
+                let error = (*e as i32 - *a as i32).abs();
+                assert!(error <= 12, "pixel {}: {:?} became {:?}", pos, expected, actual);
+            }
+        }
+    }
+
+    #[test]
+    fn test_multi_frame_to_16bit_is_rejected() {
+        let mut sti_file = gradient_8bit();
+        let extra = sti_file.images[0].clone();
+        sti_file.images.push(extra);
+        assert!(convert_to_16bit(&sti_file).is_err());
+    }
+
+    #[test]
+    fn test_multi_frame_to_8bit_keeps_every_frame() {
+        let pixels = |values: &[u16]| values.iter().flat_map(|value| value.to_le_bytes()).collect::<Vec<u8>>();
+        let sti_file = crate::sti::builder::StiBuilder::new_rgb16((0xF800, 0x07E0, 0x001F))
+            .transparent_color(0x0000)
+            .add_frame(2, 1, pixels(&[0xF800, 0x0000]), 0, 0)
+            .add_frame(1, 2, pixels(&[0x07E0, 0x001F]), -4, 7)
+            .build()
+            .unwrap();
+        
+        let converted = convert_to_8bit(&sti_file).unwrap();
+        assert_eq!(converted.images.len(), 2);
+        assert_eq!((converted.header.width, converted.header.height), (0, 0));
+        let offsets = converted.images[1].header.as_ref().map(|sub_header| (sub_header.offset_x, sub_header.offset_y));
+        assert_eq!(offsets, Some((-4, 7)));
+        
+        // Both frames draw from the one palette
+        let palette = converted.palette.unwrap();
+        let colors = |index: usize| converted.images[index].decompressed_data.as_ref().unwrap()
+            .iter().map(|&pixel| palette[pixel as usize]).collect::<Vec<_>>();
+        assert_eq!(converted.images[0].decompressed_data.as_ref().unwrap()[1], 0);
+        assert_eq!(colors(0)[0], [255, 0, 0]);
+        assert_eq!(colors(1), vec![[0, 255, 0], [0, 0, 255]]);
+    }
+}
@@ -1,5 +1,37 @@
+use serde::{Deserialize, Serialize};
 use crate::sti::types::{StiError, StiResult};
 
+/// How a compressed stream ends its rows
+///
+/// Most producers close every row with 0x00; some leave the marker off the last row, since
+/// the decoder stops once the image is full anyway.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum RowEndMode {
+    #[default]
+    AlwaysMarker,
+    OmitLast,
+}
+
+/// What the decoder had to do beyond plain decoding to produce a full frame
+///
+/// All counts zero means a clean decode; anything else is a best-effort salvage of a damaged
+/// or nonstandard stream.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct DecompressReport {
+    pub consumed: usize,         // Input bytes the decoder used
+    pub trailing_bytes: usize,   // Input left over once every row was filled
+    pub padded_pixels: usize,    // Transparent pixels added because the stream ended early
+    pub truncated_pixels: usize, // Run pixels past the last row, dropped
+    pub wrapped_rows: usize,     // Rows a run overflowed instead of ending with a marker
+    pub row_end: RowEndMode,
+}
+
+impl DecompressReport {
+    pub fn is_clean(&self) -> bool {
+        self.trailing_bytes == 0 && self.padded_pixels == 0 && self.truncated_pixels == 0 && self.wrapped_rows == 0
+    }
+}
+
 /// ETRLE (Extended Transparent Run-Length Encoding) decompression
 /// 
 /// The ETRLE algorithm works as follows:
@@ -7,22 +39,67 @@ use crate::sti::types::{StiError, StiResult};
 /// - If the highest bit (bit 7) is 1: lower 7 bits = number of transparent pixels
 /// - If the highest bit (bit 7) is 0: lower 7 bits = number of non-transparent pixels to follow
 /// - Each row ends with a full zero byte (0x00)
+///
+/// Transparent runs expand to palette index 0 unless the decoder is built with
+/// `with_transparent`; that index can then never be stored as a literal pixel.
 pub struct EtrleDecoder {
     width: usize,
     height: usize,
+    transparent_index: u8,
+    row_end: RowEndMode,
 }
 
 impl EtrleDecoder {
     pub fn new(width: u16, height: u16) -> Self {
+        Self::with_transparent(width, height, 0)
+    }
+
+    /// Decoder whose transparent runs stand for `transparent_index` instead of 0
+    pub fn with_transparent(width: u16, height: u16, transparent_index: u8) -> Self {
         Self {
             width: width as usize,
             height: height as usize,
+            transparent_index,
+            row_end: RowEndMode::AlwaysMarker,
         }
     }
 
+    /// Encoder that ends rows the way `row_end` says, to reproduce a file's original bytes
+    pub fn with_row_end(mut self, row_end: RowEndMode) -> Self {
+        self.row_end = row_end;
+        self
+    }
+
     /// Decompress ETRLE compressed data
+    ///
+    /// Rows normally end with a 0x00 marker, but the game's decoder tolerates
+    /// streams that omit it: a run that would overflow the row wraps onto the
+    /// next row, and a stream that ends mid-row leaves the rest transparent.
     pub fn decompress(&self, compressed_data: &[u8]) -> StiResult<Vec<u8>> {
-        let mut decompressed = Vec::with_capacity(self.width * self.height);
+        self.decompress_counting(compressed_data).map(|(decompressed, _)| decompressed)
+    }
+
+    /// Decompress ETRLE data, also returning how many input bytes were consumed
+    ///
+    /// Decoding stops once every row is filled, so a count below the input
+    /// length means the stream carried trailing bytes.
+    pub fn decompress_counting(&self, compressed_data: &[u8]) -> StiResult<(Vec<u8>, usize)> {
+        self.decompress_with_report(compressed_data).map(|(decompressed, report)| (decompressed, report.consumed))
+    }
+
+    /// Decompress ETRLE data, also reporting which row-end convention the stream used
+    pub fn decompress_detecting(&self, compressed_data: &[u8]) -> StiResult<(Vec<u8>, RowEndMode)> {
+        self.decompress_with_report(compressed_data).map(|(decompressed, report)| (decompressed, report.row_end))
+    }
+
+    /// Decompress ETRLE data, recording any padding, truncation or row overflow it took
+    pub fn decompress_with_report(&self, compressed_data: &[u8]) -> StiResult<(Vec<u8>, DecompressReport)> {
+        let expected_size = self.width * self.height;
+        // One input byte yields at most a 127-pixel run or the rest of a row, so corrupt
+        // dimensions can't reserve more than the stream could decode to up front
+        let max_size = compressed_data.len().saturating_mul(self.width.max(127));
+        let mut decompressed = Vec::with_capacity(expected_size.min(max_size));
+        let mut report = DecompressReport::default();
         let mut input_pos = 0;
         let mut current_row = 0;
         let mut current_col = 0;
@@ -35,7 +112,7 @@ impl EtrleDecoder {
                 // End of row marker
                 // Fill remaining pixels in row with transparent (0)
                 while current_col < self.width {
-                    decompressed.push(0);
+                    decompressed.push(self.transparent_index);
                     current_col += 1;
                 }
                 current_row += 1;
@@ -43,53 +120,62 @@ impl EtrleDecoder {
                 continue;
             }
 
-            if (control_byte & 0x80) != 0 {
-                // Transparent pixels: highest bit is 1
-                let transparent_count = (control_byte & 0x7F) as usize;
-                
-                for _ in 0..transparent_count {
-                    if current_col >= self.width {
-                        return Err(StiError::Decompression(
-                            "Transparent pixels exceed row width".to_string()
-                        ));
+            let run_length = (control_byte & 0x7F) as usize;
+            let is_transparent = (control_byte & 0x80) != 0;
+            
+            if !is_transparent && input_pos + run_length > compressed_data.len() {
+                return Err(StiError::Decompression(
+                    "Not enough data for non-transparent pixels".to_string()
+                ));
+            }
+
+            for i in 0..run_length {
+                if current_col >= self.width {
+                    // Row is full but had no end marker: continue on the next row
+                    current_row += 1;
+                    current_col = 0;
+                    if current_row >= self.height {
+                        report.truncated_pixels += run_length - i;
+                        break;
                     }
-                    decompressed.push(0); // Transparent color is 0
-                    current_col += 1;
-                }
-            } else {
-                // Non-transparent pixels: highest bit is 0
-                let pixel_count = (control_byte & 0x7F) as usize;
-                
-                if input_pos + pixel_count > compressed_data.len() {
-                    return Err(StiError::Decompression(
-                        "Not enough data for non-transparent pixels".to_string()
-                    ));
+                    report.wrapped_rows += 1;
                 }
                 
-                for i in 0..pixel_count {
-                    if current_col >= self.width {
-                        return Err(StiError::Decompression(
-                            "Non-transparent pixels exceed row width".to_string()
-                        ));
-                    }
-                    decompressed.push(compressed_data[input_pos + i]);
-                    current_col += 1;
-                }
-                input_pos += pixel_count;
+                // Transparent pixels: highest bit is 1, otherwise literal pixels follow
+                decompressed.push(if is_transparent { self.transparent_index } else { compressed_data[input_pos + i] });
+                current_col += 1;
+            }
+            
+            if !is_transparent {
+                input_pos += run_length;
             }
         }
 
-        // Ensure we have the expected amount of data
-        let expected_size = self.width * self.height;
-        if decompressed.len() != expected_size {
-            // Pad with transparent pixels if needed
-            decompressed.resize(expected_size, 0);
-        }
+        // Only a last row filled to its final pixel and left unterminated counts as OmitLast;
+        // streams cut short anywhere else are damaged rather than a convention
+        report.row_end = if self.height > 0 && current_row == self.height - 1 && current_col == self.width {
+            RowEndMode::OmitLast
+        } else {
+            RowEndMode::AlwaysMarker
+        };
+
+        // A stream that ended mid-row (or early) is padded with transparent pixels;
+        // rows stay aligned because every pixel above was placed at its column
+        // Zero-width frames still wrap runs into their rows; those pixels are cut like any overflow
+        report.truncated_pixels += decompressed.len().saturating_sub(expected_size);
+        report.padded_pixels = expected_size.saturating_sub(decompressed.len());
+        decompressed.resize(expected_size, self.transparent_index);
+        report.consumed = input_pos;
+        report.trailing_bytes = compressed_data.len() - input_pos;
 
-        Ok(decompressed)
+        Ok((decompressed, report))
     }
 
     /// Compress pixel data using ETRLE algorithm
+    ///
+    /// Every run is as long as the format allows (127 pixels), which gives the smallest stream
+    /// the game can read: transparent pixels must stay runs, and each row must be spelled out to
+    /// its last pixel because the game's blitters skip to the next row assuming a full one.
     pub fn compress(&self, pixel_data: &[u8]) -> StiResult<Vec<u8>> {
         if pixel_data.len() != self.width * self.height {
             return Err(StiError::InvalidFormat(
@@ -107,21 +193,25 @@ impl EtrleDecoder {
             self.compress_row(row_data, &mut compressed)?;
             
             // Add end-of-row marker
-            compressed.push(0x00);
+            if row + 1 < self.height || self.row_end == RowEndMode::AlwaysMarker {
+                compressed.push(0x00);
+            }
         }
 
         Ok(compressed)
     }
 
+    // Each pass starts on the pixel at `pos` and takes it into the run, so every control byte
+    // counts 1-127 pixels: a zero count (0x00 or 0x80) would read as a row marker or a no-op
     fn compress_row(&self, row_data: &[u8], compressed: &mut Vec<u8>) -> StiResult<()> {
         let mut pos = 0;
         
         while pos < row_data.len() {
-            if row_data[pos] == 0 {
+            if row_data[pos] == self.transparent_index {
                 // Count consecutive transparent pixels
                 let mut transparent_count = 0;
                 while pos + transparent_count < row_data.len() && 
-                      row_data[pos + transparent_count] == 0 && 
+                      row_data[pos + transparent_count] == self.transparent_index && 
                       transparent_count < 127 {
                     transparent_count += 1;
                 }
@@ -133,7 +223,7 @@ impl EtrleDecoder {
                 // Count consecutive non-transparent pixels
                 let mut pixel_count = 0;
                 while pos + pixel_count < row_data.len() && 
-                      row_data[pos + pixel_count] != 0 && 
+                      row_data[pos + pixel_count] != self.transparent_index && 
                       pixel_count < 127 {
                     pixel_count += 1;
                 }
@@ -197,6 +287,65 @@ mod tests {
         assert_eq!(result, vec![0, 0, 0]);
     }
 
+    #[test]
+    fn test_etrle_missing_final_row_marker() {
+        let decoder = EtrleDecoder::new(3, 2);
+        // Second row has 2 transparent pixels and the stream ends without 0x00
+        let compressed = vec![0x03, 1, 2, 3, 0x00, 0x81, 0x01, 4];
+        
+        let result = decoder.decompress(&compressed).unwrap();
+        assert_eq!(result, vec![1, 2, 3, 0, 4, 0]);
+    }
+
+    #[test]
+    fn test_etrle_missing_marker_between_rows() {
+        let decoder = EtrleDecoder::new(3, 2);
+        // The first row is full and the next run starts without a marker
+        let compressed = vec![0x03, 1, 2, 3, 0x03, 4, 5, 6, 0x00];
+        
+        let result = decoder.decompress(&compressed).unwrap();
+        assert_eq!(result, vec![1, 2, 3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn test_decompress_report() {
+        let decoder = EtrleDecoder::new(3, 2);
+        let (_, clean) = decoder.decompress_with_report(&[0x03, 1, 2, 3, 0x00, 0x83, 0x00]).unwrap();
+        assert!(clean.is_clean());
+        assert_eq!(clean.consumed, 7);
+        
+        // Wraps into row two without a marker, then runs two pixels past the last row
+        let (_, overflowed) = decoder.decompress_with_report(&[0x04, 1, 2, 3, 4, 0x84, 0x00, 0x00]).unwrap();
+        assert_eq!(overflowed.wrapped_rows, 1);
+        assert_eq!(overflowed.truncated_pixels, 2);
+        assert_eq!(overflowed.trailing_bytes, 2);
+        
+        // Ends after one pixel of the second row
+        let (_, short) = decoder.decompress_with_report(&[0x83, 0x00, 0x01, 9]).unwrap();
+        assert_eq!(short.padded_pixels, 2);
+        assert!(!short.is_clean());
+        
+        // A tall frame with no stream at all is padded, not rejected
+        let (tall, report) = EtrleDecoder::new(1, 300).decompress_with_report(&[]).unwrap();
+        assert_eq!(tall, vec![0; 300]);
+        assert_eq!(report.padded_pixels, 300);
+        
+        let (empty, zero_width) = EtrleDecoder::new(0, 3).decompress_with_report(&[0x02, 5, 6]).unwrap();
+        assert!(empty.is_empty());
+        assert_eq!(zero_width.truncated_pixels, 2);
+        assert_eq!(zero_width.padded_pixels, 0);
+    }
+
+    #[test]
+    fn test_etrle_run_split_across_rows() {
+        let decoder = EtrleDecoder::new(2, 2);
+        // A single run longer than the row wraps instead of erroring
+        let compressed = vec![0x83, 0x01, 7];
+        
+        let result = decoder.decompress(&compressed).unwrap();
+        assert_eq!(result, vec![0, 0, 0, 7]);
+    }
+
     #[test]
     fn test_etrle_no_transparent() {
         let decoder = EtrleDecoder::new(3, 1);
@@ -205,4 +354,141 @@ mod tests {
         let result = decoder.decompress(&compressed).unwrap();
         assert_eq!(result, vec![1, 2, 3]);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_compress_tricky_rows() {
+        let encode = |width: u16, pixels: &[u8]| EtrleDecoder::new(width, 1).compress(pixels).unwrap();
+        
+        assert_eq!(encode(3, &[0, 0, 0]), vec![0x83, 0x00]);
+        assert_eq!(encode(3, &[7, 8, 9]), vec![0x03, 7, 8, 9, 0x00]);
+        assert_eq!(encode(4, &[0, 5, 0, 6]), vec![0x81, 0x01, 5, 0x81, 0x01, 6, 0x00]);
+        assert_eq!(encode(4, &[5, 0, 6, 0]), vec![0x01, 5, 0x81, 0x01, 6, 0x81, 0x00]);
+        
+        // Exactly one full run, then one byte past the 127-pixel limit
+        assert_eq!(encode(127, &[0; 127]), vec![0xFF, 0x00]);
+        assert_eq!(encode(128, &[0; 128]), vec![0xFF, 0x81, 0x00]);
+        
+        let mut expected = vec![0x7F];
+        expected.extend([4; 127]);
+        expected.push(0x00);
+        assert_eq!(encode(127, &[4; 127]), expected);
+        
+        let mut expected = vec![0x7F];
+        expected.extend([4; 127]);
+        expected.extend([0x01, 4, 0x00]);
+        assert_eq!(encode(128, &[4; 128]), expected);
+        
+        // A transparent run that ends on the 127 boundary is followed by the next run cleanly
+        let mut pixels = vec![0; 127];
+        pixels.push(3);
+        assert_eq!(encode(128, &pixels), vec![0xFF, 0x01, 3, 0x00]);
+    }
+
+    #[test]
+    fn test_compress_never_emits_empty_runs() {
+        // Two rows so row breaks get checked too: exactly one marker per row
+        let pixels: Vec<u8> = (0..512u32).map(|i| if i % 7 < 3 { 0 } else { (i % 5) as u8 + 1 }).collect();
+        let compressed = EtrleDecoder::new(256, 2).compress(&pixels).unwrap();
+        
+        let mut pos = 0;
+        let mut markers = 0;
+        while pos < compressed.len() {
+            let control = compressed[pos];
+            pos += 1;
+            if control == 0x00 {
+                markers += 1;
+                continue;
+            }
+            assert_ne!(control, 0x80, "empty transparent run at byte {}", pos - 1);
+            if control & 0x80 == 0 {
+                pos += control as usize;
+            }
+        }
+        assert_eq!(markers, 2);
+        assert_eq!(compressed.last(), Some(&0x00));
+    }
+
+    #[test]
+    fn test_custom_transparent_index() {
+        let decoder = EtrleDecoder::with_transparent(4, 1, 5);
+        let pixels = vec![5, 5, 0, 7];
+        
+        let compressed = decoder.compress(&pixels).unwrap();
+        // Index 5 is run-length encoded, index 0 is now an ordinary literal
+        assert_eq!(compressed, vec![0x82, 0x02, 0, 7, 0x00]);
+        assert_eq!(decoder.decompress(&compressed).unwrap(), pixels);
+    }
+
+    #[test]
+    fn test_custom_transparent_index_padding() {
+        let decoder = EtrleDecoder::with_transparent(3, 2, 9);
+        // Row markers and a stream that ends early both fill with the transparent index
+        let compressed = vec![0x01, 4, 0x00];
+        
+        assert_eq!(decoder.decompress(&compressed).unwrap(), vec![4, 9, 9, 9, 9, 9]);
+    }
+
+    #[test]
+    fn test_row_end_modes_round_trip() {
+        let pixels = vec![0, 1, 2, 3, 0, 0];
+        for row_end in [RowEndMode::AlwaysMarker, RowEndMode::OmitLast] {
+            let encoder = EtrleDecoder::new(3, 2).with_row_end(row_end);
+            let compressed = encoder.compress(&pixels).unwrap();
+            
+            let (decompressed, detected) = EtrleDecoder::new(3, 2).decompress_detecting(&compressed).unwrap();
+            assert_eq!(decompressed, pixels);
+            assert_eq!(detected, row_end);
+            assert_eq!(encoder.compress(&decompressed).unwrap(), compressed);
+        }
+        
+        let omitted = EtrleDecoder::new(3, 2).with_row_end(RowEndMode::OmitLast).compress(&pixels).unwrap();
+        assert_eq!(omitted, vec![0x81, 0x02, 1, 2, 0x00, 0x01, 3, 0x82]);
+    }
+
+    #[test]
+    fn test_short_stream_is_not_omit_last() {
+        // The last row stops mid-way: padded on decode, but not a convention to reproduce
+        let (_, detected) = EtrleDecoder::new(3, 2).decompress_detecting(&[0x83, 0x00, 0x01, 4]).unwrap();
+        assert_eq!(detected, RowEndMode::AlwaysMarker);
+    }
+
+    // Fewest bytes any row-complete encoding can use: a marker per row, one control byte per
+    // 127-pixel piece of every run, and the literal pixels themselves
+    fn minimal_size(pixels: &[u8], width: usize, transparent: u8) -> usize {
+        pixels.chunks(width).map(|row| {
+            let mut size = 1;
+            let mut start = 0;
+            while start < row.len() {
+                let is_transparent = row[start] == transparent;
+                let len = row[start..].iter().take_while(|&&pixel| (pixel == transparent) == is_transparent).count();
+                size += len.div_ceil(127) + if is_transparent { 0 } else { len };
+                start += len;
+            }
+            size
+        }).sum()
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn compress_round_trips_at_minimal_size(
+            width in 1u16..300,
+            height in 1u16..4,
+            runs in proptest::collection::vec((proptest::bool::ANY, 1usize..300, proptest::num::u8::ANY), 1..20),
+            transparent in proptest::num::u8::ANY,
+        ) {
+            // Long runs of either kind, so the 127-pixel limit and row breaks both get exercised
+            let mut pixels: Vec<u8> = runs.iter()
+                .flat_map(|&(is_transparent, len, value)| {
+                    let pixel = if is_transparent { transparent } else if value == transparent { value.wrapping_add(1) } else { value };
+                    std::iter::repeat_n(pixel, len)
+                })
+                .collect();
+            pixels.resize(width as usize * height as usize, transparent);
+            let decoder = EtrleDecoder::with_transparent(width, height, transparent);
+            
+            let compressed = decoder.compress(&pixels).unwrap();
+            proptest::prop_assert_eq!(decoder.decompress(&compressed).unwrap(), pixels.clone());
+            proptest::prop_assert_eq!(compressed.len(), minimal_size(&pixels, width as usize, transparent));
+        }
+    }
+}
@@ -0,0 +1,224 @@
+use std::io::Cursor;
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, Frame, Rgba, RgbaImage};
+use crate::sti::types::*;
+
+/// Common canvas that every frame of an animation is placed on
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AnimationCanvas {
+    pub origin_x: i32, // Smallest offset_x across all frames
+    pub origin_y: i32, // Smallest offset_y across all frames
+    pub width: u32,
+    pub height: u32,
+}
+
+impl AnimationCanvas {
+    /// Compute the canvas covering every frame at its sub-image offset
+    pub fn for_file(sti_file: &StiFile) -> StiResult<Self> {
+        if sti_file.images.is_empty() {
+            return Err(StiError::InvalidFormat("STI file has no images".to_string()));
+        }
+        
+        let mut min_x = i32::MAX;
+        let mut min_y = i32::MAX;
+        let mut max_x = i32::MIN;
+        let mut max_y = i32::MIN;
+        
+        for image in &sti_file.images {
+            let (offset_x, offset_y) = frame_offset(image);
+            min_x = min_x.min(offset_x);
+            min_y = min_y.min(offset_y);
+            max_x = max_x.max(offset_x + image.width as i32);
+            max_y = max_y.max(offset_y + image.height as i32);
+        }
+        
+        Ok(Self {
+            origin_x: min_x,
+            origin_y: min_y,
+            width: (max_x - min_x).max(1) as u32,
+            height: (max_y - min_y).max(1) as u32,
+        })
+    }
+    
+    /// Top-left position of a frame on this canvas
+    pub fn frame_position(&self, image: &StiImage) -> (u32, u32) {
+        let (offset_x, offset_y) = frame_offset(image);
+        ((offset_x - self.origin_x) as u32, (offset_y - self.origin_y) as u32)
+    }
+}
+
+fn frame_offset(image: &StiImage) -> (i32, i32) {
+    image.header.as_ref()
+        .map(|h| (h.offset_x as i32, h.offset_y as i32))
+        .unwrap_or((0, 0))
+}
+
+/// Draw one 8-bit frame onto a transparent canvas at its offset
+pub fn composite_frame(sti_file: &StiFile, image_index: usize, canvas: &AnimationCanvas) -> StiResult<RgbaImage> {
+    let palette = sti_file.palette.as_ref()
+        .ok_or_else(|| StiError::InvalidFormat("8-bit STI file requires palette".to_string()))?;
+    let image = sti_file.images.get(image_index)
+        .ok_or_else(|| StiError::InvalidFormat(format!("Image index {} out of bounds", image_index)))?;
+    let pixel_data = image.decompressed_data.as_ref()
+        .ok_or_else(|| StiError::Decompression(format!("Image {} is not decompressed", image_index)))?;
+    
+    let transparent_index = sti_file.header.transparent_color;
+    let (left, top) = canvas.frame_position(image);
+    
+    let mut frame = RgbaImage::new(canvas.width, canvas.height);
+    for (pos, &index) in pixel_data.iter().enumerate().take(image.width as usize * image.height as usize) {
+        if index as u32 == transparent_index {
+            continue;
+        }
+        let x = left + (pos % image.width as usize) as u32;
+        let y = top + (pos / image.width as usize) as u32;
+        let [r, g, b] = palette[index as usize];
+        frame.put_pixel(x, y, Rgba([r, g, b, 255]));
+    }
+    
+    Ok(frame)
+}
+
+/// Encode every frame of an 8-bit STI file as a looping animated GIF
+pub fn encode_animated_gif(sti_file: &StiFile, frame_delay_ms: u16) -> StiResult<Vec<u8>> {
+    if !sti_file.is_8bit() {
+        return Err(StiError::UnsupportedFormat(
+            "Animated GIF export requires an 8-bit multi-frame STI file".to_string()
+        ));
+    }
+    
+    let canvas = AnimationCanvas::for_file(sti_file)?;
+    let delay = Delay::from_numer_denom_ms(frame_delay_ms as u32, 1);
+    
+    let mut frames = Vec::with_capacity(sti_file.images.len());
+    for index in 0..sti_file.images.len() {
+        let buffer = composite_frame(sti_file, index, &canvas)?;
+        frames.push(Frame::from_parts(buffer, 0, 0, delay));
+    }
+    
+    let mut output = Vec::new();
+    {
+        let mut encoder = GifEncoder::new(Cursor::new(&mut output));
+        encoder.set_repeat(Repeat::Infinite)
+            .map_err(|e| StiError::Encoding(e.to_string()))?;
+        encoder.encode_frames(frames)
+            .map_err(|e| StiError::Encoding(e.to_string()))?;
+    }
+    
+    Ok(output)
+}
+
+/// A composed strip, the canvas every cell shares, and the top-left corner of every cell
+pub type AnimationStrip = (RgbaImage, AnimationCanvas, Vec<(u32, u32)>);
+
+/// Lay every frame of an 8-bit STI file out in one row (or column) of canvas-sized cells
+///
+/// Each frame sits at its offset inside its cell, so baselines line up across the strip.
+/// Returns the strip with the top-left corner of every cell.
+pub fn compose_strip(sti_file: &StiFile, horizontal: bool) -> StiResult<AnimationStrip> {
+    if !sti_file.is_8bit() {
+        return Err(StiError::UnsupportedFormat(
+            "Animation strips require an 8-bit STI file".to_string()
+        ));
+    }
+    
+    let canvas = AnimationCanvas::for_file(sti_file)?;
+    let count = sti_file.images.len() as u32;
+    let (strip_width, strip_height) = if horizontal {
+        (canvas.width * count, canvas.height)
+    } else {
+        (canvas.width, canvas.height * count)
+    };
+    
+    let mut strip = RgbaImage::new(strip_width, strip_height);
+    let mut cells = Vec::with_capacity(sti_file.images.len());
+    for index in 0..sti_file.images.len() {
+        let cell = if horizontal {
+            (index as u32 * canvas.width, 0)
+        } else {
+            (0, index as u32 * canvas.height)
+        };
+        let frame = composite_frame(sti_file, index, &canvas)?;
+        image::imageops::replace(&mut strip, &frame, cell.0 as i64, cell.1 as i64);
+        cells.push(cell);
+    }
+    
+    Ok((strip, canvas, cells))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::AnimationDecoder;
+    use image::codecs::gif::GifDecoder;
+    
+    fn two_frame_file() -> StiFile {
+        let mut sti_file = StiFile::new();
+        sti_file.header.flags.indexed = true;
+        sti_file.header.flags.etrle_compressed = true;
+        sti_file.header.num_images = 2;
+        
+        let mut palette = [[0u8; 3]; 256];
+        palette[1] = [255, 0, 0];
+        palette[2] = [0, 0, 255];
+        sti_file.palette = Some(palette);
+        
+        for (offset_x, color) in [(0i16, 1u8), (2, 2)] {
+            let mut image = StiImage::with_header(StiSubImageHeader {
+                data_offset: 0,
+                data_size: 0,
+                offset_x,
+                offset_y: 0,
+                height: 2,
+                width: 2,
+            });
+            image.decompressed_data = Some(vec![color, 0, 0, color]);
+            sti_file.images.push(image);
+        }
+        
+        sti_file
+    }
+    
+    #[test]
+    fn test_canvas_covers_offsets() {
+        let canvas = AnimationCanvas::for_file(&two_frame_file()).unwrap();
+        assert_eq!(canvas, AnimationCanvas { origin_x: 0, origin_y: 0, width: 4, height: 2 });
+    }
+    
+    #[test]
+    fn test_gif_round_trip() {
+        let gif_data = encode_animated_gif(&two_frame_file(), 100).unwrap();
+        
+        let decoder = GifDecoder::new(Cursor::new(gif_data)).unwrap();
+        let frames = decoder.into_frames().collect_frames().unwrap();
+        assert_eq!(frames.len(), 2);
+        
+        let first = frames[0].buffer();
+        assert_eq!(first.dimensions(), (4, 2));
+        // Transparent index stays transparent, drawn pixels are opaque
+        assert_eq!(first.get_pixel(1, 0)[3], 0);
+        assert_eq!(first.get_pixel(0, 0)[3], 255);
+        assert_eq!(first.get_pixel(2, 0)[3], 0);
+        
+        let second = frames[1].buffer();
+        assert_eq!(second.get_pixel(2, 0)[3], 255);
+    }
+    
+    #[test]
+    fn test_vertical_strip_keeps_offsets() {
+        let (strip, canvas, cells) = compose_strip(&two_frame_file(), false).unwrap();
+        assert_eq!(strip.dimensions(), (4, 4));
+        assert_eq!(cells, vec![(0, 0), (0, canvas.height)]);
+        
+        // The second frame is offset 2 pixels right inside its cell
+        assert_eq!(strip.get_pixel(0, 2)[3], 0);
+        assert_eq!(strip.get_pixel(2, 2)[3], 255);
+    }
+    
+    #[test]
+    fn test_gif_rejects_16bit() {
+        let mut sti_file = StiFile::new();
+        sti_file.header.flags.rgb = true;
+        assert!(encode_animated_gif(&sti_file, 100).is_err());
+    }
+}
@@ -1,6 +1,14 @@
 pub mod parser;
 pub mod etrle;
 pub mod types;
+pub mod gif;
+pub mod quantize;
+pub mod convert;
+pub mod palette;
+pub mod transform;
+pub mod slf;
+pub mod builder;
+pub mod ops;
 
 pub use parser::*;
 pub use types::*;
\ No newline at end of file
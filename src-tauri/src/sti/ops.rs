@@ -0,0 +1,1095 @@
+use std::fs;
+use std::path::Path;
+use image::GenericImageView;
+use serde::{Deserialize, Serialize};
+use crate::sti::builder::{encode_frames, update_header_sizes};
+use crate::sti::parser::StiParser;
+use crate::sti::types::*;
+
+// File and export operations behind the Tauri commands, usable without Tauri (CLI, tests)
+
+/// Read and parse an STI file from disk
+pub fn read_file(path: &Path) -> StiResult<StiFile> {
+    let data = fs::read(path)?;
+    StiParser::parse(&data)
+}
+
+/// Compress every frame, recompute the header sizes and serialize the file
+pub fn encode_file(sti_file: &mut StiFile) -> StiResult<Vec<u8>> {
+    encode_frames(sti_file, &[])?;
+    update_header_sizes(sti_file);
+    StiParser::write(sti_file)
+}
+
+/// Encode a file and write it to disk, returning the number of bytes written
+pub fn write_file(path: &Path, sti_file: &mut StiFile) -> StiResult<usize> {
+    let file_bytes = encode_file(sti_file)?;
+    fs::write(path, &file_bytes)?;
+    Ok(file_bytes.len())
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ExportOptions {
+    pub jpeg_quality: Option<u8>,        // 1-100, encoder default when unset
+    pub png_compression: Option<String>, // "default", "fast" or "best"
+    pub gamma: Option<f32>,              // out = 255 * (in / 255)^(1 / gamma), 1.0 leaves colors alone
+    pub brightness: Option<i16>,         // Added to every channel after gamma, clamped to 0-255
+    pub indexed: Option<bool>,           // PNG of an 8-bit file: keep the palette instead of expanding to RGB
+}
+
+/// Lookup table for the export-only gamma and brightness adjustment, None when nothing changes
+pub fn tone_table(options: &ExportOptions) -> StiResult<Option<[u8; 256]>> {
+    let gamma = options.gamma.unwrap_or(1.0);
+    let brightness = options.brightness.unwrap_or(0);
+    if !gamma.is_finite() || gamma <= 0.0 {
+        return Err(StiError::Encoding(format!("Gamma must be a positive number, got {}", gamma)));
+    }
+    if gamma == 1.0 && brightness == 0 {
+        return Ok(None);
+    }
+    
+    let mut table = [0u8; 256];
+    for (value, entry) in table.iter_mut().enumerate() {
+        let corrected = 255.0 * (value as f32 / 255.0).powf(1.0 / gamma);
+        *entry = (corrected.round() as i32 + brightness as i32).clamp(0, 255) as u8;
+    }
+    Ok(Some(table))
+}
+
+/// Decode a frame to RGBA, alpha is 0 only for the file's transparent color (for 16-bit
+/// files, only when the header's transparent flag is set)
+pub fn frame_to_rgba(sti_file: &StiFile, image_index: usize) -> StiResult<image::RgbaImage> {
+    let image = sti_file.images.get(image_index)
+        .ok_or_else(|| StiError::InvalidFormat(format!("Image index {} out of bounds", image_index)))?;
+    let pixel_data = image.decompressed_data.as_ref()
+        .ok_or_else(|| StiError::Decompression(format!("Image {} is not decompressed", image_index)))?;
+    let transparent_color = sti_file.header.transparent_color;
+    
+    // A misaligned buffer would otherwise surface as an opaque from_raw failure
+    let bytes_per_pixel = match sti_file.format() {
+        StiFormat::Indexed8 => 1,
+        StiFormat::Rgb16 => 2,
+        StiFormat::Unknown => return Err(StiError::UnsupportedFormat(format!(
+            "Unsupported or ambiguous STI format: indexed={} rgb={}, expected exactly one",
+            sti_file.header.flags.indexed, sti_file.header.flags.rgb
+        ))),
+    };
+    let expected_len = image.width as usize * image.height as usize * bytes_per_pixel;
+    if pixel_data.len() != expected_len {
+        return Err(StiError::InvalidFormat(format!(
+            "Image {} has {} bytes of pixel data but {}x{} at {} byte(s) per pixel needs {}",
+            image_index, pixel_data.len(), image.width, image.height, bytes_per_pixel, expected_len
+        )));
+    }
+    
+    let rgba_data = if bytes_per_pixel == 1 {
+        let palette = sti_file.palette.as_ref()
+            .ok_or_else(|| StiError::InvalidFormat("8-bit STI file requires palette".to_string()))?;
+        
+        // An alpha table, when the file has one, replaces the opaque default
+        let alpha_table = image.alpha.as_ref().filter(|alpha| alpha.len() == pixel_data.len());
+        let mut rgba = Vec::with_capacity(pixel_data.len() * 4);
+        for (i, &pixel) in pixel_data.iter().enumerate() {
+            let color = palette[pixel as usize];
+            rgba.extend_from_slice(&color);
+            let alpha = alpha_table.map_or(255, |alpha| alpha[i]);
+            rgba.push(if pixel as u32 == transparent_color { 0 } else { alpha });
+        }
+        rgba
+    } else {
+        // Convert 16-bit pixels to 24-bit RGB using the header masks; the length check above
+        // guarantees whole pixels, so no trailing byte is dropped
+        let keyed = sti_file.header.flags.transparent;
+        let mut rgba = Vec::with_capacity(pixel_data.len() / 2 * 4);
+        for chunk in pixel_data.chunks_exact(2) {
+            let value = u16::from_le_bytes([chunk[0], chunk[1]]);
+            rgba.extend_from_slice(&sti_file.header.decode_rgb16(value));
+            // The declared alpha channel wins over the opaque default, the transparent color over both
+            let alpha = sti_file.header.decode_alpha16(value).unwrap_or(255);
+            rgba.push(if keyed && value as u32 == transparent_color { 0 } else { alpha });
+        }
+        rgba
+    };
+    
+    image::RgbaImage::from_raw(image.width as u32, image.height as u32, rgba_data)
+        .ok_or_else(|| StiError::Encoding("Failed to create image from data".to_string()))
+}
+
+/// Write one frame of a parsed file to an image file in the given format
+pub fn export_frame(sti_file: &StiFile, image_index: usize, output_path: &str, format: &str, transparent: bool, options: &ExportOptions) -> StiResult<()> {
+    if image_index >= sti_file.images.len() {
+        return Err(StiError::InvalidFormat(format!("Image index {} out of bounds", image_index)));
+    }
+    
+    let image_format = match format.to_lowercase().as_str() {
+        "png" => image::ImageFormat::Png,
+        "jpeg" | "jpg" => image::ImageFormat::Jpeg,
+        "bmp" => image::ImageFormat::Bmp,
+        "tiff" | "tif" => image::ImageFormat::Tiff,
+        "tga" => image::ImageFormat::Tga,
+        "webp" => image::ImageFormat::WebP,
+        _ => return Err(StiError::UnsupportedFormat(format!(
+            "Unsupported export format: {} (supported: png, jpeg, bmp, tiff, tga, webp)", format
+        ))),
+    };
+    
+    if transparent && image_format == image::ImageFormat::Jpeg {
+        return Err(StiError::UnsupportedFormat(
+            "JPEG cannot store transparency, export as PNG, TGA or WebP instead".to_string()
+        ));
+    }
+    
+    if options.indexed.unwrap_or(false) && image_format == image::ImageFormat::Png && sti_file.is_8bit() {
+        return encode_indexed_png(sti_file, image_index, output_path, transparent, options);
+    }
+    
+    let mut rgba_img = frame_to_rgba(sti_file, image_index)?;
+    if let Some(table) = tone_table(options)? {
+        for pixel in rgba_img.pixels_mut() {
+            for channel in &mut pixel.0[..3] {
+                *channel = table[*channel as usize];
+            }
+        }
+    }
+    // An alpha channel (16-bit mask or 8-bit alpha table) is part of the image itself, keep it
+    // wherever the format can hold it
+    let declares_alpha = (sti_file.is_16bit() && sti_file.header.decode_alpha16(0).is_some())
+        || sti_file.images[image_index].alpha.is_some();
+    let img = if transparent || (declares_alpha && image_format != image::ImageFormat::Jpeg) {
+        image::DynamicImage::ImageRgba8(rgba_img)
+    } else {
+        image::DynamicImage::ImageRgb8(image::DynamicImage::ImageRgba8(rgba_img).to_rgb8())
+    };
+    
+    match image_format {
+        image::ImageFormat::Png => encode_png(&img, output_path, options, &frame_png_text(sti_file, image_index)),
+        image::ImageFormat::Jpeg if options.jpeg_quality.is_some() => encode_jpeg(&img, output_path, options),
+        image::ImageFormat::WebP => encode_webp(&img, output_path),
+        _ => img.save_with_format(output_path, image_format)
+            .map_err(|e| StiError::Encoding(format!("Failed to save image: {}", e))),
+    }
+}
+
+/// Highlight where two decoded frames differ: changed pixels in magenta, the rest of the
+/// left frame dimmed to a third of its brightness
+///
+/// Only the overlapping top-left region is compared; returns the diff and the changed pixel count.
+pub fn diff_rgba(left: &image::RgbaImage, right: &image::RgbaImage) -> (image::RgbaImage, usize) {
+    let width = left.width().min(right.width());
+    let height = left.height().min(right.height());
+    let mut changed = 0;
+    
+    let diff = image::RgbaImage::from_fn(width, height, |x, y| {
+        let a = left.get_pixel(x, y);
+        let b = right.get_pixel(x, y);
+        if a != b {
+            changed += 1;
+            image::Rgba([255, 0, 255, 255])
+        } else {
+            image::Rgba([a[0] / 3, a[1] / 3, a[2] / 3, a[3]])
+        }
+    });
+    (diff, changed)
+}
+
+// The image crate can't encode WebP without libwebp, so use the pure-Rust lossless encoder
+fn encode_webp(img: &image::DynamicImage, output_path: &str) -> StiResult<()> {
+    let file = fs::File::create(output_path)?;
+    let (width, height) = img.dimensions();
+    
+    let (data, color) = match img {
+        image::DynamicImage::ImageRgba8(rgba) => (rgba.as_raw().clone(), image_webp::ColorType::Rgba8),
+        other => (other.to_rgb8().into_raw(), image_webp::ColorType::Rgb8),
+    };
+    
+    image_webp::WebPEncoder::new(std::io::BufWriter::new(file))
+        .encode(&data, width, height, color)
+        .map_err(|e| StiError::Encoding(format!("Failed to save image: {}", e)))
+}
+
+// Encode JPEG with an explicit quality setting
+fn encode_jpeg(img: &image::DynamicImage, output_path: &str, options: &ExportOptions) -> StiResult<()> {
+    use image::ImageEncoder;
+    
+    let quality = options.jpeg_quality.unwrap_or(75);
+    if !(1..=100).contains(&quality) {
+        return Err(StiError::Encoding(format!("JPEG quality must be between 1 and 100, got {}", quality)));
+    }
+    
+    let file = fs::File::create(output_path)?;
+    let (width, height) = img.dimensions();
+    image::codecs::jpeg::JpegEncoder::new_with_quality(std::io::BufWriter::new(file), quality)
+        .write_image(img.as_bytes(), width, height, img.color())
+        .map_err(|e| StiError::Encoding(format!("Failed to save image: {}", e)))
+}
+
+// tEXt keys that carry a frame's position, so a re-import can put the frame back where it was
+const PNG_OFFSET_X_KEY: &str = "sti:offset_x";
+const PNG_OFFSET_Y_KEY: &str = "sti:offset_y";
+const PNG_FRAME_INDEX_KEY: &str = "sti:frame_index";
+
+fn frame_png_text(sti_file: &StiFile, image_index: usize) -> Vec<(&'static str, String)> {
+    let (offset_x, offset_y) = sti_file.images[image_index].header.as_ref()
+        .map_or((0, 0), |header| (header.offset_x, header.offset_y));
+    vec![
+        (PNG_OFFSET_X_KEY, offset_x.to_string()),
+        (PNG_OFFSET_Y_KEY, offset_y.to_string()),
+        (PNG_FRAME_INDEX_KEY, image_index.to_string()),
+    ]
+}
+
+/// Frame offsets stored by an earlier export, None for PNGs without them (or files that aren't PNG)
+pub fn read_png_frame_offsets(image_path: &str) -> Option<(i16, i16)> {
+    let file = fs::File::open(image_path).ok()?;
+    let reader = png::Decoder::new(std::io::BufReader::new(file)).read_info().ok()?;
+    let text = &reader.info().uncompressed_latin1_text;
+    let value = |key: &str| text.iter()
+        .find(|chunk| chunk.keyword == key)
+        .and_then(|chunk| chunk.text.trim().parse::<i16>().ok());
+    Some((value(PNG_OFFSET_X_KEY)?, value(PNG_OFFSET_Y_KEY)?))
+}
+
+fn png_compression(options: &ExportOptions) -> StiResult<png::Compression> {
+    match options.png_compression.as_deref().map(str::to_lowercase).as_deref() {
+        None | Some("default") => Ok(png::Compression::Default),
+        Some("fast") => Ok(png::Compression::Fast),
+        Some("best") => Ok(png::Compression::Best),
+        Some(other) => Err(StiError::UnsupportedFormat(format!(
+            "Unsupported PNG compression: {} (use default, fast or best)", other
+        ))),
+    }
+}
+
+// Truecolor PNG through the png crate, since the image crate can't attach text chunks
+fn encode_png(img: &image::DynamicImage, output_path: &str, options: &ExportOptions, text: &[(&str, String)]) -> StiResult<()> {
+    let compression = png_compression(options)?;
+    let (width, height) = img.dimensions();
+    let (color, data) = match img {
+        image::DynamicImage::ImageRgba8(rgba) => (png::ColorType::Rgba, rgba.as_raw().clone()),
+        other => (png::ColorType::Rgb, other.to_rgb8().into_raw()),
+    };
+    
+    let file = fs::File::create(output_path)?;
+    let mut encoder = png::Encoder::new(std::io::BufWriter::new(file), width, height);
+    encoder.set_color(color);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_compression(compression);
+    encoder.set_adaptive_filter(png::AdaptiveFilterType::Adaptive);
+    for (keyword, value) in text {
+        encoder.add_text_chunk(keyword.to_string(), value.clone())
+            .map_err(|e| StiError::Encoding(format!("Failed to save image: {}", e)))?;
+    }
+    
+    let mut writer = encoder.write_header()
+        .map_err(|e| StiError::Encoding(format!("Failed to save image: {}", e)))?;
+    writer.write_image_data(&data)
+        .map_err(|e| StiError::Encoding(format!("Failed to save image: {}", e)))
+}
+
+// Write an 8-bit frame as a palette PNG: the STI palette becomes PLTE and, for transparent
+// exports, the transparent index gets alpha 0 through tRNS. The image crate only writes truecolor
+fn encode_indexed_png(sti_file: &StiFile, image_index: usize, output_path: &str, transparent: bool, options: &ExportOptions) -> StiResult<()> {
+    let image = &sti_file.images[image_index];
+    let pixel_data = image.decompressed_data.as_ref()
+        .ok_or_else(|| StiError::Decompression(format!("Image {} is not decompressed", image_index)))?;
+    if pixel_data.len() != image.width as usize * image.height as usize {
+        return Err(StiError::InvalidFormat(format!(
+            "Image {} has {} bytes of pixel data but {}x{} needs {}",
+            image_index, pixel_data.len(), image.width, image.height, image.width as usize * image.height as usize
+        )));
+    }
+    let palette = sti_file.palette.as_ref()
+        .ok_or_else(|| StiError::InvalidFormat("8-bit STI file requires palette".to_string()))?;
+    
+    let table = tone_table(options)?;
+    let plte: Vec<u8> = palette.iter()
+        .flat_map(|color| color.iter().map(|&channel| table.map_or(channel, |table| table[channel as usize])))
+        .collect();
+    
+    let compression = png_compression(options)?;
+    
+    let file = fs::File::create(output_path)?;
+    let mut encoder = png::Encoder::new(std::io::BufWriter::new(file), image.width as u32, image.height as u32);
+    encoder.set_color(png::ColorType::Indexed);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_compression(compression);
+    encoder.set_palette(plte);
+    for (keyword, value) in frame_png_text(sti_file, image_index) {
+        encoder.add_text_chunk(keyword.to_string(), value)
+            .map_err(|e| StiError::Encoding(format!("Failed to save image: {}", e)))?;
+    }
+    if transparent {
+        // tRNS only needs entries up to the last non-opaque index
+        let transparent_index = sti_file.header.transparent_color.min(255) as usize;
+        let mut trns = vec![255u8; transparent_index + 1];
+        trns[transparent_index] = 0;
+        encoder.set_trns(trns);
+    }
+    
+    let mut writer = encoder.write_header()
+        .map_err(|e| StiError::Encoding(format!("Failed to save image: {}", e)))?;
+    writer.write_image_data(pixel_data)
+        .map_err(|e| StiError::Encoding(format!("Failed to save image: {}", e)))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EditableImage {
+    pub width: u16,
+    pub height: u16,
+    pub data: Vec<u8>, // Palette indices for 8-bit, RGB565 bytes for 16-bit
+    #[serde(default)]
+    pub offset_x: i16, // Sub-image placement, 0 for older frontend payloads
+    #[serde(default)]
+    pub offset_y: i16,
+    #[serde(default)]
+    pub alpha: Option<Vec<u8>>, // 8-bit files with an alpha table: one alpha byte per pixel
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EditableStiFile {
+    pub file_path: String,
+    pub is_8bit: bool,
+    pub is_16bit: bool,
+    pub palette: Option<Vec<[u8; 3]>>,
+    pub images: Vec<EditableImage>,
+    pub transparent_color: u32,
+    pub flags: u32,
+    #[serde(default)]
+    pub animation_data: Vec<StiAnimationData>, // One 16-byte record per image when animated
+    #[serde(default)]
+    pub app_data: Vec<u8>, // Raw application data block, kept verbatim when it isn't animation records
+    #[serde(default)]
+    pub main_header_dimensions: Option<bool>, // 8-bit single-image files: size also in the main header; None writes it
+    #[serde(default)]
+    pub palette_colors: Option<u32>, // 8-bit files: palette entries stored, 1-256; None writes all 256
+    #[serde(default)]
+    pub rgb_masks: Option<(u32, u32, u32)>, // 16-bit files: red, green and blue masks; None packs as RGB565
+}
+
+impl EditableStiFile {
+    /// Check the invariants a save relies on, so a bad payload fails instead of writing a corrupt file
+    pub fn validate(&self) -> StiResult<()> {
+        if self.is_8bit == self.is_16bit {
+            return Err(StiError::InvalidFormat("Exactly one of is_8bit and is_16bit must be set".to_string()));
+        }
+        
+        if self.is_8bit {
+            match &self.palette {
+                None => return Err(StiError::InvalidFormat("8-bit STI file requires a palette".to_string())),
+                Some(palette) if palette.len() < 256 => {
+                    return Err(StiError::InvalidFormat(format!("8-bit palette has {} colors, expected 256", palette.len())));
+                }
+                Some(_) => {}
+            }
+        }
+        
+        let bytes_per_pixel = if self.is_16bit { 2 } else { 1 };
+        for (index, image) in self.images.iter().enumerate() {
+            let pixel_count = image.width as usize * image.height as usize;
+            let expected = pixel_count * bytes_per_pixel;
+            // 16-bit frames may also arrive as RGB888, packed when the file is rebuilt
+            let rgb888 = self.is_16bit && image.data.len() == pixel_count * 3;
+            if image.data.len() != expected && !rgb888 {
+                return Err(StiError::InvalidFormat(format!(
+                    "Image {} has {} bytes of data, expected {} for {}x{}",
+                    index, image.data.len(), expected, image.width, image.height
+                )));
+            }
+            
+            // A shortened palette has to cover every index, or the saved file won't read back
+            if let Some(colors) = self.palette_colors.filter(|_| self.is_8bit) {
+                if let Some(&pixel) = image.data.iter().find(|&&pixel| pixel as u32 >= colors) {
+                    return Err(StiError::InvalidFormat(format!(
+                        "Image {} uses palette index {}, but the palette has {} colors", index, pixel, colors
+                    )));
+                }
+            }
+        }
+        
+        Ok(())
+    }
+}
+
+// Single-image 8-bit files come both ways: size repeated in the main header, or left at 0 there
+// as multi-image files do. Recorded so a save writes the header back the way it was read.
+fn main_header_dimensions(sti_file: &StiFile) -> Option<bool> {
+    (sti_file.is_8bit() && sti_file.images.len() == 1)
+        .then_some(sti_file.header.width != 0 || sti_file.header.height != 0)
+}
+
+/// Unpack a parsed file into the form the editor works on
+pub fn editable_from_sti(sti_file: &StiFile) -> StiResult<EditableStiFile> {
+    let mut editable_images = Vec::new();
+    
+    for image in &sti_file.images {
+        let pixel_data = image.decompressed_data.as_ref()
+            .ok_or_else(|| StiError::Decompression("Image data not decompressed".to_string()))?;
+        
+        let (offset_x, offset_y) = image.header.as_ref()
+            .map(|h| (h.offset_x, h.offset_y))
+            .unwrap_or((0, 0));
+        
+        editable_images.push(EditableImage {
+            width: image.width,
+            height: image.height,
+            data: pixel_data.clone(),
+            offset_x,
+            offset_y,
+            alpha: image.alpha.clone(),
+        });
+    }
+    
+    Ok(EditableStiFile {
+        file_path: String::new(), // Set by the caller, which knows where the file came from
+        is_8bit: sti_file.is_8bit(),
+        is_16bit: sti_file.is_16bit(),
+        palette: sti_file.palette.map(|p| p.to_vec()),
+        images: editable_images,
+        transparent_color: sti_file.header.transparent_color,
+        flags: sti_file.header.flags_value(),
+        animation_data: sti_file.animation_data.clone(),
+        app_data: sti_file.app_data.clone(),
+        main_header_dimensions: main_header_dimensions(sti_file),
+        palette_colors: sti_file.is_8bit().then(|| sti_file.header.palette_entries() as u32),
+        rgb_masks: sti_file.is_16bit().then(|| sti_file.header.rgb16_masks()),
+    })
+}
+
+/// Rebuild a StiFile from an editable one, frames left uncompressed
+pub fn editable_to_sti(editable: &EditableStiFile) -> StiResult<StiFile> {
+    let mut sti_file = StiFile::new();
+    
+    // Convert header
+    let mut header = StiHeader {
+        signature: [b'S', b'T', b'C', b'I'],
+        transparent_color: editable.transparent_color,
+        flags: StiFlags::from(editable.flags),
+        raw_flags: editable.flags,
+        ..StiHeader::default()
+    };
+    
+    if editable.is_8bit {
+        header.flags.indexed = true;
+        header.flags.rgb = false;
+        header.flags.etrle_compressed = true; // Enable ETRLE compression for 8-bit
+        header.palette_colors = editable.palette_colors.filter(|colors| (1..=256).contains(colors)).unwrap_or(256);
+        header.num_images = editable.images.len() as u16;
+        header.color_depth = 8;
+        
+        // For 8-bit multi-image files, DON'T set width/height in main header
+        // These are stored in individual sub-image headers
+        if header.num_images == 1 && editable.main_header_dimensions != Some(false) {
+            // Single image 8-bit files can have width/height in main header
+            if let Some(first_image) = editable.images.first() {
+                header.width = first_image.width;
+                header.height = first_image.height;
+            }
+        } else {
+            // Multi-image files: width/height should be 0 in main header
+            header.width = 0;
+            header.height = 0;
+        }
+    } else if editable.is_16bit {
+        header.flags.rgb = true;
+        header.flags.indexed = false;
+        header.color_depth = 16;
+        header.num_images = editable.images.len() as u16;
+        if let Some((red_mask, green_mask, blue_mask)) = editable.rgb_masks {
+            header.red_mask = red_mask;
+            header.green_mask = green_mask;
+            header.blue_mask = blue_mask;
+            header.red_depth = red_mask.count_ones() as u8;
+            header.green_depth = green_mask.count_ones() as u8;
+            header.blue_depth = blue_mask.count_ones() as u8;
+        }
+        
+        if let Some(first_image) = editable.images.first() {
+            header.width = first_image.width;
+            header.height = first_image.height;
+        }
+    }
+    
+    sti_file.header = header;
+    
+    // Convert palette
+    if editable.is_8bit {
+        if let Some(palette_data) = &editable.palette {
+            let mut palette = [[0u8; 3]; 256];
+            for (i, color) in palette_data.iter().enumerate() {
+                if i < 256 {
+                    palette[i] = *color;
+                }
+            }
+            sti_file.palette = Some(palette);
+        } else {
+            return Err(StiError::InvalidFormat("8-bit STI file requires a palette".to_string()));
+        }
+    }
+    
+    // Convert images
+    for editable_image in editable.images.iter() {
+        let mut image = if editable.is_8bit || editable.images.len() > 1 {
+            // Create sub-image header for 8-bit and multi-image 16-bit files
+            let sub_header = StiSubImageHeader {
+                data_offset: 0, // Will be set properly when the frames are encoded
+                data_size: 0, // Will be set after compression
+                offset_x: editable_image.offset_x,
+                offset_y: editable_image.offset_y,
+                height: editable_image.height,
+                width: editable_image.width,
+            };
+            
+            StiImage::with_header(sub_header)
+        } else {
+            StiImage::new(editable_image.width, editable_image.height)
+        };
+        
+        // 16-bit frames may arrive as 24-bit RGB and are packed using the header masks
+        let pixel_count = editable_image.width as usize * editable_image.height as usize;
+        let pixel_data = if editable.is_16bit && editable_image.data.len() == pixel_count * 3 {
+            let mut packed = Vec::with_capacity(pixel_count * 2);
+            for rgb in editable_image.data.chunks_exact(3) {
+                let value = sti_file.header.encode_rgb16([rgb[0], rgb[1], rgb[2]]);
+                packed.extend_from_slice(&value.to_le_bytes());
+            }
+            packed
+        } else {
+            editable_image.data.clone()
+        };
+        
+        // Set decompressed data (will be compressed later if needed)
+        image.decompressed_data = Some(pixel_data);
+        image.width = editable_image.width;
+        image.height = editable_image.height;
+        if editable.is_8bit {
+            image.alpha = editable_image.alpha.clone();
+        }
+        
+        sti_file.images.push(image);
+    }
+    
+    // The alpha table only goes out when every frame has one, so the flag must not outlive it
+    if sti_file.header.flags.alpha && editable.is_8bit {
+        sti_file.header.flags.alpha = sti_file.images.iter().all(|image| {
+            image.alpha.as_ref().is_some_and(|alpha| alpha.len() == image.width as usize * image.height as usize)
+        });
+    }
+    
+    // Restore animation records, reconciling the count with added or removed images
+    if editable.is_8bit && !editable.animation_data.is_empty() {
+        let mut animation_data = editable.animation_data.clone();
+        animation_data.resize(sti_file.images.len(), StiAnimationData::default());
+        sti_file.animation_data = animation_data;
+    }
+    if editable.is_8bit {
+        sti_file.app_data = editable.app_data.clone();
+    }
+    
+    Ok(sti_file)
+}
+
+// Carry over the original header fields an editable file doesn't describe, and the stored
+// bytes of frames whose pixels are unchanged; returns which frames can skip recompression
+fn preserve_unchanged_data(sti_file: &mut StiFile, original: &StiFile) -> Vec<bool> {
+    if sti_file.is_8bit() != original.is_8bit() || sti_file.is_16bit() != original.is_16bit() {
+        return Vec::new();
+    }
+    
+    let derived = sti_file.header.clone();
+    sti_file.header = original.header.clone();
+    sti_file.header.transparent_color = derived.transparent_color;
+    sti_file.header.flags = derived.flags;
+    sti_file.header.width = derived.width;
+    sti_file.header.height = derived.height;
+    sti_file.header.palette_colors = derived.palette_colors;
+    sti_file.header.num_images = derived.num_images;
+    sti_file.header.color_depth = derived.color_depth;
+    // RGB888 frames were packed with the editable file's masks, so those are the ones that describe them
+    if derived.red_mask != 0 || derived.green_mask != 0 || derived.blue_mask != 0 {
+        sti_file.header.red_mask = derived.red_mask;
+        sti_file.header.green_mask = derived.green_mask;
+        sti_file.header.blue_mask = derived.blue_mask;
+        sti_file.header.red_depth = derived.red_depth;
+        sti_file.header.green_depth = derived.green_depth;
+        sti_file.header.blue_depth = derived.blue_depth;
+    }
+    
+    // Stored bytes only stay valid while they decode the same way
+    let same_encoding = derived.flags.etrle_compressed == original.header.flags.etrle_compressed
+        && derived.flags.zlib_compressed == original.header.flags.zlib_compressed
+        && derived.etrle_transparent_index() == original.header.etrle_transparent_index();
+    
+    sti_file.images.iter_mut().enumerate()
+        .map(|(index, image)| {
+            // Re-encoded frames still end their rows the way the file on disk did
+            if let Some(previous) = original.images.get(index) {
+                image.etrle_row_end = previous.etrle_row_end;
+            }
+            let unchanged = match original.images.get(index) {
+                Some(previous) => same_encoding
+                    && !previous.raw_data.is_empty()
+                    && (previous.width, previous.height) == (image.width, image.height)
+                    && previous.decompressed_data.is_some()
+                    && previous.decompressed_data == image.decompressed_data,
+                None => false,
+            };
+            if unchanged {
+                image.raw_data = original.images[index].raw_data.clone();
+            }
+            unchanged
+        })
+        .collect()
+}
+
+/// Serialize an editable file, recompressing only what differs from `original`
+pub fn encode_editable(editable_sti: &EditableStiFile, original: Option<&StiFile>) -> StiResult<Vec<u8>> {
+    // Convert EditableStiFile back to StiFile format
+    let mut sti_file = editable_to_sti(editable_sti)?;
+    
+    let clean = match original {
+        Some(original) => preserve_unchanged_data(&mut sti_file, original),
+        None => Vec::new(),
+    };
+    
+    log::debug!(
+        "Encoding {} frames, {} reused unchanged from the file on disk",
+        sti_file.images.len(),
+        clean.iter().filter(|&&is_clean| is_clean).count()
+    );
+    
+    // Compress image data using ETRLE if needed
+    encode_frames(&mut sti_file, &clean)?;
+    
+    // Calculate and update header sizes
+    update_header_sizes(&mut sti_file);
+    log::debug!(
+        "Header sizes: compressed {} / original {} bytes, app data {} bytes",
+        sti_file.header.compressed_size, sti_file.header.original_size, sti_file.header.app_data_size
+    );
+    
+    // An unmodified file keeps its declared sizes, even ones that disagree with the data
+    if let Some(original) = original {
+        let frames_unchanged = clean.len() == original.images.len()
+            && clean.iter().all(|&is_clean| is_clean);
+        let unmodified = frames_unchanged
+            && sti_file.animation_data.len() == original.animation_data.len();
+        if frames_unchanged && original.stale_compressed_size {
+            // The frames it described are untouched, so a bogus value written by another tool stays
+            sti_file.header.compressed_size = original.header.compressed_size;
+        }
+        if unmodified {
+            sti_file.header.original_size = original.header.original_size;
+            sti_file.header.compressed_size = original.header.compressed_size;
+            sti_file.header.app_data_size = original.header.app_data_size;
+        }
+    }
+    
+    // Write the STI file to bytes
+    StiParser::write(&sti_file)
+}
+
+/// Save an editable file over `path`, reusing the stored bytes of frames that match the
+/// file currently there; returns the number of bytes written
+pub fn write_editable(path: &Path, editable_sti: &EditableStiFile) -> StiResult<usize> {
+    let original = fs::read(path).ok()
+        .and_then(|data| StiParser::parse(&data).ok());
+    let file_bytes = encode_editable(editable_sti, original.as_ref())?;
+    fs::write(path, &file_bytes)?;
+    Ok(file_bytes.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_write_and_read_file() {
+        let mut sti_file = crate::sti::builder::StiBuilder::new_indexed([[4u8; 3]; 256])
+            .add_frame(2, 1, vec![3, 0], 1, -1)
+            .build()
+            .unwrap();
+        
+        let path = std::env::temp_dir().join(format!("sti_ops_test_{}.sti", std::process::id()));
+        let written = write_file(&path, &mut sti_file).unwrap();
+        let read_back = read_file(&path);
+        let _ = fs::remove_file(&path);
+        
+        let read_back = read_back.unwrap();
+        assert_eq!(written, encode_file(&mut sti_file).unwrap().len());
+        assert_eq!(read_back.images[0].decompressed_data, Some(vec![3, 0]));
+        assert!(matches!(read_file(&path), Err(StiError::Io(_))));
+    }
+    
+    #[test]
+    fn test_zlib_checkerboard_round_trip() {
+        // Alternating pixels make ETRLE output larger than width * height
+        let pixels: Vec<u8> = (0..256).map(|i| if (i + i / 16) % 2 == 0 { 0 } else { 9 }).collect();
+        let mut sti_file = crate::sti::builder::StiBuilder::new_indexed([[0u8; 3]; 256])
+            .add_frame(16, 16, pixels.clone(), 0, 0)
+            .build()
+            .unwrap();
+        sti_file.header.flags.zlib_compressed = true;
+        
+        let data = encode_file(&mut sti_file).unwrap();
+        let parsed = StiParser::parse(&data).unwrap();
+        assert_eq!(parsed.images[0].decompressed_data, Some(pixels));
+    }
+    
+    #[test]
+    fn test_export_tga_round_trip() {
+        let mut sti_file = StiFile::new();
+        sti_file.header.flags.indexed = true;
+        sti_file.header.num_images = 1;
+        let mut palette = [[0u8; 3]; 256];
+        palette[1] = [255, 0, 0];
+        palette[2] = [0, 128, 255];
+        sti_file.palette = Some(palette);
+        
+        let mut image = StiImage::new(2, 2);
+        image.decompressed_data = Some(vec![0, 1, 2, 1]);
+        sti_file.images.push(image);
+        
+        let output_path = std::env::temp_dir().join(format!("sti_export_test_{}.tga", std::process::id()));
+        let output = output_path.to_string_lossy().to_string();
+        export_frame(&sti_file, 0, &output, "tga", true, &ExportOptions::default()).unwrap();
+        
+        let read_back = image::open(&output_path).unwrap().to_rgba8();
+        let _ = fs::remove_file(&output_path);
+        
+        assert_eq!(read_back.dimensions(), (2, 2));
+        assert_eq!(read_back.get_pixel(0, 0)[3], 0);
+        assert_eq!(read_back.get_pixel(1, 0).0, [255, 0, 0, 255]);
+        assert_eq!(read_back.get_pixel(0, 1).0, [0, 128, 255, 255]);
+    }
+    
+    #[test]
+    fn test_rgb16_export_rejects_wrong_length() {
+        let mut sti_file = crate::sti::builder::StiBuilder::new_rgb16((0xF800, 0x07E0, 0x001F))
+            .add_frame(2, 1, vec![0x1F, 0x00, 0xE0, 0x07], 0, 0)
+            .build()
+            .unwrap();
+        // One byte short of the second pixel
+        sti_file.images[0].decompressed_data = Some(vec![0x1F, 0x00, 0xE0]);
+        
+        let output_path = std::env::temp_dir().join(format!("sti_odd_length_test_{}.png", std::process::id()));
+        let output = output_path.to_string_lossy().to_string();
+        let result = export_frame(&sti_file, 0, &output, "png", false, &ExportOptions::default());
+        assert!(matches!(result, Err(StiError::InvalidFormat(message)) if message.contains("3 bytes of pixel data")));
+        assert!(!output_path.exists());
+        
+        sti_file.images[0].decompressed_data = Some(vec![0x1F, 0x00, 0xE0, 0x07]);
+        assert_eq!(frame_to_rgba(&sti_file, 0).unwrap().get_pixel(1, 0).0, [0, 255, 0, 255]);
+    }
+    
+    #[test]
+    fn test_rgb16_transparency_needs_flag() {
+        let pixels = [0x001Fu16, 0xF800].iter().flat_map(|value| value.to_le_bytes()).collect::<Vec<u8>>();
+        let mut sti_file = crate::sti::builder::StiBuilder::new_rgb16((0xF800, 0x07E0, 0x001F))
+            .transparent_color(0x001F)
+            .add_frame(2, 1, pixels, 0, 0)
+            .build()
+            .unwrap();
+        assert_eq!(frame_to_rgba(&sti_file, 0).unwrap().get_pixel(0, 0).0[3], 0);
+        
+        // Without the flag the stored key is just a color
+        sti_file.header.flags.transparent = false;
+        assert_eq!(frame_to_rgba(&sti_file, 0).unwrap().get_pixel(0, 0).0, [0, 0, 255, 255]);
+    }
+    
+    #[test]
+    fn test_export_indexed_png_keeps_palette() {
+        let mut sti_file = StiFile::new();
+        sti_file.header.flags.indexed = true;
+        sti_file.header.num_images = 1;
+        let mut palette = [[0u8; 3]; 256];
+        for (index, color) in palette.iter_mut().enumerate() {
+            *color = [index as u8, 255 - index as u8, 7];
+        }
+        sti_file.palette = Some(palette);
+        let mut image = StiImage::new(2, 2);
+        image.decompressed_data = Some(vec![0, 1, 2, 255]);
+        sti_file.images.push(image);
+        
+        let output_path = std::env::temp_dir().join(format!("sti_indexed_test_{}.png", std::process::id()));
+        let output = output_path.to_string_lossy().to_string();
+        let options = ExportOptions { indexed: Some(true), ..ExportOptions::default() };
+        export_frame(&sti_file, 0, &output, "png", true, &options).unwrap();
+        
+        let mut decoder = png::Decoder::new(fs::File::open(&output_path).unwrap());
+        decoder.set_transformations(png::Transformations::IDENTITY);
+        let mut reader = decoder.read_info().unwrap();
+        let info = reader.info();
+        assert_eq!(info.color_type, png::ColorType::Indexed);
+        let expected: Vec<u8> = palette.iter().flatten().copied().collect();
+        assert_eq!(info.palette.as_deref(), Some(expected.as_slice()));
+        assert_eq!(info.trns.as_deref(), Some(&[0u8][..]));
+        
+        let mut pixels = vec![0u8; reader.output_buffer_size()];
+        reader.next_frame(&mut pixels).unwrap();
+        let _ = fs::remove_file(&output_path);
+        assert_eq!(&pixels[..4], &[0, 1, 2, 255]);
+    }
+    
+    #[test]
+    fn test_png_export_keeps_frame_offsets() {
+        let sti_file = crate::sti::builder::StiBuilder::new_indexed([[9u8; 3]; 256])
+            .add_frame(2, 1, vec![1, 2], -12, 30)
+            .build()
+            .unwrap();
+        
+        for indexed in [false, true] {
+            let output_path = std::env::temp_dir().join(format!("sti_offsets_test_{}_{}.png", std::process::id(), indexed));
+            let output = output_path.to_string_lossy().to_string();
+            let options = ExportOptions { indexed: Some(indexed), ..ExportOptions::default() };
+            export_frame(&sti_file, 0, &output, "png", false, &options).unwrap();
+            
+            let offsets = read_png_frame_offsets(&output);
+            let _ = fs::remove_file(&output_path);
+            assert_eq!(offsets, Some((-12, 30)));
+        }
+    }
+    
+    #[test]
+    fn test_diff_rgba() {
+        let left = image::RgbaImage::from_pixel(3, 2, image::Rgba([90, 60, 30, 255]));
+        let mut right = image::RgbaImage::from_pixel(2, 2, image::Rgba([90, 60, 30, 255]));
+        right.put_pixel(1, 0, image::Rgba([0, 0, 0, 255]));
+        
+        let (diff, changed) = diff_rgba(&left, &right);
+        assert_eq!(diff.dimensions(), (2, 2));
+        assert_eq!(changed, 1);
+        assert_eq!(diff.get_pixel(1, 0), &image::Rgba([255, 0, 255, 255]));
+        assert_eq!(diff.get_pixel(0, 0), &image::Rgba([30, 20, 10, 255]));
+    }
+    
+    #[test]
+    fn test_tone_table() {
+        assert!(tone_table(&ExportOptions::default()).unwrap().is_none());
+        assert!(tone_table(&ExportOptions { gamma: Some(0.0), ..ExportOptions::default() }).is_err());
+        
+        let brighter = tone_table(&ExportOptions { gamma: Some(2.2), ..ExportOptions::default() }).unwrap().unwrap();
+        assert_eq!((brighter[0], brighter[255]), (0, 255));
+        assert!(brighter[64] > 64);
+        
+        let offset = tone_table(&ExportOptions { brightness: Some(-20), ..ExportOptions::default() }).unwrap().unwrap();
+        assert_eq!((offset[10], offset[100], offset[255]), (0, 80, 235));
+    }
+    
+    #[test]
+    fn test_unmodified_save_is_byte_identical() {
+        let mut sti_file = StiFile::new();
+        sti_file.header.flags.indexed = true;
+        sti_file.header.flags.etrle_compressed = true;
+        sti_file.header.palette_colors = 256;
+        sti_file.header.num_images = 2;
+        sti_file.header.color_depth = 8;
+        sti_file.header.red_depth = 8;
+        sti_file.header.green_depth = 8;
+        sti_file.header.blue_depth = 8;
+        sti_file.palette = Some([[7u8; 3]; 256]);
+        
+        // Split literal runs the encoder would merge, so recompressing would change the bytes
+        let frames: [Vec<u8>; 2] = [
+            vec![0x01, 1, 0x01, 2, 0x00, 0x02, 3, 4, 0x00],
+            vec![0x81, 0x01, 5, 0x00, 0x82, 0x00],
+        ];
+        let mut data_offset = 0;
+        for raw_data in frames {
+            let mut image = StiImage::with_header(StiSubImageHeader {
+                data_offset,
+                data_size: raw_data.len() as u32,
+                offset_x: -3,
+                offset_y: 2,
+                height: 2,
+                width: 2,
+            });
+            data_offset += raw_data.len() as u32;
+            image.raw_data = raw_data;
+            sti_file.images.push(image);
+        }
+        sti_file.header.compressed_size = data_offset;
+        sti_file.header.original_size = 8;
+        let bytes = StiParser::write(&sti_file).unwrap();
+        
+        let original = StiParser::parse(&bytes).unwrap();
+        let editable = editable_from_sti(&original).unwrap();
+        assert_eq!(encode_editable(&editable, Some(&original)).unwrap(), bytes);
+        
+        // Only the edited frame is recompressed
+        let mut edited = editable_from_sti(&original).unwrap();
+        edited.images[1].data = vec![5, 5, 5, 5];
+        let saved = StiParser::parse(&encode_editable(&edited, Some(&original)).unwrap()).unwrap();
+        assert_eq!(saved.images[0].raw_data, original.images[0].raw_data);
+        assert_eq!(saved.images[1].decompressed_data, Some(vec![5, 5, 5, 5]));
+    }
+    
+    #[test]
+    fn test_zero_compressed_size_is_preserved() {
+        let mut sti_file = StiFile::new();
+        sti_file.header.flags.indexed = true;
+        sti_file.header.flags.etrle_compressed = true;
+        sti_file.header.palette_colors = 256;
+        sti_file.header.num_images = 2;
+        sti_file.header.color_depth = 8;
+        sti_file.palette = Some([[7u8; 3]; 256]);
+        let mut data_offset = 0;
+        for _ in 0..2 {
+            let raw_data = vec![0x02, 1, 2, 0x00, 0x02, 3, 4, 0x00];
+            let mut image = StiImage::with_header(StiSubImageHeader {
+                data_offset,
+                data_size: raw_data.len() as u32,
+                offset_x: 0,
+                offset_y: 0,
+                height: 2,
+                width: 2,
+            });
+            data_offset += raw_data.len() as u32;
+            image.raw_data = raw_data;
+            sti_file.images.push(image);
+        }
+        sti_file.header.original_size = 8;
+        let original = StiParser::parse(&StiParser::write(&sti_file).unwrap()).unwrap();
+        assert!(original.stale_compressed_size);
+        
+        // Animation records change the layout but not the frames, the zero survives
+        let mut editable = editable_from_sti(&original).unwrap();
+        editable.animation_data = vec![StiAnimationData::single_frame(2), StiAnimationData::default()];
+        let saved = StiParser::parse(&encode_editable(&editable, Some(&original)).unwrap()).unwrap();
+        assert_eq!(saved.header.compressed_size, 0);
+        
+        // Once a frame changes the value is recomputed
+        editable.images[0].data = vec![9, 9, 9, 9];
+        let saved = StiParser::parse(&encode_editable(&editable, Some(&original)).unwrap()).unwrap();
+        assert_eq!(saved.header.compressed_size as usize, saved.images.iter().map(|image| image.raw_data.len()).sum::<usize>());
+    }
+    
+    #[test]
+    fn test_single_image_header_dimensions_round_trip() {
+        for in_main_header in [true, false] {
+            let mut sti_file = crate::sti::builder::StiBuilder::new_indexed([[0u8; 3]; 256])
+                .add_frame(3, 2, vec![1; 6], 0, 0)
+                .build()
+                .unwrap();
+            if in_main_header {
+                sti_file.header.width = 3;
+                sti_file.header.height = 2;
+            }
+            let original = StiParser::write(&sti_file).unwrap();
+            let parsed = StiParser::parse(&original).unwrap();
+            
+            let editable = editable_from_sti(&parsed).unwrap();
+            assert_eq!(editable.main_header_dimensions, Some(in_main_header));
+            let saved = encode_editable(&editable, Some(&parsed)).unwrap();
+            assert_eq!(saved[20..24], original[20..24]);
+            assert_eq!(StiParser::parse(&saved).unwrap().images[0].decompressed_data, Some(vec![1; 6]));
+        }
+    }
+    
+    #[test]
+    fn test_16_color_palette_survives_edit() {
+        let mut sti_file = crate::sti::builder::StiBuilder::new_indexed([[7u8; 3]; 256])
+            .add_frame(2, 2, vec![0, 15, 3, 0], 0, 0)
+            .build()
+            .unwrap();
+        sti_file.header.palette_colors = 16;
+        let original = StiParser::write(&sti_file).unwrap();
+        let parsed = StiParser::parse(&original).unwrap();
+        
+        let mut editable = editable_from_sti(&parsed).unwrap();
+        assert_eq!(editable.palette_colors, Some(16));
+        let saved = encode_editable(&editable, Some(&parsed)).unwrap();
+        assert_eq!(saved, original);
+        
+        editable.images[0].data[1] = 14;
+        let saved = StiParser::parse(&encode_editable(&editable, Some(&parsed)).unwrap()).unwrap();
+        assert_eq!(saved.header.palette_colors, 16);
+        assert_eq!(saved.images[0].decompressed_data, Some(vec![0, 14, 3, 0]));
+        
+        // Index 16 is past the end of the palette
+        editable.images[0].data[1] = 16;
+        assert!(editable.validate().is_err());
+    }
+    
+    #[test]
+    fn test_rgb888_save_into_rgb555_file() {
+        let masks = (0x7C00, 0x03E0, 0x001F);
+        let sti_file = crate::sti::builder::StiBuilder::new_rgb16(masks)
+            .add_frame(2, 1, vec![0, 0, 0, 0], 0, 0)
+            .build()
+            .unwrap();
+        let parsed = StiParser::parse(&StiParser::write(&sti_file).unwrap()).unwrap();
+        
+        let mut editable = editable_from_sti(&parsed).unwrap();
+        assert_eq!(editable.rgb_masks, Some(masks));
+        editable.images[0].data = vec![255, 0, 0, 0, 255, 0];
+        let saved = StiParser::parse(&encode_editable(&editable, Some(&parsed)).unwrap()).unwrap();
+        assert_eq!(saved.header.rgb16_masks(), masks);
+        assert_eq!(saved.images[0].decompressed_data, Some(vec![0x00, 0x7C, 0xE0, 0x03]));
+        assert_eq!(frame_to_rgba(&saved, 0).unwrap().get_pixel(1, 0).0, [0, 255, 0, 255]);
+    }
+    
+    #[test]
+    fn test_editable_keeps_alpha_table() {
+        let mut sti_file = crate::sti::builder::StiBuilder::new_indexed([[0u8; 3]; 256])
+            .add_frame(2, 1, vec![1, 2], 0, 0)
+            .add_frame(1, 1, vec![3], 0, 0)
+            .build()
+            .unwrap();
+        sti_file.header.flags.alpha = true;
+        sti_file.images[0].alpha = Some(vec![10, 20]);
+        sti_file.images[1].alpha = Some(vec![30]);
+        let parsed = StiParser::parse(&StiParser::write(&sti_file).unwrap()).unwrap();
+    
+        let mut editable = editable_from_sti(&parsed).unwrap();
+        let saved = StiParser::parse(&encode_editable(&editable, Some(&parsed)).unwrap()).unwrap();
+        assert!(saved.header.flags.alpha);
+        assert_eq!(saved.images[1].alpha, Some(vec![30]));
+    
+        // A frame without a table means none can be written, so the flag goes too
+        editable.images[1].alpha = None;
+        let saved = StiParser::parse(&encode_editable(&editable, Some(&parsed)).unwrap()).unwrap();
+        assert!(!saved.header.flags.alpha);
+        assert_eq!(saved.images[0].alpha, None);
+    }
+    
+    #[test]
+    fn test_editable_validation() {
+        let frame = EditableImage { width: 2, height: 2, data: vec![0; 4], offset_x: 0, offset_y: 0, alpha: None };
+        let mut editable = EditableStiFile {
+            file_path: String::new(),
+            is_8bit: true,
+            is_16bit: false,
+            palette: Some(vec![[0, 0, 0]; 256]),
+            images: vec![frame],
+            transparent_color: 0,
+            flags: 0,
+            animation_data: Vec::new(),
+            app_data: Vec::new(),
+            main_header_dimensions: None,
+            palette_colors: None,
+            rgb_masks: None,
+        };
+        assert!(editable.validate().is_ok());
+        
+        editable.images[0].data.pop();
+        assert!(editable.validate().unwrap_err().to_string().contains("Image 0"));
+        editable.images[0].data.push(0);
+        
+        editable.palette = Some(vec![[0, 0, 0]; 16]);
+        assert!(editable.validate().is_err());
+        
+        editable.palette = Some(vec![[0, 0, 0]; 256]);
+        editable.is_16bit = true;
+        assert!(editable.validate().is_err());
+        
+        // 16-bit frames take RGB565 bytes or RGB888 triples, nothing in between
+        editable.is_8bit = false;
+        editable.palette = None;
+        editable.images[0].data = vec![0; 12];
+        assert!(editable.validate().is_ok());
+        editable.images[0].data = vec![0; 8];
+        assert!(editable.validate().is_ok());
+        editable.images[0].data = vec![0; 10];
+        assert!(editable.validate().is_err());
+    }
+}
@@ -0,0 +1,507 @@
+use std::collections::HashMap;
+
+use super::quantize::{median_cut_palette, nearest_palette_index};
+use super::types::{StiError, StiPalette, StiResult};
+
+/// Standalone palette file formats that can be exchanged with other tools
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PaletteFormat {
+    Act,  // Adobe color table, 768 raw RGB bytes
+    Jasc, // JASC-PAL text file
+    Gimp, // GIMP .gpl text file
+}
+
+impl PaletteFormat {
+    /// Resolve a format from a name or file extension such as "act", "pal" or "gpl"
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim_start_matches('.').to_lowercase().as_str() {
+            "act" => Some(PaletteFormat::Act),
+            "pal" | "jasc" => Some(PaletteFormat::Jasc),
+            "gpl" | "gimp" => Some(PaletteFormat::Gimp),
+            _ => None,
+        }
+    }
+}
+
+/// Serialize a palette in the given format
+pub fn write_palette(palette: &StiPalette, format: PaletteFormat) -> Vec<u8> {
+    match format {
+        PaletteFormat::Act => palette.iter().flatten().copied().collect(),
+        PaletteFormat::Jasc => {
+            let mut text = String::from("JASC-PAL\r\n0100\r\n256\r\n");
+            for color in palette {
+                text.push_str(&format!("{} {} {}\r\n", color[0], color[1], color[2]));
+            }
+            text.into_bytes()
+        }
+        PaletteFormat::Gimp => {
+            let mut text = String::from("GIMP Palette\nName: STI palette\nColumns: 16\n#\n");
+            for (index, color) in palette.iter().enumerate() {
+                text.push_str(&format!("{:3} {:3} {:3}\tIndex {}\n", color[0], color[1], color[2], index));
+            }
+            text.into_bytes()
+        }
+    }
+}
+
+/// Parse a palette file, requiring exactly 256 colors
+pub fn read_palette(data: &[u8], format: PaletteFormat) -> StiResult<StiPalette> {
+    let colors = match format {
+        PaletteFormat::Act => {
+            // Some tools append a 4-byte color count / transparency trailer
+            if data.len() != 768 && data.len() != 772 {
+                return Err(StiError::InvalidFormat(format!(
+                    "ACT palette must be 768 bytes, got {}", data.len()
+                )));
+            }
+            data[..768].chunks_exact(3).map(|c| [c[0], c[1], c[2]]).collect()
+        }
+        PaletteFormat::Jasc => {
+            let text = palette_text(data)?;
+            let mut lines = text.lines().map(str::trim).filter(|line| !line.is_empty());
+
+            if lines.next() != Some("JASC-PAL") {
+                return Err(StiError::InvalidFormat("Missing JASC-PAL header".to_string()));
+            }
+            lines.next(); // Version
+            let declared: usize = lines.next()
+                .and_then(|line| line.parse().ok())
+                .ok_or_else(|| StiError::InvalidFormat("Missing JASC-PAL color count".to_string()))?;
+
+            let colors = lines.map(parse_color_line).collect::<StiResult<Vec<_>>>()?;
+            if colors.len() != declared {
+                return Err(StiError::InvalidFormat(format!(
+                    "JASC-PAL declares {} colors but contains {}", declared, colors.len()
+                )));
+            }
+            colors
+        }
+        PaletteFormat::Gimp => {
+            let text = palette_text(data)?;
+            let mut lines = text.lines().map(str::trim);
+
+            if lines.next() != Some("GIMP Palette") {
+                return Err(StiError::InvalidFormat("Missing GIMP Palette header".to_string()));
+            }
+
+            lines
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .filter(|line| !line.starts_with("Name:") && !line.starts_with("Columns:"))
+                .map(parse_color_line)
+                .collect::<StiResult<Vec<_>>>()?
+        }
+    };
+
+    if colors.len() != 256 {
+        return Err(StiError::InvalidFormat(format!(
+            "Palette must contain exactly 256 colors, found {}", colors.len()
+        )));
+    }
+
+    let mut palette = [[0u8; 3]; 256];
+    palette.copy_from_slice(&colors);
+    Ok(palette)
+}
+
+/// Index of the palette entry closest to `color` by squared RGB distance
+pub fn nearest_index(palette: &StiPalette, color: [u8; 3]) -> u8 {
+    nearest_palette_index(palette, color, 0) as u8
+}
+
+/// Closest entry other than `transparent_index`, which 8-bit files reserve for transparency
+pub fn nearest_opaque_index(palette: &StiPalette, color: [u8; 3], transparent_index: u8) -> u8 {
+    let mut best_index = if transparent_index == 0 { 1 } else { 0 };
+    let mut best_distance = u32::MAX;
+    
+    for (i, entry) in palette.iter().enumerate() {
+        if i == transparent_index as usize {
+            continue;
+        }
+        let dr = (entry[0] as i32 - color[0] as i32).unsigned_abs();
+        let dg = (entry[1] as i32 - color[1] as i32).unsigned_abs();
+        let db = (entry[2] as i32 - color[2] as i32).unsigned_abs();
+        let distance = dr * dr + dg * dg + db * db;
+        
+        if distance < best_distance {
+            best_distance = distance;
+            best_index = i;
+            if distance == 0 {
+                break;
+            }
+        }
+    }
+    
+    best_index as u8
+}
+
+/// Remembers resolved colors so repeated pixels skip the palette search
+pub struct NearestColorCache<'a> {
+    palette: &'a StiPalette,
+    skip: Option<u8>,
+    resolved: HashMap<[u8; 3], u8>,
+}
+
+impl<'a> NearestColorCache<'a> {
+    /// Cache for lookups against every palette entry
+    pub fn new(palette: &'a StiPalette) -> Self {
+        Self { palette, skip: None, resolved: HashMap::new() }
+    }
+    
+    /// Cache for lookups that never resolve to `transparent_index`
+    pub fn opaque(palette: &'a StiPalette, transparent_index: u8) -> Self {
+        Self { palette, skip: Some(transparent_index), resolved: HashMap::new() }
+    }
+    
+    pub fn nearest(&mut self, color: [u8; 3]) -> u8 {
+        let (palette, skip) = (self.palette, self.skip);
+        *self.resolved.entry(color).or_insert_with(|| match skip {
+            Some(transparent_index) => nearest_opaque_index(palette, color, transparent_index),
+            None => nearest_palette_index(palette, color, 0) as u8,
+        })
+    }
+}
+
+/// For every index of `old`, the closest opaque index of `new`; `transparent_index` maps to itself
+pub fn remap_table(old: &StiPalette, new: &StiPalette, transparent_index: u8) -> [u8; 256] {
+    let mut table = [0u8; 256];
+    for (index, color) in old.iter().enumerate() {
+        table[index] = if index == transparent_index as usize {
+            transparent_index
+        } else {
+            nearest_opaque_index(new, *color, transparent_index)
+        };
+    }
+    table
+}
+
+/// Re-express indexed pixels drawn with `old` using the closest colors of `new`
+pub fn remap_image(indices: &[u8], old: &StiPalette, new: &StiPalette, transparent_index: u8) -> Vec<u8> {
+    let table = remap_table(old, new, transparent_index);
+    indices.iter().map(|&index| table[index as usize]).collect()
+}
+
+/// Deduplicate and sort a palette, keeping `transparent` where it is
+///
+/// Returns the new palette, a table mapping every old index to its new one, and
+/// how many indices were collapsed into an earlier duplicate. Slots left over
+/// after the unique colors are padded with black.
+pub fn normalize_palette(palette: &StiPalette, transparent: u8) -> (StiPalette, [u8; 256], usize) {
+    let mut unique: Vec<[u8; 3]> = palette.iter().enumerate()
+        .filter(|&(index, _)| index != transparent as usize)
+        .map(|(_, color)| *color)
+        .collect();
+    unique.sort();
+    unique.dedup();
+    
+    let mut normalized = [[0u8; 3]; 256];
+    normalized[transparent as usize] = palette[transparent as usize];
+    let slots = (0..256).filter(|&index| index != transparent as usize);
+    for (slot, color) in slots.zip(&unique) {
+        normalized[slot] = *color;
+    }
+    
+    // Every old color is present, so the nearest lookup resolves to its exact match
+    let table = remap_table(palette, &normalized, transparent);
+    (normalized, table, 255 - unique.len())
+}
+
+/// Move the colors marked as used to the lowest indices, keeping `transparent` in place
+///
+/// Returns the new palette (unused slots zeroed), a table mapping every old index to
+/// its new one, and the number of used entries including the transparent one.
+/// Unused old indices map to the transparent index.
+pub fn compact_palette(palette: &StiPalette, used: &[bool], transparent: u8) -> (StiPalette, [u8; 256], usize) {
+    let mut compacted = [[0u8; 3]; 256];
+    let mut table = [transparent; 256];
+    compacted[transparent as usize] = palette[transparent as usize];
+    
+    let mut next = 0usize;
+    let mut moved = 0usize;
+    for index in 0..256 {
+        if index == transparent as usize || !used.get(index).copied().unwrap_or(false) {
+            continue;
+        }
+        if next == transparent as usize {
+            next += 1;
+        }
+        compacted[next] = palette[index];
+        table[index] = next as u8;
+        next += 1;
+        moved += 1;
+    }
+    
+    (compacted, table, moved + 1)
+}
+
+/// Render the palette as a 16x16 grid of `swatch_size`-pixel cells, index 0 top left
+///
+/// The transparent index's cell is a gray checkerboard so it can't be mistaken for its color.
+pub fn swatch_image(palette: &StiPalette, swatch_size: u32, transparent_index: u8) -> image::RgbImage {
+    let check = (swatch_size / 4).max(1);
+    image::RgbImage::from_fn(16 * swatch_size, 16 * swatch_size, |x, y| {
+        let index = (y / swatch_size * 16 + x / swatch_size) as usize;
+        if index == transparent_index as usize {
+            let light = (x / check + y / check).is_multiple_of(2);
+            image::Rgb(if light { [0xCC; 3] } else { [0x88; 3] })
+        } else {
+            image::Rgb(palette[index])
+        }
+    })
+}
+
+/// Black-to-white ramp, the palette new 8-bit files start with
+pub fn grayscale_palette() -> StiPalette {
+    std::array::from_fn(|index| [index as u8; 3])
+}
+
+/// Rotate the hue of a color by `degrees` in HSV space, keeping saturation and value
+pub fn shift_hue(color: [u8; 3], degrees: f32) -> [u8; 3] {
+    let [r, g, b] = color.map(|channel| channel as f32 / 255.0);
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let delta = max - min;
+    if delta == 0.0 {
+        // Grays have no hue to rotate
+        return color;
+    }
+    
+    let hue = if max == r {
+        60.0 * ((g - b) / delta)
+    } else if max == g {
+        60.0 * ((b - r) / delta + 2.0)
+    } else {
+        60.0 * ((r - g) / delta + 4.0)
+    };
+    let hue = (hue + degrees).rem_euclid(360.0);
+    let saturation = delta / max;
+    
+    let chroma = max * saturation;
+    let x = chroma * (1.0 - ((hue / 60.0) % 2.0 - 1.0).abs());
+    let m = max - chroma;
+    let (r, g, b) = match (hue / 60.0) as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    [r, g, b].map(|channel| ((channel + m) * 255.0).round().clamp(0.0, 255.0) as u8)
+}
+
+/// Build a median-cut palette for truecolor pixels, keeping index 0 free for transparency
+///
+/// Returns the 256-entry palette and each pixel's index into it. At most
+/// `max_colors` entries (and never more than 255) are filled from the pixels.
+pub fn generate_palette(pixels: &[[u8; 3]], max_colors: usize) -> (StiPalette, Vec<u8>) {
+    generate_palette_reserving(pixels, max_colors, 0)
+}
+
+/// Like `generate_palette`, but leaves `transparent_index` free instead of index 0
+pub fn generate_palette_reserving(pixels: &[[u8; 3]], max_colors: usize, transparent_index: u8) -> (StiPalette, Vec<u8>) {
+    let colors = median_cut_palette(pixels, max_colors.min(255));
+    let slots: Vec<u8> = (0..=255u8).filter(|&index| index != transparent_index).collect();
+    
+    let mut palette = [[0u8; 3]; 256];
+    for (&slot, color) in slots.iter().zip(&colors) {
+        palette[slot as usize] = *color;
+    }
+    
+    let mut resolved: HashMap<[u8; 3], u8> = HashMap::new();
+    let indices = pixels.iter()
+        .map(|color| {
+            *resolved.entry(*color)
+                .or_insert_with(|| slots[nearest_palette_index(&colors, *color, 0)])
+        })
+        .collect();
+    
+    (palette, indices)
+}
+
+fn palette_text(data: &[u8]) -> StiResult<&str> {
+    std::str::from_utf8(data)
+        .map_err(|_| StiError::InvalidFormat("Palette file is not valid text".to_string()))
+}
+
+// Parse "R G B" from the start of a line, ignoring any trailing color name
+fn parse_color_line(line: &str) -> StiResult<[u8; 3]> {
+    let mut components = line.split_whitespace().map(|part| part.parse::<u8>());
+    let mut color = [0u8; 3];
+    for channel in color.iter_mut() {
+        *channel = match components.next() {
+            Some(Ok(value)) => value,
+            _ => return Err(StiError::InvalidFormat(format!("Invalid palette line: {}", line))),
+        };
+    }
+    Ok(color)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_palette() -> StiPalette {
+        let mut palette = [[0u8; 3]; 256];
+        for (i, color) in palette.iter_mut().enumerate() {
+            *color = [i as u8, 255 - i as u8, (i * 7) as u8];
+        }
+        palette
+    }
+
+    #[test]
+    fn test_palette_round_trip() {
+        let palette = test_palette();
+        for format in [PaletteFormat::Act, PaletteFormat::Jasc, PaletteFormat::Gimp] {
+            let bytes = write_palette(&palette, format);
+            assert_eq!(read_palette(&bytes, format).unwrap(), palette, "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn test_normalize_palette() {
+        let mut palette = [[9u8; 3]; 256];
+        palette[0] = [255, 0, 255];
+        palette[1] = [200, 0, 0];
+        palette[2] = [9, 9, 9];
+        palette[3] = [200, 0, 0];
+        
+        let (normalized, table, collapsed) = normalize_palette(&palette, 0);
+        assert_eq!(collapsed, 253);
+        assert_eq!(normalized[0], [255, 0, 255]);
+        assert_eq!(normalized[1..3], [[9u8, 9, 9], [200, 0, 0]]);
+        assert_eq!(table[0], 0);
+        assert_eq!((table[1], table[2], table[3], table[200]), (2, 1, 2, 1));
+        
+        // A transparent index other than 0 stays put and is never a merge target
+        let (normalized, table, _) = normalize_palette(&palette, 2);
+        assert_eq!(normalized[..3], [[9u8, 9, 9], [200, 0, 0], [9, 9, 9]]);
+        assert_eq!(normalized[3], [255, 0, 255]);
+        assert_eq!((table[0], table[1], table[2], table[3]), (3, 1, 2, 1));
+    }
+
+    #[test]
+    fn test_compact_palette() {
+        let mut palette = [[0u8; 3]; 256];
+        for (index, color) in palette.iter_mut().enumerate() {
+            *color = [index as u8, 0, 0];
+        }
+        let mut used = [false; 256];
+        used[0] = true;
+        used[7] = true;
+        used[200] = true;
+        
+        let (compacted, table, used_count) = compact_palette(&palette, &used, 0);
+        assert_eq!(used_count, 3);
+        assert_eq!(compacted[..4], [[0u8, 0, 0], [7, 0, 0], [200, 0, 0], [0, 0, 0]]);
+        assert_eq!((table[0], table[7], table[200], table[50]), (0, 1, 2, 0));
+        
+        // A transparent index other than 0 keeps its slot and is skipped over
+        let (compacted, table, _) = compact_palette(&palette, &used, 1);
+        assert_eq!(compacted[..3], [[0u8, 0, 0], [1, 0, 0], [7, 0, 0]]);
+        assert_eq!((table[0], table[7], table[200]), (0, 2, 3));
+    }
+
+    fn known_palette() -> StiPalette {
+        let mut palette = [[255u8; 3]; 256];
+        palette[0] = [0, 0, 0];
+        palette[1] = [255, 0, 0];
+        palette[2] = [0, 255, 0];
+        palette[3] = [0, 0, 255];
+        palette[4] = [10, 10, 10];
+        palette
+    }
+
+    #[test]
+    fn test_swatch_image() {
+        let palette = known_palette();
+        let swatches = swatch_image(&palette, 4, 0);
+        assert_eq!(swatches.dimensions(), (64, 64));
+        assert_eq!(swatches.get_pixel(5, 2).0, palette[1]);
+        assert_eq!(swatches.get_pixel(63, 63).0, palette[255]);
+        // Transparent cell alternates per pixel at this size
+        assert_ne!(swatches.get_pixel(0, 0), swatches.get_pixel(1, 0));
+    }
+    
+    #[test]
+    fn test_shift_hue() {
+        assert_eq!(shift_hue([255, 0, 0], 120.0), [0, 255, 0]);
+        assert_eq!(shift_hue([255, 0, 0], -120.0), [0, 0, 255]);
+        assert_eq!(shift_hue([200, 100, 50], 360.0), [200, 100, 50]);
+        assert_eq!(shift_hue([90, 90, 90], 45.0), [90, 90, 90]);
+    }
+    
+    #[test]
+    fn test_nearest_index() {
+        let palette = known_palette();
+        assert_eq!(nearest_index(&palette, [240, 20, 10]), 1);
+        assert_eq!(nearest_index(&palette, [0, 200, 30]), 2);
+        assert_eq!(nearest_index(&palette, [2, 2, 2]), 0);
+        assert_eq!(nearest_opaque_index(&palette, [2, 2, 2], 0), 4);
+        assert_eq!(nearest_opaque_index(&palette, [2, 2, 2], 4), 0);
+        
+        let mut cache = NearestColorCache::opaque(&palette, 0);
+        assert_eq!(cache.nearest([2, 2, 2]), 4);
+        assert_eq!(cache.nearest([2, 2, 2]), 4);
+        assert_eq!(cache.nearest([10, 20, 250]), 3);
+    }
+
+    #[test]
+    fn test_remap_image() {
+        let old = known_palette();
+        let mut new = [[128u8; 3]; 256];
+        new[7] = [250, 5, 5];
+        new[9] = [0, 0, 240];
+        
+        let remapped = remap_image(&[0, 1, 3, 1, 0], &old, &new, 0);
+        assert_eq!(remapped, vec![0, 7, 9, 7, 0]);
+        
+        // A file keyed on index 3 keeps it and sends index 0 to an opaque color
+        let remapped = remap_image(&[0, 1, 3, 1, 0], &old, &new, 3);
+        assert_eq!(remapped[1..4], [7, 3, 7]);
+        assert_ne!(remapped[0], 3);
+    }
+
+    #[test]
+    fn test_generate_palette_solid_colors() {
+        let pixels = [[255, 0, 0], [0, 0, 255], [255, 0, 0], [0, 255, 0]];
+        let (palette, indices) = generate_palette(&pixels, 255);
+        
+        assert!(indices.iter().all(|&index| index != 0));
+        for (pixel, &index) in pixels.iter().zip(&indices) {
+            assert_eq!(palette[index as usize], *pixel);
+        }
+        assert_eq!(indices[0], indices[2]);
+    }
+
+    #[test]
+    fn test_generate_palette_gradient() {
+        let pixels: Vec<[u8; 3]> = (0..=255u8).map(|v| [v, v, 255 - v]).collect();
+        let (palette, indices) = generate_palette(&pixels, 16);
+        
+        let mut used: Vec<u8> = indices.clone();
+        used.sort_unstable();
+        used.dedup();
+        assert!(used.len() <= 16);
+        assert!(!used.contains(&0));
+        
+        for (pixel, &index) in pixels.iter().zip(&indices) {
+            let error = (palette[index as usize][0] as i32 - pixel[0] as i32).abs();
+            assert!(error <= 16, "{:?} mapped to {:?}", pixel, palette[index as usize]);
+        }
+    }
+
+    #[test]
+    fn test_generate_palette_reserves_transparent_index() {
+        let pixels: Vec<[u8; 3]> = (0..=255u8).map(|v| [v, 0, 0]).collect();
+        let (_, indices) = generate_palette_reserving(&pixels, 255, 5);
+        assert!(!indices.contains(&5));
+        assert!(indices.contains(&0));
+    }
+
+    #[test]
+    fn test_palette_requires_256_colors() {
+        let text = "JASC-PAL\n0100\n2\n0 0 0\n255 255 255\n";
+        assert!(read_palette(text.as_bytes(), PaletteFormat::Jasc).is_err());
+        assert!(read_palette(&[0u8; 300], PaletteFormat::Act).is_err());
+    }
+}
@@ -1,32 +1,351 @@
 use std::io::{Read, Seek, SeekFrom, Cursor, Write};
+use std::ops::Range;
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use crate::sti::types::*;
-use crate::sti::etrle::EtrleDecoder;
+use crate::sti::etrle::{DecompressReport, EtrleDecoder, RowEndMode};
+use flate2::{Decompress, FlushDecompress, Status};
+use rayon::prelude::*;
 
 pub struct StiParser;
 
+/// Below this many frames an 8-bit file is decoded on the calling thread, the rayon
+/// hand-off costs more than it saves on a handful of small frames
+const PARALLEL_DECODE_MIN_FRAMES: usize = 16;
+
+/// Per-image decode statistics used to diagnose broken files
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ImageDecodeReport {
+    pub index: usize,
+    pub declared_size: usize,    // data_size from the sub-header (raw length for 16-bit)
+    pub consumed: usize,         // Stored bytes the decoder actually used
+    pub decompressed_len: usize,
+    pub expected_len: usize,     // width * height * bytes per pixel
+    pub etrle: Option<DecompressReport>, // ETRLE frames only: what the decoder had to salvage
+    pub error: Option<String>,
+}
+
+impl ImageDecodeReport {
+    pub fn has_mismatch(&self) -> bool {
+        self.error.is_some()
+            || self.consumed != self.declared_size
+            || self.decompressed_len != self.expected_len
+            || self.etrle.as_ref().is_some_and(|report| !report.is_clean())
+    }
+}
+
+/// A problem `parse_salvage` worked around instead of failing the whole file
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct StiWarning {
+    pub image: Option<usize>, // Frame the problem was found in, None for the file as a whole
+    pub message: String,
+}
+
+/// Below this confidence `identify` recommends a full byte-level report
+const LOW_CONFIDENCE: f32 = 0.75;
+
+/// Header-only guess at which STCI layout a file uses, for triaging files that fail to parse
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct FormatIdentification {
+    pub detected_variant: String,
+    pub format_confidence: f32,          // 1.0 when every header invariant holds, 0.0 for non-STCI data
+    pub issues: Vec<String>,
+    pub recommendation: Option<String>,
+}
+
+impl FormatIdentification {
+    fn new(detected_variant: String, format_confidence: f32, issues: Vec<String>) -> Self {
+        let recommendation = (format_confidence < LOW_CONFIDENCE)
+            .then(|| "Run debug_sti_file for a byte-level report before trusting a full parse".to_string());
+        Self { detected_variant, format_confidence, issues, recommendation }
+    }
+}
+
+/// Options controlling how strictly a file is parsed
+#[derive(Debug, Clone, Default)]
+pub struct ParseOptions {
+    /// Reject files whose declared header sizes don't match the image data
+    pub strict: bool,
+    /// Ignore stored sub-image data offsets and read frames back to back,
+    /// recovering files written with corrupt offsets
+    pub sequential_offsets: bool,
+    /// Only decompress frames in this range; the others keep their raw data
+    /// for `decompress_image` to decode on demand. `None` decompresses every frame
+    pub images: Option<Range<usize>>,
+    /// Replace frames whose data overruns the file or fails to decode with blank
+    /// ones instead of rejecting the file; see `parse_salvage`
+    pub salvage: bool,
+}
+
+impl ParseOptions {
+    fn decompresses(&self, index: usize) -> bool {
+        match &self.images {
+            Some(range) => range.contains(&index),
+            None => true,
+        }
+    }
+}
+
 impl StiParser {
     /// Parse an STI file from bytes
     pub fn parse(data: &[u8]) -> StiResult<StiFile> {
+        Self::parse_with_options(data, &ParseOptions::default())
+    }
+    
+    /// Parse an STI file from bytes, decompressing only the frames in `images`
+    pub fn parse_with_range(data: &[u8], images: Option<Range<usize>>) -> StiResult<StiFile> {
+        Self::parse_with_options(data, &ParseOptions { images, ..ParseOptions::default() })
+    }
+    
+    /// Parse an STI file from bytes with explicit options
+    pub fn parse_with_options(data: &[u8], options: &ParseOptions) -> StiResult<StiFile> {
+        Self::parse_collecting(data, options, &mut Vec::new())
+    }
+    
+    /// Parse as much of a damaged file as possible
+    ///
+    /// Frames whose data runs past the end of the file or fails to decode become blank frames of
+    /// their declared size, and frames the ETRLE decoder had to pad or cut (a missing row marker,
+    /// say) keep what it decoded. Each is recorded as a warning so the good frames can still be
+    /// inspected and exported. Header and palette errors still fail the parse.
+    pub fn parse_salvage(data: &[u8]) -> StiResult<(StiFile, Vec<StiWarning>)> {
+        let options = ParseOptions { salvage: true, ..ParseOptions::default() };
+        let mut warnings = Vec::new();
+        let sti_file = Self::parse_collecting(data, &options, &mut warnings)?;
+        
+        for report in Self::decode_report(&sti_file) {
+            // Blanked frames already have their warning
+            if warnings.iter().any(|warning| warning.image == Some(report.index)) {
+                continue;
+            }
+            if let Some(etrle) = report.etrle.filter(|etrle| !etrle.is_clean()) {
+                warnings.push(StiWarning {
+                    image: Some(report.index),
+                    message: format!(
+                        "ETRLE data needed repair: {} pixels padded, {} cut, {} rows without an end marker, {} trailing bytes",
+                        etrle.padded_pixels, etrle.truncated_pixels, etrle.wrapped_rows, etrle.trailing_bytes
+                    ),
+                });
+            }
+        }
+        
+        Ok((sti_file, warnings))
+    }
+    
+    fn parse_collecting(data: &[u8], options: &ParseOptions, warnings: &mut Vec<StiWarning>) -> StiResult<StiFile> {
+        Self::check_header_length(data)?;
         let mut cursor = Cursor::new(data);
         let header = Self::parse_header(&mut cursor)?;
+        log::debug!(
+            "Header parsed: {} bytes, flags {:?}, {} images, {}x{}, compressed {} / original {} bytes",
+            data.len(), header.flags, header.num_images, header.width, header.height,
+            header.compressed_size, header.original_size
+        );
         
         let mut sti_file = StiFile::new();
         sti_file.header = header;
         
         if sti_file.is_8bit() {
-            Self::parse_8bit_file(&mut cursor, &mut sti_file)?;
+            Self::parse_8bit_file(&mut cursor, &mut sti_file, options, warnings)?;
         } else if sti_file.is_16bit() {
-            Self::parse_16bit_file(&mut cursor, &mut sti_file)?;
+            Self::parse_16bit_file(&mut cursor, &mut sti_file, options, warnings)?;
         } else {
             return Err(StiError::UnsupportedFormat(
                 "Unknown STI format - neither 8-bit nor 16-bit".to_string()
             ));
         }
         
+        if options.strict {
+            Self::validate_sizes(&sti_file)?;
+        }
+        
+        // Some tools write 0 here; frames are located through their sub-headers, so the
+        // file still loads, and saves can keep the value instead of silently fixing it
+        let stored_size: u64 = sti_file.images.iter().map(|image| image.raw_data.len() as u64).sum();
+        sti_file.stale_compressed_size = sti_file.header.compressed_size as u64 != stored_size;
+        
         Ok(sti_file)
     }
     
+    /// Parse only the main header, palette and sub-image headers, skipping all pixel data
+    ///
+    /// Images carry their dimensions and sub-headers but empty `raw_data` and no
+    /// `decompressed_data`, which is enough to describe a file in a listing.
+    pub fn parse_headers_only(data: &[u8]) -> StiResult<StiFile> {
+        Self::check_header_length(data)?;
+        let mut cursor = Cursor::new(data);
+        let header = Self::parse_header(&mut cursor)?;
+        
+        let mut sti_file = StiFile::new();
+        sti_file.header = header;
+        
+        if sti_file.is_8bit() {
+            let sub_headers = Self::parse_palette_and_sub_headers(&mut cursor, &mut sti_file)?;
+            sti_file.images = sub_headers.into_iter().map(StiImage::with_header).collect();
+        } else if sti_file.is_16bit() && sti_file.header.num_images > 1 {
+            sti_file.images = (0..sti_file.header.num_images)
+                .map(|_| Self::parse_sub_image_header(&mut cursor).map(StiImage::with_header))
+                .collect::<StiResult<Vec<_>>>()?;
+        } else if sti_file.is_16bit() {
+            sti_file.images.push(StiImage::new(sti_file.header.width, sti_file.header.height));
+        } else {
+            return Err(StiError::UnsupportedFormat(
+                "Unknown STI format - neither 8-bit nor 16-bit".to_string()
+            ));
+        }
+        
+        Ok(sti_file)
+    }
+    
+    /// Byte offset where image data begins, after the header, palette and sub-image headers
+    pub fn image_data_start(data: &[u8]) -> StiResult<u64> {
+        Self::check_header_length(data)?;
+        let mut cursor = Cursor::new(data);
+        let header = Self::parse_header(&mut cursor)?;
+        
+        let mut sti_file = StiFile::new();
+        sti_file.header = header;
+        if sti_file.is_8bit() {
+            Self::parse_palette_and_sub_headers(&mut cursor, &mut sti_file)?;
+        } else if sti_file.header.num_images > 1 {
+            // 16-bit sub-image headers have a fixed size
+            return Ok(cursor.position() + sti_file.header.num_images as u64 * 16);
+        }
+        
+        Ok(cursor.position())
+    }
+    
+    /// Check the header's declared sizes against the image data actually read
+    pub fn validate_sizes(sti_file: &StiFile) -> StiResult<()> {
+        let stored_size: u64 = sti_file.images.iter()
+            .map(|image| image.raw_data.len() as u64)
+            .sum();
+        if stored_size != sti_file.header.compressed_size as u64 {
+            return Err(StiError::InvalidFormat(format!(
+                "Header declares {} bytes of image data but the images contain {}",
+                sti_file.header.compressed_size, stored_size
+            )));
+        }
+        
+        // Only meaningful when every image could be decompressed
+        if sti_file.images.iter().all(|image| image.decompressed_data.is_some()) {
+            let original_size: u64 = sti_file.images.iter()
+                .filter_map(|image| image.decompressed_data.as_ref())
+                .map(|data| data.len() as u64)
+                .sum();
+            if original_size != sti_file.header.original_size as u64 {
+                return Err(StiError::InvalidFormat(format!(
+                    "Header declares an original size of {} bytes but the images decompress to {}",
+                    sti_file.header.original_size, original_size
+                )));
+            }
+        }
+        
+        Ok(())
+    }
+    
+    /// Check the 64-byte header against the invariants known-good files hold, without
+    /// reading the palette or image data
+    ///
+    /// `file_len` is the length of the whole file, used to spot sizes that can't fit.
+    pub fn identify(data: &[u8], file_len: u64) -> FormatIdentification {
+        let mut issues = Vec::new();
+        if data.len() < 64 {
+            issues.push(format!("Only {} bytes, the header alone needs 64", data.len()));
+            return FormatIdentification::new("unknown".to_string(), 0.0, issues);
+        }
+        if &data[0..4] != b"STCI" {
+            issues.push(format!("Signature {:?} is not STCI", String::from_utf8_lossy(&data[0..4])));
+            return FormatIdentification::new("unknown".to_string(), 0.0, issues);
+        }
+        
+        let u16_at = |offset: usize| u16::from_le_bytes([data[offset], data[offset + 1]]);
+        let u32_at = |offset: usize| u32::from_le_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]]);
+        let original_size = u32_at(4);
+        let compressed_size = u32_at(8);
+        let raw_flags = u32_at(16);
+        let flags = StiFlags::from(raw_flags);
+        let height = u16_at(20);
+        let width = u16_at(22);
+        let color_depth = data[44];
+        let app_data_size = u32_at(45);
+        
+        if raw_flags & !0x3F != 0 {
+            issues.push(format!("Unknown flag bits set: 0x{:08X}", raw_flags & !0x3F));
+        }
+        
+        let detected_variant = if flags.indexed && !flags.rgb {
+            let palette_colors = u32_at(24);
+            let num_images = u16_at(28);
+            if !(1..=256).contains(&palette_colors) {
+                issues.push(format!("Palette declares {} colors, expected 1 to 256", palette_colors));
+            }
+            if num_images == 0 {
+                issues.push("Image count is 0".to_string());
+            }
+            if color_depth != 8 {
+                issues.push(format!("Color depth {} in an indexed file, expected 8", color_depth));
+            }
+            let palette_bytes = palette_colors.clamp(1, 256) as u64 * 3;
+            let needed = 64 + palette_bytes + num_images as u64 * 16 + compressed_size as u64 + app_data_size as u64;
+            if needed > file_len {
+                issues.push(format!("Header sizes need {} bytes but the file is {}", needed, file_len));
+            }
+            
+            let compression = match (flags.zlib_compressed, flags.etrle_compressed) {
+                (true, true) => "ZLIB+ETRLE",
+                (true, false) => "ZLIB",
+                (false, true) => "ETRLE",
+                (false, false) => "uncompressed",
+            };
+            format!("8-bit indexed, {}", compression)
+        } else if flags.rgb && !flags.indexed {
+            let masks = [u32_at(24), u32_at(28), u32_at(32)];
+            let num_images = u16_at(49);
+            if color_depth != 16 {
+                issues.push(format!("Color depth {} in an RGB file, expected 16", color_depth));
+            }
+            if masks.contains(&0) || masks[0] & masks[1] != 0 || masks[1] & masks[2] != 0 || masks[0] & masks[2] != 0 {
+                issues.push(format!("Color masks {:04X}/{:04X}/{:04X} are empty or overlap", masks[0], masks[1], masks[2]));
+            }
+            if flags.etrle_compressed || flags.zlib_compressed {
+                issues.push("Compression flag set on a 16-bit file, which is always stored raw".to_string());
+            }
+            if original_size != compressed_size {
+                issues.push(format!("Original size {} differs from stored size {}", original_size, compressed_size));
+            }
+            if num_images <= 1 {
+                if width == 0 || height == 0 {
+                    issues.push(format!("Image size {}x{} is empty", width, height));
+                }
+                let needed = 64 + width as u64 * height as u64 * 2;
+                if needed > file_len {
+                    issues.push(format!("A {}x{} image needs {} bytes but the file is {}", width, height, needed, file_len));
+                }
+                "16-bit RGB, single image".to_string()
+            } else {
+                let needed = 64 + num_images as u64 * 16 + compressed_size as u64;
+                if needed > file_len {
+                    issues.push(format!("Header sizes need {} bytes but the file is {}", needed, file_len));
+                }
+                format!("16-bit RGB, {} images", num_images)
+            }
+        } else {
+            issues.push("Flags mark the file as both or neither of RGB and indexed".to_string());
+            return FormatIdentification::new("unknown STCI".to_string(), 0.2, issues);
+        };
+        
+        let confidence = (1.0 - 0.25 * issues.len() as f32).max(0.0);
+        FormatIdentification::new(detected_variant, confidence, issues)
+    }
+    
+    /// Reject empty and truncated files before reading the header
+    fn check_header_length(data: &[u8]) -> StiResult<()> {
+        if data.len() < 64 {
+            return Err(StiError::InvalidFormat("file smaller than 64-byte header".to_string()));
+        }
+        Ok(())
+    }
+    
     /// Parse the 64-byte STI header
     fn parse_header(cursor: &mut Cursor<&[u8]>) -> StiResult<StiHeader> {
         let mut header = StiHeader::default();
@@ -46,6 +365,7 @@ impl StiParser {
         
         let flags_value = cursor.read_u32::<LittleEndian>()?;
         header.flags = StiFlags::from(flags_value);
+        header.raw_flags = flags_value;
         
         // Width and height are only used for 16-bit files
         if header.flags.rgb && !header.flags.indexed {
@@ -63,12 +383,10 @@ impl StiParser {
             header.blue_depth = cursor.read_u8()?;
             header.alpha_depth = cursor.read_u8()?;
         } else if header.flags.indexed && !header.flags.rgb {
-            // 8-bit file format - width/height are NOT in main header
-            header.height = 0; // Will be set from sub-image headers
-            header.width = 0;  // Will be set from sub-image headers
-            
-            // Skip to byte 25 for 8-bit specific data
-            cursor.seek(SeekFrom::Current(4))?; // Skip bytes 21-24
+            // 8-bit file format - width/height normally live in the sub-image
+            // headers, but single-image files may store them here instead
+            header.height = cursor.read_u16::<LittleEndian>()?;
+            header.width = cursor.read_u16::<LittleEndian>()?;
             
             header.palette_colors = cursor.read_u32::<LittleEndian>()?;
             header.num_images = cursor.read_u16::<LittleEndian>()?;
@@ -88,33 +406,147 @@ impl StiParser {
         header.color_depth = cursor.read_u8()?;
         header.app_data_size = cursor.read_u32::<LittleEndian>()?;
         
+        // 16-bit headers have no image count field; multi-image files keep it in the
+        // otherwise unused bytes 50-51. Older single-image files leave them zero
+        if header.flags.rgb {
+            header.num_images = cursor.read_u16::<LittleEndian>()?.max(1);
+        }
+        
         // Skip remaining unused bytes to reach byte 64
         cursor.seek(SeekFrom::Start(64))?;
         
         Ok(header)
     }
     
-    /// Parse 8-bit indexed STI file
-    fn parse_8bit_file(cursor: &mut Cursor<&[u8]>, sti_file: &mut StiFile) -> StiResult<()> {
-        // Read palette (768 bytes = 256 colors * 3 bytes)
+    /// Read the 8-bit palette and sub-image headers, leaving the cursor at the image data
+    fn parse_palette_and_sub_headers(cursor: &mut Cursor<&[u8]>, sti_file: &mut StiFile) -> StiResult<Vec<StiSubImageHeader>> {
+        let remaining = |cursor: &Cursor<&[u8]>| (cursor.get_ref().len() as u64).saturating_sub(cursor.position());
+        let entries = sti_file.header.palette_entries();
+        if remaining(cursor) < entries as u64 * 3 {
+            return Err(StiError::InvalidFormat(format!(
+                "File truncated: the palette needs {} bytes but only {} remain",
+                entries * 3,
+                remaining(cursor)
+            )));
+        }
+        
+        // Read palette (3 bytes per color); smaller palettes leave the rest of the 256 entries black
         let mut palette = [[0u8; 3]; 256];
-        for i in 0..256 {
-            cursor.read_exact(&mut palette[i])?;
+        for color in palette.iter_mut().take(entries) {
+            cursor.read_exact(color)?;
         }
         sti_file.palette = Some(palette);
+        log::debug!("Palette read, sub-image headers start at byte {}", cursor.position());
         
         // Read sub-image headers
         let num_images = sti_file.header.num_images as usize;
         let mut sub_headers = Vec::with_capacity(num_images);
         
-        for _ in 0..num_images {
-            let sub_header = Self::parse_sub_image_header(cursor)?;
-            sub_headers.push(sub_header);
+        if Self::omits_sub_image_header(cursor, &sti_file.header) {
+            // Single image described by the main header: synthesize its sub-header
+            let remaining = (cursor.get_ref().len() as u64)
+                .saturating_sub(cursor.position())
+                .saturating_sub(sti_file.header.app_data_size as u64) as u32;
+            let declared = sti_file.header.compressed_size;
+            
+            sub_headers.push(StiSubImageHeader {
+                data_offset: 0,
+                data_size: if declared > 0 && declared <= remaining { declared } else { remaining },
+                offset_x: 0,
+                offset_y: 0,
+                height: sti_file.header.height,
+                width: sti_file.header.width,
+            });
+            sti_file.header.num_images = 1;
+        } else {
+            if remaining(cursor) < num_images as u64 * 16 {
+                return Err(StiError::InvalidFormat(format!(
+                    "File truncated: {} sub-image headers need {} bytes but only {} remain",
+                    num_images,
+                    num_images * 16,
+                    remaining(cursor)
+                )));
+            }
+            for i in 0..num_images {
+                let sub_header = Self::parse_sub_image_header(cursor)?;
+                log::debug!(
+                    "Sub-image header {}: {}x{} at ({}, {}), {} bytes at data offset {}",
+                    i, sub_header.width, sub_header.height, sub_header.offset_x, sub_header.offset_y,
+                    sub_header.data_size, sub_header.data_offset
+                );
+                sub_headers.push(sub_header);
+            }
+        }
+        
+        Ok(sub_headers)
+    }
+    
+    /// Give a frame salvage couldn't read placeholder pixels, returning how it was replaced
+    ///
+    /// The frame is filled with the transparent color (the ETRLE transparent index for 8-bit
+    /// files, the little-endian pixel value for 16-bit) unless its declared size is more than the
+    /// whole file could encode; then it becomes an empty 0x0 frame instead of a huge allocation.
+    fn blank_out(header: &StiHeader, image: &mut StiImage, file_len: usize) -> &'static str {
+        let pixel_count = image.width as usize * image.height as usize;
+        if pixel_count > Self::max_frame_pixels(header, image.width, file_len) {
+            image.width = 0;
+            image.height = 0;
+            if let Some(sub_header) = &mut image.header {
+                sub_header.width = 0;
+                sub_header.height = 0;
+            }
+            image.decompressed_data = Some(Vec::new());
+            return "replaced with an empty frame, its declared size is more than the file could hold";
+        }
+        
+        image.decompressed_data = Some(if header.flags.rgb {
+            (header.transparent_color as u16).to_le_bytes().repeat(pixel_count)
+        } else {
+            vec![header.etrle_transparent_index(); pixel_count]
+        });
+        "replaced with a blank frame"
+    }
+    
+    /// Most pixels a frame `width` wide can hold in a file of `file_len` bytes
+    ///
+    /// 16-bit pixels are stored as is; an ETRLE byte yields at most a 127-pixel run or the rest
+    /// of a row. Short streams are still padded, this only bounds what the whole file allows.
+    fn max_frame_pixels(header: &StiHeader, width: u16, file_len: usize) -> usize {
+        if header.flags.rgb {
+            file_len / 2
+        } else {
+            file_len.saturating_mul((width as usize).max(127))
+        }
+    }
+    
+    /// Refuse frame sizes that add up to more than the bytes left after the headers
+    ///
+    /// Sizes come straight from the file, so a corrupt one would otherwise be allocated before
+    /// the read fails: a 65535x65535 16-bit frame asks for 8 GiB.
+    fn check_frame_sizes(cursor: &Cursor<&[u8]>, sizes: impl Iterator<Item = u64>) -> StiResult<()> {
+        let remaining = (cursor.get_ref().len() as u64).saturating_sub(cursor.position());
+        let total: u64 = sizes.sum();
+        if total > remaining {
+            return Err(StiError::InvalidFormat(format!(
+                "Frames declare {} bytes of image data but only {} bytes remain in the file",
+                total, remaining
+            )));
+        }
+        Ok(())
+    }
+    
+    /// Parse 8-bit indexed STI file
+    fn parse_8bit_file(cursor: &mut Cursor<&[u8]>, sti_file: &mut StiFile, options: &ParseOptions, warnings: &mut Vec<StiWarning>) -> StiResult<()> {
+        let sub_headers = Self::parse_palette_and_sub_headers(cursor, sti_file)?;
+        // Salvage checks each frame against the file instead, so one bad size doesn't sink the rest
+        if !options.salvage {
+            Self::check_frame_sizes(cursor, sub_headers.iter().map(|sub_header| sub_header.data_size as u64))?;
         }
         
         // Read image data
         // For 8-bit files, image data comes immediately after all sub-image headers
         let image_data_start = cursor.position();
+        let mut sequential_position = image_data_start;
         
         for (i, sub_header) in sub_headers.iter().enumerate() {
             let mut image = StiImage::with_header(sub_header.clone());
@@ -123,11 +555,32 @@ impl StiParser {
             let image_position = if i == 0 {
                 // First image starts right after the sub-headers
                 image_data_start
+            } else if options.sequential_offsets {
+                // Frames are laid out back to back, whatever the offsets claim
+                sequential_position
             } else {
                 // Subsequent images use data_offset from previous position
                 image_data_start + sub_header.data_offset as u64
             };
             
+            // Reject sub-headers that point past the end of the file
+            let data_end = image_position + sub_header.data_size as u64;
+            if data_end > cursor.get_ref().len() as u64 {
+                let message = format!(
+                    "Image {} data ({} bytes at offset {}) extends past the end of the file ({} bytes)",
+                    i, sub_header.data_size, image_position, cursor.get_ref().len()
+                );
+                if !options.salvage {
+                    return Err(StiError::InvalidFormat(message));
+                }
+                let replacement = Self::blank_out(&sti_file.header, &mut image, cursor.get_ref().len());
+                warnings.push(StiWarning { image: Some(i), message: format!("{}; {}", message, replacement) });
+                sti_file.images.push(image);
+                continue;
+            }
+            
+            sequential_position = data_end;
+            
             // Seek to the correct position for this image's data
             cursor.seek(SeekFrom::Start(image_position))?;
             
@@ -135,59 +588,404 @@ impl StiParser {
             image.raw_data = vec![0u8; sub_header.data_size as usize];
             cursor.read_exact(&mut image.raw_data)?;
             
-            // Decompress if using ETRLE
-            if sti_file.header.flags.etrle_compressed {
-                let decoder = EtrleDecoder::new(sub_header.width, sub_header.height);
-                image.decompressed_data = Some(decoder.decompress(&image.raw_data)?);
+            sti_file.images.push(image);
+        }
+        
+        // Frames decode independently, so large files spread them over the rayon pool
+        let header = &sti_file.header;
+        let file_len = cursor.get_ref().len();
+        let decode = |(i, image): (usize, &mut StiImage)| -> StiResult<Option<StiWarning>> {
+            // Frames salvage has already blanked keep their placeholder pixels
+            if !options.decompresses(i) || image.decompressed_data.is_some() {
+                return Ok(None);
+            }
+            let sub_header = &sub_headers[i];
+            // Padding a corrupt size out to the declared frame would allocate gigabytes
+            let pixel_count = sub_header.width as usize * sub_header.height as usize;
+            let decoded = if pixel_count > Self::max_frame_pixels(header, sub_header.width, file_len) {
+                Err(StiError::InvalidFormat(format!(
+                    "Image {} declares {}x{} pixels, more than the whole file could encode",
+                    i, sub_header.width, sub_header.height
+                )))
+            } else {
+                Self::decode_8bit_image_detecting(header, sub_header, &image.raw_data, i)
+            };
+            let (decoded, row_end) = match decoded {
+                Ok(result) => result,
+                Err(e) if options.salvage => {
+                    let replacement = Self::blank_out(header, image, file_len);
+                    return Ok(Some(StiWarning { image: Some(i), message: format!("{}; {}", e, replacement) }));
+                }
+                Err(e) => return Err(e),
+            };
+            image.decompressed_data = decoded;
+            image.etrle_row_end = row_end;
+            log::debug!(
+                "Frame {}: {} stored bytes decoded to {} (expected {})",
+                i, image.raw_data.len(),
+                image.decompressed_data.as_ref().map_or(image.raw_data.len(), |data| data.len()),
+                sub_header.width as usize * sub_header.height as usize
+            );
+            Ok(None)
+        };
+        let frame_warnings: Vec<Option<StiWarning>> = if sti_file.images.len() >= PARALLEL_DECODE_MIN_FRAMES {
+            sti_file.images.par_iter_mut().enumerate().map(decode).collect::<StiResult<_>>()?
+        } else {
+            sti_file.images.iter_mut().enumerate().map(decode).collect::<StiResult<_>>()?
+        };
+        warnings.extend(frame_warnings.into_iter().flatten());
+        
+        if sti_file.header.flags.alpha {
+            Self::read_alpha_table(cursor, sti_file)?;
+        }
+        
+        Self::read_app_data(cursor, sti_file)
+    }
+    
+    /// Read the application data block that follows the image data, keeping the raw block
+    /// whatever its size
+    ///
+    /// Animation data is optional, so a file too short to hold the declared block loads without it.
+    fn read_app_data(cursor: &mut Cursor<&[u8]>, sti_file: &mut StiFile) -> StiResult<()> {
+        let app_data_size = sti_file.header.app_data_size;
+        if app_data_size == 0 {
+            return Ok(());
+        }
+        
+        let remaining_bytes = (cursor.get_ref().len() as u64).saturating_sub(cursor.position());
+        if remaining_bytes < app_data_size as u64 {
+            log::warn!(
+                "Header declares {} bytes of application data but only {} remain; ignoring it",
+                app_data_size, remaining_bytes
+            );
+            return Ok(());
+        }
+        
+        let mut app_data = vec![0u8; app_data_size as usize];
+        cursor.read_exact(&mut app_data)?;
+        
+        // Whole 16-byte records are animation data
+        if app_data.len().is_multiple_of(16) {
+            sti_file.animation_data = app_data.chunks_exact(16)
+                .map(|record| StiAnimationData::from_bytes(record.try_into().unwrap()))
+                .collect();
+        }
+        sti_file.app_data = app_data;
+        Ok(())
+    }
+    
+    /// Read the per-pixel alpha table STCI_ALPHA announces, laid out as described in FILEFORMAT.md
+    ///
+    /// A file too short to hold it is left without alpha rather than rejected.
+    fn read_alpha_table(cursor: &mut Cursor<&[u8]>, sti_file: &mut StiFile) -> StiResult<()> {
+        let table_size: u64 = sti_file.images.iter()
+            .map(|image| image.width as u64 * image.height as u64)
+            .sum();
+        let remaining = (cursor.get_ref().len() as u64).saturating_sub(cursor.position());
+        if remaining < table_size + sti_file.header.app_data_size as u64 {
+            log::warn!(
+                "Alpha flag set but only {} bytes follow the image data, {} needed for the alpha table; ignoring it",
+                remaining, table_size + sti_file.header.app_data_size as u64
+            );
+            return Ok(());
+        }
+        
+        for image in &mut sti_file.images {
+            let mut alpha = vec![0u8; image.width as usize * image.height as usize];
+            cursor.read_exact(&mut alpha)?;
+            image.alpha = Some(alpha);
+        }
+        Ok(())
+    }
+    
+    /// Decode one 8-bit frame's stored bytes, `None` when they are stored uncompressed
+    fn decode_8bit_image(header: &StiHeader, sub_header: &StiSubImageHeader, raw_data: &[u8], index: usize) -> StiResult<Option<Vec<u8>>> {
+        Self::decode_8bit_image_detecting(header, sub_header, raw_data, index).map(|(decoded, _)| decoded)
+    }
+    
+    /// `decode_8bit_image`, also reporting how the ETRLE stream ended its rows
+    fn decode_8bit_image_detecting(header: &StiHeader, sub_header: &StiSubImageHeader, raw_data: &[u8], index: usize) -> StiResult<(Option<Vec<u8>>, RowEndMode)> {
+        // ZLIB runs first: some tiles are stored zlib-then-ETRLE
+        let inflated = if header.flags.zlib_compressed {
+            let size_hint = sub_header.width as usize * sub_header.height as usize;
+            Some(Self::inflate_zlib(raw_data, size_hint)
+                .map_err(|e| StiError::Decompression(format!("Image {}: {}", index, e)))?)
+        } else {
+            None
+        };
+        
+        // Decompress if using ETRLE
+        let (decoded, row_end) = if header.flags.etrle_compressed {
+            let decoder = EtrleDecoder::with_transparent(
+                sub_header.width, sub_header.height, header.etrle_transparent_index()
+            );
+            let etrle_data = inflated.as_deref().unwrap_or(raw_data);
+            let (decompressed, row_end) = decoder.decompress_detecting(etrle_data)?;
+            (Some(decompressed), row_end)
+        } else {
+            // ZLIB only: the inflated buffer is raw indexed data
+            (inflated, RowEndMode::default())
+        };
+        
+        Self::check_palette_indices(header, decoded.as_deref().unwrap_or(raw_data), index)?;
+        Ok((decoded, row_end))
+    }
+    
+    /// Reject pixels that point past a palette with fewer than 256 entries
+    ///
+    /// The transparent index is exempt, since ETRLE runs expand to it whatever the palette holds.
+    fn check_palette_indices(header: &StiHeader, pixels: &[u8], index: usize) -> StiResult<()> {
+        let entries = header.palette_entries();
+        if entries == 256 {
+            return Ok(());
+        }
+        
+        let transparent_index = header.etrle_transparent_index();
+        match pixels.iter().find(|&&pixel| pixel as usize >= entries && pixel != transparent_index) {
+            Some(pixel) => Err(StiError::InvalidFormat(format!(
+                "Image {} uses palette index {} but the palette has only {} colors",
+                index, pixel, entries
+            ))),
+            None => Ok(()),
+        }
+    }
+    
+    /// Decompress a frame that was skipped by `parse_with_range`
+    pub fn decompress_image(sti_file: &StiFile, index: usize) -> StiResult<Option<Vec<u8>>> {
+        let image = sti_file.images.get(index)
+            .ok_or_else(|| StiError::InvalidFormat(format!("Image {} does not exist", index)))?;
+        
+        if sti_file.is_16bit() {
+            return Ok(Some(image.raw_data.clone()));
+        }
+        
+        let sub_header = image.header.as_ref()
+            .ok_or_else(|| StiError::InvalidFormat(format!("Image {} has no sub-image header", index)))?;
+        Self::decode_8bit_image(&sti_file.header, sub_header, &image.raw_data, index)
+    }
+    
+    /// Deflate one frame's stored bytes into a ZLIB stream, the inverse of how frames are inflated
+    pub fn deflate_zlib(data: &[u8]) -> StiResult<Vec<u8>> {
+        use flate2::write::ZlibEncoder;
+        use flate2::Compression;
+        
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::best());
+        encoder.write_all(data)?;
+        Ok(encoder.finish()?)
+    }
+    
+    /// Inflate a ZLIB stream, failing if the stream ends before its terminator
+    fn inflate_zlib(data: &[u8], size_hint: usize) -> Result<Vec<u8>, String> {
+        Self::inflate_zlib_counting(data, size_hint).map(|(output, _)| output)
+    }
+    
+    /// Inflate a ZLIB stream, also returning how many input bytes it used
+    ///
+    /// `size_hint` is only the starting capacity: ETRLE data can inflate to more than
+    /// `width * height` bytes, so the output grows until the stream ends.
+    fn inflate_zlib_counting(data: &[u8], size_hint: usize) -> Result<(Vec<u8>, usize), String> {
+        let mut inflater = Decompress::new(true);
+        // Deflate can't expand beyond about 1032:1, so a corrupt hint can't force a huge allocation
+        let mut output = Vec::with_capacity(size_hint.min(data.len().saturating_mul(1032)).max(64));
+        
+        loop {
+            if output.len() == output.capacity() {
+                output.reserve(output.capacity());
             }
             
-            sti_file.images.push(image);
+            let consumed = inflater.total_in() as usize;
+            let produced = inflater.total_out();
+            let status = inflater
+                .decompress_vec(&data[consumed..], &mut output, FlushDecompress::None)
+                .map_err(|e| format!("Corrupt zlib stream: {}", e))?;
+            
+            if status == Status::StreamEnd {
+                return Ok((output, inflater.total_in() as usize));
+            }
+            
+            // Out of input (or stuck) with room left in the output means the stream was cut short
+            let input_exhausted = inflater.total_in() as usize >= data.len();
+            let stalled = inflater.total_in() as usize == consumed && inflater.total_out() == produced;
+            if (input_exhausted || stalled) && output.len() < output.capacity() {
+                return Err(format!(
+                    "Truncated zlib stream after {} of {} bytes",
+                    inflater.total_in(),
+                    data.len()
+                ));
+            }
+        }
+    }
+    
+    /// Re-decode every image's stored bytes and report what the decoders consumed and produced
+    pub fn decode_report(sti_file: &StiFile) -> Vec<ImageDecodeReport> {
+        (0..sti_file.images.len()).filter_map(|index| Self::frame_decode_report(sti_file, index)).collect()
+    }
+    
+    /// `decode_report` for a single frame, `None` when `index` is out of range
+    pub fn frame_decode_report(sti_file: &StiFile, index: usize) -> Option<ImageDecodeReport> {
+        let flags = sti_file.header.flags;
+        let image = sti_file.images.get(index)?;
+        let pixel_count = image.width as usize * image.height as usize;
+        let mut report = ImageDecodeReport {
+            index,
+            declared_size: image.header.as_ref()
+                .map_or(image.raw_data.len(), |header| header.data_size as usize),
+            consumed: image.raw_data.len(),
+            decompressed_len: 0,
+            expected_len: if sti_file.is_16bit() { pixel_count * 2 } else { pixel_count },
+            etrle: None,
+            error: None,
+        };
+        
+        if sti_file.is_16bit() {
+            report.decompressed_len = image.raw_data.len();
+            return Some(report);
         }
         
-        // Read animation data if present
-        if sti_file.header.app_data_size > 0 {
-            let remaining_bytes = cursor.get_ref().len() as u64 - cursor.position();
-            if remaining_bytes >= sti_file.header.app_data_size as u64 {
-                let animation_count = (sti_file.header.app_data_size / 16) as usize;
-                for _ in 0..animation_count {
-                    let mut anim_data = StiAnimationData {
-                        unknown1: [0; 8],
-                        frame_count: 0,
-                        unknown2: 0,
-                        unknown3: [0; 6],
-                    };
-                    
-                    cursor.read_exact(&mut anim_data.unknown1)?;
-                    anim_data.frame_count = cursor.read_u8()?;
-                    anim_data.unknown2 = cursor.read_u8()?;
-                    cursor.read_exact(&mut anim_data.unknown3)?;
-                    
-                    sti_file.animation_data.push(anim_data);
+        let stage = if flags.zlib_compressed {
+            match Self::inflate_zlib_counting(&image.raw_data, pixel_count) {
+                Ok((inflated, consumed)) => {
+                    report.consumed = consumed;
+                    inflated
+                }
+                Err(e) => {
+                    report.error = Some(e);
+                    return Some(report);
                 }
-            } else {
-                // Not enough data for animation, but that's okay for some files
-                // Animation data is optional
             }
+        } else {
+            image.raw_data.clone()
+        };
+        
+        if flags.etrle_compressed {
+            let decoder = EtrleDecoder::with_transparent(
+                image.width, image.height, sti_file.header.etrle_transparent_index()
+            );
+            match decoder.decompress_with_report(&stage) {
+                Ok((decompressed, etrle_report)) => {
+                    if !flags.zlib_compressed {
+                        report.consumed = etrle_report.consumed;
+                    } else if etrle_report.consumed != stage.len() {
+                        report.error = Some(format!(
+                            "ETRLE used {} of {} inflated bytes", etrle_report.consumed, stage.len()
+                        ));
+                    }
+                    report.decompressed_len = decompressed.len();
+                    report.etrle = Some(etrle_report);
+                }
+                Err(e) => report.error = Some(e.to_string()),
+            }
+        } else {
+            report.decompressed_len = stage.len();
         }
         
-        Ok(())
+        Some(report)
+    }
+    
+    /// Detect single-image 8-bit files that keep their dimensions in the main
+    /// header and go straight from the palette to the image data
+    fn omits_sub_image_header(cursor: &mut Cursor<&[u8]>, header: &StiHeader) -> bool {
+        if header.num_images > 1 || header.width == 0 || header.height == 0 {
+            return false;
+        }
+        
+        // A real sub-header repeats the main header's dimensions
+        let start = cursor.position();
+        let matches = match Self::parse_sub_image_header(cursor) {
+            Ok(sub_header) => sub_header.width == header.width && sub_header.height == header.height,
+            Err(_) => false,
+        };
+        cursor.set_position(start);
+        
+        !matches
     }
     
     /// Parse 16-bit RGB STI file
-    fn parse_16bit_file(cursor: &mut Cursor<&[u8]>, sti_file: &mut StiFile) -> StiResult<()> {
+    fn parse_16bit_file(cursor: &mut Cursor<&[u8]>, sti_file: &mut StiFile, options: &ParseOptions, warnings: &mut Vec<StiWarning>) -> StiResult<()> {
+        if sti_file.header.num_images > 1 {
+            return Self::parse_16bit_frames(cursor, sti_file, options, warnings);
+        }
+        
+        // Single image: the pixels follow the main header directly
         let width = sti_file.header.width;
         let height = sti_file.header.height;
         let data_size = (width as usize) * (height as usize) * 2; // 2 bytes per pixel
-        
         let mut image = StiImage::new(width, height);
+        
+        if let Err(e) = Self::check_frame_sizes(cursor, std::iter::once(data_size as u64)) {
+            if !options.salvage {
+                return Err(e);
+            }
+            let replacement = Self::blank_out(&sti_file.header, &mut image, cursor.get_ref().len());
+            warnings.push(StiWarning { image: Some(0), message: format!("{}; {}", e, replacement) });
+            // The main header carries a lone 16-bit image's size
+            sti_file.header.width = image.width;
+            sti_file.header.height = image.height;
+            sti_file.images.push(image);
+            return Ok(());
+        }
+        
         image.raw_data = vec![0u8; data_size];
         cursor.read_exact(&mut image.raw_data)?;
         
         // For 16-bit images, the raw data is already decompressed
-        image.decompressed_data = Some(image.raw_data.clone());
+        if options.decompresses(0) {
+            image.decompressed_data = Some(image.raw_data.clone());
+        }
         
         sti_file.images.push(image);
-        Ok(())
+        Self::read_app_data(cursor, sti_file)
+    }
+    
+    /// Parse a multi-image 16-bit file: sub-image headers, then uncompressed frames
+    fn parse_16bit_frames(cursor: &mut Cursor<&[u8]>, sti_file: &mut StiFile, options: &ParseOptions, warnings: &mut Vec<StiWarning>) -> StiResult<()> {
+        let sub_headers = (0..sti_file.header.num_images)
+            .map(|_| Self::parse_sub_image_header(cursor))
+            .collect::<StiResult<Vec<_>>>()?;
+        if !options.salvage {
+            Self::check_frame_sizes(
+                cursor, sub_headers.iter().map(|sub_header| sub_header.width as u64 * sub_header.height as u64 * 2)
+            )?;
+        }
+        let image_data_start = cursor.position();
+        let mut image_data_end = image_data_start;
+        
+        for (i, sub_header) in sub_headers.into_iter().enumerate() {
+            let data_size = sub_header.width as usize * sub_header.height as usize * 2;
+            let image_position = image_data_start + sub_header.data_offset as u64;
+            
+            if image_position + data_size as u64 > cursor.get_ref().len() as u64 {
+                let message = format!(
+                    "Image {} data ({} bytes at offset {}) extends past the end of the file ({} bytes)",
+                    i, data_size, image_position, cursor.get_ref().len()
+                );
+                if !options.salvage {
+                    return Err(StiError::InvalidFormat(message));
+                }
+                let mut image = StiImage::with_header(sub_header);
+                let replacement = Self::blank_out(&sti_file.header, &mut image, cursor.get_ref().len());
+                warnings.push(StiWarning { image: Some(i), message: format!("{}; {}", message, replacement) });
+                sti_file.images.push(image);
+                continue;
+            }
+            image_data_end = image_data_end.max(image_position + data_size as u64);
+            
+            cursor.seek(SeekFrom::Start(image_position))?;
+            let mut image = StiImage::with_header(sub_header);
+            image.raw_data = vec![0u8; data_size];
+            cursor.read_exact(&mut image.raw_data)?;
+            
+            log::debug!("16-bit frame {}: {}x{}, {} bytes at byte {}", i, image.width, image.height, data_size, image_position);
+            if options.decompresses(i) {
+                image.decompressed_data = Some(image.raw_data.clone());
+            }
+            
+            sti_file.images.push(image);
+        }
+        
+        // Application data follows the last frame in the file, wherever the offsets put it
+        cursor.seek(SeekFrom::Start(image_data_end))?;
+        Self::read_app_data(cursor, sti_file)
     }
     
     /// Parse sub-image header (16 bytes)
@@ -231,7 +1029,7 @@ impl StiParser {
         cursor.write_u32::<LittleEndian>(header.original_size)?;
         cursor.write_u32::<LittleEndian>(header.compressed_size)?;
         cursor.write_u32::<LittleEndian>(header.transparent_color)?;
-        cursor.write_u32::<LittleEndian>(header.flags.into())?;
+        cursor.write_u32::<LittleEndian>(header.flags_value())?;
         cursor.write_u16::<LittleEndian>(header.height)?;
         cursor.write_u16::<LittleEndian>(header.width)?;
         
@@ -261,6 +1059,9 @@ impl StiParser {
         // Write remaining fields
         cursor.write_u8(header.color_depth)?;
         cursor.write_u32::<LittleEndian>(header.app_data_size)?;
+        if header.flags.rgb {
+            cursor.write_u16::<LittleEndian>(header.num_images)?;
+        }
         
         // Pad to 64 bytes
         let current_pos = cursor.position() as usize;
@@ -272,9 +1073,9 @@ impl StiParser {
     }
     
     fn write_8bit_file(cursor: &mut Cursor<&mut Vec<u8>>, sti_file: &StiFile) -> StiResult<()> {
-        // Write palette
+        // Write palette, only as many entries as the header declares
         if let Some(palette) = &sti_file.palette {
-            for color in palette.iter() {
+            for color in palette.iter().take(sti_file.header.palette_entries()) {
                 cursor.write_all(color)?;
             }
         } else {
@@ -299,25 +1100,42 @@ impl StiParser {
             cursor.write_all(&image.raw_data)?;
         }
         
-        // Write animation data
-        for anim_data in &sti_file.animation_data {
-            cursor.write_all(&anim_data.unknown1)?;
-            cursor.write_u8(anim_data.frame_count)?;
-            cursor.write_u8(anim_data.unknown2)?;
-            cursor.write_all(&anim_data.unknown3)?;
+        // The alpha table only goes out whole, a partial one couldn't be read back
+        let has_alpha_table = sti_file.images.iter().all(|image| {
+            image.alpha.as_ref().is_some_and(|alpha| alpha.len() == image.width as usize * image.height as usize)
+        });
+        if sti_file.header.flags.alpha && has_alpha_table {
+            for image in &sti_file.images {
+                cursor.write_all(image.alpha.as_deref().unwrap_or_default())?;
+            }
         }
         
+        // Write application data (animation records or the raw block)
+        cursor.write_all(&sti_file.app_data_bytes())?;
+        
         Ok(())
     }
     
     fn write_16bit_file(cursor: &mut Cursor<&mut Vec<u8>>, sti_file: &StiFile) -> StiResult<()> {
-        if let Some(image) = sti_file.images.first() {
+        if sti_file.header.num_images > 1 {
+            for image in &sti_file.images {
+                let header = image.header.as_ref().ok_or_else(|| StiError::InvalidFormat(
+                    "Multi-image 16-bit STI files require sub-image headers".to_string()
+                ))?;
+                Self::write_sub_image_header(cursor, header)?;
+            }
+            for image in &sti_file.images {
+                cursor.write_all(&image.raw_data)?;
+            }
+        } else if let Some(image) = sti_file.images.first() {
             cursor.write_all(&image.raw_data)?;
         } else {
             return Err(StiError::InvalidFormat(
                 "16-bit STI file requires at least one image".to_string()
             ));
         }
+        
+        cursor.write_all(&sti_file.app_data_bytes())?;
         Ok(())
     }
     
@@ -349,6 +1167,592 @@ mod tests {
         let header = StiParser::parse_header(&mut cursor).unwrap();
         
         assert_eq!(header.signature, [b'S', b'T', b'C', b'I']);
-        assert_eq!(header.flags.indexed, true);
+        assert!(header.flags.indexed);
+    }
+    
+    fn single_frame_file_bytes() -> Vec<u8> {
+        let mut sti_file = StiFile::new();
+        sti_file.header.flags.indexed = true;
+        sti_file.header.flags.etrle_compressed = true;
+        sti_file.header.palette_colors = 256;
+        sti_file.header.num_images = 1;
+        sti_file.header.color_depth = 8;
+        sti_file.palette = Some([[0u8; 3]; 256]);
+        
+        let pixels = vec![0, 1, 2, 0];
+        let mut image = StiImage::with_header(StiSubImageHeader {
+            data_offset: 0,
+            data_size: 0,
+            offset_x: 0,
+            offset_y: 0,
+            height: 2,
+            width: 2,
+        });
+        image.raw_data = EtrleDecoder::new(2, 2).compress(&pixels).unwrap();
+        if let Some(header) = &mut image.header {
+            header.data_size = image.raw_data.len() as u32;
+        }
+        sti_file.header.compressed_size = image.raw_data.len() as u32;
+        sti_file.header.original_size = pixels.len() as u32;
+        image.decompressed_data = Some(pixels);
+        sti_file.images.push(image);
+        
+        StiParser::write(&sti_file).unwrap()
+    }
+    
+    #[test]
+    fn test_parallel_decode_keeps_frame_order() {
+        let mut sti_file = StiFile::new();
+        sti_file.header.flags.indexed = true;
+        sti_file.header.flags.etrle_compressed = true;
+        sti_file.header.palette_colors = 256;
+        sti_file.header.color_depth = 8;
+        sti_file.palette = Some([[0u8; 3]; 256]);
+        
+        let frame_count = PARALLEL_DECODE_MIN_FRAMES * 3;
+        let mut data_offset = 0;
+        for i in 0..frame_count {
+            let pixels = vec![i as u8 + 1, 0, i as u8 + 1, i as u8 + 2];
+            let mut image = StiImage::with_header(StiSubImageHeader {
+                data_offset,
+                data_size: 0,
+                offset_x: 0,
+                offset_y: 0,
+                height: 2,
+                width: 2,
+            });
+            image.raw_data = EtrleDecoder::new(2, 2).compress(&pixels).unwrap();
+            if let Some(header) = &mut image.header {
+                header.data_size = image.raw_data.len() as u32;
+            }
+            data_offset += image.raw_data.len() as u32;
+            sti_file.images.push(image);
+        }
+        sti_file.header.num_images = frame_count as u16;
+        
+        let parsed = StiParser::parse(&StiParser::write(&sti_file).unwrap()).unwrap();
+        for (i, image) in parsed.images.iter().enumerate() {
+            assert_eq!(image.decompressed_data, Some(vec![i as u8 + 1, 0, i as u8 + 1, i as u8 + 2]));
+        }
+    }
+    
+    // Timing comparison, run with `cargo test --release -- --ignored bench_parallel_decode`
+    #[test]
+    #[ignore]
+    fn bench_parallel_decode_100_frames() {
+        const FRAMES: usize = 100;
+        const SIZE: u16 = 256;
+    
+        // Runs of varying length with transparent gaps, so ETRLE has real work to do
+        let mut builder = crate::sti::builder::StiBuilder::new_indexed([[0u8; 3]; 256]);
+        for frame in 0..FRAMES {
+            let pixels = (0..SIZE as usize * SIZE as usize)
+                .map(|i| if (i / 7 + frame) % 5 == 0 { 0 } else { ((i * 31 + frame) % 255) as u8 + 1 })
+                .collect();
+            builder = builder.add_frame(SIZE, SIZE, pixels, 0, 0);
+        }
+        let data = StiParser::write(&builder.build().unwrap()).unwrap();
+        let undecoded = StiParser::parse_with_options(&data, &ParseOptions { images: Some(0..0), ..ParseOptions::default() }).unwrap();
+    
+        let start = std::time::Instant::now();
+        let sequential: Vec<Vec<u8>> = undecoded.images.iter()
+            .map(|image| EtrleDecoder::new(image.width, image.height).decompress(&image.raw_data).unwrap())
+            .collect();
+        let sequential_time = start.elapsed();
+    
+        let start = std::time::Instant::now();
+        let parsed = StiParser::parse(&data).unwrap();
+        let parallel_time = start.elapsed();
+    
+        println!(
+            "{} frames of {}x{}: sequential decode {:?}, parse with parallel decode {:?}",
+            FRAMES, SIZE, SIZE, sequential_time, parallel_time
+        );
+        for (image, expected) in parsed.images.iter().zip(&sequential) {
+            assert_eq!(image.decompressed_data.as_ref(), Some(expected));
+        }
+    }
+    
+    #[test]
+    fn test_reserved_flag_bits_round_trip() {
+        let mut data = single_frame_file_bytes();
+        let flags = u32::from_le_bytes([data[16], data[17], data[18], data[19]]) | 0x8000_0100;
+        data[16..20].copy_from_slice(&flags.to_le_bytes());
+        
+        let mut sti_file = StiParser::parse(&data).unwrap();
+        assert_eq!(sti_file.header.raw_flags, flags);
+        assert_eq!(StiParser::write(&sti_file).unwrap(), data);
+        
+        // Edits to known flags keep the reserved bits
+        sti_file.header.flags.transparent = true;
+        let written = StiParser::write(&sti_file).unwrap();
+        assert_eq!(u32::from_le_bytes([written[16], written[17], written[18], written[19]]), flags | 0x01);
+    }
+    
+    #[test]
+    fn test_8bit_alpha_table() {
+        let mut data = single_frame_file_bytes();
+        data[16] |= 0x02; // STCI_ALPHA
+        assert!(StiParser::parse(&data).unwrap().images[0].alpha.is_none());
+        
+        data.extend_from_slice(&[0, 255, 128, 255]);
+        let sti_file = StiParser::parse(&data).unwrap();
+        assert_eq!(sti_file.images[0].alpha, Some(vec![0, 255, 128, 255]));
+        assert_eq!(StiParser::write(&sti_file).unwrap(), data);
+    }
+    
+    #[test]
+    fn test_16_color_palette() {
+        let mut palette = [[0u8; 3]; 256];
+        for (index, color) in palette.iter_mut().enumerate().take(16) {
+            *color = [index as u8 * 16, 1, 2];
+        }
+        let mut sti_file = crate::sti::builder::StiBuilder::new_indexed(palette)
+            .add_frame(2, 2, vec![0, 15, 3, 0], 0, 0)
+            .build()
+            .unwrap();
+        sti_file.header.palette_colors = 16;
+        
+        let data = StiParser::write(&sti_file).unwrap();
+        let full_palette_len = {
+            sti_file.header.palette_colors = 256;
+            StiParser::write(&sti_file).unwrap().len()
+        };
+        assert_eq!(data.len(), full_palette_len - 240 * 3);
+        
+        let parsed = StiParser::parse(&data).unwrap();
+        assert_eq!(parsed.header.palette_colors, 16);
+        assert_eq!(parsed.palette, Some(palette));
+        assert_eq!(parsed.images[0].decompressed_data, Some(vec![0, 15, 3, 0]));
+        assert_eq!(StiParser::write(&parsed).unwrap(), data);
+        
+        // Index 16 lies past the end of the palette
+        sti_file.header.palette_colors = 16;
+        sti_file.images[0].decompressed_data = Some(vec![0, 16, 3, 0]);
+        crate::sti::builder::encode_frames(&mut sti_file, &[]).unwrap();
+        assert!(matches!(StiParser::parse(&StiParser::write(&sti_file).unwrap()), Err(StiError::InvalidFormat(_))));
+    }
+    
+    #[test]
+    fn test_truncated_files() {
+        assert!(matches!(
+            StiParser::parse(&[]),
+            Err(StiError::InvalidFormat(message)) if message == "file smaller than 64-byte header"
+        ));
+        assert!(matches!(StiParser::parse_headers_only(b"STCI"), Err(StiError::InvalidFormat(_))));
+        
+        // Cut inside the palette
+        let data = single_frame_file_bytes();
+        assert!(matches!(StiParser::parse(&data[..64 + 100]), Err(StiError::InvalidFormat(_))));
+        assert!(matches!(StiParser::parse_headers_only(&data[..64 + 100]), Err(StiError::InvalidFormat(_))));
+        
+        // Declare two images (bytes 29-30) but keep only part of the first sub-image header
+        let mut data = data;
+        data[28..30].copy_from_slice(&2u16.to_le_bytes());
+        assert!(matches!(StiParser::parse(&data[..64 + 768 + 8]), Err(StiError::InvalidFormat(_))));
+        assert!(matches!(StiParser::parse_headers_only(&data[..64 + 768 + 8]), Err(StiError::InvalidFormat(_))));
+    }
+    
+    #[test]
+    fn test_16bit_app_data_round_trip() {
+        let pixels: Vec<u8> = [0xF800u16, 0x07E0].iter().flat_map(|value| value.to_le_bytes()).collect();
+        for frames in [1, 2] {
+            let mut builder = crate::sti::builder::StiBuilder::new_rgb16((0xF800, 0x07E0, 0x001F));
+            for _ in 0..frames {
+                builder = builder.add_frame(2, 1, pixels.clone(), 0, 0);
+            }
+            let mut sti_file = builder.build().unwrap();
+            sti_file.app_data = vec![1, 2, 3, 4, 5];
+            sti_file.header.app_data_size = 5;
+            
+            let data = StiParser::write(&sti_file).unwrap();
+            let parsed = StiParser::parse(&data).unwrap();
+            assert_eq!(parsed.app_data, vec![1, 2, 3, 4, 5]);
+            assert_eq!(parsed.images[frames as usize - 1].decompressed_data, Some(pixels.clone()));
+            assert_eq!(StiParser::write(&parsed).unwrap(), data);
+            
+            // A block that runs past the end of the file is dropped, not an error
+            let parsed = StiParser::parse(&data[..data.len() - 2]).unwrap();
+            assert!(parsed.app_data.is_empty());
+        }
+    }
+    
+    #[test]
+    fn test_rejects_absurd_data_sizes() {
+        // Sub-image data_size (bytes 5-8 of the sub-header) near the u32 limit
+        let mut data = single_frame_file_bytes();
+        data[64 + 768 + 4..64 + 768 + 8].copy_from_slice(&0xFFFF_FFF0u32.to_le_bytes());
+        assert!(matches!(StiParser::parse(&data), Err(StiError::InvalidFormat(_))));
+        
+        // A 65535x65535 frame (height and width are bytes 13-16) backed by a few ETRLE bytes
+        let mut data = single_frame_file_bytes();
+        data[64 + 768 + 12..64 + 768 + 16].copy_from_slice(&[0xFF; 4]);
+        assert!(matches!(StiParser::parse(&data), Err(StiError::InvalidFormat(_))));
+        
+        // A lone 16-bit image sized 65535x65535 in a 64-byte file
+        let mut header = crate::sti::builder::StiBuilder::new_rgb16((0xF800, 0x07E0, 0x001F))
+            .add_frame(1, 1, vec![0, 0], 0, 0)
+            .build()
+            .unwrap()
+            .header;
+        header.width = u16::MAX;
+        header.height = u16::MAX;
+        let mut data = Vec::new();
+        StiParser::write_header(&mut Cursor::new(&mut data), &header).unwrap();
+        assert!(matches!(StiParser::parse(&data), Err(StiError::InvalidFormat(_))));
+    }
+    
+    #[test]
+    fn test_parse_salvage_keeps_good_frames() {
+        let sti_file = crate::sti::builder::StiBuilder::new_indexed([[0u8; 3]; 256])
+            .add_frame(2, 1, vec![1, 2], 0, 0)
+            .add_frame(2, 1, vec![3, 4], 0, 0)
+            .add_frame(1, 1, vec![5], 0, 0)
+            .build()
+            .unwrap();
+        let mut data = StiParser::write(&sti_file).unwrap();
+        let image_data_start = 64 + 768 + 3 * 16;
+        // Frame 1 claims a 127-pixel literal run, frame 2 a data size past the end of the file
+        data[image_data_start + sti_file.images[0].raw_data.len()] = 0x7F;
+        data[64 + 768 + 32 + 4..64 + 768 + 32 + 8].copy_from_slice(&5000u32.to_le_bytes());
+        assert!(StiParser::parse(&data).is_err());
+        
+        let (salvaged, warnings) = StiParser::parse_salvage(&data).unwrap();
+        assert_eq!(salvaged.images.len(), 3);
+        assert_eq!(salvaged.images[0].decompressed_data, Some(vec![1, 2]));
+        assert_eq!(salvaged.images[1].decompressed_data, Some(vec![0, 0]));
+        assert_eq!(salvaged.images[2].decompressed_data, Some(vec![0]));
+        assert_eq!(warnings.iter().map(|warning| warning.image).collect::<Vec<_>>(), vec![Some(2), Some(1)]);
+        
+        // Absurd declared sizes become empty frames rather than multi-gigabyte blank ones
+        let mut data = StiParser::write(&sti_file).unwrap();
+        data[64 + 768 + 32 + 12..64 + 768 + 32 + 16].copy_from_slice(&[0xFF; 4]);
+        let (salvaged, warnings) = StiParser::parse_salvage(&data).unwrap();
+        assert_eq!((salvaged.images[2].width, salvaged.images[2].height), (0, 0));
+        assert_eq!(salvaged.images[2].decompressed_data, Some(Vec::new()));
+        assert_eq!(warnings.len(), 1);
+        
+        let mut rgb = StiFile::new();
+        rgb.header.flags.rgb = true;
+        rgb.header.color_depth = 16;
+        rgb.header.width = u16::MAX;
+        rgb.header.height = u16::MAX;
+        let mut data = Vec::new();
+        StiParser::write_header(&mut Cursor::new(&mut data), &rgb.header).unwrap();
+        let (salvaged, warnings) = StiParser::parse_salvage(&data).unwrap();
+        assert_eq!(salvaged.dimensions(), (0, 0));
+        assert_eq!(warnings[0].image, Some(0));
+        
+        // A clean file salvages without warnings
+        let (_, warnings) = StiParser::parse_salvage(&StiParser::write(&sti_file).unwrap()).unwrap();
+        assert!(warnings.is_empty());
+    }
+    
+    #[test]
+    fn test_strict_size_validation() {
+        let data = single_frame_file_bytes();
+        let strict = ParseOptions { strict: true, ..ParseOptions::default() };
+        assert!(StiParser::parse_with_options(&data, &strict).is_ok());
+        
+        // Corrupt compressed_size (bytes 9-12)
+        let mut corrupt = data;
+        corrupt[8..12].copy_from_slice(&999u32.to_le_bytes());
+        assert!(matches!(
+            StiParser::parse_with_options(&corrupt, &strict),
+            Err(StiError::InvalidFormat(_))
+        ));
+        
+        // Lenient parsing keeps today's behavior
+        assert!(StiParser::parse(&corrupt).is_ok());
+    }
+    
+    #[test]
+    fn test_parse_headers_only() {
+        let data = single_frame_file_bytes();
+        let sti_file = StiParser::parse_headers_only(&data).unwrap();
+        
+        assert_eq!(sti_file.images.len(), 1);
+        assert_eq!((sti_file.images[0].width, sti_file.images[0].height), (2, 2));
+        assert!(sti_file.images[0].raw_data.is_empty());
+        assert!(sti_file.images[0].decompressed_data.is_none());
+        assert!(sti_file.palette.is_some());
+    }
+    
+    #[test]
+    fn test_parse_with_range() {
+        let data = single_frame_file_bytes();
+        let skipped = StiParser::parse_with_range(&data, Some(1..2)).unwrap();
+        
+        assert!(skipped.images[0].decompressed_data.is_none());
+        assert!(!skipped.images[0].raw_data.is_empty());
+        assert_eq!(StiParser::decompress_image(&skipped, 0).unwrap(), Some(vec![0, 1, 2, 0]));
+        
+        let selected = StiParser::parse_with_range(&data, Some(0..1)).unwrap();
+        assert_eq!(selected.images[0].decompressed_data, Some(vec![0, 1, 2, 0]));
+    }
+    
+    #[test]
+    fn test_multi_image_16bit_round_trip() {
+        let mut sti_file = StiFile::new();
+        sti_file.header.flags.rgb = true;
+        sti_file.header.color_depth = 16;
+        sti_file.header.num_images = 2;
+        sti_file.header.width = 2;
+        sti_file.header.height = 1;
+        
+        let frames = [(2u16, 1u16, vec![0x1F, 0x00, 0xE0, 0x07]), (1, 2, vec![0x00, 0xF8, 0xFF, 0xFF])];
+        let mut data_offset = 0;
+        for (width, height, pixels) in frames {
+            let mut image = StiImage::with_header(StiSubImageHeader {
+                data_offset,
+                data_size: pixels.len() as u32,
+                offset_x: 1,
+                offset_y: -1,
+                height,
+                width,
+            });
+            data_offset += pixels.len() as u32;
+            image.raw_data = pixels.clone();
+            image.decompressed_data = Some(pixels);
+            sti_file.images.push(image);
+        }
+        
+        let data = StiParser::write(&sti_file).unwrap();
+        let parsed = StiParser::parse(&data).unwrap();
+        
+        assert_eq!(parsed.header.num_images, 2);
+        assert_eq!(parsed.images.len(), 2);
+        assert_eq!((parsed.images[1].width, parsed.images[1].height), (1, 2));
+        assert_eq!(parsed.images[0].decompressed_data, Some(vec![0x1F, 0x00, 0xE0, 0x07]));
+        assert_eq!(parsed.images[1].decompressed_data, Some(vec![0x00, 0xF8, 0xFF, 0xFF]));
+        assert_eq!(StiParser::parse_headers_only(&data).unwrap().images.len(), 2);
+    }
+    
+    #[test]
+    fn test_odd_app_data_round_trip() {
+        let mut sti_file = StiParser::parse(&single_frame_file_bytes()).unwrap();
+        sti_file.app_data = vec![1, 2, 3, 4, 5];
+        sti_file.header.app_data_size = 5;
+        
+        let parsed = StiParser::parse(&StiParser::write(&sti_file).unwrap()).unwrap();
+        assert_eq!(parsed.app_data, vec![1, 2, 3, 4, 5]);
+        assert!(parsed.animation_data.is_empty());
+        
+        let mut animated = parsed.clone();
+        animated.app_data = StiAnimationData::single_frame(4).to_bytes().to_vec();
+        animated.header.app_data_size = 16;
+        let parsed = StiParser::parse(&StiParser::write(&animated).unwrap()).unwrap();
+        assert_eq!(parsed.animation_data.len(), 1);
+        assert_eq!(parsed.animation_data[0].frame_count, 4);
+    }
+    
+    #[test]
+    fn test_identify() {
+        let data = single_frame_file_bytes();
+        let identified = StiParser::identify(&data, data.len() as u64);
+        assert_eq!(identified.detected_variant, "8-bit indexed, ETRLE");
+        assert_eq!(identified.format_confidence, 1.0);
+        assert!(identified.recommendation.is_none());
+        
+        // A zero palette size and a header claiming more data than the file holds
+        let mut broken = data.clone();
+        broken[24..28].copy_from_slice(&0u32.to_le_bytes());
+        broken[8..12].copy_from_slice(&10_000u32.to_le_bytes());
+        let identified = StiParser::identify(&broken, broken.len() as u64);
+        assert_eq!(identified.issues.len(), 2);
+        assert!(identified.recommendation.is_some());
+        
+        let mut not_sti = data;
+        not_sti[0..4].copy_from_slice(b"PNG\0");
+        assert_eq!(StiParser::identify(&not_sti, 64).format_confidence, 0.0);
+    }
+    
+    #[test]
+    fn test_decode_report() {
+        let sti_file = StiParser::parse(&single_frame_file_bytes()).unwrap();
+        let reports = StiParser::decode_report(&sti_file);
+        assert_eq!(reports.len(), 1);
+        assert!(!reports[0].has_mismatch(), "{:?}", reports[0]);
+        
+        // Trailing bytes after the last row are stored but never consumed
+        let mut padded = sti_file.clone();
+        padded.images[0].raw_data.push(0x05);
+        if let Some(header) = &mut padded.images[0].header {
+            header.data_size += 1;
+        }
+        let reports = StiParser::decode_report(&padded);
+        assert_eq!(reports[0].consumed + 1, reports[0].declared_size);
+        assert!(reports[0].has_mismatch());
+    }
+    
+    #[test]
+    fn test_sequential_offsets() {
+        let mut sti_file = StiParser::parse(&single_frame_file_bytes()).unwrap();
+        let mut second = sti_file.images[0].clone();
+        second.decompressed_data = Some(vec![3, 3, 0, 4]);
+        second.raw_data = EtrleDecoder::new(2, 2).compress(&[3, 3, 0, 4]).unwrap();
+        let first_size = sti_file.images[0].raw_data.len() as u32;
+        if let Some(header) = &mut second.header {
+            header.data_size = second.raw_data.len() as u32;
+            header.data_offset = first_size + 7; // Corrupt: points past the real data
+        }
+        sti_file.images.push(second);
+        sti_file.header.num_images = 2;
+        sti_file.header.compressed_size += sti_file.images[1].raw_data.len() as u32;
+        let mut data = StiParser::write(&sti_file).unwrap();
+        data.extend_from_slice(&[0u8; 16]); // Keep the bad offset inside the file
+        
+        let default = StiParser::parse(&data).unwrap();
+        assert_ne!(default.images[1].decompressed_data, Some(vec![3, 3, 0, 4]));
+        
+        let sequential = ParseOptions { sequential_offsets: true, ..ParseOptions::default() };
+        let repaired = StiParser::parse_with_options(&data, &sequential).unwrap();
+        assert_eq!(repaired.images[1].decompressed_data, Some(vec![3, 3, 0, 4]));
+    }
+    
+    #[test]
+    fn test_data_size_past_end_of_file() {
+        let mut data = single_frame_file_bytes();
+        // data_size of the first sub-header sits right after header and palette
+        let data_size_pos = 64 + 768 + 4;
+        data[data_size_pos..data_size_pos + 4].copy_from_slice(&5000u32.to_le_bytes());
+        
+        assert!(matches!(StiParser::parse(&data), Err(StiError::InvalidFormat(_))));
+    }
+    
+    #[test]
+    fn test_single_image_without_sub_header() {
+        let data = single_frame_file_bytes();
+        let sub_header_start = 64 + 768;
+        
+        // Move the dimensions into the main header and drop the sub-header
+        let mut headerless = data[..sub_header_start].to_vec();
+        headerless[20..22].copy_from_slice(&2u16.to_le_bytes());
+        headerless[22..24].copy_from_slice(&2u16.to_le_bytes());
+        headerless.extend_from_slice(&data[sub_header_start + 16..]);
+        
+        let sti_file = StiParser::parse(&headerless).unwrap();
+        assert_eq!(sti_file.images.len(), 1);
+        assert_eq!(sti_file.images[0].width, 2);
+        assert_eq!(sti_file.images[0].decompressed_data, Some(vec![0, 1, 2, 0]));
+        
+        // The same dimensions in the main header with a sub-header still present
+        let mut with_header = data;
+        with_header[20..22].copy_from_slice(&2u16.to_le_bytes());
+        with_header[22..24].copy_from_slice(&2u16.to_le_bytes());
+        let sti_file = StiParser::parse(&with_header).unwrap();
+        assert_eq!(sti_file.images[0].decompressed_data, Some(vec![0, 1, 2, 0]));
+    }
+    
+    #[test]
+    fn test_inflate_zlib() {
+        use flate2::write::ZlibEncoder;
+        use flate2::Compression;
+        
+        let pixels: Vec<u8> = (0..200u32).map(|i| (i % 7) as u8).collect();
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&pixels).unwrap();
+        let compressed = encoder.finish().unwrap();
+        
+        assert_eq!(StiParser::inflate_zlib(&compressed, 16).unwrap(), pixels);
+        assert_eq!(StiParser::inflate_zlib(&compressed, 0).unwrap(), pixels);
+        
+        // Dropping the tail of the stream must be reported, not silently padded
+        let truncated = &compressed[..compressed.len() / 2];
+        assert!(StiParser::inflate_zlib(truncated, 16).is_err());
+    }
+    
+    // Random small files built with StiBuilder must survive write -> parse unchanged
+    mod round_trip {
+        use super::*;
+        use crate::sti::builder::StiBuilder;
+        use proptest::prelude::*;
+        
+        type Frame = (u16, u16, Vec<u8>, i16, i16);
+        
+        fn frame(bytes_per_pixel: usize) -> impl Strategy<Value = Frame> {
+            (1u16..8, 1u16..8).prop_flat_map(move |(width, height)| (
+                Just(width),
+                Just(height),
+                proptest::collection::vec(any::<u8>(), width as usize * height as usize * bytes_per_pixel),
+                any::<i16>(),
+                any::<i16>(),
+            ))
+        }
+        
+        fn add_frames(mut builder: StiBuilder, frames: Vec<Frame>) -> StiBuilder {
+            for (width, height, pixels, offset_x, offset_y) in frames {
+                builder = builder.add_frame(width, height, pixels, offset_x, offset_y);
+            }
+            builder
+        }
+        
+        // Flag bits above the six StiFlags models must be written back as read
+        fn with_reserved_flags(mut sti_file: StiFile, reserved: u32) -> StiFile {
+            sti_file.header.raw_flags = sti_file.header.flags_value() | (reserved << 6);
+            sti_file
+        }
+        
+        fn indexed_file() -> impl Strategy<Value = StiFile> {
+            (
+                proptest::collection::vec(any::<[u8; 3]>(), 256),
+                any::<u8>(),
+                proptest::collection::vec(frame(1), 1..5),
+                proptest::option::of(proptest::collection::vec(any::<[u8; 16]>(), 4)),
+                any::<u32>(),
+            ).prop_map(|(colors, transparent, frames, records, reserved)| {
+                let mut palette = [[0u8; 3]; 256];
+                palette.copy_from_slice(&colors);
+                let frame_count = frames.len();
+                let mut builder = add_frames(StiBuilder::new_indexed(palette).transparent_color(transparent as u32), frames);
+                if let Some(records) = records {
+                    builder = builder.animation_data(
+                        records.iter().take(frame_count).map(StiAnimationData::from_bytes).collect()
+                    );
+                }
+                with_reserved_flags(builder.build().unwrap(), reserved)
+            })
+        }
+        
+        fn rgb16_file() -> impl Strategy<Value = StiFile> {
+            (
+                prop_oneof![Just((0xF800, 0x07E0, 0x001F)), Just((0x7C00, 0x03E0, 0x001F))],
+                any::<u16>(),
+                proptest::collection::vec(frame(2), 1..4),
+                any::<u32>(),
+            ).prop_map(|(masks, transparent, frames, reserved)| {
+                let builder = add_frames(StiBuilder::new_rgb16(masks).transparent_color(transparent as u32), frames);
+                with_reserved_flags(builder.build().unwrap(), reserved)
+            })
+        }
+        
+        fn check_round_trip(built: &StiFile) -> Result<(), TestCaseError> {
+            let parsed = StiParser::parse(&StiParser::write(built).unwrap()).unwrap();
+            
+            prop_assert_eq!(serde_json::to_value(&parsed.header).unwrap(), serde_json::to_value(&built.header).unwrap());
+            prop_assert_eq!(parsed.palette, built.palette);
+            prop_assert_eq!(parsed.images.len(), built.images.len());
+            for (parsed_image, built_image) in parsed.images.iter().zip(&built.images) {
+                prop_assert_eq!((parsed_image.width, parsed_image.height), (built_image.width, built_image.height));
+                let offsets = |image: &StiImage| image.header.as_ref().map(|h| (h.offset_x, h.offset_y));
+                prop_assert_eq!(offsets(parsed_image), offsets(built_image));
+                prop_assert_eq!(&parsed_image.decompressed_data, &built_image.decompressed_data);
+            }
+            let records = |sti_file: &StiFile| sti_file.animation_data.iter().map(|anim| anim.to_bytes()).collect::<Vec<_>>();
+            prop_assert_eq!(records(&parsed), records(built));
+            Ok(())
+        }
+        
+        proptest! {
+            #[test]
+            fn indexed_files_round_trip(sti_file in indexed_file()) {
+                check_round_trip(&sti_file)?;
+            }
+            
+            #[test]
+            fn rgb16_files_round_trip(sti_file in rgb16_file()) {
+                check_round_trip(&sti_file)?;
+            }
+        }
     }
 }
\ No newline at end of file
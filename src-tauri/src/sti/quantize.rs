@@ -0,0 +1,153 @@
+use std::collections::HashMap;
+
+/// Build a palette of at most `max_colors` entries using median-cut quantization
+///
+/// If the input has no more unique colors than requested, those colors are
+/// returned exactly (most frequent first) so small images convert losslessly.
+pub fn median_cut_palette(colors: &[[u8; 3]], max_colors: usize) -> Vec<[u8; 3]> {
+    if colors.is_empty() || max_colors == 0 {
+        return Vec::new();
+    }
+    
+    let mut histogram: HashMap<[u8; 3], u32> = HashMap::new();
+    for color in colors {
+        *histogram.entry(*color).or_insert(0) += 1;
+    }
+    
+    let mut unique: Vec<([u8; 3], u32)> = histogram.into_iter().collect();
+    unique.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    
+    if unique.len() <= max_colors {
+        return unique.into_iter().map(|(color, _)| color).collect();
+    }
+    
+    let mut boxes = vec![unique];
+    while boxes.len() < max_colors {
+        // Split the box with the widest channel range
+        let candidate = boxes.iter()
+            .enumerate()
+            .filter(|(_, b)| b.len() > 1)
+            .map(|(i, b)| {
+                let (channel, range) = widest_channel(b);
+                (i, channel, range)
+            })
+            .max_by_key(|&(_, _, range)| range);
+        
+        let (box_index, channel, _) = match candidate {
+            Some(candidate) => candidate,
+            None => break, // Every box holds a single color
+        };
+        
+        let mut color_box = boxes.swap_remove(box_index);
+        color_box.sort_by_key(|(color, _)| color[channel]);
+        
+        // Split at the pixel-weighted median, keeping both halves non-empty
+        let total: u64 = color_box.iter().map(|(_, count)| *count as u64).sum();
+        let mut running = 0u64;
+        let mut split = 1;
+        for (i, (_, count)) in color_box.iter().enumerate() {
+            running += *count as u64;
+            if running * 2 >= total {
+                split = i + 1;
+                break;
+            }
+        }
+        let split = split.clamp(1, color_box.len() - 1);
+        
+        let upper = color_box.split_off(split);
+        boxes.push(color_box);
+        boxes.push(upper);
+    }
+    
+    boxes.iter().map(|b| average_color(b)).collect()
+}
+
+/// Index of the palette entry closest to `color` by squared RGB distance,
+/// searching only from `first_index` onward
+pub fn nearest_palette_index(palette: &[[u8; 3]], color: [u8; 3], first_index: usize) -> usize {
+    let mut best_index = first_index;
+    let mut best_distance = u32::MAX;
+    
+    for (i, entry) in palette.iter().enumerate().skip(first_index) {
+        let dr = (entry[0] as i32 - color[0] as i32).unsigned_abs();
+        let dg = (entry[1] as i32 - color[1] as i32).unsigned_abs();
+        let db = (entry[2] as i32 - color[2] as i32).unsigned_abs();
+        let distance = dr * dr + dg * dg + db * db;
+        
+        if distance < best_distance {
+            best_distance = distance;
+            best_index = i;
+            if distance == 0 {
+                break;
+            }
+        }
+    }
+    
+    best_index
+}
+
+fn widest_channel(colors: &[([u8; 3], u32)]) -> (usize, u8) {
+    let mut best = (0, 0);
+    for channel in 0..3 {
+        let min = colors.iter().map(|(c, _)| c[channel]).min().unwrap_or(0);
+        let max = colors.iter().map(|(c, _)| c[channel]).max().unwrap_or(0);
+        if max - min > best.1 {
+            best = (channel, max - min);
+        }
+    }
+    best
+}
+
+fn average_color(colors: &[([u8; 3], u32)]) -> [u8; 3] {
+    let mut sums = [0u64; 3];
+    let mut total = 0u64;
+    for (color, count) in colors {
+        for (sum, &value) in sums.iter_mut().zip(color.iter()) {
+            *sum += value as u64 * *count as u64;
+        }
+        total += *count as u64;
+    }
+    
+    if total == 0 {
+        return [0, 0, 0];
+    }
+    [
+        ((sums[0] + total / 2) / total) as u8,
+        ((sums[1] + total / 2) / total) as u8,
+        ((sums[2] + total / 2) / total) as u8,
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_few_colors_are_kept_exactly() {
+        let colors = vec![[1, 2, 3], [1, 2, 3], [200, 0, 0]];
+        let palette = median_cut_palette(&colors, 16);
+        assert_eq!(palette, vec![[1, 2, 3], [200, 0, 0]]);
+    }
+
+    #[test]
+    fn test_median_cut_limits_palette_size() {
+        let colors: Vec<[u8; 3]> = (0..=255u8).map(|v| [v, v / 2, 255 - v]).collect();
+        let palette = median_cut_palette(&colors, 8);
+        assert_eq!(palette.len(), 8);
+        
+        // Every input color should land reasonably close to some palette entry
+        for color in colors {
+            let nearest = palette[nearest_palette_index(&palette, color, 0)];
+            let error = (nearest[0] as i32 - color[0] as i32).abs();
+            assert!(error <= 32, "{:?} mapped to {:?}", color, nearest);
+        }
+    }
+
+    #[test]
+    fn test_nearest_palette_index_skips_reserved_entries() {
+        let palette = [[0, 0, 0], [10, 10, 10], [250, 250, 250]];
+        assert_eq!(nearest_palette_index(&palette, [0, 0, 0], 0), 0);
+        assert_eq!(nearest_palette_index(&palette, [0, 0, 0], 1), 1);
+        assert_eq!(nearest_palette_index(&palette, [240, 255, 250], 1), 2);
+    }
+}
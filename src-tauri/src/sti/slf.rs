@@ -0,0 +1,165 @@
+use std::io::{Read, Seek, SeekFrom};
+use byteorder::{LittleEndian, ReadBytesExt};
+use crate::sti::types::*;
+
+/// Size of the SLF library header at the start of the archive
+pub const SLF_HEADER_SIZE: u64 = 532;
+/// Size of one file table entry; the table sits at the very end of the archive
+pub const SLF_ENTRY_SIZE: u64 = 280;
+
+const NAME_SIZE: usize = 256;
+const STATE_OK: u8 = 0x00;
+
+/// One file stored in an SLF archive
+#[derive(Debug, Clone, PartialEq)]
+pub struct SlfEntry {
+    pub name: String, // Path inside the library, using backslashes as JA2 does
+    pub offset: u32,
+    pub length: u32,
+}
+
+/// Header and file table of a JA2 SLF archive
+#[derive(Debug, Clone)]
+pub struct SlfArchive {
+    pub lib_name: String,
+    pub path: String, // Directory the library is mounted at in the game's data tree
+    pub entries: Vec<SlfEntry>,
+}
+
+impl SlfArchive {
+    /// Read the header and file table, skipping entries marked as deleted or outdated
+    pub fn read<R: Read + Seek>(reader: &mut R) -> StiResult<Self> {
+        let file_len = reader.seek(SeekFrom::End(0))?;
+        if file_len < SLF_HEADER_SIZE {
+            return Err(StiError::InvalidFormat("file smaller than 532-byte SLF header".to_string()));
+        }
+        
+        reader.seek(SeekFrom::Start(0))?;
+        let lib_name = read_name(reader)?;
+        let path = read_name(reader)?;
+        let num_entries = reader.read_i32::<LittleEndian>()?;
+        // iUsed, iSort, iVersion, fContainsSubDirectories and iReserved aren't needed to read entries
+        
+        if num_entries < 0 {
+            return Err(StiError::InvalidFormat(format!("Negative SLF entry count {}", num_entries)));
+        }
+        let table_size = num_entries as u64 * SLF_ENTRY_SIZE;
+        if table_size > file_len - SLF_HEADER_SIZE {
+            return Err(StiError::InvalidFormat(format!(
+                "SLF declares {} entries but the file is only {} bytes",
+                num_entries, file_len
+            )));
+        }
+        
+        reader.seek(SeekFrom::Start(file_len - table_size))?;
+        let mut entries = Vec::with_capacity(num_entries as usize);
+        for _ in 0..num_entries {
+            let mut record = [0u8; SLF_ENTRY_SIZE as usize];
+            reader.read_exact(&mut record)?;
+            
+            let name = decode_name(&record[..NAME_SIZE]);
+            let offset = u32::from_le_bytes([record[256], record[257], record[258], record[259]]);
+            let length = u32::from_le_bytes([record[260], record[261], record[262], record[263]]);
+            let state = record[264];
+            
+            if state != STATE_OK || name.is_empty() {
+                continue;
+            }
+            if offset as u64 + length as u64 > file_len {
+                return Err(StiError::InvalidFormat(format!(
+                    "SLF entry '{}' ({} bytes at offset {}) extends past the end of the file",
+                    name, length, offset
+                )));
+            }
+            entries.push(SlfEntry { name, offset, length });
+        }
+        
+        Ok(Self { lib_name, path, entries })
+    }
+    
+    /// Find an entry by name, ignoring case and slash direction like the game does
+    pub fn find(&self, name: &str) -> Option<&SlfEntry> {
+        let wanted = normalize_name(name);
+        self.entries.iter().find(|entry| normalize_name(&entry.name) == wanted)
+    }
+    
+    /// Entries with an .sti extension
+    pub fn sti_entries(&self) -> impl Iterator<Item = &SlfEntry> {
+        self.entries.iter().filter(|entry| entry.name.to_ascii_lowercase().ends_with(".sti"))
+    }
+    
+    /// Read the bytes of one entry
+    pub fn read_entry<R: Read + Seek>(reader: &mut R, entry: &SlfEntry) -> StiResult<Vec<u8>> {
+        reader.seek(SeekFrom::Start(entry.offset as u64))?;
+        let mut data = vec![0u8; entry.length as usize];
+        reader.read_exact(&mut data)?;
+        Ok(data)
+    }
+}
+
+fn read_name<R: Read>(reader: &mut R) -> StiResult<String> {
+    let mut buffer = [0u8; NAME_SIZE];
+    reader.read_exact(&mut buffer)?;
+    Ok(decode_name(&buffer))
+}
+
+// Names are NUL-terminated; anything after the terminator is leftover garbage
+fn decode_name(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).into_owned()
+}
+
+fn normalize_name(name: &str) -> String {
+    name.replace('/', "\\").to_ascii_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    
+    fn archive_bytes(files: &[(&str, &[u8], u8)]) -> Vec<u8> {
+        let mut data = vec![0u8; SLF_HEADER_SIZE as usize];
+        data[..8].copy_from_slice(b"test.slf");
+        data[512..516].copy_from_slice(&(files.len() as i32).to_le_bytes());
+        
+        let mut records = Vec::new();
+        for (name, contents, state) in files {
+            let mut record = vec![0u8; SLF_ENTRY_SIZE as usize];
+            record[..name.len()].copy_from_slice(name.as_bytes());
+            record[256..260].copy_from_slice(&(data.len() as u32).to_le_bytes());
+            record[260..264].copy_from_slice(&(contents.len() as u32).to_le_bytes());
+            record[264] = *state;
+            records.extend(record);
+            data.extend_from_slice(contents);
+        }
+        data.extend(records);
+        data
+    }
+    
+    #[test]
+    fn test_read_archive() {
+        let data = archive_bytes(&[
+            ("ANIMS\\SOLDIER.STI", &b"first"[..], STATE_OK),
+            ("readme.txt", &b"notes"[..], STATE_OK),
+            ("OLD.STI", &b"gone"[..], 0xFF),
+        ]);
+        let mut cursor = Cursor::new(data);
+        let archive = SlfArchive::read(&mut cursor).unwrap();
+        
+        assert_eq!(archive.lib_name, "test.slf");
+        assert_eq!(archive.entries.len(), 2);
+        assert_eq!(archive.sti_entries().count(), 1);
+        
+        let entry = archive.find("anims/soldier.sti").unwrap();
+        assert_eq!(SlfArchive::read_entry(&mut cursor, entry).unwrap(), b"first");
+    }
+    
+    #[test]
+    fn test_rejects_truncated_archive() {
+        let mut data = archive_bytes(&[("A.STI", &b"data"[..], STATE_OK)]);
+        data[512..516].copy_from_slice(&100i32.to_le_bytes());
+        assert!(matches!(SlfArchive::read(&mut Cursor::new(data)), Err(StiError::InvalidFormat(_))));
+        assert!(SlfArchive::read(&mut Cursor::new(vec![0u8; 10])).is_err());
+    }
+}
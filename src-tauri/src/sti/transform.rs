@@ -0,0 +1,111 @@
+use super::types::{StiError, StiResult};
+
+/// Lossless flips and quarter-turn rotations of a frame
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FrameTransform {
+    FlipHorizontal,
+    FlipVertical,
+    Rotate90,
+    Rotate180,
+    Rotate270,
+}
+
+impl FrameTransform {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "flip_h" => Some(FrameTransform::FlipHorizontal),
+            "flip_v" => Some(FrameTransform::FlipVertical),
+            "rotate_90" => Some(FrameTransform::Rotate90),
+            "rotate_180" => Some(FrameTransform::Rotate180),
+            "rotate_270" => Some(FrameTransform::Rotate270),
+            _ => None,
+        }
+    }
+
+    /// Whether the transform swaps width and height
+    pub fn swaps_dimensions(&self) -> bool {
+        matches!(self, FrameTransform::Rotate90 | FrameTransform::Rotate270)
+    }
+}
+
+/// Permute the pixels of a frame, returning the new data and dimensions
+///
+/// Pixels are moved as opaque `bytes_per_pixel` units (palette indices or
+/// RGB565 words), so no colors are ever blended or changed. Rotations are clockwise.
+pub fn transform_pixels(
+    data: &[u8],
+    width: u16,
+    height: u16,
+    bytes_per_pixel: usize,
+    transform: FrameTransform,
+) -> StiResult<(Vec<u8>, u16, u16)> {
+    let (w, h) = (width as usize, height as usize);
+    if data.len() != w * h * bytes_per_pixel {
+        return Err(StiError::InvalidFormat(format!(
+            "Frame data is {} bytes but {}x{} at {} byte(s) per pixel needs {}",
+            data.len(), width, height, bytes_per_pixel, w * h * bytes_per_pixel
+        )));
+    }
+
+    let (out_width, out_height) = if transform.swaps_dimensions() { (height, width) } else { (width, height) };
+    let out_w = out_width as usize;
+    let mut out = vec![0u8; data.len()];
+
+    for y in 0..h {
+        for x in 0..w {
+            let (dst_x, dst_y) = match transform {
+                FrameTransform::FlipHorizontal => (w - 1 - x, y),
+                FrameTransform::FlipVertical => (x, h - 1 - y),
+                FrameTransform::Rotate90 => (h - 1 - y, x),
+                FrameTransform::Rotate180 => (w - 1 - x, h - 1 - y),
+                FrameTransform::Rotate270 => (y, w - 1 - x),
+            };
+            let src = (y * w + x) * bytes_per_pixel;
+            let dst = (dst_y * out_w + dst_x) * bytes_per_pixel;
+            out[dst..dst + bytes_per_pixel].copy_from_slice(&data[src..src + bytes_per_pixel]);
+        }
+    }
+
+    Ok((out, out_width, out_height))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 3x2 frame:
+    // 1 2 3
+    // 4 5 6
+    const FRAME: [u8; 6] = [1, 2, 3, 4, 5, 6];
+
+    #[test]
+    fn test_flip_twice_is_identity() {
+        for transform in [FrameTransform::FlipHorizontal, FrameTransform::FlipVertical] {
+            let (once, w, h) = transform_pixels(&FRAME, 3, 2, 1, transform).unwrap();
+            assert_ne!(once, FRAME);
+            let (twice, w, h) = transform_pixels(&once, w, h, 1, transform).unwrap();
+            assert_eq!((twice.as_slice(), w, h), (&FRAME[..], 3, 2));
+        }
+    }
+
+    #[test]
+    fn test_rotations() {
+        let (rotated, w, h) = transform_pixels(&FRAME, 3, 2, 1, FrameTransform::Rotate90).unwrap();
+        assert_eq!((w, h), (2, 3));
+        assert_eq!(rotated, vec![4, 1, 5, 2, 6, 3]);
+
+        let (rotated, w, h) = transform_pixels(&FRAME, 3, 2, 1, FrameTransform::Rotate270).unwrap();
+        assert_eq!((w, h), (2, 3));
+        assert_eq!(rotated, vec![3, 6, 2, 5, 1, 4]);
+
+        let (rotated, _, _) = transform_pixels(&FRAME, 3, 2, 1, FrameTransform::Rotate180).unwrap();
+        assert_eq!(rotated, vec![6, 5, 4, 3, 2, 1]);
+    }
+
+    #[test]
+    fn test_16bit_pixels_move_as_units() {
+        let data = [0x01, 0x02, 0x03, 0x04];
+        let (flipped, _, _) = transform_pixels(&data, 2, 1, 2, FrameTransform::FlipHorizontal).unwrap();
+        assert_eq!(flipped, vec![0x03, 0x04, 0x01, 0x02]);
+    }
+}
@@ -1,5 +1,6 @@
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
+use crate::sti::etrle::RowEndMode;
 
 #[derive(Error, Debug)]
 pub enum StiError {
@@ -11,6 +12,8 @@ pub enum StiError {
     Decompression(String),
     #[error("Unsupported format: {0}")]
     UnsupportedFormat(String),
+    #[error("Encoding error: {0}")]
+    Encoding(String),
 }
 
 pub type StiResult<T> = Result<T, StiError>;
@@ -39,19 +42,22 @@ impl From<u32> for StiFlags {
     }
 }
 
-impl Into<u32> for StiFlags {
-    fn into(self) -> u32 {
-        let mut flags = 0u32;
-        if self.transparent { flags |= 0x01; }
-        if self.alpha { flags |= 0x02; }
-        if self.rgb { flags |= 0x04; }
-        if self.indexed { flags |= 0x08; }
-        if self.zlib_compressed { flags |= 0x10; }
-        if self.etrle_compressed { flags |= 0x20; }
-        flags
+impl From<StiFlags> for u32 {
+    fn from(flags: StiFlags) -> Self {
+        let mut value = 0u32;
+        if flags.transparent { value |= 0x01; }
+        if flags.alpha { value |= 0x02; }
+        if flags.rgb { value |= 0x04; }
+        if flags.indexed { value |= 0x08; }
+        if flags.zlib_compressed { value |= 0x10; }
+        if flags.etrle_compressed { value |= 0x20; }
+        value
     }
 }
 
+/// Flag bits modelled by `StiFlags`
+pub const KNOWN_FLAG_BITS: u32 = 0x3F;
+
 /// Main STI file header (64 bytes)
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StiHeader {
@@ -60,8 +66,10 @@ pub struct StiHeader {
     pub compressed_size: u32,         // Compressed size in bytes
     pub transparent_color: u32,       // Transparent color index (8-bit only)
     pub flags: StiFlags,              // Format flags
-    pub height: u16,                  // Image height (16-bit only)
-    pub width: u16,                   // Image width (16-bit only)
+    #[serde(default)]
+    pub raw_flags: u32,               // Flags word as read, keeps reserved bits StiFlags doesn't model
+    pub height: u16,                  // Image height (16-bit only, intentionally 0 in 8-bit files)
+    pub width: u16,                   // Image width (16-bit only, intentionally 0 in 8-bit files)
     
     // Color masks and depths for 16-bit files
     pub red_mask: u32,
@@ -97,6 +105,7 @@ impl Default for StiHeader {
                 zlib_compressed: false,
                 etrle_compressed: false,
             },
+            raw_flags: 0,
             height: 0,
             width: 0,
             red_mask: 0,
@@ -115,6 +124,93 @@ impl Default for StiHeader {
     }
 }
 
+impl StiHeader {
+    /// Flags word to store: the known bits from `flags`, every other bit as read from the file
+    pub fn flags_value(&self) -> u32 {
+        let known: u32 = self.flags.into();
+        (self.raw_flags & !KNOWN_FLAG_BITS) | known
+    }
+    
+    /// Palette entries stored in an 8-bit file: `palette_colors` when it's 1-256, the standard 256 otherwise
+    pub fn palette_entries(&self) -> usize {
+        match self.palette_colors {
+            1..=256 => self.palette_colors as usize,
+            _ => 256,
+        }
+    }
+    
+    /// Palette index that ETRLE transparent runs stand for in 8-bit files
+    ///
+    /// Must fit a palette index; out-of-range values fall back to the standard index 0.
+    pub fn etrle_transparent_index(&self) -> u8 {
+        u8::try_from(self.transparent_color).unwrap_or(0)
+    }
+    
+    /// Color masks for 16-bit pixels, falling back to RGB565 when the header stores none
+    pub fn rgb16_masks(&self) -> (u32, u32, u32) {
+        if self.red_mask == 0 && self.green_mask == 0 && self.blue_mask == 0 {
+            (0xF800, 0x07E0, 0x001F)
+        } else {
+            (self.red_mask, self.green_mask, self.blue_mask)
+        }
+    }
+    
+    /// Decode a 16-bit pixel to 24-bit RGB using the header's channel masks
+    pub fn decode_rgb16(&self, pixel: u16) -> [u8; 3] {
+        let (red_mask, green_mask, blue_mask) = self.rgb16_masks();
+        [
+            Self::expand_channel(pixel, red_mask),
+            Self::expand_channel(pixel, green_mask),
+            Self::expand_channel(pixel, blue_mask),
+        ]
+    }
+    
+    /// Alpha of a 16-bit pixel when the header declares an alpha channel (e.g. ARGB1555), None otherwise
+    pub fn decode_alpha16(&self, pixel: u16) -> Option<u8> {
+        if self.alpha_mask & 0xFFFF == 0 || self.alpha_depth == 0 {
+            return None;
+        }
+        Some(Self::expand_channel(pixel, self.alpha_mask & 0xFFFF))
+    }
+    
+    /// Alpha bits to OR into an encoded 16-bit pixel, 0 when the header declares no alpha channel
+    pub fn encode_alpha16(&self, alpha: u8) -> u16 {
+        if self.alpha_mask & 0xFFFF == 0 || self.alpha_depth == 0 {
+            return 0;
+        }
+        Self::reduce_channel(alpha, self.alpha_mask & 0xFFFF) as u16
+    }
+    
+    /// Encode 24-bit RGB into a 16-bit pixel, the exact inverse of `decode_rgb16`
+    pub fn encode_rgb16(&self, rgb: [u8; 3]) -> u16 {
+        let (red_mask, green_mask, blue_mask) = self.rgb16_masks();
+        (Self::reduce_channel(rgb[0], red_mask)
+            | Self::reduce_channel(rgb[1], green_mask)
+            | Self::reduce_channel(rgb[2], blue_mask)) as u16
+    }
+    
+    /// Scale a 0-255 channel down to the masked bit range, rounding to nearest
+    fn reduce_channel(value: u8, mask: u32) -> u32 {
+        if mask == 0 {
+            return 0;
+        }
+        let shift = mask.trailing_zeros();
+        let max = mask >> shift;
+        (((value as u32 * max + 127) / 255) << shift) & mask
+    }
+    
+    /// Scale the masked channel bits up to the full 0-255 range
+    fn expand_channel(pixel: u16, mask: u32) -> u8 {
+        if mask == 0 {
+            return 0;
+        }
+        let shift = mask.trailing_zeros();
+        let max = mask >> shift;
+        let value = (pixel as u32 & mask) >> shift;
+        ((value * 255 + max / 2) / max) as u8
+    }
+}
+
 /// Sub-image header for 8-bit multi-image files (16 bytes each)
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StiSubImageHeader {
@@ -127,7 +223,7 @@ pub struct StiSubImageHeader {
 }
 
 /// Animation data for animated STI files (16 bytes per image)
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct StiAnimationData {
     pub unknown1: [u8; 8],            // Unknown purpose, usually 0
     pub frame_count: u8,              // Number of frames in this direction (first frame only)
@@ -135,6 +231,60 @@ pub struct StiAnimationData {
     pub unknown3: [u8; 6],            // Unknown purpose, usually 0
 }
 
+/// Flag in `unknown2` marking the first frame of an animation sequence
+pub const ANIMATION_FLAG_ANIMATED: u8 = 0x02;
+
+impl StiAnimationData {
+    /// Decode one 16-byte record
+    pub fn from_bytes(record: &[u8; 16]) -> Self {
+        let mut anim = Self::default();
+        anim.unknown1.copy_from_slice(&record[0..8]);
+        anim.frame_count = record[8];
+        anim.unknown2 = record[9];
+        anim.unknown3.copy_from_slice(&record[10..16]);
+        anim
+    }
+
+    /// Encode as the 16-byte record stored in the file
+    pub fn to_bytes(&self) -> [u8; 16] {
+        let mut record = [0u8; 16];
+        record[0..8].copy_from_slice(&self.unknown1);
+        record[8] = self.frame_count;
+        record[9] = self.unknown2;
+        record[10..16].copy_from_slice(&self.unknown3);
+        record
+    }
+
+    /// Record for the first frame of a sequence (one direction) spanning `count` frames
+    pub fn single_frame(count: u8) -> Self {
+        Self {
+            frame_count: count,
+            unknown2: if count > 0 { ANIMATION_FLAG_ANIMATED } else { 0 },
+            ..Self::default()
+        }
+    }
+
+    /// Number of frames in the sequence starting at this image, 0 for continuation frames
+    pub fn frames(&self) -> u8 {
+        self.frame_count
+    }
+
+    /// Whether this image starts an animation sequence
+    pub fn starts_sequence(&self) -> bool {
+        self.unknown2 & ANIMATION_FLAG_ANIMATED != 0
+    }
+
+    /// Wall orientation used by JA2 structure tiles (byte 0 of the record)
+    pub fn wall_orientation(&self) -> u8 {
+        self.unknown1[0]
+    }
+
+    /// Frame index within its sequence as stored by JA2 (byte 7 of the record)
+    pub fn current_frame(&self) -> u8 {
+        self.unknown1[7]
+    }
+}
+
 /// Color palette for 8-bit images (256 colors * 3 bytes = 768 bytes)
 pub type StiPalette = [[u8; 3]; 256];
 
@@ -144,17 +294,21 @@ pub struct StiFile {
     pub header: StiHeader,
     pub palette: Option<StiPalette>,
     pub images: Vec<StiImage>,
-    pub animation_data: Vec<StiAnimationData>,
+    pub animation_data: Vec<StiAnimationData>, // View over app_data when it holds whole 16-byte records
+    pub app_data: Vec<u8>,                     // Raw application data block as stored in the file
+    pub stale_compressed_size: bool,           // Parsed header's compressed_size was 0 or didn't match the frames
 }
 
 /// Individual image within an STI file
 #[derive(Debug, Clone)]
 pub struct StiImage {
-    pub header: Option<StiSubImageHeader>, // None for 16-bit files
+    pub header: Option<StiSubImageHeader>, // None for single-image 16-bit files
     pub raw_data: Vec<u8>,                 // Raw compressed data
     pub decompressed_data: Option<Vec<u8>>, // Decompressed pixel data
     pub width: u16,
     pub height: u16,
+    pub alpha: Option<Vec<u8>>,            // Per-pixel alpha from an 8-bit file's alpha table (see FILEFORMAT.md)
+    pub etrle_row_end: RowEndMode,         // How the stored ETRLE stream ended its rows, reused on save
 }
 
 impl StiImage {
@@ -165,6 +319,8 @@ impl StiImage {
             decompressed_data: None,
             width,
             height,
+            alpha: None,
+            etrle_row_end: RowEndMode::default(),
         }
     }
     
@@ -175,10 +331,20 @@ impl StiImage {
             header: Some(header),
             raw_data: Vec::new(),
             decompressed_data: None,
+            alpha: None,
+            etrle_row_end: RowEndMode::default(),
         }
     }
 }
 
+/// Pixel layout of an STI file, derived from its flags
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StiFormat {
+    Indexed8,
+    Rgb16,
+    Unknown,
+}
+
 impl StiFile {
     pub fn new() -> Self {
         Self {
@@ -186,6 +352,60 @@ impl StiFile {
             palette: None,
             images: Vec::new(),
             animation_data: Vec::new(),
+            app_data: Vec::new(),
+            stale_compressed_size: false,
+        }
+    }
+}
+
+impl Default for StiFile {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StiFile {
+    /// Application data block to store: the animation records, or the raw block read
+    /// from the file unchanged when there are no records to write in its place
+    pub fn app_data_bytes(&self) -> Vec<u8> {
+        if self.animation_data.is_empty() {
+            return self.app_data.clone();
+        }
+        self.animation_data.iter().flat_map(|anim| anim.to_bytes()).collect()
+    }
+    
+    /// Overall size of the file's image: the header size for 16-bit files, and for 8-bit
+    /// files (whose header stores 0) the bounding box of every frame placed at its offset
+    pub fn dimensions(&self) -> (u16, u16) {
+        if self.is_16bit() {
+            return (self.header.width, self.header.height);
+        }
+        if self.images.is_empty() {
+            return (0, 0);
+        }
+        
+        let (mut min_x, mut min_y) = (i32::MAX, i32::MAX);
+        let (mut max_x, mut max_y) = (i32::MIN, i32::MIN);
+        for image in &self.images {
+            let (offset_x, offset_y) = image.header.as_ref()
+                .map(|header| (header.offset_x as i32, header.offset_y as i32))
+                .unwrap_or((0, 0));
+            min_x = min_x.min(offset_x);
+            min_y = min_y.min(offset_y);
+            max_x = max_x.max(offset_x + image.width as i32);
+            max_y = max_y.max(offset_y + image.height as i32);
+        }
+        
+        let clamp = |extent: i32| extent.clamp(0, u16::MAX as i32) as u16;
+        (clamp(max_x - min_x), clamp(max_y - min_y))
+    }
+    
+    /// Pixel layout named by the flags; `Unknown` when neither or both of rgb/indexed are set
+    pub fn format(&self) -> StiFormat {
+        match (self.header.flags.indexed, self.header.flags.rgb) {
+            (true, false) => StiFormat::Indexed8,
+            (false, true) => StiFormat::Rgb16,
+            _ => StiFormat::Unknown,
         }
     }
     
@@ -204,4 +424,151 @@ impl StiFile {
     pub fn is_compressed(&self) -> bool {
         self.header.flags.etrle_compressed || self.header.flags.zlib_compressed
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_animation_single_frame() {
+        let first = StiAnimationData::single_frame(8);
+        assert_eq!(first.frames(), 8);
+        assert!(first.starts_sequence());
+        assert_eq!(first.unknown1, [0; 8]);
+        assert_eq!(first.unknown3, [0; 6]);
+
+        let continuation = StiAnimationData::single_frame(0);
+        assert_eq!(continuation.frames(), 0);
+        assert!(!continuation.starts_sequence());
+    }
+    
+    #[test]
+    fn test_8bit_dimensions_from_frames() {
+        let mut sti_file = StiFile::new();
+        sti_file.header.flags.indexed = true;
+        for (width, height, offset_x, offset_y) in [(10, 4, -2, 0), (3, 8, 5, -1)] {
+            sti_file.images.push(StiImage::with_header(StiSubImageHeader {
+                data_offset: 0,
+                data_size: 0,
+                offset_x,
+                offset_y,
+                height,
+                width,
+            }));
+        }
+        // x spans -2..8 and y spans -1..7
+        assert_eq!(sti_file.dimensions(), (10, 8));
+        
+        sti_file.header.flags.indexed = false;
+        sti_file.header.flags.rgb = true;
+        sti_file.header.width = 640;
+        sti_file.header.height = 480;
+        assert_eq!(sti_file.dimensions(), (640, 480));
+    }
+    
+    #[test]
+    fn test_format_from_flags() {
+        let mut sti_file = StiFile::new();
+        assert_eq!(sti_file.format(), StiFormat::Unknown);
+        
+        sti_file.header.flags.indexed = true;
+        assert_eq!(sti_file.format(), StiFormat::Indexed8);
+        
+        sti_file.header.flags.rgb = true;
+        assert_eq!(sti_file.format(), StiFormat::Unknown);
+        
+        sti_file.header.flags.indexed = false;
+        assert_eq!(sti_file.format(), StiFormat::Rgb16);
+    }
+    
+    #[test]
+    fn test_decode_rgb565() {
+        // All-zero masks fall back to 565
+        let header = StiHeader::default();
+        
+        assert_eq!(header.decode_rgb16(0xFFFF), [255, 255, 255]);
+        assert_eq!(header.decode_rgb16(0x0000), [0, 0, 0]);
+        assert_eq!(header.decode_rgb16(0xF800), [255, 0, 0]);
+        assert_eq!(header.decode_rgb16(0x07E0), [0, 255, 0]);
+        assert_eq!(header.decode_rgb16(0x001F), [0, 0, 255]);
+        assert_eq!(header.decode_rgb16(0x8410), [132, 130, 132]);
+    }
+    
+    #[test]
+    fn test_decode_argb1555_alpha() {
+        let mut header = StiHeader::default();
+        assert_eq!(header.decode_alpha16(0xFFFF), None);
+        
+        header.red_mask = 0x7C00;
+        header.green_mask = 0x03E0;
+        header.blue_mask = 0x001F;
+        header.alpha_mask = 0x8000;
+        header.alpha_depth = 1;
+        
+        assert_eq!(header.decode_alpha16(0x8000), Some(255));
+        assert_eq!(header.decode_alpha16(0x7FFF), Some(0));
+        assert_eq!(header.decode_rgb16(0xFC00), [255, 0, 0]);
+    }
+    
+    #[test]
+    fn test_decode_rgb555() {
+        let header = StiHeader {
+            red_mask: 0x7C00,
+            green_mask: 0x03E0,
+            blue_mask: 0x001F,
+            red_depth: 5,
+            green_depth: 5,
+            blue_depth: 5,
+            ..StiHeader::default()
+        };
+        
+        assert_eq!(header.decode_rgb16(0x7FFF), [255, 255, 255]);
+        assert_eq!(header.decode_rgb16(0x7C00), [255, 0, 0]);
+        assert_eq!(header.decode_rgb16(0x03E0), [0, 255, 0]);
+        assert_eq!(header.decode_rgb16(0x001F), [0, 0, 255]);
+        // The unused top bit must not leak into red
+        assert_eq!(header.decode_rgb16(0x8000), [0, 0, 0]);
+    }
+    
+    #[test]
+    fn test_rgb16_round_trip() {
+        let header = StiHeader::default();
+        
+        // Every 16-bit value survives decode then encode unchanged
+        for pixel in 0..=u16::MAX {
+            assert_eq!(header.encode_rgb16(header.decode_rgb16(pixel)), pixel);
+        }
+        
+        // Truecolor input stays within one quantization step of the original
+        let colors = [
+            [0, 0, 0], [255, 255, 255], [128, 128, 128], [255, 0, 0],
+            [0, 255, 0], [0, 0, 255], [12, 200, 77], [250, 3, 129],
+            [99, 99, 1], [7, 8, 9],
+        ];
+        for color in colors {
+            let decoded = header.decode_rgb16(header.encode_rgb16(color));
+            for channel in 0..3 {
+                let step = if channel == 1 { 255.0 / 63.0 } else { 255.0 / 31.0 };
+                let error = (decoded[channel] as f64 - color[channel] as f64).abs();
+                assert!(error <= step, "{:?} decoded as {:?}", color, decoded);
+            }
+        }
+    }
+    
+    #[test]
+    fn test_rgb555_round_trip() {
+        let header = StiHeader {
+            red_mask: 0x7C00,
+            green_mask: 0x03E0,
+            blue_mask: 0x001F,
+            ..StiHeader::default()
+        };
+        
+        // The unused top bit is dropped, every 15-bit value survives decode then encode
+        for pixel in 0..=0x7FFFu16 {
+            assert_eq!(header.encode_rgb16(header.decode_rgb16(pixel)), pixel);
+            assert_eq!(header.encode_rgb16(header.decode_rgb16(pixel | 0x8000)), pixel);
+        }
+    }
+}
@@ -1,7 +1,7 @@
 import { useState, useRef } from 'react';
 import './App.css';
 import { StiFileInfo, StiImageData, StiMetadata, EditableStiFile } from './types/sti';
-import { StiApi, StiEditingApi, DirectoryApi } from './services/api';
+import { StiApi, StiEditingApi, DirectoryApi, errorMessage } from './services/api';
 import FileExplorer, { FileExplorerHandle } from './components/FileExplorer';
 import ImageViewer from './components/ImageViewer';
 import ImageEditor from './components/ImageEditor';
@@ -85,7 +85,7 @@ function App() {
     } catch (error) {
       setState(prev => ({
         ...prev,
-        error: error instanceof Error ? error.message : 'Unknown error',
+        error: errorMessage(error, 'Unknown error'),
         loading: false,
       }));
     }
@@ -109,7 +109,7 @@ function App() {
     } catch (error) {
       setState(prev => ({
         ...prev,
-        error: error instanceof Error ? error.message : 'Failed to load image',
+        error: errorMessage(error, 'Failed to load image'),
         loading: false,
       }));
     }
@@ -160,7 +160,7 @@ function App() {
     } catch (error) {
       setState(prev => ({
         ...prev,
-        error: error instanceof Error ? error.message : 'Failed to enter edit mode',
+        error: errorMessage(error, 'Failed to enter edit mode'),
         loading: false,
       }));
     }
@@ -219,7 +219,7 @@ function App() {
       // Fallback to exit edit mode and show error
       setState(prev => ({
         ...prev,
-        error: error instanceof Error ? error.message : 'Failed to reload after save',
+        error: errorMessage(error, 'Failed to reload after save'),
         loading: false,
         isEditMode: false,
         editableSti: null,
@@ -310,7 +310,7 @@ function App() {
       console.error('Failed to refresh file after update:', error);
       setState(prev => ({
         ...prev,
-        error: error instanceof Error ? error.message : 'Failed to refresh file',
+        error: errorMessage(error, 'Failed to refresh file'),
         loading: false,
       }));
     }
@@ -1,6 +1,6 @@
 import React, { useState, useEffect } from 'react';
 import { StiFileInfo } from '../types/sti';
-import { StiApi } from '../services/api';
+import { StiApi, errorMessage } from '../services/api';
 import { open } from '@tauri-apps/plugin-dialog';
 import './ExportDialog.css';
 
@@ -187,7 +187,7 @@ const ExportDialog: React.FC<ExportDialogProps> = ({
       onClose();
     } catch (error) {
       console.error('Export failed:', error);
-      alert(`Export failed: ${error}`);
+      alert(`Export failed: ${errorMessage(error)}`);
     } finally {
       setIsExporting(false);
       setExportProgress({ current: 0, total: 0 });
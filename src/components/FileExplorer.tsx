@@ -1,5 +1,5 @@
 import React, { useState, forwardRef, useImperativeHandle } from 'react';
-import { DirectoryApi } from '../services/api';
+import { DirectoryApi, errorMessage } from '../services/api';
 import { DirectoryContents, DirectoryItem } from '../types/sti';
 import './FileExplorer.css';
 
@@ -63,7 +63,7 @@ const FileExplorer = forwardRef<FileExplorerHandle, FileExplorerProps>(({
         await loadDirectory(selectedPath);
       }
     } catch (err) {
-      setError(err instanceof Error ? err.message : 'Failed to select directory');
+      setError(errorMessage(err, 'Failed to select directory'));
     }
   };
 
@@ -78,7 +78,7 @@ const FileExplorer = forwardRef<FileExplorerHandle, FileExplorerProps>(({
         onCurrentPathChange(path);
       }
     } catch (err) {
-      setError(err instanceof Error ? err.message : 'Failed to load directory');
+      setError(errorMessage(err, 'Failed to load directory'));
       setDirectoryContents(null);
     } finally {
       setLoading(false);
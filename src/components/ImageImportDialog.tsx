@@ -1,6 +1,6 @@
 import React, { useState, useCallback, useEffect } from 'react';
 import { open } from '@tauri-apps/plugin-dialog';
-import { ImageImportApi, DirectoryApi, FileSystem } from '../services/api';
+import { ImageImportApi, DirectoryApi, FileSystem, errorMessage } from '../services/api';
 import { ImageAnalysisResult, ImportOptions } from '../types/sti';
 import './ImageImportDialog.css';
 
@@ -62,14 +62,14 @@ export const ImageImportDialog: React.FC<ImageImportDialogProps> = ({
             setNewStiPath(`${directory}/${baseName}.sti`);
           }
         } catch (err) {
-          setError(`Failed to analyze image: ${err}`);
+          setError(`Failed to analyze image: ${errorMessage(err)}`);
           setImageAnalysis(null);
         } finally {
           setIsAnalyzing(false);
         }
       }
     } catch (err) {
-      setError(`Failed to open file dialog: ${err}`);
+      setError(`Failed to open file dialog: ${errorMessage(err)}`);
       setIsAnalyzing(false);
     }
   }, [importDestination]);
@@ -109,7 +109,7 @@ export const ImageImportDialog: React.FC<ImageImportDialogProps> = ({
         setNewStiPath(selected);
       }
     } catch (err) {
-      setError(`Failed to select destination: ${err}`);
+      setError(`Failed to select destination: ${errorMessage(err)}`);
     }
   }, []);
 
@@ -169,7 +169,7 @@ export const ImageImportDialog: React.FC<ImageImportDialogProps> = ({
       setError(null);
       
     } catch (err) {
-      setError(`Import failed: ${err}`);
+      setError(`Import failed: ${errorMessage(err)}`);
     } finally {
       setIsImporting(false);
     }
@@ -1,6 +1,6 @@
 import React, { useState, useEffect } from 'react';
 import { StiFileInfo } from '../types/sti';
-import { StiApi, StiEditingApi } from '../services/api';
+import { StiApi, StiEditingApi, errorMessage } from '../services/api';
 import ConfirmationDialog, { ConfirmationDialogProps } from './ConfirmationDialog';
 import './ImageList.css';
 
@@ -160,7 +160,7 @@ const ImageList: React.FC<ImageListProps> = ({
           setConfirmationDialog(prev => ({ ...prev, isOpen: false }));
         } catch (error) {
           console.error('Failed to save reorder changes:', error);
-          alert(`Failed to save changes: ${error}`);
+          alert(`Failed to save changes: ${errorMessage(error)}`);
         } finally {
           setLoading(false);
         }
@@ -229,7 +229,7 @@ const ImageList: React.FC<ImageListProps> = ({
           setConfirmationDialog(prev => ({ ...prev, isOpen: false }));
         } catch (error) {
           console.error('Failed to remove images:', error);
-          alert(`Failed to remove images: ${error}`);
+          alert(`Failed to remove images: ${errorMessage(error)}`);
         } finally {
           setLoading(false);
         }
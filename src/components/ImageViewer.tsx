@@ -102,6 +102,15 @@ const ImageViewer: React.FC<ImageViewerProps> = ({
           imageDataArray.data[pixelIndex + 3] = 255; // Fully opaque
         }
       }
+    } else if (imageData.rgb_data) {
+      // 16-bit data already decoded by the backend using the header masks
+      for (let i = 0; i < imageData.width * imageData.height; i++) {
+        const pixelIndex = i * 4;
+        imageDataArray.data[pixelIndex] = imageData.rgb_data[i * 3];
+        imageDataArray.data[pixelIndex + 1] = imageData.rgb_data[i * 3 + 1];
+        imageDataArray.data[pixelIndex + 2] = imageData.rgb_data[i * 3 + 2];
+        imageDataArray.data[pixelIndex + 3] = 255;
+      }
     } else {
       // 16-bit RGB565 or direct RGB data
       for (let i = 0; i < imageData.data.length; i += 2) {
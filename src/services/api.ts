@@ -1,30 +1,128 @@
 import { invoke } from '@tauri-apps/api/core';
-import { StiFileInfo, StiImageData, StiMetadata, DirectoryContents, EditableStiFile, EditableImage, ImageAnalysisResult, ImportOptions } from '../types/sti';
+import { listen, UnlistenFn } from '@tauri-apps/api/event';
+import { StiFileInfo, StiImageData, StiMetadata, DirectoryContents, DirectoryTreeNode, EditableStiFile, EditableImage, ImageAnalysisResult, ImportOptions, ScanProgress, ScanResult, ExportOptions, CacheInvalidated, PixelValue, StructurePreview, ColorHistogram, FormatIdentification, ImageDecodeReport, MemoryEstimate, SalvagedStiFile, CommandError } from '../types/sti';
+
+// Readable text for anything a command or dialog rejects with, CommandError objects included
+export function errorMessage(error: unknown, fallback = 'Unknown error'): string {
+  if (error instanceof Error) {
+    return error.message;
+  }
+  if (typeof error === 'string') {
+    return error;
+  }
+  if (error && typeof error === 'object' && typeof (error as CommandError).message === 'string') {
+    return (error as CommandError).message;
+  }
+  return fallback;
+}
 
 export class StiApi {
   static async openStiFile(filePath: string): Promise<StiFileInfo> {
     return await invoke('open_sti_file', { filePath });
   }
 
+  // Header-only variant for file listings, skips frame decompression
+  static async openStiFileFast(filePath: string): Promise<StiFileInfo> {
+    return await invoke('open_sti_file_fast', { filePath });
+  }
+
+  // For files open_sti_file rejects: damaged frames load blank and are listed in warnings
+  static async openStiFileSalvage(filePath: string): Promise<SalvagedStiFile> {
+    return await invoke('open_sti_file_salvage', { filePath });
+  }
+
+  // Parses bytes the caller already holds; other calls then take `name` in place of a file path
+  static async openStiBytes(name: string, bytes: Uint8Array): Promise<StiFileInfo> {
+    return await invoke('open_sti_bytes', { name, bytes: Array.from(bytes) });
+  }
+
+  // STI entry names inside a JA2 .slf archive, e.g. "ANIMS\\SOLDIER.STI"
+  static async browseSlf(slfPath: string): Promise<string[]> {
+    return await invoke('browse_slf', { slfPath });
+  }
+
+  static async openStiFromSlf(slfPath: string, entryName: string): Promise<StiFileInfo> {
+    return await invoke('open_sti_from_slf', { slfPath, entryName });
+  }
+
   static async getStiImage(filePath: string, imageIndex: number): Promise<StiImageData> {
     return await invoke('get_sti_image', { filePath, imageIndex });
   }
 
+  // PNG data URL with palette and transparency applied, ready for <img src>
+  static async getFrameDataUrl(filePath: string, imageIndex: number): Promise<string> {
+    return await invoke('get_frame_data_url', { filePath, imageIndex });
+  }
+
+  // All frames when indices is omitted; only the first returned frame carries the palette
+  static async getStiImages(filePath: string, indices?: number[]): Promise<StiImageData[]> {
+    return await invoke('get_sti_images', { filePath, indices });
+  }
+
+  // Only the visible part of a frame, clipped to its bounds; offsets place the region like a frame
+  static async getRegion(filePath: string, imageIndex: number, x: number, y: number, w: number, h: number): Promise<StiImageData> {
+    return await invoke('get_region', { filePath, imageIndex, x, y, w, h });
+  }
+
+  // Header-only check of which STCI layout a file uses, for files that fail to open
+  static async identifySti(filePath: string): Promise<FormatIdentification> {
+    return await invoke('identify_sti', { filePath });
+  }
+
+  // Whether a frame decoded cleanly or was padded/truncated to fit
+  static async analyzeFrame(filePath: string, index: number): Promise<ImageDecodeReport> {
+    return await invoke('analyze_frame', { filePath, index });
+  }
+
+  // Decompressed frame and palette sizes against the stored bytes
+  static async estimateMemory(filePath: string): Promise<MemoryEstimate> {
+    return await invoke('estimate_memory', { filePath });
+  }
+
   static async getStiMetadata(filePath: string): Promise<StiMetadata> {
     return await invoke('get_sti_metadata', { filePath });
   }
 
+  // One frame's color usage, or the whole file's when imageIndex is omitted
+  static async colorHistogram(filePath: string, imageIndex?: number): Promise<ColorHistogram> {
+    return await invoke('color_histogram', { filePath, imageIndex });
+  }
+
   static async saveStiFile(filePath: string, stiData: any): Promise<void> {
     return await invoke('save_sti_file', { filePath, stiData });
   }
 
+  // Writes an edited copy elsewhere, the source file stays open and unchanged
+  static async saveStiFileAs(editableSti: EditableStiFile, outputPath: string, overwrite: boolean = false): Promise<void> {
+    return await invoke('save_sti_file_as', { editableSti, outputPath, overwrite });
+  }
+
+  // One transparent frame; 8-bit files without a palette get a grayscale ramp
+  static async createNewSti(
+    outputPath: string,
+    is8bit: boolean,
+    width: number,
+    height: number,
+    palette: [number, number, number][] | null = null,
+    transparentColor: number = 0
+  ): Promise<void> {
+    return await invoke('create_new_sti', { outputPath, is8bit, width, height, palette, transparentColor });
+  }
+
   static async exportImage(
     filePath: string,
     imageIndex: number,
     outputPath: string,
-    format: string
+    format: string,
+    transparent: boolean = false,
+    options?: ExportOptions
   ): Promise<void> {
-    return await invoke('export_image', { filePath, imageIndex, outputPath, format });
+    return await invoke('export_image', { filePath, imageIndex, outputPath, format, transparent, options });
+  }
+
+  // Fired whenever a save or edit leaves a file's cached metadata stale
+  static async onCacheInvalidated(handler: (event: CacheInvalidated) => void): Promise<UnlistenFn> {
+    return await listen<CacheInvalidated>('sti-cache-invalidated', (event) => handler(event.payload));
   }
 }
 
@@ -33,17 +131,61 @@ export class DirectoryApi {
     return await invoke('select_directory');
   }
 
-  static async browseDirectory(directoryPath: string): Promise<DirectoryContents> {
-    return await invoke('browse_directory', { directoryPath });
+  // Dot-prefixed files and directories are left out unless includeHidden is set
+  static async browseDirectory(directoryPath: string, maxDepth?: number, includeHidden: boolean = false): Promise<DirectoryContents> {
+    return await invoke('browse_directory', { directoryPath, maxDepth, includeHidden });
+  }
+
+  // Every directory below root that holds STI files, nested, in one call
+  static async buildDirectoryTree(root: string, maxDepth: number): Promise<DirectoryTreeNode> {
+    return await invoke('build_directory_tree', { root, maxDepth });
+  }
+
+  // Progress events carry the scan id, pass it to cancelScan to stop early with partial results
+  static async scanForStiFiles(directoryPath: string, recursive: boolean = true, scanId?: string, includeHidden: boolean = false): Promise<ScanResult> {
+    return await invoke('scan_for_sti_files', { directoryPath, recursive, scanId, includeHidden });
+  }
+
+  static async cancelScan(scanId: string): Promise<boolean> {
+    return await invoke('cancel_scan', { scanId });
+  }
+
+  static async onScanProgress(handler: (progress: ScanProgress) => void): Promise<UnlistenFn> {
+    return await listen<ScanProgress>('sti-scan-progress', (event) => handler(event.payload));
   }
 
-  static async scanForStiFiles(directoryPath: string, recursive: boolean = true): Promise<string[]> {
-    return await invoke('scan_for_sti_files', { directoryPath, recursive });
+  static async onScanComplete(handler: (progress: ScanProgress) => void): Promise<UnlistenFn> {
+    return await listen<ScanProgress>('sti-scan-complete', (event) => handler(event.payload));
   }
 
   static async clearStiCache(): Promise<void> {
     return await invoke('clear_sti_cache');
   }
+
+  // Paths whose unsaved edits would overwrite a change made on disk by another program
+  static async listEditConflicts(): Promise<string[]> {
+    return await invoke('list_edit_conflicts');
+  }
+
+  // After a rename or save-as, so the new path doesn't have to be parsed again
+  static async moveCacheEntry(oldPath: string, newPath: string): Promise<void> {
+    return await invoke('move_cache_entry', { oldPath, newPath });
+  }
+
+  // In-memory only, the defaults (50 files, 200 directories) return on restart
+  static async setCacheLimits(stiMax: number, dirMax: number): Promise<void> {
+    return await invoke('set_cache_limits', { stiMax, dirMax });
+  }
+
+  // Confine saves and backups to these directories; an empty list allows writing anywhere
+  static async setAllowedRoots(roots: string[]): Promise<void> {
+    return await invoke('set_allowed_roots', { roots });
+  }
+
+  // Backend trace verbosity: 'off' | 'error' | 'warn' | 'info' | 'debug' | 'trace'
+  static async setLogLevel(level: string): Promise<void> {
+    return await invoke('set_log_level', { level });
+  }
 }
 
 export class StiEditingApi {
@@ -59,22 +201,88 @@ export class StiEditingApi {
     return await invoke('add_new_image', { filePath, imageData, position });
   }
 
+  // Frame filled with the transparent color; resolves to the index it was inserted at
+  static async addBlankFrame(filePath: string, width: number, height: number, position?: number): Promise<number> {
+    return await invoke('add_blank_frame', { filePath, width, height, position });
+  }
+
   static async reorderImages(filePath: string, newOrder: number[]): Promise<void> {
     return await invoke('reorder_images', { filePath, newOrder });
   }
 
+  static async swapFrames(filePath: string, a: number, b: number): Promise<void> {
+    return await invoke('swap_frames', { filePath, a, b });
+  }
+
   static async deleteImage(filePath: string, imageIndex: number): Promise<void> {
     return await invoke('delete_image', { filePath, imageIndex });
   }
 
-  static async removeImages(filePath: string, indices: number[]): Promise<void> {
-    return await invoke('remove_images_from_sti', { filePath, indices });
+  // Removes fully transparent frames from either end; resolves to how many were removed
+  static async trimBlankFrames(filePath: string, leading: boolean = true, trailing: boolean = true): Promise<number> {
+    return await invoke('trim_blank_frames', { filePath, leading, trailing });
+  }
+
+  // Removing frames of an animation sequence fails unless fixAnimation shortens the sequence instead
+  static async removeImages(filePath: string, indices: number[], fixAnimation = false): Promise<void> {
+    return await invoke('remove_images_from_sti', { filePath, indices, fixAnimation });
+  }
+
+  // Groups of byte-identical frames, each listed by index
+  static async findDuplicateFrames(filePath: string): Promise<{ groups: number[][]; duplicate_frames: number }> {
+    return await invoke('find_duplicate_frames', { filePath });
+  }
+
+  // Keeps the first frame of every group; resolves to how many frames were removed
+  static async dedupeFrames(filePath: string, updateAnimation = false): Promise<number> {
+    return await invoke('dedupe_frames', { filePath, updateAnimation });
+  }
+
+  // Dry runs of the structural edits above: same validation, nothing is backed up or written
+  static async previewAddNewImage(filePath: string, imageData: EditableImage, position?: number): Promise<StructurePreview> {
+    return await invoke('add_new_image_preview', { filePath, imageData, position });
+  }
+
+  static async previewReorderImages(filePath: string, newOrder: number[]): Promise<StructurePreview> {
+    return await invoke('reorder_images_preview', { filePath, newOrder });
+  }
+
+  static async previewRemoveImages(filePath: string, indices: number[], fixAnimation = false): Promise<StructurePreview> {
+    return await invoke('remove_images_from_sti_preview', { filePath, indices, fixAnimation });
+  }
+
+  // Resolve to false when there is nothing left to undo/redo
+  static async undoEdit(filePath: string): Promise<boolean> {
+    return await invoke('undo_edit', { filePath });
+  }
+
+  static async redoEdit(filePath: string): Promise<boolean> {
+    return await invoke('redo_edit', { filePath });
   }
 
   static async saveStiFile(filePath: string, editableSti: EditableStiFile): Promise<void> {
     return await invoke('save_sti_file', { filePath, editableSti });
   }
 
+  // Single-pixel access for paint tools; value is a palette index (8-bit) or packed RGB565 (16-bit)
+  static async getPixel(filePath: string, imageIndex: number, x: number, y: number): Promise<PixelValue> {
+    return await invoke('get_pixel', { filePath, imageIndex, x, y });
+  }
+
+  static async setPixel(filePath: string, imageIndex: number, x: number, y: number, value: number): Promise<void> {
+    return await invoke('set_pixel', { filePath, imageIndex, x, y, value });
+  }
+
+  // Points are [x, y, value] triples, applied all-or-nothing
+  static async setPixels(filePath: string, imageIndex: number, points: [number, number, number][]): Promise<void> {
+    return await invoke('set_pixels', { filePath, imageIndex, points });
+  }
+
+  // Repositions a frame in the cached file; the pixels and their stored bytes are left alone
+  static async setFrameOffset(filePath: string, imageIndex: number, offsetX: number, offsetY: number): Promise<void> {
+    return await invoke('set_frame_offset', { filePath, imageIndex, offsetX, offsetY });
+  }
+
   // Backup and safety operations
   static async createBackup(filePath: string): Promise<string> {
     return await invoke('create_sti_backup', { filePath });
@@ -84,8 +292,14 @@ export class StiEditingApi {
     return await invoke('validate_sti_integrity', { filePath });
   }
 
-  static async restoreFromBackup(filePath: string, backupPath: string): Promise<void> {
-    return await invoke('restore_sti_from_backup', { filePath, backupPath });
+  // One entry per STI file found; files that fail to parse are listed with their error as an issue
+  static async validateDirectory(directoryPath: string, recursive = true): Promise<{ path: string; parses: boolean; issues: string[] }[]> {
+    return await invoke('validate_directory', { directoryPath, recursive });
+  }
+
+  // Refuses a backup that doesn't parse unless forced; resolves to the restored file's info, null when forced past a bad backup
+  static async restoreFromBackup(filePath: string, backupPath: string, force = false): Promise<StiFileInfo | null> {
+    return await invoke('restore_sti_from_backup', { filePath, backupPath, force });
   }
 }
 
@@ -115,6 +329,15 @@ export class ImageImportApi {
       options
     });
   }
+
+  // Diffable form of a file: manifest.json plus one PNG per frame; both resolve to the frame count
+  static async exportBundle(filePath: string, outputDir: string): Promise<number> {
+    return await invoke('export_bundle', { filePath, outputDir });
+  }
+
+  static async importBundle(bundleDir: string, outputPath: string): Promise<number> {
+    return await invoke('import_bundle', { bundleDir, outputPath });
+  }
 }
 
 export class FileSystem {
@@ -14,6 +14,10 @@ export interface StiImageData {
   height: number;
   data: number[];
   palette?: number[][];
+  rgb_data?: number[]; // 16-bit only: RGB888 decoded using the header masks
+  alpha_data?: number[]; // 16-bit files with an alpha mask only: one alpha byte per pixel
+  offset_x: number;
+  offset_y: number;
 }
 
 export interface StiMetadata {
@@ -43,6 +47,17 @@ export interface StiMetadata {
   num_images: number;
   color_depth: number;
   app_data_size: number;
+  images?: StiImageMetadata[];
+  palette_color_count?: number; // 0 when the file has no palette
+  animation_frame_counts?: number[];
+}
+
+export interface StiImageMetadata {
+  width: number;
+  height: number;
+  offset_x: number;
+  offset_y: number;
+  data_size: number;
 }
 
 export interface DirectoryItem {
@@ -60,6 +75,14 @@ export interface DirectoryContents {
   sti_count: number;
 }
 
+export interface DirectoryTreeNode {
+  name: string;
+  path: string;
+  sti_count: number; // STI files directly inside this directory
+  total_sti_count: number; // Including all listed subdirectories
+  children: DirectoryTreeNode[];
+}
+
 export interface FileItem {
   name: string;
   path: string;
@@ -73,6 +96,9 @@ export interface EditableImage {
   width: number;
   height: number;
   data: number[]; // Palette indices for 8-bit, RGB565 bytes for 16-bit
+  offset_x?: number; // Sub-image placement, defaults to 0
+  offset_y?: number;
+  alpha?: number[] | null; // 8-bit files with an alpha table: one alpha byte per pixel
 }
 
 export interface EditableStiFile {
@@ -83,6 +109,18 @@ export interface EditableStiFile {
   images: EditableImage[];
   transparent_color: number;
   flags: number;
+  animation_data?: StiAnimationData[]; // One record per image when animated
+  app_data?: number[]; // Raw application data block, kept as is when it isn't animation records
+  main_header_dimensions?: boolean | null; // 8-bit single-image files: size also stored in the main header
+  palette_colors?: number | null; // 8-bit files: palette entries stored, all 256 when unset
+  rgb_masks?: [number, number, number] | null; // 16-bit files: red, green and blue masks, RGB565 when unset
+}
+
+export interface StiAnimationData {
+  unknown1: number[];
+  frame_count: number;
+  unknown2: number;
+  unknown3: number[];
 }
 
 // Image import types
@@ -100,4 +138,107 @@ export interface ImportOptions {
   palette_strategy: 'match' | 'regenerate' | 'auto'; // "match", "regenerate", or "auto"
   compression: boolean; // Use ETRLE compression
   transparent_color?: number; // Palette index for transparency
-}
\ No newline at end of file
+}
+export interface ExportOptions {
+  jpeg_quality?: number; // 1-100
+  png_compression?: 'default' | 'fast' | 'best';
+  gamma?: number; // Export-only gamma curve, 1.0 leaves colors alone
+  brightness?: number; // Export-only offset added to every channel, clamped to 0-255
+  indexed?: boolean; // PNG export of 8-bit files keeps the palette instead of expanding to RGB
+}
+
+export interface ScanProgress {
+  scan_id: string;
+  scanned: number; // Directory entries examined so far
+  found: number; // STI files found so far
+  current_path: string;
+}
+
+export interface ScanResult {
+  scan_id: string;
+  files: string[]; // Files found before the scan finished or was cancelled
+  aborted: boolean;
+}
+
+export interface CacheInvalidated {
+  file_path: string; // File whose cached state changed on the backend
+}
+
+export interface PixelValue {
+  index?: number; // 8-bit only
+  rgb565?: number; // 16-bit only
+  rgb: [number, number, number];
+}
+
+export interface StructurePreview {
+  image_count: number;
+  frames: { width: number; height: number }[];
+}
+
+// Pixel counts per palette index (8-bit), or the most used colors busiest first (16-bit)
+export type ColorHistogram =
+  | { format: 'indexed'; counts: number[] }
+  | { format: 'rgb16'; colors: { pixel: number; rgb: [number, number, number]; count: number }[] };
+
+export interface FormatIdentification {
+  detected_variant: string;
+  format_confidence: number; // 0..1
+  issues: string[];
+  recommendation: string | null;
+}
+
+export interface DecompressReport {
+  consumed: number;
+  trailing_bytes: number;
+  padded_pixels: number;
+  truncated_pixels: number;
+  wrapped_rows: number;
+  row_end: 'AlwaysMarker' | 'OmitLast';
+}
+
+export interface ImageDecodeReport {
+  index: number;
+  declared_size: number;
+  consumed: number;
+  decompressed_len: number;
+  expected_len: number;
+  etrle: DecompressReport | null; // ETRLE frames only
+  error: string | null;
+}
+
+export interface FrameMemoryEstimate {
+  index: number;
+  width: number;
+  height: number;
+  decompressed_size: number;
+  compressed_size: number;
+  compression_ratio: number | null; // null when the frame stores no bytes
+}
+
+// Sizes in bytes
+export interface MemoryEstimate {
+  decompressed_size: number;
+  palette_size: number;
+  total_memory: number;
+  compressed_size: number;
+  compression_ratio: number | null;
+  file_size: number | null;
+  frames: FrameMemoryEstimate[];
+}
+
+// A problem worked around while salvaging a damaged file
+export interface StiWarning {
+  image: number | null; // null for problems with the file as a whole
+  message: string;
+}
+
+export interface SalvagedStiFile {
+  info: StiFileInfo;
+  warnings: StiWarning[];
+}
+
+// Rejection value of every backend command
+export interface CommandError {
+  code: 'not_found' | 'invalid_format' | 'out_of_bounds' | 'unsupported' | 'already_exists' | 'io' | 'internal';
+  message: string;
+}